@@ -171,7 +171,7 @@ async fn main(spawner: Spawner) {
     join3(
         run_all!(matrix, encoder, adc_device, storage),
         run_all!(battery_processor),
-        run_rmk_split_peripheral(0, &stack),
+        run_rmk_split_peripheral::<4, 7, _>(0, &stack),
     )
     .await;
 }
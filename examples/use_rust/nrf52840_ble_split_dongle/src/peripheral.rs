@@ -156,5 +156,9 @@ async fn main(spawner: Spawner) {
     let mut encoder = RotaryEncoder::with_resolution(pin_a, pin_b, 4, true, 1);
 
     // Start
-    join(run_all!(matrix, encoder, storage), run_rmk_split_peripheral(0, &stack)).await;
+    join(
+        run_all!(matrix, encoder, storage),
+        run_rmk_split_peripheral::<4, 7, _>(0, &stack),
+    )
+    .await;
 }
@@ -109,5 +109,9 @@ async fn main(spawner: Spawner) {
 
     let stack = build_ble_stack(controller, ble_addr, &mut rng, &mut host_resources).await;
     // Start
-    join(run_all!(matrix, storage), run_rmk_split_peripheral(0, &stack)).await;
+    join(
+        run_all!(matrix, storage),
+        run_rmk_split_peripheral::<2, 2, _>(0, &stack),
+    )
+    .await;
 }
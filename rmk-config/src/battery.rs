@@ -0,0 +1,45 @@
+use crate::BatteryConfig;
+use crate::chip::ChipSeries;
+
+impl crate::KeyboardTomlConfig {
+    pub(crate) fn get_battery_config(&self) -> Result<BatteryConfig, String> {
+        let battery = self.battery.clone().unwrap_or_default();
+        if let Some(curve) = &battery.curve {
+            if curve.len() < 2 {
+                return Err("keyboard.toml: battery.curve should have at least 2 points".to_string());
+            }
+            if curve.iter().any(|&(_, percent)| percent > 100) {
+                return Err("keyboard.toml: percentages in battery.curve should be at most 100".to_string());
+            }
+            if curve.windows(2).any(|w| w[0].0 <= w[1].0 || w[0].1 < w[1].1) {
+                return Err(
+                    "keyboard.toml: battery.curve should be sorted by descending voltage and percentage".to_string(),
+                );
+            }
+        }
+        if battery.adc_gain.is_some_and(|gain| gain.contains(&0)) {
+            return Err("keyboard.toml: battery.adc_gain should not contain 0".to_string());
+        }
+        if battery
+            .adc_resolution
+            .is_some_and(|resolution| resolution == 0 || resolution > 16)
+        {
+            return Err("keyboard.toml: battery.adc_resolution should be between 1 and 16".to_string());
+        }
+        if battery.low_battery_threshold.is_some_and(|threshold| threshold > 100) {
+            return Err("keyboard.toml: battery.low_battery_threshold should be at most 100".to_string());
+        }
+        Ok(battery)
+    }
+}
+
+/// Check that the ADC settings of the battery are only set for chips whose battery ADC is generated: nRF52, RP2040 and ESP32
+pub(crate) fn check_battery_adc(battery: &BatteryConfig, chip: &ChipSeries) -> Result<(), String> {
+    let adc_set = battery.adc_reference_mv.is_some() || battery.adc_gain.is_some() || battery.adc_resolution.is_some();
+    if adc_set && !matches!(chip, ChipSeries::Nrf52 | ChipSeries::Rp2040 | ChipSeries::Esp32) {
+        return Err(format!(
+            "keyboard.toml: battery.adc_reference_mv, adc_gain and adc_resolution are only supported on nRF52, RP2040 and ESP32, got {chip:?}"
+        ));
+    }
+    Ok(())
+}
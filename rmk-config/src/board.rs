@@ -75,6 +75,12 @@ impl KeyboardTomlConfig {
         let input_device = self.input_device.clone();
         match (matrix, split) {
             (None, Some(s)) => {
+                if s.dongle {
+                    return resolve_dongle(s).map(BoardConfig::Split);
+                }
+                if s.central.rows == 0 || s.central.cols == 0 {
+                    return Err("`rows` and `cols` of [split.central] are required, unless the central is a dongle".to_string());
+                }
                 Ok(BoardConfig::Split(s))
             },
             (Some(m), None) => {
@@ -98,3 +104,27 @@ impl KeyboardTomlConfig {
         }
     }
 }
+
+/// Check the config of a dongle central, and place the peripherals side by side if none of them sets its offsets
+fn resolve_dongle(mut split: SplitConfig) -> Result<SplitConfig, String> {
+    if split.connection != "ble" {
+        return Err("`dongle` is only supported by BLE split".to_string());
+    }
+    let central = &split.central;
+    if central.rows != 0
+        || central.cols != 0
+        || central.matrix.row_pins.is_some()
+        || central.matrix.col_pins.is_some()
+        || central.matrix.direct_pins.is_some()
+    {
+        return Err("The dongle central has no keys, remove the matrix from [split.central]".to_string());
+    }
+    if split.peripheral.iter().all(|p| p.row_offset == 0 && p.col_offset == 0) {
+        let mut col_offset = 0;
+        for p in split.peripheral.iter_mut() {
+            p.col_offset = col_offset;
+            col_offset += p.cols;
+        }
+    }
+    Ok(split)
+}
@@ -452,6 +452,11 @@ impl KeyboardTomlConfig {
                                     key_action_sequence.push(action);
                                 }
 
+                                Rule::cpi_action => {
+                                    let action = inner_pair.as_str().to_string();
+                                    key_action_sequence.push(action);
+                                }
+
                                 Rule::EOI | Rule::WHITESPACE => {
                                     // Ignore End of input marker
                                 }
@@ -731,4 +736,29 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_cpi_grammar() {
+        let test_cases = vec![
+            ("CPI_UP(1)", Some(Rule::cpi_action)),
+            ("cpi_down(0)", Some(Rule::cpi_action)),
+            ("CPI_CYCLE(2)", Some(Rule::cpi_action)),
+            ("CPI_PRESET(2)", Some(Rule::cpi_action)),
+            ("CPI_PRESET(2, 1)", Some(Rule::cpi_action)),
+            // Without arguments, all pointing devices are targeted
+            ("CPI_UP", Some(Rule::simple_keycode)),
+        ];
+
+        for (input, expected_rule) in test_cases {
+            let result = ConfigParser::parse(Rule::key_map, input);
+            assert!(result.is_ok(), "Failed to parse: {}", input);
+
+            let found_rule = result
+                .unwrap()
+                .flat_map(|pair| pair.into_inner())
+                .map(|pair| pair.as_rule())
+                .find(|rule| *rule != Rule::EOI);
+            assert_eq!(found_rule, expected_rule, "Input: {}", input);
+        }
+    }
 }
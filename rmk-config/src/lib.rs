@@ -14,6 +14,7 @@ pub(crate) mod keyboard;
 pub mod resolved;
 #[rustfmt::skip]
 pub mod usb_interrupt_map;
+pub(crate) mod battery;
 pub(crate) mod behavior;
 pub(crate) mod board;
 pub(crate) mod display;
@@ -76,6 +77,8 @@ pub struct KeyboardTomlConfig {
     input_device: Option<InputDeviceConfig>,
     /// Display config
     display: Option<DisplayConfig>,
+    /// Battery config
+    battery: Option<BatteryConfig>,
     /// Output Pin config
     output: Option<Vec<OutputConfig>>,
     /// Set host configurations
@@ -242,6 +245,12 @@ pub(crate) struct RmkConstantsConfig {
     /// BLE Split Central sleep timeout in minutes (0 = disabled)
     #[serde_inline_default(0)]
     pub split_central_sleep_timeout_seconds: u32,
+    /// Idle time in seconds before a wireless keyboard sleeps when running on battery (0 = disabled)
+    #[serde_inline_default(0)]
+    pub sleep_timeout_seconds: u32,
+    /// Idle time in seconds before a wireless keyboard sleeps when USB is powered (0 = disabled)
+    #[serde_inline_default(0)]
+    pub usb_sleep_timeout_seconds: u32,
     /// Maximum number of key actions in a bulk keymap transfer (protocol).
     /// Smaller values reduce firmware RAM usage but require more round-trips.
     #[serde_inline_default(8)]
@@ -315,6 +324,8 @@ impl Default for RmkConstantsConfig {
             split_peripherals_num: 0,
             ble_profiles_num: 3,
             split_central_sleep_timeout_seconds: 0,
+            sleep_timeout_seconds: 0,
+            usb_sleep_timeout_seconds: 0,
             protocol_max_bulk_size: 8,
             protocol_macro_chunk_size: 64,
         }
@@ -383,6 +394,8 @@ macro_rules! define_event_config {
 define_event_config!(
     // BLE events
     ble_status_change,
+    ble_link_stats,
+    passkey_display,
     // Connection events
     connection_change,
     // Input events
@@ -390,6 +403,7 @@ define_event_config!(
     keyboard,
     // Keyboard state events
     layer_change,
+    default_layer_change,
     wpm_update,
     led_indicator,
     sleep_state,
@@ -397,12 +411,20 @@ define_event_config!(
     battery_status,
     battery_adc,
     charging_state,
+    low_battery,
     // Pointing device events
     pointing,
+    pointing_button,
+    pointing_set_cpi,
     // Split events
     peripheral_connected,
     central_connected,
     peripheral_battery,
+    central_battery,
+    split_mismatch,
+    split_custom,
+    split_link_quality,
+    peripheral_link_stats,
     clear_peer,
     // Action events
     action,
@@ -505,6 +527,26 @@ pub struct BleConfig {
     pub use_2m_phy: Option<bool>,
     pub passkey_entry: Option<bool>,
     pub passkey_entry_timeout: Option<u32>,
+    /// Show the passkey on the display and confirm numeric comparison with a key when pairing
+    pub passkey_display: Option<bool>,
+    /// Advertising interval in milliseconds
+    pub adv_interval_ms: Option<u16>,
+    /// How profiles with a bonded host advertise: "undirected", "filtered" or "directed"
+    pub bonded_advertising: Option<String>,
+    /// If set, empty profiles only advertise for this many seconds after the pairing window is opened
+    pub pairing_window_secs: Option<u32>,
+    /// Connection interval in microseconds requested while typing, a multiple of 1250
+    pub conn_interval_us: Option<u32>,
+    /// Number of connection events which can be skipped while typing
+    pub conn_latency: Option<u16>,
+    /// Connection interval in microseconds requested after `low_power_idle_secs` without reports, a multiple of 1250
+    pub low_power_conn_interval_us: Option<u32>,
+    /// Number of connection events which can be skipped in low power mode
+    pub low_power_conn_latency: Option<u16>,
+    /// Seconds without reports before switching to the low power connection parameters, 0 disables the switch
+    pub low_power_idle_secs: Option<u32>,
+    /// Stay connected to the bonded hosts of all profiles, the reports are sent to the host of the active profile
+    pub multi_host: Option<bool>,
 }
 
 /// Default passkey entry timeout in seconds.
@@ -513,6 +555,27 @@ pub const DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS: u32 = 120;
 /// Minimum passkey entry timeout in seconds.
 pub const MIN_PASSKEY_ENTRY_TIMEOUT_SECS: u32 = 30;
 
+/// Default BLE advertising interval in milliseconds.
+pub const DEFAULT_ADV_INTERVAL_MS: u16 = 200;
+
+/// Default BLE TX power of advertising in dBm.
+pub const DEFAULT_ADV_TX_POWER: i8 = 8;
+
+/// Default BLE connection interval in microseconds while typing.
+pub const DEFAULT_CONN_INTERVAL_US: u32 = 7500;
+
+/// Default BLE connection latency while typing.
+pub const DEFAULT_CONN_LATENCY: u16 = 30;
+
+/// Default BLE connection interval in microseconds in low power mode.
+pub const DEFAULT_LOW_POWER_CONN_INTERVAL_US: u32 = 30_000;
+
+/// Default BLE connection latency in low power mode.
+pub const DEFAULT_LOW_POWER_CONN_LATENCY: u16 = 30;
+
+/// Default seconds without reports before switching to the low power connection parameters.
+pub const DEFAULT_LOW_POWER_IDLE_SECS: u32 = 30;
+
 /// Config for chip-specific settings
 #[derive(Clone, Default, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -754,29 +817,51 @@ pub(crate) struct MorseActionPair {
 #[serde(deny_unknown_fields)]
 pub struct SplitConfig {
     pub connection: String,
+    /// The central is a dongle without keys, only for BLE split.
+    ///
+    /// `[split.central]` can be omitted, and if no peripheral sets its offsets, the peripherals are placed side by side.
+    #[serde(default)]
+    pub dongle: bool,
+    /// Ignore the keys of a peripheral which runs a different firmware build, until a key on it is pressed
+    #[serde(default)]
+    pub confirm_mismatched_build: bool,
+    #[serde(default)]
     pub central: SplitBoardConfig,
     pub peripheral: Vec<SplitBoardConfig>,
 }
 
 /// Configurations for each split board
 ///
-/// Either ble_addr or serial must be set, but not both.
+/// One of ble_addr, serial, i2c or single_wire must be set, according to the connection type.
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct SplitBoardConfig {
-    /// Row number of the split board
+    /// Row number of the split board, 0 for the dongle central
+    #[serde(default)]
     pub rows: usize,
-    /// Col number of the split board
+    /// Col number of the split board, 0 for the dongle central
+    #[serde(default)]
     pub cols: usize,
     /// Row offset of the split board
+    #[serde(default)]
     pub row_offset: usize,
     /// Col offset of the split board
+    #[serde(default)]
     pub col_offset: usize,
     /// Ble address
     pub ble_addr: Option<[u8; 6]>,
     /// Serial config, the vector length should be 1 for peripheral
     pub serial: Option<Vec<SerialConfig>>,
+    /// I2C config, used if the connection type is "i2c".
+    ///
+    /// The central is the I2C controller, `address` of a peripheral is its address on the bus.
+    pub i2c: Option<I2cConfig>,
+    /// Pins of the single-wire UART, used if the connection type is "single_wire".
+    ///
+    /// The central has one pin for each peripheral, the vector length should be 1 for peripheral.
+    pub single_wire: Option<Vec<String>>,
     /// Matrix config for the split
+    #[serde(default)]
     pub matrix: MatrixConfig,
     /// Input device config for the split
     pub input_device: Option<InputDeviceConfig>,
@@ -790,6 +875,22 @@ pub struct SplitBoardConfig {
     pub adc_divider_total: Option<u32>,
     /// Output Pin config for the split
     pub output: Option<Vec<OutputConfig>>,
+    /// Pin which reads high when the USB is powered, only for serial split peripherals.
+    ///
+    /// If it's set, the peripheral which boots with USB works as a standalone keyboard with the keymap of the whole keyboard.
+    pub usb_detect_pin: Option<String>,
+    /// BLE connection interval in microseconds between the central and this peripheral, 7500 by default.
+    ///
+    /// It should be a multiple of 1250. Only for BLE split peripherals.
+    pub ble_conn_interval_us: Option<u32>,
+    /// Number of connection events which this peripheral can skip when it has nothing to send, 30 by default.
+    ///
+    /// Only for BLE split peripherals.
+    pub ble_conn_latency: Option<u16>,
+    /// Name of the peripheral, e.g. "Right".
+    ///
+    /// The central of a wireless keyboard reports the battery of the peripheral to the host with this name.
+    pub name: Option<String>,
 }
 
 /// Serial port config
@@ -867,6 +968,7 @@ pub struct InputDeviceConfig {
     pub pmw3610: Option<Vec<Pmw3610Config>>,
     pub pmw33xx: Option<Vec<Pmw33xxConfig>>,
     pub iqs5xx: Option<Vec<Iqs5xxConfig>>,
+    pub pinnacle: Option<Vec<PinnacleConfig>>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -883,6 +985,32 @@ pub struct JoystickConfig {
     pub transform: Vec<Vec<i16>>,
     pub bias: Vec<i16>,
     pub resolution: u16,
+    /// Output mode of each layer, layers without an entry use the first one. Defaults to `["mouse"]`
+    #[serde(default)]
+    pub modes: Vec<JoystickMode>,
+    /// Axis value per wheel/pan step in scroll mode. Defaults to 8
+    pub scroll_divisor: Option<u16>,
+    /// Key positions `[row, col]` of up, down, left and right, used in keys mode
+    pub keys: Option<Vec<[u8; 2]>>,
+    /// Axis value needed to press a direction in keys mode. Defaults to 20
+    pub dead_zone: Option<u16>,
+    /// How far below `dead_zone` a direction is released in keys mode. Defaults to 5
+    pub hysteresis: Option<u16>,
+    /// Allow diagonals in keys mode
+    #[serde(default)]
+    pub eight_way: bool,
+}
+
+/// Output mode of the joystick
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JoystickMode {
+    #[default]
+    Mouse,
+    Scroll,
+    Keys,
+    /// Requires the `gamepad` feature of rmk
+    Gamepad,
 }
 
 /// PMW3610 optical mouse sensor configuration
@@ -899,6 +1027,9 @@ pub struct Pmw3610Config {
     pub motion: Option<String>,
     /// CPI resolution (200-3200, step 200). Optional, uses sensor default if not set.
     pub cpi: Option<u16>,
+    /// CPI presets switched by the CPI actions in the keymap, requires the `pointing_cpi` feature of rmk
+    #[serde(default)]
+    pub cpi_presets: Vec<u16>,
     /// Invert X axis
     #[serde(default)]
     pub invert_x: bool,
@@ -950,6 +1081,9 @@ pub struct Pmw33xxConfig {
     pub motion: Option<String>,
     // CPI resolution (100-12000, step 100).Optional, uses sensor default 1600 if not set.
     pub cpi: Option<u16>,
+    /// CPI presets switched by the CPI actions in the keymap, requires the `pointing_cpi` feature of rmk
+    #[serde(default)]
+    pub cpi_presets: Vec<u16>,
     // Rotational transform angle (-127 to 127) Optional, uses sensor default 0 if not set.
     pub rot_trans_angle: Option<i8>,
     // liftoff distance. Optional, uses sensor default 0 if not set.
@@ -1005,6 +1139,87 @@ pub struct Iqs5xxI2cConfig {
     pub scl: String,
 }
 
+/// Cirque Pinnacle trackpad configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PinnacleConfig {
+    /// Name of the trackpad (used for variable naming).
+    pub name: String,
+    /// RMK pointing-device id (0-255). Defaults to 0.
+    pub id: Option<u8>,
+    /// SPI bus of the trackpad. Exactly one of `spi` and `i2c` must be set.
+    pub spi: Option<SpiConfig>,
+    /// I²C bus of the trackpad. The address is fixed to `0x2A`.
+    pub i2c: Option<PinnacleI2cConfig>,
+    /// Optional `HW_DR` (data ready) pin. Without it the driver polls the status register.
+    pub dr: Option<String>,
+    /// Data mode, `absolute` (default) or `relative`.
+    #[serde(default)]
+    pub mode: PinnacleMode,
+    /// Output resolution in absolute mode. Defaults to 800.
+    pub cpi: Option<u16>,
+    /// CPI presets switched by the CPI actions in the keymap, requires the `pointing_cpi` feature of rmk
+    #[serde(default)]
+    pub cpi_presets: Vec<u16>,
+    /// Diameter of the trackpad in millimeters, used to scale absolute mode. Defaults to 35.
+    pub diameter_mm: Option<u8>,
+    /// ADC attenuation (1-4), higher is less sensitive. Defaults to 4.
+    pub attenuation: Option<u8>,
+    /// Apply the tuning for curved overlays.
+    #[serde(default)]
+    pub curved_overlay: bool,
+    /// Enable tap to click. Defaults to true.
+    #[serde(default = "default_true")]
+    pub tap: bool,
+    /// Enable secondary tap (upper right corner) as right click, relative mode only.
+    #[serde(default)]
+    pub secondary_tap: bool,
+    /// Maximum touch duration of a tap in absolute mode. Defaults to 200ms.
+    pub tap_term_ms: Option<u16>,
+    /// Invert X axis
+    #[serde(default)]
+    pub invert_x: bool,
+    /// Invert Y axis
+    #[serde(default)]
+    pub invert_y: bool,
+    /// Swap X and Y axes
+    #[serde(default)]
+    pub swap_xy: bool,
+    /// Enable the low power sleep mode of the trackpad
+    #[serde(default)]
+    pub sleep: bool,
+    /// Report rate (Hz). Motion will be accumulated and emitted at this rate.
+    #[serde(default = "default_pointing_report_hz")]
+    pub report_hz: u16,
+    /// Invert X in the PointingProcessor.
+    #[serde(default)]
+    pub proc_invert_x: bool,
+    /// Invert Y in the PointingProcessor.
+    #[serde(default)]
+    pub proc_invert_y: bool,
+    /// Swap X and Y in the PointingProcessor.
+    #[serde(default)]
+    pub proc_swap_xy: bool,
+}
+
+/// Data mode of the Cirque Pinnacle
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PinnacleMode {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+/// I²C bus configuration for the Cirque Pinnacle.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PinnacleI2cConfig {
+    pub instance: String,
+    pub sda: String,
+    pub scl: String,
+}
+
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct EncoderConfig {
@@ -1084,6 +1299,10 @@ pub struct I2cConfig {
     /// 7-bit I2C address. Defaults to 0x3C when omitted.
     #[serde(default = "default_i2c_address")]
     pub address: u8,
+    /// TX DMA channel, required by the I2C split on STM32
+    pub tx_dma: Option<String>,
+    /// RX DMA channel, required by the I2C split on STM32
+    pub rx_dma: Option<String>,
 }
 
 const fn default_i2c_address() -> u8 {
@@ -1119,6 +1338,39 @@ pub struct DisplayConfig {
     pub min_render_interval: Option<u64>,
 }
 
+/// Battery chemistry, selects the built-in discharge curve
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryChemistry {
+    #[default]
+    Lipo,
+    Lifepo4,
+    #[serde(rename = "alkaline_2aa")]
+    Alkaline2aa,
+}
+
+/// Battery configuration, shared by all boards of a split keyboard.
+///
+/// The voltage divider is configured per board, in `[ble]` or `[split.central]`/`[[split.peripheral]]`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatteryConfig {
+    /// Battery chemistry, "lipo" by default
+    #[serde(default)]
+    pub chemistry: BatteryChemistry,
+    /// Custom discharge curve as `[millivolts, percent]` points, sorted by descending voltage.
+    /// Overrides the curve of `chemistry`.
+    pub curve: Option<Vec<(u16, u8)>>,
+    /// Reference voltage of the ADC in millivolts, chip default if not set
+    pub adc_reference_mv: Option<u32>,
+    /// Input gain of the ADC as `[numerator, denominator]`, chip default if not set
+    pub adc_gain: Option<[u32; 2]>,
+    /// Resolution of the ADC in bits, chip default if not set
+    pub adc_resolution: Option<u8>,
+    /// Battery level in percent at or below which the battery is reported low, 10 by default
+    pub low_battery_threshold: Option<u8>,
+}
+
 /// Configuration for an output pin
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -1146,6 +1398,8 @@ impl KeyboardTomlConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board::BoardConfig;
+    use crate::chip::ChipSeries;
 
     #[test]
     fn test_event_config_default_values() {
@@ -1225,4 +1479,139 @@ subs = 2
         assert_eq!(config.event.layer_change.pubs, 2);
         assert_eq!(config.event.layer_change.subs, 2);
     }
+
+    #[test]
+    fn test_dongle_peripheral_offsets() {
+        let user_toml = r#"
+[split]
+connection = "ble"
+dongle = true
+
+[[split.peripheral]]
+rows = 4
+cols = 6
+ble_addr = [0x7e, 0xfe, 0x73, 0x9e, 0x11, 0xe3]
+
+[[split.peripheral]]
+rows = 4
+cols = 7
+ble_addr = [0x7e, 0xfe, 0x71, 0x91, 0x11, 0xe3]
+"#;
+        let config: KeyboardTomlConfig = Config::builder()
+            .add_source(File::from_str(user_toml, FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        let BoardConfig::Split(split) = config.get_board_config().unwrap() else {
+            panic!("Expected a split board");
+        };
+        assert_eq!(split.central.rows, 0);
+        let offsets: Vec<_> = split.peripheral.iter().map(|p| (p.row_offset, p.col_offset)).collect();
+        assert_eq!(offsets, [(0, 0), (0, 6)]);
+    }
+
+    #[test]
+    fn test_battery_config() {
+        let parse = |user_toml: &str| -> KeyboardTomlConfig {
+            Config::builder()
+                .add_source(File::from_str(user_toml, FileFormat::Toml))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap()
+        };
+
+        let battery = parse("").get_battery_config().unwrap();
+        assert_eq!(battery.chemistry, BatteryChemistry::Lipo);
+        assert!(battery.curve.is_none());
+
+        let battery = parse(
+            r#"
+[battery]
+chemistry = "alkaline_2aa"
+curve = [[4200, 100], [3700, 30], [3300, 0]]
+low_battery_threshold = 20
+"#,
+        )
+        .get_battery_config()
+        .unwrap();
+        assert_eq!(battery.chemistry, BatteryChemistry::Alkaline2aa);
+        assert_eq!(battery.curve, Some(vec![(4200, 100), (3700, 30), (3300, 0)]));
+        assert_eq!(battery.low_battery_threshold, Some(20));
+
+        let unsorted = parse(
+            r#"
+[battery]
+curve = [[3300, 0], [4200, 100]]
+"#,
+        );
+        assert!(unsorted.get_battery_config().is_err());
+
+        // ADC settings are only used by the battery ADC of nRF52, RP2040 and ESP32
+        let adc = parse(
+            r#"
+[battery]
+adc_reference_mv = 3300
+adc_resolution = 12
+"#,
+        )
+        .get_battery_config()
+        .unwrap();
+        assert!(battery::check_battery_adc(&adc, &ChipSeries::Nrf52).is_ok());
+        assert!(battery::check_battery_adc(&adc, &ChipSeries::Stm32).is_err());
+        assert!(battery::check_battery_adc(&battery, &ChipSeries::Stm32).is_ok());
+    }
+
+    #[test]
+    fn test_battery_adc_chips() {
+        let chip_battery = |chip: &str| -> (ChipSeries, BatteryConfig) {
+            let config: KeyboardTomlConfig = Config::builder()
+                .add_source(File::from_str(
+                    &format!(
+                        r#"
+[keyboard]
+name = "Battery"
+product_id = 0x4643
+vendor_id = 0x4c4b
+chip = "{chip}"
+
+[ble]
+enabled = true
+battery_adc_pin = "PIN_29"
+adc_divider_measured = 1
+adc_divider_total = 3
+
+[battery]
+adc_reference_mv = 3300
+adc_gain = [1, 1]
+adc_resolution = 12
+"#
+                    ),
+                    FileFormat::Toml,
+                ))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap();
+            (
+                config.get_chip_model().unwrap().series,
+                config.get_battery_config().unwrap(),
+            )
+        };
+
+        let (series, battery) = chip_battery("rp2040");
+        assert_eq!(series, ChipSeries::Rp2040);
+        assert_eq!(battery.adc_reference_mv, Some(3300));
+        assert!(battery::check_battery_adc(&battery, &series).is_ok());
+
+        let (series, battery) = chip_battery("esp32c3");
+        assert_eq!(series, ChipSeries::Esp32);
+        assert_eq!(battery.adc_gain, Some([1, 1]));
+        assert!(battery::check_battery_adc(&battery, &series).is_ok());
+
+        let (series, battery) = chip_battery("stm32f411ce");
+        assert!(battery::check_battery_adc(&battery, &series).is_err());
+    }
 }
@@ -1,6 +1,10 @@
 use serde::Deserialize;
 
-use crate::{DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS, MIN_PASSKEY_ENTRY_TIMEOUT_SECS};
+use crate::{
+    DEFAULT_ADV_INTERVAL_MS, DEFAULT_ADV_TX_POWER, DEFAULT_CONN_INTERVAL_US, DEFAULT_CONN_LATENCY,
+    DEFAULT_LOW_POWER_CONN_INTERVAL_US, DEFAULT_LOW_POWER_CONN_LATENCY, DEFAULT_LOW_POWER_IDLE_SECS,
+    DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS, MIN_PASSKEY_ENTRY_TIMEOUT_SECS,
+};
 
 const SUBSCRIBER_DEFAULT_CONFIG: &str = include_str!("../default_config/subscriber_default.toml");
 
@@ -46,10 +50,18 @@ pub struct BuildConstants {
     pub split_peripherals_num: usize,
     pub ble_profiles_num: usize,
     pub split_central_sleep_timeout_seconds: u32,
+    pub sleep_timeout_seconds: u32,
+    pub usb_sleep_timeout_seconds: u32,
     pub protocol_max_bulk_size: usize,
     pub protocol_macro_chunk_size: usize,
     pub events: Vec<EventChannel>,
     pub passkey: Option<Passkey>,
+    pub advertising: Option<Advertising>,
+    pub conn_params: Option<ConnParams>,
+    /// Whether the keyboard stays connected to the hosts of all BLE profiles
+    pub ble_multi_host: bool,
+    /// Names of the Battery Services of the split peripherals, empty if the central doesn't report them over BLE
+    pub peripheral_battery_names: Vec<String>,
 }
 
 pub struct EventChannel {
@@ -62,6 +74,28 @@ pub struct EventChannel {
 pub struct Passkey {
     pub enabled: bool,
     pub timeout_secs: u32,
+    /// Whether the passkey is shown on the display of the central
+    pub display: bool,
+}
+
+/// BLE advertising settings, `bonded` is a variant name of `rmk_types::ble::BondedAdvertising`.
+pub struct Advertising {
+    pub interval_ms: u16,
+    pub use_2m_phy: bool,
+    pub tx_power: i8,
+    pub bonded: &'static str,
+    /// 0 means empty profiles always advertise
+    pub pairing_window_secs: u32,
+}
+
+/// BLE connection parameters requested to the host while typing, and in low power mode after being idle
+pub struct ConnParams {
+    pub interval_us: u32,
+    pub latency: u16,
+    pub low_power_interval_us: u32,
+    pub low_power_latency: u16,
+    /// 0 means the low power parameters are never requested
+    pub low_power_idle_secs: u32,
 }
 
 impl crate::KeyboardTomlConfig {
@@ -96,20 +130,31 @@ impl crate::KeyboardTomlConfig {
 
         let mut events = event_channels!(
             ble_status_change,
+            ble_link_stats,
+            passkey_display,
             connection_change,
             modifier,
             keyboard,
             layer_change,
+            default_layer_change,
             wpm_update,
             led_indicator,
             sleep_state,
             battery_status,
             battery_adc,
             charging_state,
+            low_battery,
             pointing,
+            pointing_button,
+            pointing_set_cpi,
             peripheral_connected,
             central_connected,
             peripheral_battery,
+            central_battery,
+            split_mismatch,
+            split_custom,
+            split_link_quality,
+            peripheral_link_stats,
             clear_peer,
             action,
         );
@@ -118,6 +163,12 @@ impl crate::KeyboardTomlConfig {
         // Declarations live in subscriber_default.toml.
         apply_feature_subscriber_bumps(&mut events, active_features);
 
+        let ble_multi_host =
+            active_features.contains(&"_ble") && self.ble.as_ref().and_then(|ble| ble.multi_host).unwrap_or(false);
+        if ble_multi_host {
+            apply_multi_host_subscriber_bumps(&mut events, rmk.ble_profiles_num, active_features.contains(&"split"));
+        }
+
         // Only validate passkey settings when the build will emit passkey constants.
         let passkey = if active_features.contains(&"passkey_entry") {
             self.ble.as_ref().map(resolve_passkey_enabled).transpose()?
@@ -125,6 +176,32 @@ impl crate::KeyboardTomlConfig {
             None
         };
 
+        let advertising = if active_features.contains(&"_ble") {
+            let advertising = resolve_advertising(&self.ble.clone().unwrap_or_default())?;
+            // The split central connects to the peripherals through the filter accept list
+            if advertising.bonded == "Filtered" && active_features.contains(&"split") {
+                return Err(
+                    "keyboard.toml: [ble.bonded_advertising] \"filtered\" is not supported by split keyboards"
+                        .to_string(),
+                );
+            }
+            Some(advertising)
+        } else {
+            None
+        };
+
+        let conn_params = if active_features.contains(&"_ble") {
+            Some(resolve_conn_params(&self.ble.clone().unwrap_or_default())?)
+        } else {
+            None
+        };
+
+        let peripheral_battery_names = if active_features.contains(&"_ble") && active_features.contains(&"split") {
+            resolve_peripheral_battery_names(self.split.as_ref(), split_peripherals_num)
+        } else {
+            Vec::new()
+        };
+
         // Validate that config values do not exceed protocol ceilings.
         use crate::protocol_limits;
         if rmk.combo_max_length > protocol_limits::MAX_COMBO_SIZE {
@@ -172,10 +249,16 @@ impl crate::KeyboardTomlConfig {
             split_peripherals_num,
             ble_profiles_num: rmk.ble_profiles_num,
             split_central_sleep_timeout_seconds: rmk.split_central_sleep_timeout_seconds,
+            sleep_timeout_seconds: rmk.sleep_timeout_seconds,
+            usb_sleep_timeout_seconds: rmk.usb_sleep_timeout_seconds,
             protocol_max_bulk_size: rmk.protocol_max_bulk_size,
             protocol_macro_chunk_size: rmk.protocol_macro_chunk_size,
             events,
             passkey,
+            advertising,
+            conn_params,
+            ble_multi_host,
+            peripheral_battery_names,
         })
     }
 }
@@ -204,6 +287,16 @@ fn apply_feature_subscriber_bumps(events: &mut [EventChannel], active_features:
     }
 }
 
+/// With `multi_host`, each connected host runs its own battery servers, while the defaults count a single host.
+fn apply_multi_host_subscriber_bumps(events: &mut [EventChannel], hosts: usize, split: bool) {
+    let extra_hosts = hosts.saturating_sub(1);
+    for event in events.iter_mut() {
+        if event.name == "battery_status" || (split && event.name == "peripheral_battery") {
+            event.subs += extra_hosts;
+        }
+    }
+}
+
 fn resolve_passkey_enabled(ble: &crate::BleConfig) -> Result<Passkey, String> {
     let enabled = ble.passkey_entry.unwrap_or(false);
     let timeout_secs = ble.passkey_entry_timeout.unwrap_or(DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS);
@@ -213,13 +306,101 @@ fn resolve_passkey_enabled(ble: &crate::BleConfig) -> Result<Passkey, String> {
             MIN_PASSKEY_ENTRY_TIMEOUT_SECS, timeout_secs
         ));
     }
-    Ok(Passkey { enabled, timeout_secs })
+    Ok(Passkey {
+        enabled,
+        timeout_secs,
+        display: ble.passkey_display.unwrap_or(false),
+    })
+}
+
+/// Name of each peripheral, "Peripheral" or "Peripheral <id>" if it's not set in `[[split.peripheral]]`
+fn resolve_peripheral_battery_names(split: Option<&crate::SplitConfig>, peripherals_num: usize) -> Vec<String> {
+    (0..peripherals_num)
+        .map(|id| {
+            split
+                .and_then(|split| split.peripheral.get(id))
+                .and_then(|peripheral| peripheral.name.clone())
+                .unwrap_or_else(|| match peripherals_num {
+                    1 => "Peripheral".to_string(),
+                    _ => format!("Peripheral {id}"),
+                })
+        })
+        .collect()
+}
+
+fn resolve_advertising(ble: &crate::BleConfig) -> Result<Advertising, String> {
+    let interval_ms = ble.adv_interval_ms.unwrap_or(DEFAULT_ADV_INTERVAL_MS);
+    // Range allowed by the Bluetooth Core spec
+    if !(20..=10_240).contains(&interval_ms) {
+        return Err(format!(
+            "keyboard.toml: [ble.adv_interval_ms] must be between 20 and 10240, got {interval_ms}"
+        ));
+    }
+    let bonded = match ble.bonded_advertising.as_deref() {
+        None | Some("undirected") => "Undirected",
+        Some("filtered") => "Filtered",
+        Some("directed") => "Directed",
+        Some(mode) => {
+            return Err(format!(
+                "keyboard.toml: [ble.bonded_advertising] must be \"undirected\", \"filtered\" or \"directed\", got \"{mode}\""
+            ));
+        }
+    };
+    Ok(Advertising {
+        interval_ms,
+        use_2m_phy: ble.use_2m_phy.unwrap_or(true),
+        tx_power: ble.default_tx_power.unwrap_or(DEFAULT_ADV_TX_POWER),
+        bonded,
+        pairing_window_secs: ble.pairing_window_secs.unwrap_or(0),
+    })
+}
+
+/// Supervision timeout requested with the connection parameters, in microseconds
+const SUPERVISION_TIMEOUT_US: u64 = 5_000_000;
+
+fn resolve_conn_params(ble: &crate::BleConfig) -> Result<ConnParams, String> {
+    let params = ConnParams {
+        interval_us: ble.conn_interval_us.unwrap_or(DEFAULT_CONN_INTERVAL_US),
+        latency: ble.conn_latency.unwrap_or(DEFAULT_CONN_LATENCY),
+        low_power_interval_us: ble
+            .low_power_conn_interval_us
+            .unwrap_or(DEFAULT_LOW_POWER_CONN_INTERVAL_US),
+        low_power_latency: ble.low_power_conn_latency.unwrap_or(DEFAULT_LOW_POWER_CONN_LATENCY),
+        low_power_idle_secs: ble.low_power_idle_secs.unwrap_or(DEFAULT_LOW_POWER_IDLE_SECS),
+    };
+    for (key, interval_us, latency) in [
+        ("conn_interval_us", params.interval_us, params.latency),
+        (
+            "low_power_conn_interval_us",
+            params.low_power_interval_us,
+            params.low_power_latency,
+        ),
+    ] {
+        // Range allowed by the Bluetooth Core spec
+        if !(7_500..=4_000_000).contains(&interval_us) || interval_us % 1250 != 0 {
+            return Err(format!(
+                "keyboard.toml: [ble.{key}] must be a multiple of 1250 between 7500 and 4000000, got {interval_us}"
+            ));
+        }
+        // The link must survive the skipped connection events
+        if (latency as u64 + 1) * interval_us as u64 * 2 >= SUPERVISION_TIMEOUT_US {
+            return Err(format!(
+                "keyboard.toml: [ble.{key}] {interval_us} with latency {latency} exceeds the supervision timeout of 5s"
+            ));
+        }
+    }
+    Ok(params)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::resolve_passkey_enabled;
-    use crate::{BleConfig, DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS, MIN_PASSKEY_ENTRY_TIMEOUT_SECS};
+    use super::{
+        EventChannel, apply_multi_host_subscriber_bumps, resolve_advertising, resolve_conn_params,
+        resolve_passkey_enabled, resolve_peripheral_battery_names,
+    };
+    use crate::{
+        BleConfig, DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS, MIN_PASSKEY_ENTRY_TIMEOUT_SECS, SplitBoardConfig, SplitConfig,
+    };
 
     #[test]
     fn validates_passkey_timeout() {
@@ -248,6 +429,106 @@ mod tests {
         let passkey = resolve_passkey_enabled(&ble).unwrap();
 
         assert!(!passkey.enabled);
+        assert!(!passkey.display);
         assert_eq!(passkey.timeout_secs, DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS);
     }
+
+    #[test]
+    fn resolves_advertising() {
+        let adv = resolve_advertising(&BleConfig::default()).unwrap();
+        assert_eq!(adv.interval_ms, 200);
+        assert!(adv.use_2m_phy);
+        assert_eq!(adv.tx_power, 8);
+        assert_eq!(adv.bonded, "Undirected");
+        assert_eq!(adv.pairing_window_secs, 0);
+
+        let ble = BleConfig {
+            adv_interval_ms: Some(1000),
+            use_2m_phy: Some(false),
+            default_tx_power: Some(0),
+            bonded_advertising: Some("filtered".to_string()),
+            pairing_window_secs: Some(60),
+            ..Default::default()
+        };
+        let adv = resolve_advertising(&ble).unwrap();
+        assert_eq!(adv.interval_ms, 1000);
+        assert!(!adv.use_2m_phy);
+        assert_eq!(adv.tx_power, 0);
+        assert_eq!(adv.bonded, "Filtered");
+        assert_eq!(adv.pairing_window_secs, 60);
+    }
+
+    #[test]
+    fn rejects_invalid_advertising() {
+        let ble = BleConfig {
+            adv_interval_ms: Some(10),
+            ..Default::default()
+        };
+        assert!(resolve_advertising(&ble).is_err());
+
+        let ble = BleConfig {
+            bonded_advertising: Some("whitelist".to_string()),
+            ..Default::default()
+        };
+        assert!(resolve_advertising(&ble).is_err());
+    }
+
+    #[test]
+    fn resolves_peripheral_battery_names() {
+        assert_eq!(resolve_peripheral_battery_names(None, 1), ["Peripheral"]);
+
+        let split = SplitConfig {
+            peripheral: vec![
+                SplitBoardConfig {
+                    name: Some("Right".to_string()),
+                    ..Default::default()
+                },
+                SplitBoardConfig::default(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_peripheral_battery_names(Some(&split), 2),
+            ["Right", "Peripheral 1"]
+        );
+    }
+
+    #[test]
+    fn resolves_conn_params() {
+        let params = resolve_conn_params(&BleConfig::default()).unwrap();
+        assert_eq!(params.interval_us, 7500);
+        assert_eq!(params.latency, 30);
+        assert_eq!(params.low_power_interval_us, 30_000);
+        assert_eq!(params.low_power_latency, 30);
+        assert_eq!(params.low_power_idle_secs, 30);
+
+        let ble = BleConfig {
+            conn_interval_us: Some(7600),
+            ..Default::default()
+        };
+        assert!(resolve_conn_params(&ble).is_err());
+
+        // 100ms * 31 * 2 exceeds the supervision timeout
+        let ble = BleConfig {
+            low_power_conn_interval_us: Some(100_000),
+            ..Default::default()
+        };
+        assert!(resolve_conn_params(&ble).is_err());
+    }
+
+    #[test]
+    fn bumps_multi_host_subscribers() {
+        let event = |name: &str| EventChannel {
+            name: name.to_string(),
+            channel_size: 1,
+            pubs: 1,
+            subs: 1,
+        };
+        let mut events = vec![event("battery_status"), event("peripheral_battery"), event("keyboard")];
+        apply_multi_host_subscriber_bumps(&mut events, 3, false);
+        assert_eq!(events.iter().map(|e| e.subs).collect::<Vec<_>>(), [3, 1, 1]);
+
+        apply_multi_host_subscriber_bumps(&mut events, 2, true);
+        assert_eq!(events.iter().map(|e| e.subs).collect::<Vec<_>>(), [4, 2, 1]);
+    }
 }
@@ -8,10 +8,11 @@ pub use crate::board::{BoardConfig, UniBodyConfig};
 pub use crate::chip::{ChipModel, ChipSeries};
 pub use crate::communication::{CommunicationConfig, UsbInfo};
 pub use crate::{
-    BleConfig, ChipConfig, CommunicationProtocol, DependencyConfig, DisplayConfig, DisplayDriver, EncoderConfig,
-    EncoderResolution, I2cConfig, InputDeviceConfig, Iqs5xxConfig, Iqs5xxI2cConfig, JoystickConfig, KeyInfo,
-    LightConfig, MatrixConfig, MatrixType, OutputConfig, PinConfig, Pmw33xxConfig, Pmw33xxType, Pmw3610Config,
-    PointingDeviceConfig, SerialConfig, SpiConfig, SplitBoardConfig, SplitConfig,
+    BatteryChemistry, BatteryConfig, BleConfig, ChipConfig, CommunicationProtocol, DependencyConfig, DisplayConfig,
+    DisplayDriver, EncoderConfig, EncoderResolution, I2cConfig, InputDeviceConfig, Iqs5xxConfig, Iqs5xxI2cConfig,
+    JoystickConfig, JoystickMode, KeyInfo, LightConfig, MatrixConfig, MatrixType, OutputConfig, PinConfig,
+    PinnacleConfig, PinnacleI2cConfig, PinnacleMode, Pmw33xxConfig, Pmw33xxType, Pmw3610Config, PointingDeviceConfig,
+    SerialConfig, SpiConfig, SplitBoardConfig, SplitConfig,
 };
 
 /// Resolved storage hardware config
@@ -31,6 +32,7 @@ pub struct Hardware {
     pub storage: Option<Storage>,
     pub light: LightConfig,
     pub display: Option<DisplayConfig>,
+    pub battery: BatteryConfig,
     pub output: Vec<OutputConfig>,
     pub dependency: DependencyConfig,
 }
@@ -55,6 +57,8 @@ impl crate::KeyboardTomlConfig {
         };
         let light = self.get_light_config();
         let display = self.get_display_config();
+        let battery = self.get_battery_config()?;
+        crate::battery::check_battery_adc(&battery, &chip.series)?;
         let output = self.get_output_config()?;
         let dependency = self.get_dependency_config();
         Ok(Hardware {
@@ -65,6 +69,7 @@ impl crate::KeyboardTomlConfig {
             storage,
             light,
             display,
+            battery,
             output,
             dependency,
         })
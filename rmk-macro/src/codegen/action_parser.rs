@@ -11,6 +11,20 @@ use rmk_config::resolved::KEYCODE_ALIAS;
 use rmk_config::resolved::behavior::MorseProfile;
 use strum::VariantNames;
 
+use crate::codegen::feature::{get_rmk_features, is_feature_enabled};
+
+/// Panic when the action needs an RMK feature which isn't enabled in Cargo.toml.
+///
+/// Nothing is checked when Cargo.toml can't be read.
+fn check_rmk_feature(feature: &str, action: &str) {
+    let rmk_features = get_rmk_features();
+    if rmk_features.is_some() && !is_feature_enabled(&rmk_features, feature) {
+        panic!(
+            "\n\u{274c} keyboard.toml: {action} requires the `{feature}` feature of rmk, enable it in Cargo.toml"
+        );
+    }
+}
+
 struct ModifierCombinationMacro {
     right: bool,
     gui: bool,
@@ -392,6 +406,7 @@ pub(crate) fn parse_key(
             }
         }
         s if s.to_lowercase().starts_with("stn(") => {
+            check_rmk_feature("steno", "STN(key)");
             let prefix = s.get(0..4).unwrap();
             if let Some(internal) = s.trim_start_matches(prefix).strip_suffix(")") {
                 let key_ident = format_ident!("{}", internal.trim().to_uppercase());
@@ -402,6 +417,63 @@ pub(crate) fn parse_key(
                 );
             }
         }
+        s if s.to_lowercase().starts_with("gp(") => {
+            check_rmk_feature("gamepad", "GP(n)");
+            let button = get_number(s.clone(), s.get(0..3).unwrap(), ")");
+            if button >= 32 {
+                panic!(
+                    "\n\u{274c} keyboard.toml: GP(n) invalid - gamepad button should be 0-31, got {button}"
+                );
+            }
+            quote! { ::rmk::gamepad!(#button) }
+        }
+        s if s.to_lowercase().starts_with("hat(") => {
+            check_rmk_feature("gamepad", "HAT(direction)");
+            let direction = match s
+                .get(4..)
+                .and_then(|d| d.strip_suffix(")"))
+                .map(|d| d.trim().to_lowercase())
+            {
+                Some(d) if d == "up" => quote! { Up },
+                Some(d) if d == "down" => quote! { Down },
+                Some(d) if d == "left" => quote! { Left },
+                Some(d) if d == "right" => quote! { Right },
+                _ => panic!(
+                    "\n\u{274c} keyboard.toml: HAT(direction) invalid - use HAT(UP), HAT(DOWN), HAT(LEFT) or HAT(RIGHT)"
+                ),
+            };
+            quote! { ::rmk::hat!(#direction) }
+        }
+        s if s.to_lowercase().starts_with("cpi_") => {
+            // CPI_UP, CPI_DOWN and CPI_CYCLE take an optional device id, CPI_PRESET takes the preset index and an optional device id
+            let lower = s.to_lowercase();
+            let (name, args) = match lower.split_once('(') {
+                Some((name, rest)) => (name.trim(), rest.strip_suffix(")")),
+                None => (lower.as_str(), Some("")),
+            };
+            let args: Option<Vec<u8>> = args.and_then(|args| {
+                args.split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(|a| a.parse::<u8>().ok())
+                    .collect()
+            });
+            match (name, args.as_deref()) {
+                ("cpi_up", Some([])) => quote! { ::rmk::cpi!(Up) },
+                ("cpi_up", Some([device])) => quote! { ::rmk::cpi!(Up, #device) },
+                ("cpi_down", Some([])) => quote! { ::rmk::cpi!(Down) },
+                ("cpi_down", Some([device])) => quote! { ::rmk::cpi!(Down, #device) },
+                ("cpi_cycle", Some([])) => quote! { ::rmk::cpi!(Cycle) },
+                ("cpi_cycle", Some([device])) => quote! { ::rmk::cpi!(Cycle, #device) },
+                ("cpi_preset", Some([preset])) => quote! { ::rmk::cpi!(Preset, #preset) },
+                ("cpi_preset", Some([preset, device])) => {
+                    quote! { ::rmk::cpi!(Preset, #preset, #device) }
+                }
+                _ => panic!(
+                    "\n\u{274c} keyboard.toml: {s} invalid - use CPI_UP, CPI_DOWN, CPI_CYCLE or CPI_PRESET(n), with an optional pointing device id, e.g. CPI_UP(1) or CPI_PRESET(2, 1)"
+                ),
+            }
+        }
         s if s.to_lowercase().starts_with("user") => {
             // Support both User(X) and UserX formats
             let number_str = if s.to_lowercase().starts_with("user(") {
@@ -453,7 +453,10 @@ fn expand_forks(
     }
 }
 
-pub(crate) fn expand_behavior_config(behavior: &Behavior) -> proc_macro2::TokenStream {
+pub(crate) fn expand_behavior_config(
+    behavior: &Behavior,
+    pointing_cpi: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
     let profiles = behavior
         .morse
         .as_ref()
@@ -480,6 +483,7 @@ pub(crate) fn expand_behavior_config(behavior: &Behavior) -> proc_macro2::TokenS
             keyboard_macros: #macros,
             mouse_key: ::rmk::config::MouseKeyConfig::default(),
             tap: ::rmk::config::TapConfig::default(),
+            pointing_cpi: #pointing_cpi,
         };
     }
 }
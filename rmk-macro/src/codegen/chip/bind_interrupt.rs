@@ -12,6 +12,8 @@ use syn::ItemMod;
 use crate::codegen::display::expand_display_interrupt;
 use crate::codegen::feature::{get_rmk_features, is_feature_enabled};
 use crate::codegen::input_device::iqs5xx::expand_iqs5xx_interrupts;
+use crate::codegen::input_device::pinnacle::expand_pinnacle_interrupts;
+use crate::codegen::split::central::expand_stm32_split_interrupts;
 
 /// Expand `bind_interrupt!` stuffs, and other code before `main` function
 pub(crate) fn expand_bind_interrupt(hardware: &Hardware, item_mod: &ItemMod) -> TokenStream2 {
@@ -93,18 +95,49 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
     };
     let iqs5xx_interrupt = expand_iqs5xx_interrupts(&chip.series, &iqs5xx_config);
 
+    // Pinnacle trackpads need an SPI or I²C interrupt binding as well
+    let pinnacle_config = match board {
+        BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => {
+            input_device.clone().pinnacle.unwrap_or(Vec::new())
+        }
+        BoardConfig::Split(split_config) => split_config
+            .central
+            .input_device
+            .clone()
+            .unwrap_or(InputDeviceConfig::default())
+            .pinnacle
+            .unwrap_or(Vec::new()),
+    };
+    let pinnacle_interrupt = expand_pinnacle_interrupts(&chip.series, &pinnacle_config);
+
     match chip.series {
         rmk_config::resolved::hardware::ChipSeries::Stm32 => {
             // For stm32, bind USB interrupt and EXTI interrupts (if async_matrix is enabled)
             let rmk_features = get_rmk_features();
             let async_matrix = is_feature_enabled(&rmk_features, "async_matrix");
 
-            // Generate EXTI interrupt bindings for async_matrix
-            let exti_interrupts = if async_matrix {
-                generate_stm32_exti_interrupts(board)
+            // Generate EXTI interrupt bindings for async_matrix and the single-wire split
+            let mut exti_pins = if async_matrix {
+                match board {
+                    BoardConfig::UniBody(unibody) => {
+                        unibody.matrix.row_pins.clone().unwrap_or_default()
+                    }
+                    BoardConfig::Split(split) => {
+                        split.central.matrix.row_pins.clone().unwrap_or_default()
+                    }
+                }
+            } else {
+                Vec::new()
+            };
+            let split_interrupt = if let BoardConfig::Split(split) = board {
+                let (split_interrupt, split_exti_pins) =
+                    expand_stm32_split_interrupts(&split.connection, &split.central);
+                exti_pins.extend(split_exti_pins);
+                split_interrupt
             } else {
                 quote! {}
             };
+            let exti_interrupts = generate_stm32_exti_interrupts(&exti_pins);
 
             if let Some(usb_info) = communication.get_usb_info() {
                 let interrupt_name = format_ident!("{}", usb_info.interrupt_name);
@@ -114,23 +147,21 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
                     bind_interrupts!(struct Irqs {
                         #interrupt_name => ::embassy_stm32::usb::InterruptHandler<::embassy_stm32::peripherals::#peripheral_name>;
                         #exti_interrupts
+                        #split_interrupt
                         #display_interrupt
                         #extern_irqs
                     });
                 }
-            } else if async_matrix {
+            } else if async_matrix
+                || !exti_interrupts.is_empty()
+                || !split_interrupt.is_empty()
+                || !display_interrupt.is_empty()
+            {
                 quote! {
                     use ::embassy_stm32::bind_interrupts;
                     bind_interrupts!(struct Irqs {
                         #exti_interrupts
-                        #display_interrupt
-                        #extern_irqs
-                    });
-                }
-            } else if !display_interrupt.is_empty() {
-                quote! {
-                    use ::embassy_stm32::bind_interrupts;
-                    bind_interrupts!(struct Irqs {
+                        #split_interrupt
                         #display_interrupt
                         #extern_irqs
                     });
@@ -142,18 +173,76 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
             }
         }
         rmk_config::resolved::hardware::ChipSeries::Nrf52 => {
-            // Usb and clock interrupt
-            let usb_and_clock_interrupt = if let Some(usb_info) = communication.get_usb_info() {
-                let interrupt_name = format_ident!("{}", usb_info.interrupt_name);
-                let peripheral_name = format_ident!("{}", usb_info.peripheral_name);
-                quote! {
-                    #interrupt_name => ::embassy_nrf::usb::InterruptHandler<::embassy_nrf::peripherals::#peripheral_name>;
-                    CLOCK_POWER => ::nrf_sdc::mpsl::ClockInterruptHandler, ::embassy_nrf::usb::vbus_detect::InterruptHandler;
+            // Usb and clock interrupt, wired nRF keyboards don't run nrf-sdc
+            let ble_enabled = communication.ble_enabled();
+            let usb_and_clock_interrupt = match (communication.get_usb_info(), ble_enabled) {
+                (Some(usb_info), true) => {
+                    let interrupt_name = format_ident!("{}", usb_info.interrupt_name);
+                    let peripheral_name = format_ident!("{}", usb_info.peripheral_name);
+                    quote! {
+                        #interrupt_name => ::embassy_nrf::usb::InterruptHandler<::embassy_nrf::peripherals::#peripheral_name>;
+                        CLOCK_POWER => ::nrf_sdc::mpsl::ClockInterruptHandler, ::embassy_nrf::usb::vbus_detect::InterruptHandler;
+                    }
                 }
+                (Some(usb_info), false) => {
+                    let interrupt_name = format_ident!("{}", usb_info.interrupt_name);
+                    let peripheral_name = format_ident!("{}", usb_info.peripheral_name);
+                    quote! {
+                        #interrupt_name => ::embassy_nrf::usb::InterruptHandler<::embassy_nrf::peripherals::#peripheral_name>;
+                        CLOCK_POWER => ::embassy_nrf::usb::vbus_detect::InterruptHandler;
+                    }
+                }
+                (None, true) => quote! { CLOCK_POWER => ::nrf_sdc::mpsl::ClockInterruptHandler; },
+                (None, false) => quote! {},
+            };
+
+            // Extract PMW33xx configuration
+            let pmw33xx_config = match &board {
+                BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => {
+                    input_device.clone().pmw33xx.unwrap_or(Vec::new())
+                }
+                BoardConfig::Split(split_config) => split_config
+                    .central
+                    .input_device
+                    .clone()
+                    .unwrap_or(InputDeviceConfig::default())
+                    .pmw33xx
+                    .unwrap_or(Vec::new()),
+            };
+
+            // Generate SPI interrupts for each sensor
+            let mut pmw33xx_spi_interrupts = Vec::new();
+
+            for sensor in &pmw33xx_config {
+                let instance_ident = format_ident!("{}", &sensor.spi.instance);
+
+                pmw33xx_spi_interrupts.push(quote! {
+                    #instance_ident => ::embassy_nrf::spim::InterruptHandler<::embassy_nrf::peripherals::#instance_ident>;
+                });
+            }
+
+            let pmw33xx_spi_interrupts = if pmw33xx_spi_interrupts.is_empty() {
+                quote! {}
             } else {
-                quote! { CLOCK_POWER => ::nrf_sdc::mpsl::ClockInterruptHandler; }
+                quote! {
+                    #(#pmw33xx_spi_interrupts)*
+                }
             };
 
+            if !ble_enabled {
+                return quote! {
+                    use ::embassy_nrf::bind_interrupts;
+                    bind_interrupts!(struct Irqs {
+                        #usb_and_clock_interrupt
+                        #pmw33xx_spi_interrupts
+                        #iqs5xx_interrupt
+                        #pinnacle_interrupt
+                        #display_interrupt
+                        #extern_irqs
+                    });
+                };
+            }
+
             let ble_config = communication.get_ble_config().unwrap();
             let tx_power = if let Some(pwr) = ble_config.default_tx_power {
                 quote! { .default_tx_power(#pwr)?  }
@@ -202,39 +291,6 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
                 },
             };
 
-            // Extract PMW33xx configuration
-            let pmw33xx_config = match &board {
-                BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => {
-                    input_device.clone().pmw33xx.unwrap_or(Vec::new())
-                }
-                BoardConfig::Split(split_config) => split_config
-                    .central
-                    .input_device
-                    .clone()
-                    .unwrap_or(InputDeviceConfig::default())
-                    .pmw33xx
-                    .unwrap_or(Vec::new()),
-            };
-
-            // Generate SPI interrupts for each sensor
-            let mut pmw33xx_spi_interrupts = Vec::new();
-
-            for sensor in &pmw33xx_config {
-                let instance_ident = format_ident!("{}", &sensor.spi.instance);
-
-                pmw33xx_spi_interrupts.push(quote! {
-                    #instance_ident => ::embassy_nrf::spim::InterruptHandler<::embassy_nrf::peripherals::#instance_ident>;
-                });
-            }
-
-            let pmw33xx_spi_interrupts = if pmw33xx_spi_interrupts.is_empty() {
-                quote! {}
-            } else {
-                quote! {
-                    #(#pmw33xx_spi_interrupts)*
-                }
-            };
-
             quote! {
                 use ::embassy_nrf::bind_interrupts;
                 bind_interrupts!(struct Irqs {
@@ -246,6 +302,7 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
                     RTC0 => ::nrf_sdc::mpsl::HighPrioInterruptHandler;
                     #pmw33xx_spi_interrupts
                     #iqs5xx_interrupt
+                    #pinnacle_interrupt
                     #display_interrupt
                     #extern_irqs
                 });
@@ -304,6 +361,7 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
                     #dma_irq_0
                     #pio0_irq_0
                     #iqs5xx_interrupt
+                    #pinnacle_interrupt
                     #display_interrupt
                 });
                 #ble_task
@@ -313,22 +371,16 @@ pub(crate) fn bind_interrupt_default(hardware: &Hardware, item_mod: &ItemMod) ->
     }
 }
 
-/// Generate STM32 EXTI interrupt bindings based on row pins
+/// Generate STM32 EXTI interrupt bindings of the given pins
 /// STM32 EXTI lines:
 /// - EXTI0 - EXTI4: each has its own interrupt
 /// - EXTI5 - EXTI9: share EXTI9_5 interrupt
 /// - EXTI10 - EXTI15: share EXTI15_10 interrupt
-fn generate_stm32_exti_interrupts(board: &BoardConfig) -> TokenStream2 {
-    // Collect all row pins from the matrix configuration
-    let row_pins: Vec<String> = match board {
-        BoardConfig::UniBody(unibody) => unibody.matrix.row_pins.clone().unwrap_or_default(),
-        BoardConfig::Split(split) => split.central.matrix.row_pins.clone().unwrap_or_default(),
-    };
-
+pub(crate) fn generate_stm32_exti_interrupts(pins: &[String]) -> TokenStream2 {
     // Extract pin numbers and determine required EXTI interrupts
     let mut required_interrupts: HashSet<String> = HashSet::new();
 
-    for pin in &row_pins {
+    for pin in pins {
         if let Some(pin_num_str) = get_pin_num_stm32(pin)
             && let Ok(pin_num) = pin_num_str.parse::<u8>()
         {
@@ -364,7 +416,7 @@ fn generate_stm32_exti_interrupts(board: &BoardConfig) -> TokenStream2 {
 
 /// Get pin number from pin str.
 /// For example, if the pin str is "PD13", this function will return "13".
-fn get_pin_num_stm32(gpio_name: &str) -> Option<String> {
+pub(crate) fn get_pin_num_stm32(gpio_name: &str) -> Option<String> {
     if gpio_name.len() < 3 {
         None
     } else {
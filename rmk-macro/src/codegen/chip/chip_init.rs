@@ -50,9 +50,14 @@ pub(crate) fn chip_init_default(hardware: &Hardware, peripheral_id: Option<usize
     let communication = &hardware.communication;
     let peri_num = hardware.board.get_num_periphreal();
     let set_io_capabilities = if peripheral_id.is_none() {
+        // The passkey is shown on the display of the central
+        let has_display = match &hardware.board {
+            BoardConfig::UniBody(_) => hardware.display.is_some(),
+            BoardConfig::Split(split_config) => split_config.central.display.is_some(),
+        };
         quote! {
-            if ::rmk::ble::passkey_entry_enabled() {
-                stack.set_io_capabilities(::rmk::IoCapabilities::KeyboardOnly);
+            if let Some(io_capabilities) = ::rmk::ble::pairing_io_capabilities(#has_display) {
+                stack.set_io_capabilities(io_capabilities);
             }
         }
     } else {
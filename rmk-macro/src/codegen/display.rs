@@ -40,7 +40,7 @@ pub(crate) fn expand_display_interrupt(
     }
 }
 
-fn expand_i2c_interrupt(chip_series: &ChipSeries, i2c: &I2cConfig) -> TokenStream {
+pub(crate) fn expand_i2c_interrupt(chip_series: &ChipSeries, i2c: &I2cConfig) -> TokenStream {
     let instance = format_ident!("{}", i2c.instance);
 
     match chip_series {
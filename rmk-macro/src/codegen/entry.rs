@@ -56,7 +56,9 @@ pub(crate) fn rmk_entry_select(
 ) -> TokenStream2 {
     let devices_task = {
         let mut devs = devices.clone();
-        devs.push(quote! {matrix});
+        if !matches!(&hardware.board, BoardConfig::Split(split) if split.dongle) {
+            devs.push(quote! {matrix});
+        }
         if hardware.storage.is_some() {
             devs.push(quote! {storage});
         }
@@ -125,7 +127,10 @@ pub(crate) fn rmk_entry_select(
                 };
                 tasks.push(scan_task);
                 join_all_tasks(tasks)
-            } else if split_config.connection == "serial" {
+            } else if matches!(
+                &split_config.connection[..],
+                "serial" | "i2c" | "single_wire"
+            ) {
                 let rmk_task = quote! {
                     ::rmk::run_rmk(#usb_driver_arg rmk_config),
                 };
@@ -133,35 +138,62 @@ pub(crate) fn rmk_entry_select(
                 if !processors.is_empty() {
                     tasks.push(processors_task);
                 };
-                let central_serials = split_config
-                    .central
-                    .serial
-                    .clone()
-                    .expect("No serial defined for central");
                 split_config.peripheral.iter().enumerate().for_each(|(idx, p)| {
                     let row = p.rows;
                     let col = p.cols;
                     let row_offset = p.row_offset;
                     let col_offset = p.col_offset;
-                    let uart_instance = format_ident!(
-                        "{}",
-                        central_serials
-                            .get(idx)
-                            .expect("No or not enough serial defined for peripheral in central")
-                            .instance
-                            .to_lowercase()
-                    );
-                    tasks.push(quote! {
-                        ::rmk::split::central::run_peripheral_manager::<#row, #col, #row_offset, #col_offset, _>(
-                            #idx,
-                            #uart_instance,
-                        )
-                    });
+                    let manager = match &split_config.connection[..] {
+                        "serial" => {
+                            let uart_instance = format_ident!(
+                                "{}",
+                                split_config
+                                    .central
+                                    .serial
+                                    .as_ref()
+                                    .expect("No serial defined for central")
+                                    .get(idx)
+                                    .expect("No or not enough serial defined for peripheral in central")
+                                    .instance
+                                    .to_lowercase()
+                            );
+                            quote! {
+                                ::rmk::split::central::run_peripheral_manager::<#row, #col, #row_offset, #col_offset, _>(
+                                    #idx,
+                                    #uart_instance,
+                                )
+                            }
+                        }
+                        "i2c" => {
+                            let address = p
+                                .i2c
+                                .as_ref()
+                                .expect("No i2c defined for peripheral")
+                                .address;
+                            quote! {
+                                ::rmk::split::i2c::run_i2c_peripheral_manager::<#row, #col, #row_offset, #col_offset, _>(
+                                    #idx,
+                                    &split_i2c_bus,
+                                    #address,
+                                )
+                            }
+                        }
+                        _ => {
+                            let single_wire = format_ident!("split_single_wire{}", idx);
+                            quote! {
+                                ::rmk::split::central::run_peripheral_manager::<#row, #col, #row_offset, #col_offset, _>(
+                                    #idx,
+                                    #single_wire,
+                                )
+                            }
+                        }
+                    };
+                    tasks.push(manager);
                 });
                 join_all_tasks(tasks)
             } else {
                 panic!(
-                    "Invalid split connection type: {}, only \"ble\" and \"serial\" are supported",
+                    "Invalid split connection type: {}, only \"ble\", \"serial\", \"i2c\" and \"single_wire\" are supported",
                     split_config.connection
                 );
             }
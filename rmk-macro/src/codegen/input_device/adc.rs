@@ -1,5 +1,8 @@
+use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
-use rmk_config::resolved::hardware::{BleConfig, ChipSeries, JoystickConfig};
+use rmk_config::resolved::hardware::{
+    BatteryChemistry, BatteryConfig, BleConfig, ChipSeries, JoystickConfig, JoystickMode,
+};
 
 use super::Initializer;
 
@@ -8,6 +11,7 @@ use super::Initializer;
 pub(crate) fn expand_adc_device(
     joystick_config: Vec<JoystickConfig>,
     ble_config: Option<BleConfig>,
+    battery_config: &BatteryConfig,
     chip_model: ChipSeries,
 ) -> (Vec<Initializer>, Vec<Initializer>) {
     match chip_model {
@@ -49,9 +53,16 @@ pub(crate) fn expand_adc_device(
                     )
                 };
                 let bat_ident = format_ident!("battery_processor");
+                let battery_options = expand_battery_options(
+                    battery_config,
+                    &ChipSeries::Nrf52,
+                    adc_divider_measured,
+                    adc_divider_total,
+                );
                 let battery_processor = Initializer {
                     initializer: quote! {
-                        let mut #bat_ident = ::rmk::input_device::battery::BatteryProcessor::new(#adc_divider_measured, #adc_divider_total);
+                        let mut #bat_ident = ::rmk::input_device::battery::BatteryProcessor::new(#adc_divider_measured, #adc_divider_total)
+                            #battery_options;
                     },
                     var_name: bat_ident,
                 };
@@ -66,7 +77,7 @@ pub(crate) fn expand_adc_device(
 
             for joystick in joystick_config {
                 let mut cnt = 0u8;
-                for pin in [joystick.pin_x, joystick.pin_y, joystick.pin_z].iter() {
+                for pin in [&joystick.pin_x, &joystick.pin_y, &joystick.pin_z] {
                     if pin == "_" {
                         break;
                     }
@@ -81,15 +92,22 @@ pub(crate) fn expand_adc_device(
                     ::rmk::input_device::adc::AnalogEventType::Joystick(#cnt)
                 });
                 let joy_ident = format_ident!("joystick_processor_{}", joystick.name);
+                let modes = expand_joystick_modes(&joystick);
+                let keys = expand_joystick_keys(&joystick);
                 let JoystickConfig {
                     transform,
                     bias,
                     resolution,
+                    scroll_divisor,
                     ..
                 } = joystick;
+                let scroll_divisor = scroll_divisor.unwrap_or(8);
                 let joystick_processor = Initializer {
                     initializer: quote! {
-                        let mut #joy_ident = rmk::input_device::joystick::JoystickProcessor::new([#([#(#transform),*]),*], [#(#bias),*], #resolution, &keymap);
+                        let mut #joy_ident = rmk::input_device::joystick::JoystickProcessor::new([#([#(#transform),*]),*], [#(#bias),*], #resolution, &keymap)
+                            .with_modes(&[#(#modes),*])
+                            .with_scroll_divisor(#scroll_divisor)
+                            #keys;
                     },
                     var_name: joy_ident,
                 };
@@ -132,6 +150,171 @@ pub(crate) fn expand_adc_device(
                 (Vec::new(), Vec::new())
             }
         }
+        ChipSeries::Rp2040 | ChipSeries::Esp32 => {
+            let Some(ble) = ble_config.filter(|ble| ble.enabled) else {
+                return (Vec::new(), Vec::new());
+            };
+            let Some(adc_pin) = ble.battery_adc_pin else {
+                return (Vec::new(), Vec::new());
+            };
+            if adc_pin == "vddh" {
+                panic!("battery_adc_pin = \"vddh\" is only supported on nRF52");
+            }
+            let adc_pin = format_ident!("{}", adc_pin);
+            let adc_reader = if chip_model == ChipSeries::Rp2040 {
+                quote! {
+                    ::embassy_rp::bind_interrupts!(struct AdcIrqs {
+                        ADC_IRQ_FIFO => ::embassy_rp::adc::InterruptHandler;
+                    });
+                    let adc = ::embassy_rp::adc::Adc::new(p.ADC, AdcIrqs, ::embassy_rp::adc::Config::default());
+                    let channel = ::embassy_rp::adc::Channel::new_pin(p.#adc_pin, ::embassy_rp::gpio::Pull::None);
+                    ::rmk::input_device::adc::RpAdc::new(adc, channel)
+                }
+            } else {
+                quote! {
+                    use ::esp_hal::analog::adc::{Adc, AdcConfig, Attenuation};
+                    let mut adc_config = AdcConfig::new();
+                    let mut pin = adc_config.enable_pin(p.#adc_pin, Attenuation::_11dB);
+                    let mut adc = Adc::new(p.ADC1, adc_config);
+                    ::rmk::input_device::adc::PollAdc(move || adc.read_oneshot(&mut pin).ok())
+                }
+            };
+            let adc_device = Initializer {
+                initializer: quote! {
+                    let mut battery_adc = {
+                        let adc_reader = { #adc_reader };
+                        ::rmk::input_device::adc::BatteryAdc::new(adc_reader, ::embassy_time::Duration::from_secs(30))
+                    };
+                },
+                var_name: format_ident!("battery_adc"),
+            };
+
+            let adc_divider_measured = ble.adc_divider_measured.unwrap_or(1);
+            let adc_divider_total = ble.adc_divider_total.unwrap_or(1);
+            let battery_options = expand_battery_options(
+                battery_config,
+                &chip_model,
+                adc_divider_measured,
+                adc_divider_total,
+            );
+            let bat_ident = format_ident!("battery_processor");
+            let battery_processor = Initializer {
+                initializer: quote! {
+                    let mut #bat_ident = ::rmk::input_device::battery::BatteryProcessor::new(#adc_divider_measured, #adc_divider_total)
+                        #battery_options;
+                },
+                var_name: bat_ident,
+            };
+            (vec![adc_device], vec![battery_processor])
+        }
         _ => (Vec::new(), Vec::new()),
     }
 }
+
+/// Expand the builder calls of the battery processor.
+///
+/// ADC settings which aren't set in `[battery]` use the defaults of the ADC of the chip.
+/// `BatteryProcessor::new` already uses the nRF52 SAADC defaults, so they are only set for other chips.
+fn expand_battery_options(
+    battery: &BatteryConfig,
+    chip_model: &ChipSeries,
+    divider_measured: u32,
+    divider_total: u32,
+) -> TokenStream {
+    let adc = if battery.adc_reference_mv.is_some()
+        || battery.adc_gain.is_some()
+        || battery.adc_resolution.is_some()
+        || *chip_model != ChipSeries::Nrf52
+    {
+        let (default_reference_mv, default_gain) = match chip_model {
+            ChipSeries::Rp2040 => (3300, [1, 1]),
+            // 11dB attenuation
+            ChipSeries::Esp32 => (3100, [1, 1]),
+            _ => (600, [1, 6]),
+        };
+        let reference_mv = battery.adc_reference_mv.unwrap_or(default_reference_mv);
+        let [gain_num, gain_den] = battery.adc_gain.unwrap_or(default_gain);
+        let resolution = battery.adc_resolution.unwrap_or(12);
+        quote! {
+            .with_adc(::rmk::input_device::battery::AdcConfig {
+                reference_mv: #reference_mv,
+                gain: (#gain_num, #gain_den),
+                resolution: #resolution,
+                divider_measured: #divider_measured,
+                divider_total: #divider_total,
+            })
+        }
+    } else {
+        quote! {}
+    };
+    let curve = match (&battery.curve, battery.chemistry) {
+        (Some(points), _) => {
+            let points = points
+                .iter()
+                .map(|(mv, percent)| quote! { (#mv, #percent) });
+            quote! { .with_curve(::rmk::input_device::battery::BatteryCurve::new(&[#(#points),*])) }
+        }
+        (None, BatteryChemistry::Lipo) => quote! {},
+        (None, BatteryChemistry::Lifepo4) => {
+            quote! { .with_curve(::rmk::input_device::battery::BatteryCurve::LIFEPO4) }
+        }
+        (None, BatteryChemistry::Alkaline2aa) => {
+            quote! { .with_curve(::rmk::input_device::battery::BatteryCurve::ALKALINE_2AA) }
+        }
+    };
+    let threshold = match battery.low_battery_threshold {
+        Some(threshold) => quote! { .with_low_battery_threshold(#threshold) },
+        None => quote! {},
+    };
+    quote! { #adc #curve #threshold }
+}
+
+/// Expand the output mode of each layer of the joystick
+fn expand_joystick_modes(joystick: &JoystickConfig) -> Vec<TokenStream> {
+    joystick
+        .modes
+        .iter()
+        .map(|mode| match mode {
+            JoystickMode::Mouse => quote! { ::rmk::input_device::joystick::JoystickMode::Mouse },
+            JoystickMode::Scroll => quote! { ::rmk::input_device::joystick::JoystickMode::Scroll },
+            JoystickMode::Keys => quote! { ::rmk::input_device::joystick::JoystickMode::Keys },
+            JoystickMode::Gamepad => {
+                quote! { ::rmk::input_device::joystick::JoystickMode::Gamepad }
+            }
+        })
+        .collect()
+}
+
+/// Expand the `with_keys` call of the joystick processor, which is needed by the keys mode
+fn expand_joystick_keys(joystick: &JoystickConfig) -> TokenStream {
+    let Some(keys) = &joystick.keys else {
+        if joystick.modes.contains(&JoystickMode::Keys) {
+            panic!(
+                "joystick {}: `keys` is required by the keys mode",
+                joystick.name
+            );
+        }
+        return quote! {};
+    };
+    if keys.len() != 4 {
+        panic!(
+            "joystick {}: `keys` should be 4 positions of up, down, left and right, got {}",
+            joystick.name,
+            keys.len()
+        );
+    }
+    let positions = keys.iter().map(|[row, col]| {
+        quote! { ::rmk::event::KeyPos { row: #row, col: #col } }
+    });
+    let dead_zone = joystick.dead_zone.unwrap_or(20);
+    let hysteresis = joystick.hysteresis.unwrap_or(5);
+    let eight_way = joystick.eight_way;
+    quote! {
+        .with_keys(::rmk::input_device::joystick::JoystickKeys {
+            positions: [#(#positions),*],
+            dead_zone: #dead_zone,
+            hysteresis: #hysteresis,
+            eight_way: #eight_way,
+        })
+    }
+}
@@ -1,6 +1,7 @@
 use adc::expand_adc_device;
 use encoder::expand_encoder_device;
 use iqs5xx::expand_iqs5xx_device;
+use pinnacle::expand_pinnacle_device;
 use pmw33xx::expand_pmw33xx_device;
 use pmw3610::expand_pmw3610_device;
 use proc_macro2::{Ident, TokenStream};
@@ -13,6 +14,7 @@ use rmk_config::resolved::hardware::{
 pub(crate) mod adc;
 pub(crate) mod encoder;
 pub(crate) mod iqs5xx;
+pub(crate) mod pinnacle;
 pub(crate) mod pmw33xx;
 pub(crate) mod pmw3610;
 
@@ -45,6 +47,7 @@ pub(crate) fn expand_input_device_config(
         BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => expand_adc_device(
             input_device.clone().joystick.unwrap_or(Vec::new()),
             ble_config,
+            &hardware.battery,
             chip.series.clone(),
         ),
         BoardConfig::Split(split_config) => {
@@ -93,6 +96,7 @@ pub(crate) fn expand_input_device_config(
                     .joystick
                     .unwrap_or(Vec::new()),
                 central_ble_config,
+                &hardware.battery,
                 chip.series.clone(),
             )
         }
@@ -295,5 +299,126 @@ pub(crate) fn expand_input_device_config(
         }
     }
 
+    // generate Cirque Pinnacle configuration
+    let (pinnacle_device_initializers, pinnacle_processor_initializers) = match board {
+        BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => {
+            expand_pinnacle_device(input_device.clone().pinnacle.unwrap_or(Vec::new()), chip)
+        }
+        BoardConfig::Split(split_config) => expand_pinnacle_device(
+            split_config
+                .central
+                .input_device
+                .clone()
+                .unwrap_or(InputDeviceConfig::default())
+                .pinnacle
+                .unwrap_or(Vec::new()),
+            chip,
+        ),
+    };
+
+    for initializer in pinnacle_device_initializers {
+        initialization.extend(initializer.initializer);
+        let device_name = initializer.var_name;
+        devices.push(quote! { #device_name });
+    }
+
+    for initializer in pinnacle_processor_initializers {
+        initialization.extend(initializer.initializer);
+        let processor_name = initializer.var_name;
+        processors.push(quote! { #processor_name });
+    }
+
+    // For split keyboards, also generate processors for Pinnacle devices on peripherals
+    // The devices run on peripherals, but processors need to run on central to handle the events
+    if let BoardConfig::Split(split_config) = board {
+        for peripheral in &split_config.peripheral {
+            let peripheral_pinnacle_config = peripheral
+                .input_device
+                .clone()
+                .unwrap_or(InputDeviceConfig::default())
+                .pinnacle
+                .unwrap_or(Vec::new());
+
+            // Only generate processors (not devices) for peripheral Pinnacle
+            let (_, peripheral_pinnacle_processors) =
+                expand_pinnacle_device(peripheral_pinnacle_config, chip);
+
+            for initializer in peripheral_pinnacle_processors {
+                initialization.extend(initializer.initializer);
+                let processor_name = initializer.var_name;
+                processors.push(quote! { #processor_name });
+            }
+        }
+    }
+
     (initialization, devices, processors)
 }
+
+/// Same as `rmk::config::POINTING_CPI_DEVICE_MAX_NUM`
+const POINTING_CPI_DEVICE_MAX_NUM: usize = 8;
+
+/// Expands the CPI presets of pointing devices, which are switched by the CPI actions in the keymap.
+///
+/// Devices on split peripherals are included, because the CPI is set on the central and forwarded to them.
+pub(crate) fn expand_pointing_cpi_config(hardware: &Hardware) -> TokenStream {
+    let input_devices = match &hardware.board {
+        BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => vec![input_device.clone()],
+        BoardConfig::Split(split_config) => core::iter::once(&split_config.central)
+            .chain(split_config.peripheral.iter())
+            .map(|board| board.input_device.clone().unwrap_or_default())
+            .collect(),
+    };
+
+    // (device id, initial cpi, presets)
+    let mut sensors: Vec<(u8, Option<u16>, Vec<u16>)> = Vec::new();
+    for input_device in input_devices {
+        for s in input_device.pmw3610.unwrap_or_default() {
+            sensors.push((s.id.unwrap_or(0), s.cpi, s.cpi_presets));
+        }
+        for s in input_device.pmw33xx.unwrap_or_default() {
+            sensors.push((s.id.unwrap_or(0), s.cpi, s.cpi_presets));
+        }
+        for s in input_device.pinnacle.unwrap_or_default() {
+            sensors.push((s.id.unwrap_or(0), s.cpi, s.cpi_presets));
+        }
+    }
+
+    let sensors: Vec<_> = sensors
+        .into_iter()
+        .filter(|(_, _, presets)| !presets.is_empty())
+        .collect();
+    if sensors.len() > POINTING_CPI_DEVICE_MAX_NUM {
+        panic!(
+            "\n\u{274c} keyboard.toml: at most {} pointing devices can have cpi_presets, got {}",
+            POINTING_CPI_DEVICE_MAX_NUM,
+            sensors.len()
+        );
+    }
+
+    let devices = sensors
+        .into_iter()
+        .map(|(device_id, cpi, presets)| {
+            if presets.len() > u8::MAX as usize {
+                panic!(
+                    "\n\u{274c} keyboard.toml: pointing device {device_id} has more than 255 cpi_presets"
+                );
+            }
+            // Start from the preset matching `cpi`, or the first one
+            let current = cpi
+                .and_then(|cpi| presets.iter().position(|p| *p == cpi))
+                .unwrap_or(0) as u8;
+            quote! {
+                ::rmk::config::PointingCpiPresets {
+                    device_id: #device_id,
+                    presets: &[#(#presets),*],
+                    current: #current,
+                }
+            }
+        });
+
+    quote! {
+        ::rmk::config::PointingCpiConfig {
+            devices: ::rmk::heapless::Vec::from_iter([#(#devices),*]),
+        }
+    }
+}
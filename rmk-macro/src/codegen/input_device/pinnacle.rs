@@ -0,0 +1,259 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use rmk_config::resolved::hardware::{ChipModel, ChipSeries, PinnacleConfig, PinnacleMode};
+
+use super::Initializer;
+
+/// Expand Cirque Pinnacle device configuration.
+/// Returns (device initializers, processor initializers).
+pub(crate) fn expand_pinnacle_device(
+    pinnacle_config: Vec<PinnacleConfig>,
+    chip: &ChipModel,
+) -> (Vec<Initializer>, Vec<Initializer>) {
+    if pinnacle_config.is_empty() {
+        return (Vec::new(), Vec::new());
+    }
+
+    match chip.series {
+        ChipSeries::Nrf52 | ChipSeries::Rp2040 => {}
+        _ => {
+            panic!("Cirque Pinnacle is only supported on nRF52 and RP2040 chips");
+        }
+    }
+
+    let mut device_initializers = vec![];
+    let mut processor_initializers = vec![];
+
+    for (idx, sensor) in pinnacle_config.iter().enumerate() {
+        let sensor_id = sensor.id.unwrap_or(0);
+        let sensor_name = if sensor.name.is_empty() {
+            format!("pinnacle_{}_id{}", idx, sensor_id)
+        } else {
+            format!("{}_id{}", sensor.name.clone(), sensor_id)
+        };
+
+        let device_ident = format_ident!("{}_device", sensor_name);
+        let processor_ident = format_ident!("{}_processor", sensor_name);
+        let processor_ident_config = format_ident!("{}_config", processor_ident);
+
+        // Generate config values
+        let mode = match sensor.mode {
+            PinnacleMode::Absolute => {
+                quote! { ::rmk::input_device::pinnacle::PinnacleDataMode::Absolute }
+            }
+            PinnacleMode::Relative => {
+                quote! { ::rmk::input_device::pinnacle::PinnacleDataMode::Relative }
+            }
+        };
+        let attenuation = match sensor.attenuation.unwrap_or(4) {
+            1 => quote! { ::rmk::input_device::pinnacle::PinnacleAttenuation::X1 },
+            2 => quote! { ::rmk::input_device::pinnacle::PinnacleAttenuation::X2 },
+            3 => quote! { ::rmk::input_device::pinnacle::PinnacleAttenuation::X3 },
+            4 => quote! { ::rmk::input_device::pinnacle::PinnacleAttenuation::X4 },
+            a => panic!(
+                "{}: attenuation must be 1, 2, 3 or 4, got {}",
+                sensor_name, a
+            ),
+        };
+        let cpi: u16 = sensor.cpi.unwrap_or(800);
+        let diameter_mm: u8 = sensor.diameter_mm.unwrap_or(35);
+        let tap_term_ms: u16 = sensor.tap_term_ms.unwrap_or(200);
+        let curved_overlay = sensor.curved_overlay;
+        let tap = sensor.tap;
+        let secondary_tap = sensor.secondary_tap;
+        let invert_x = sensor.invert_x;
+        let invert_y = sensor.invert_y;
+        let swap_xy = sensor.swap_xy;
+        let sleep = sensor.sleep;
+        let report_hz: u16 = sensor.report_hz;
+        let proc_invert_x = sensor.proc_invert_x;
+        let proc_invert_y = sensor.proc_invert_y;
+        let proc_swap_xy = sensor.proc_swap_xy;
+
+        // Generate data ready pin initialization (optional)
+        let dr_init = match (&sensor.dr, &chip.series) {
+            (Some(dr_pin), ChipSeries::Nrf52) => {
+                let dr_ident = format_ident!("{}", dr_pin);
+                quote! { Some(::embassy_nrf::gpio::Input::new(p.#dr_ident, ::embassy_nrf::gpio::Pull::None)) }
+            }
+            (Some(dr_pin), ChipSeries::Rp2040) => {
+                let dr_ident = format_ident!("{}", dr_pin);
+                quote! { Some(::embassy_rp::gpio::Input::new(p.#dr_ident, ::embassy_rp::gpio::Pull::None)) }
+            }
+            (None, ChipSeries::Nrf52) => quote! { None::<::embassy_nrf::gpio::Input<'static>> },
+            (None, ChipSeries::Rp2040) => quote! { None::<::embassy_rp::gpio::Input<'static>> },
+            _ => unreachable!(),
+        };
+
+        // Generate bus interface initialization
+        let interface_init = match (&sensor.spi, &sensor.i2c) {
+            (Some(spi), None) => {
+                let instance_ident = format_ident!("{}", spi.instance);
+                let sck_ident = format_ident!("{}", spi.sck);
+                let mosi_ident = format_ident!("{}", spi.mosi);
+                let miso_ident = format_ident!("{}", spi.miso);
+                let cs_ident = format_ident!(
+                    "{}",
+                    spi.cs
+                        .as_ref()
+                        .expect("pinnacle requires `cs` in spi config")
+                );
+                match chip.series {
+                    ChipSeries::Nrf52 => quote! {
+                        use ::embassy_nrf::spim::{Frequency, Spim, Config, MODE_1};
+                        use ::embassy_nrf::gpio::{Output, OutputDrive, Level};
+
+                        let cs = Output::new(p.#cs_ident, Level::High, OutputDrive::Standard);
+                        let mut spi_config = Config::default();
+                        spi_config.frequency = Frequency::M2;
+                        spi_config.mode = MODE_1;
+                        let spi_bus = Spim::new(p.#instance_ident, Irqs, p.#sck_ident, p.#miso_ident, p.#mosi_ident, spi_config);
+
+                        ::rmk::input_device::pinnacle::PinnacleSpi::new(spi_bus, cs)
+                    },
+                    ChipSeries::Rp2040 => quote! {
+                        use ::embassy_rp::spi::{Spi, Config, Polarity, Phase};
+                        use ::embassy_rp::gpio::{Output, Level};
+
+                        let cs = Output::new(p.#cs_ident, Level::High);
+                        let mut spi_config = Config::default();
+                        spi_config.polarity = Polarity::IdleLow;
+                        spi_config.phase = Phase::CaptureOnSecondTransition;
+                        spi_config.frequency = 2_000_000;
+                        let spi_bus = ::embassy_embedded_hal::adapter::BlockingAsync::new(
+                            Spi::new_blocking(p.#instance_ident, p.#sck_ident, p.#mosi_ident, p.#miso_ident, spi_config)
+                        );
+
+                        ::rmk::input_device::pinnacle::PinnacleSpi::new(spi_bus, cs)
+                    },
+                    _ => unreachable!(),
+                }
+            }
+            (None, Some(i2c)) => {
+                let instance_ident = format_ident!("{}", i2c.instance.to_uppercase());
+                let sda_ident = format_ident!("{}", i2c.sda);
+                let scl_ident = format_ident!("{}", i2c.scl);
+                match chip.series {
+                    ChipSeries::Nrf52 => {
+                        let buf_ident = format_ident!("{}_I2C_BUF", sensor_name.to_uppercase());
+                        quote! {
+                            static #buf_ident: ::static_cell::StaticCell<[u8; 16]> = ::static_cell::StaticCell::new();
+                            let i2c = ::embassy_nrf::twim::Twim::new(
+                                p.#instance_ident,
+                                Irqs,
+                                p.#sda_ident,
+                                p.#scl_ident,
+                                ::embassy_nrf::twim::Config::default(),
+                                #buf_ident.init([0u8; 16]),
+                            );
+
+                            ::rmk::input_device::pinnacle::PinnacleI2c::new(i2c)
+                        }
+                    }
+                    ChipSeries::Rp2040 => quote! {
+                        let i2c = ::embassy_rp::i2c::I2c::new_async(
+                            p.#instance_ident,
+                            p.#scl_ident,
+                            p.#sda_ident,
+                            Irqs,
+                            ::embassy_rp::i2c::Config::default(),
+                        );
+
+                        ::rmk::input_device::pinnacle::PinnacleI2c::new(i2c)
+                    },
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!(
+                "{}: pinnacle requires exactly one of `spi` and `i2c`",
+                sensor_name
+            ),
+        };
+
+        let device_init = quote! {
+            let mut #device_ident = {
+                use ::rmk::input_device::pinnacle::{Pinnacle, PinnacleConfig};
+                use ::rmk::input_device::pointing::PointingDevice;
+
+                let interface = { #interface_init };
+                let dr = #dr_init;
+
+                let config = PinnacleConfig {
+                    mode: #mode,
+                    cpi: #cpi,
+                    diameter_mm: #diameter_mm,
+                    attenuation: #attenuation,
+                    curved_overlay: #curved_overlay,
+                    tap: #tap,
+                    secondary_tap: #secondary_tap,
+                    tap_term_ms: #tap_term_ms,
+                    invert_x: #invert_x,
+                    invert_y: #invert_y,
+                    swap_xy: #swap_xy,
+                    sleep: #sleep,
+                };
+
+                PointingDevice::<Pinnacle<_, _>>::with_report_hz(#sensor_id, interface, dr, config, #report_hz)
+            };
+        };
+
+        device_initializers.push(Initializer {
+            initializer: device_init,
+            var_name: device_ident,
+        });
+
+        let processor_init = quote! {
+            let #processor_ident_config = ::rmk::input_device::pointing::PointingProcessorConfig {
+                invert_x: #proc_invert_x,
+                invert_y: #proc_invert_y,
+                swap_xy: #proc_swap_xy,
+            };
+            let mut #processor_ident = ::rmk::input_device::pointing::PointingProcessor::new(
+                &keymap,
+                #processor_ident_config,
+            );
+        };
+
+        processor_initializers.push(Initializer {
+            initializer: processor_init,
+            var_name: processor_ident,
+        });
+    }
+
+    (device_initializers, processor_initializers)
+}
+
+/// Generate `bind_interrupts!` entries for the SPI / I²C peripherals used by
+/// Cirque Pinnacle devices on `chip`. Returns an empty token stream if there are no devices.
+pub(crate) fn expand_pinnacle_interrupts(
+    chip_series: &ChipSeries,
+    pinnacle_config: &[PinnacleConfig],
+) -> TokenStream {
+    if pinnacle_config.is_empty() {
+        return quote! {};
+    }
+    let entries = pinnacle_config.iter().map(|sensor| match (&sensor.spi, &sensor.i2c, chip_series) {
+        (Some(spi), _, ChipSeries::Nrf52) => {
+            let instance = format_ident!("{}", spi.instance);
+            quote! {
+                #instance => ::embassy_nrf::spim::InterruptHandler<::embassy_nrf::peripherals::#instance>;
+            }
+        }
+        (None, Some(i2c), ChipSeries::Nrf52) => {
+            let instance = format_ident!("{}", i2c.instance.to_uppercase());
+            quote! {
+                #instance => ::embassy_nrf::twim::InterruptHandler<::embassy_nrf::peripherals::#instance>;
+            }
+        }
+        (None, Some(i2c), ChipSeries::Rp2040) => {
+            let instance = format_ident!("{}", i2c.instance.to_uppercase());
+            let irq = format_ident!("{}_IRQ", i2c.instance.to_uppercase());
+            quote! {
+                #irq => ::embassy_rp::i2c::InterruptHandler<::embassy_rp::peripherals::#instance>;
+            }
+        }
+        // Blocking SPI on RP2040 doesn't need an interrupt
+        _ => quote! {},
+    });
+    quote! { #(#entries)* }
+}
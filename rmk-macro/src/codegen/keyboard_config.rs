@@ -67,3 +67,60 @@ pub(crate) fn expand_vial_config(host: &Host) -> proc_macro2::TokenStream {
         };
     }
 }
+
+/// Set the build of the keyboard firmware which split halves compare: the version of the keyboard crate and the hash of
+/// `keyboard.toml`, which contains the keymap
+pub(crate) fn expand_firmware_build() -> proc_macro2::TokenStream {
+    let firmware_version: Vec<u8> = [
+        "CARGO_PKG_VERSION_MAJOR",
+        "CARGO_PKG_VERSION_MINOR",
+        "CARGO_PKG_VERSION_PATCH",
+    ]
+    .iter()
+    .map(|v| {
+        std::env::var(v)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0)
+    })
+    .collect();
+    let config_hash = std::env::var("KEYBOARD_TOML_PATH")
+        .ok()
+        .and_then(|path| std::fs::read(path).ok())
+        .map_or(0, |config| config_hash(&config));
+    quote! {
+        ::rmk::split::set_firmware_build([#(#firmware_version),*], #config_hash);
+    }
+}
+
+/// FNV-1a hash of the config file.
+///
+/// `\r` is skipped, so the same config checked out with different line endings has the same hash.
+fn config_hash(config: &[u8]) -> u32 {
+    config
+        .iter()
+        .filter(|&&b| b != b'\r')
+        .fold(0x811c_9dc5, |hash, &b| {
+            (hash ^ b as u32).wrapping_mul(0x0100_0193)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::config_hash;
+
+    #[test]
+    fn config_hash_ignores_line_endings() {
+        let config = b"[keyboard]\nname = \"RMK\"\n";
+        assert_eq!(
+            config_hash(config),
+            config_hash(b"[keyboard]\r\nname = \"RMK\"\r\n")
+        );
+        assert_ne!(
+            config_hash(config),
+            config_hash(b"[keyboard]\nname = \"RMK2\"\n")
+        );
+        // Same as the reference FNV-1a
+        assert_eq!(config_hash(b"a"), 0xe40c_292c);
+    }
+}
@@ -49,6 +49,8 @@ pub(crate) fn expand_matrix_config(
                 });
             }
         },
+        // The dongle central has no matrix
+        BoardConfig::Split(split_config) if split_config.dongle => {}
         BoardConfig::Split(split_config) => {
             // Matrix config for split central
             match split_config.central.matrix.matrix_type {
@@ -1,5 +1,5 @@
 use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
+use quote::{format_ident, quote};
 use rmk_config::resolved::hardware::{
     BoardConfig, ChipSeries, KeyInfo, MatrixConfig, MatrixType, UniBodyConfig,
 };
@@ -16,7 +16,7 @@ use super::display::expand_display_config;
 use super::entry::expand_rmk_entry;
 use super::feature::{get_rmk_features, is_feature_enabled};
 use super::import::expand_custom_imports;
-use super::input_device::expand_input_device_config;
+use super::input_device::{expand_input_device_config, expand_pointing_cpi_config};
 use super::keyboard_config::{expand_keyboard_info, expand_vial_config, read_keyboard_toml_config};
 use super::layout::expand_default_keymap;
 use super::matrix::{expand_bootmagic_check, expand_matrix_config};
@@ -221,7 +221,7 @@ fn expand_main(
     let chip_init = expand_chip_init(hardware, None, &item_mod);
     let usb_init = expand_usb_init(hardware, &item_mod);
     let flash_init = expand_flash_init(hardware);
-    let behavior_config = expand_behavior_config(behavior);
+    let behavior_config = expand_behavior_config(behavior, expand_pointing_cpi_config(hardware));
     let matrix_config = expand_matrix_config(hardware, rmk_features);
     let output_config = expand_output_config(hardware);
     let (ble_config, set_ble_config) = expand_ble_config(hardware);
@@ -250,6 +250,19 @@ fn expand_main(
         quote! {}
     };
 
+    // Idle sleep of non-split wireless keyboards, the timeouts are set in `[rmk]`
+    let power_manager_init = if matches!(hardware.board, BoardConfig::UniBody(_))
+        && hardware.communication.ble_enabled()
+    {
+        registered_processors.push(quote! { power_manager.run() });
+        let deep_sleep = expand_esp_deep_sleep(hardware);
+        quote! {
+            let mut power_manager = ::rmk::power::PowerManager::new() #deep_sleep;
+        }
+    } else {
+        quote! {}
+    };
+
     let host_service_init = if host.vial_enabled {
         quote! {
             let mut host_service = ::rmk::host::HostService::new(&keymap, &rmk_config);
@@ -356,6 +369,9 @@ fn expand_main(
             // Initialize display (if configured)
             #display_init
 
+            // Initialize the idle power manager (if needed)
+            #power_manager_init
+
             // Initialize split central config(if needed)
             #split_central_config
 
@@ -389,20 +405,7 @@ pub(crate) fn expand_keymap_and_storage(hardware: &Hardware, layout: &Layout) ->
         quote! { let per_key_config = ::rmk::config::PositionalConfig::new(#key_info_config); }
     };
 
-    let total_num_encoders: usize = layout.encoder_counts.iter().sum();
-
-    let keymap_data_init = if total_num_encoders == 0 {
-        quote! {
-            let mut keymap_data = ::rmk::KeymapData::new(get_default_keymap());
-        }
-    } else {
-        quote! {
-            let mut keymap_data = ::rmk::KeymapData::new_with_encoder(
-                get_default_keymap(),
-                get_default_encoder_map(),
-            );
-        }
-    };
+    let keymap_data_init = expand_keymap_data_init(layout);
 
     if hardware.storage.is_some() {
         quote! {
@@ -429,6 +432,24 @@ pub(crate) fn expand_keymap_and_storage(hardware: &Hardware, layout: &Layout) ->
     }
 }
 
+/// Initialize the default keymap as `keymap_data`
+pub(crate) fn expand_keymap_data_init(layout: &Layout) -> TokenStream2 {
+    let total_num_encoders: usize = layout.encoder_counts.iter().sum();
+
+    if total_num_encoders == 0 {
+        quote! {
+            let mut keymap_data = ::rmk::KeymapData::new(get_default_keymap());
+        }
+    } else {
+        quote! {
+            let mut keymap_data = ::rmk::KeymapData::new_with_encoder(
+                get_default_keymap(),
+                get_default_encoder_map(),
+            );
+        }
+    }
+}
+
 pub(crate) fn expand_matrix_and_keyboard_init(hardware: &Hardware) -> TokenStream2 {
     let matrix = match &hardware.board {
         BoardConfig::UniBody(UniBodyConfig {
@@ -456,6 +477,8 @@ pub(crate) fn expand_matrix_and_keyboard_init(hardware: &Hardware) -> TokenStrea
                 }
             }
         }
+        // The dongle central has no matrix, all keys come from the peripherals
+        BoardConfig::Split(split_config) if split_config.dongle => quote! {},
         BoardConfig::Split(split_config) => {
             // Matrix config for split central
             let central_row = split_config.central.rows;
@@ -527,3 +550,46 @@ pub(crate) fn get_debouncer_type(matrix_config: &MatrixConfig) -> TokenStream2 {
         _ => panic!("Invalid debouncer type, supported debouncer types are `default` and `fast`"),
     }
 }
+
+/// Expand the deep sleep hook of ESP32, which is woken up by the direct pins of the matrix.
+///
+/// A normal matrix needs its outputs driven while sleeping, which are not held in ESP32 deep sleep,
+/// so it only sleeps lightly.
+fn expand_esp_deep_sleep(hardware: &Hardware) -> TokenStream2 {
+    let BoardConfig::UniBody(UniBodyConfig { matrix, .. }) = &hardware.board else {
+        return quote! {};
+    };
+    if hardware.chip.series != ChipSeries::Esp32
+        || !matches!(matrix.matrix_type, MatrixType::DirectPin)
+    {
+        return quote! {};
+    }
+    let level = if matrix.direct_pin_low_active {
+        format_ident!("Low")
+    } else {
+        format_ident!("High")
+    };
+    let pins: Vec<_> = matrix
+        .direct_pins
+        .iter()
+        .flatten()
+        .flatten()
+        .filter(|p| p.as_str() != "_" && p.to_lowercase() != "trns")
+        .map(|p| format_ident!("{}", p))
+        .collect();
+    let vars: Vec<_> = (0..pins.len())
+        .map(|i| format_ident!("wake_pin_{}", i))
+        .collect();
+    quote! {
+        .with_deep_sleep({
+            fn esp_deep_sleep() -> ! {
+                // The chip resets when it wakes up, so the pins can be taken from the matrix
+                #(let mut #vars = unsafe { ::esp_hal::peripherals::#pins::steal() };)*
+                ::rmk::power::esp_deep_sleep(&mut [
+                    #((&mut #vars as ::rmk::power::EspWakePin<'_>, ::esp_hal::rtc_cntl::sleep::WakeupLevel::#level)),*
+                ])
+            }
+            esp_deep_sleep
+        })
+    }
+}
@@ -2,14 +2,30 @@ use core::panic;
 
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::codegen::chip::bind_interrupt::get_pin_num_stm32;
+use crate::codegen::display::expand_i2c_interrupt;
+use crate::codegen::keyboard_config::expand_firmware_build;
 use rmk_config::resolved::Hardware;
 use rmk_config::resolved::hardware::{
-    BoardConfig, ChipModel, ChipSeries, SerialConfig, SplitConfig,
+    BoardConfig, ChipModel, ChipSeries, I2cConfig, SerialConfig, SplitBoardConfig, SplitConfig,
 };
 
 pub(crate) fn expand_split_central_config(hardware: &Hardware) -> proc_macro2::TokenStream {
     if let BoardConfig::Split(split_config) = &hardware.board {
-        expand_split_communication_config(&hardware.chip, split_config)
+        let communication = expand_split_communication_config(&hardware.chip, split_config);
+        let confirm_build = if split_config.confirm_mismatched_build {
+            quote! { ::rmk::split::central::set_confirm_mismatched_build(true); }
+        } else {
+            quote! {}
+        };
+        let firmware_build = expand_firmware_build();
+        quote! {
+            #communication
+            #confirm_build
+            #firmware_build
+        }
     } else {
         quote! {}
     }
@@ -20,9 +36,31 @@ fn expand_split_communication_config(chip: &ChipModel, split_config: &SplitConfi
         "ble" => {
             // We need to create addrs for BLE
             let num_peripheral = split_config.peripheral.len();
+            let conn_params = split_config
+                .peripheral
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.ble_conn_interval_us.is_some() || p.ble_conn_latency.is_some())
+                .map(|(idx, p)| {
+                    let interval = match p.ble_conn_interval_us {
+                        Some(us) => {
+                            let us = us as u64;
+                            quote! { ::embassy_time::Duration::from_micros(#us) }
+                        }
+                        None => quote! { ::rmk::split::ble::central::DEFAULT_PERIPHERAL_CONN_INTERVAL },
+                    };
+                    let latency = match p.ble_conn_latency {
+                        Some(latency) => quote! { #latency },
+                        None => quote! { ::rmk::split::ble::central::DEFAULT_PERIPHERAL_CONN_LATENCY },
+                    };
+                    quote! {
+                        ::rmk::split::ble::central::set_peripheral_conn_params(#idx, #interval, #latency);
+                    }
+                });
             quote! {
                 // Must run before the storage task starts (both need `&mut storage`).
                 let peripheral_addrs = storage.read_peripheral_addresses::<#num_peripheral>().await;
+                #(#conn_params)*
             }
         }
         "serial" => {
@@ -34,10 +72,132 @@ fn expand_split_communication_config(chip: &ChipModel, split_config: &SplitConfi
                 .expect("central.serial is required");
             expand_serial_init(chip, serial_config)
         }
+        "i2c" => {
+            let i2c_config = split_config
+                .central
+                .i2c
+                .as_ref()
+                .expect("central.i2c is required");
+            expand_split_i2c_init(chip, i2c_config)
+        }
+        "single_wire" => {
+            let pins = split_config
+                .central
+                .single_wire
+                .clone()
+                .expect("central.single_wire is required");
+            expand_single_wire_init(chip, pins)
+        }
         _ => panic!("Invalid connection type for split"),
     }
 }
 
+/// Initialize the I2C bus of the central, shared by all peripherals
+fn expand_split_i2c_init(chip: &ChipModel, i2c: &I2cConfig) -> TokenStream2 {
+    let instance = format_ident!("{}", i2c.instance);
+    let sda = format_ident!("{}", i2c.sda);
+    let scl = format_ident!("{}", i2c.scl);
+    match chip.series {
+        ChipSeries::Rp2040 => {
+            let irq = format_ident!("{}_IRQ", i2c.instance);
+            quote! {
+                ::embassy_rp::bind_interrupts!(struct IrqsSplitI2c {
+                    #irq => ::embassy_rp::i2c::InterruptHandler<::embassy_rp::peripherals::#instance>;
+                });
+                let split_i2c = ::embassy_rp::i2c::I2c::new_async(
+                    p.#instance, p.#scl, p.#sda, IrqsSplitI2c, ::embassy_rp::i2c::Config::default()
+                );
+                let split_i2c_bus = ::rmk::split::i2c::SplitI2cBus::new(split_i2c);
+            }
+        }
+        ChipSeries::Nrf52 => quote! {
+            ::embassy_nrf::bind_interrupts!(struct IrqsSplitI2c {
+                #instance => ::embassy_nrf::twim::InterruptHandler<::embassy_nrf::peripherals::#instance>;
+            });
+            static SPLIT_I2C_BUF: ::static_cell::StaticCell<[u8; ::rmk::split::i2c::I2C_FRAME_SIZE]> = ::static_cell::StaticCell::new();
+            let split_i2c = ::embassy_nrf::twim::Twim::new(
+                p.#instance,
+                IrqsSplitI2c,
+                p.#sda,
+                p.#scl,
+                ::embassy_nrf::twim::Config::default(),
+                SPLIT_I2C_BUF.init([0_u8; ::rmk::split::i2c::I2C_FRAME_SIZE]),
+            );
+            let split_i2c_bus = ::rmk::split::i2c::SplitI2cBus::new(split_i2c);
+        },
+        ChipSeries::Stm32 => {
+            let (tx_dma, rx_dma) = get_stm32_i2c_dma(i2c);
+            // The I2C interrupts are bound in `Irqs`, together with the interrupts of the DMA channels
+            quote! {
+                let split_i2c = ::embassy_stm32::i2c::I2c::new(
+                    p.#instance, p.#scl, p.#sda, p.#tx_dma, p.#rx_dma, Irqs, ::embassy_stm32::i2c::Config::default()
+                );
+                let split_i2c_bus = ::rmk::split::i2c::SplitI2cBus::new(split_i2c);
+            }
+        }
+        ChipSeries::Esp32 => {
+            panic!("\n\u{274c} keyboard.toml: `i2c` split isn't supported on ESP32")
+        }
+    }
+}
+
+/// Initialize the single-wire UARTs, the `idx`th one is named `split_single_wire{idx}`
+pub(crate) fn expand_single_wire_init(chip: &ChipModel, pins: Vec<String>) -> TokenStream2 {
+    let mut initializers = TokenStream2::new();
+    for (idx, pin) in pins.iter().enumerate() {
+        let name = format_ident!("split_single_wire{}", idx);
+        let pin_ident = format_ident!("{}", pin);
+        let flex = match chip.series {
+            ChipSeries::Rp2040 => quote! { ::embassy_rp::gpio::Flex::new(p.#pin_ident) },
+            ChipSeries::Nrf52 => quote! { ::embassy_nrf::gpio::Flex::new(p.#pin_ident) },
+            ChipSeries::Stm32 => {
+                // The EXTI interrupt of the pin is bound in `Irqs`
+                let pin_num = get_pin_num_stm32(pin).expect("Invalid single_wire pin");
+                let exti = format_ident!("EXTI{}", pin_num);
+                quote! { ::rmk::driver::flex_pin::stm32::ExtiFlex::new(p.#pin_ident, p.#exti, Irqs) }
+            }
+            ChipSeries::Esp32 => {
+                panic!("\n\u{274c} keyboard.toml: `single_wire` split isn't supported on ESP32")
+            }
+        };
+        initializers.extend(quote! {
+            let #name = ::rmk::split::single_wire::SingleWireUart::new(#flex);
+        });
+    }
+    initializers
+}
+
+/// Interrupts of the wired split which are bound in `Irqs` on STM32.
+///
+/// Returns the interrupts of the split I2C and the single-wire pins, which need the interrupts of their EXTI lines.
+pub(crate) fn expand_stm32_split_interrupts(
+    connection: &str,
+    board: &SplitBoardConfig,
+) -> (TokenStream2, Vec<String>) {
+    match connection {
+        "i2c" => {
+            let i2c_interrupt = board
+                .i2c
+                .as_ref()
+                .map(|i2c| expand_i2c_interrupt(&ChipSeries::Stm32, i2c))
+                .unwrap_or_default();
+            (i2c_interrupt, Vec::new())
+        }
+        "single_wire" => (quote! {}, board.single_wire.clone().unwrap_or_default()),
+        _ => (quote! {}, Vec::new()),
+    }
+}
+
+/// Get the DMA channels of the I2C split on STM32, which are required by the async I2C driver
+pub(crate) fn get_stm32_i2c_dma(i2c: &I2cConfig) -> (Ident, Ident) {
+    match (&i2c.tx_dma, &i2c.rx_dma) {
+        (Some(tx_dma), Some(rx_dma)) => (format_ident!("{}", tx_dma), format_ident!("{}", rx_dma)),
+        _ => panic!(
+            "\n\u{274c} keyboard.toml: `tx_dma` and `rx_dma` of the split `i2c` are required on STM32"
+        ),
+    }
+}
+
 pub(crate) fn expand_serial_init(chip: &ChipModel, serial: Vec<SerialConfig>) -> TokenStream2 {
     let mut uart_initializers = proc_macro2::TokenStream::new();
     serial.iter().enumerate().for_each(|(idx, s)| {
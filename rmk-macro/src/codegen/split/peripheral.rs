@@ -1,16 +1,21 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote};
-use rmk_config::resolved::Hardware;
 use rmk_config::resolved::hardware::{
-    BleConfig, BoardConfig, ChipModel, ChipSeries, CommunicationConfig, InputDeviceConfig,
-    MatrixType, SplitBoardConfig, SplitConfig,
+    BleConfig, BoardConfig, ChipModel, ChipSeries, CommunicationConfig, I2cConfig,
+    InputDeviceConfig, MatrixType, SplitBoardConfig, SplitConfig,
 };
+use rmk_config::resolved::{Behavior, Hardware, Identity, Layout};
 use syn::ItemMod;
 
-use super::central::expand_serial_init;
+use super::central::{
+    expand_serial_init, expand_single_wire_init, expand_stm32_split_interrupts, get_stm32_i2c_dma,
+};
+use crate::codegen::behavior::expand_behavior_config;
+use crate::codegen::chip::bind_interrupt::{find_extern_irqs, generate_stm32_exti_interrupts};
 use crate::codegen::chip::chip_init::expand_chip_init;
+use crate::codegen::chip::comm::expand_usb_init;
 use crate::codegen::chip::flash::expand_flash_init;
-use crate::codegen::chip::gpio::expand_output_initialization;
+use crate::codegen::chip::gpio::{convert_gpio_str_to_input_pin, expand_output_initialization};
 use crate::codegen::display::{expand_display_config, expand_display_interrupt};
 use crate::codegen::entry::join_all_tasks;
 use crate::codegen::feature::{get_rmk_features, is_feature_enabled};
@@ -18,13 +23,18 @@ use crate::codegen::import::expand_custom_imports;
 use crate::codegen::input_device::adc::expand_adc_device;
 use crate::codegen::input_device::encoder::expand_encoder_device;
 use crate::codegen::input_device::iqs5xx::{expand_iqs5xx_device, expand_iqs5xx_interrupts};
+use crate::codegen::input_device::pinnacle::{expand_pinnacle_device, expand_pinnacle_interrupts};
 use crate::codegen::input_device::pmw33xx::expand_pmw33xx_device;
 use crate::codegen::input_device::pmw3610::expand_pmw3610_device;
-use crate::codegen::keyboard_config::read_keyboard_toml_config;
+use crate::codegen::input_device::{Initializer, expand_pointing_cpi_config};
+use crate::codegen::keyboard_config::{
+    expand_firmware_build, expand_keyboard_info, read_keyboard_toml_config,
+};
+use crate::codegen::layout::expand_default_keymap;
 use crate::codegen::matrix::{
     expand_bootmagic_check, expand_matrix_direct_pins, expand_matrix_input_output_pins,
 };
-use crate::codegen::orchestrator::get_debouncer_type;
+use crate::codegen::orchestrator::{expand_keymap_data_init, get_debouncer_type};
 use crate::codegen::registered_processor::expand_registered_processor_init;
 
 /// Parse split peripheral mod and generate a valid RMK main function with all needed code
@@ -43,9 +53,43 @@ pub(crate) fn parse_split_peripheral_mod(
         .hardware()
         .expect("failed to resolve hardware config");
 
-    let main_function = expand_split_peripheral(id, &hardware, item_mod, &rmk_features);
+    // The keymap of the whole keyboard is needed if the peripheral can be master
+    let master = match &hardware.board {
+        BoardConfig::Split(split_config) => split_config
+            .peripheral
+            .get(id)
+            .and_then(|p| p.usb_detect_pin.clone())
+            .map(|usb_detect_pin| MasterConfig {
+                usb_detect_pin,
+                identity: toml_config
+                    .identity()
+                    .expect("failed to resolve identity config"),
+                behavior: toml_config
+                    .behavior()
+                    .expect("failed to resolve behavior config"),
+                layout: toml_config
+                    .layout()
+                    .expect("failed to resolve layout config"),
+            }),
+        _ => None,
+    };
+    let master_constants = if let Some(master) = &master {
+        let keyboard_info = expand_keyboard_info(&master.identity, &master.layout);
+        let default_keymap = expand_default_keymap(&master.layout, &master.behavior);
+        quote! {
+            #keyboard_info
+            #default_keymap
+        }
+    } else {
+        quote! {}
+    };
+
+    let extern_irqs = find_extern_irqs(&item_mod);
+    let main_function =
+        expand_split_peripheral(id, &hardware, item_mod, &rmk_features, master.as_ref());
 
-    let bind_interrupts = expand_bind_interrupt_for_split_peripheral(&hardware.chip, &hardware, id);
+    let bind_interrupts =
+        expand_bind_interrupt_for_split_peripheral(&hardware.chip, &hardware, id, &extern_irqs);
 
     let chip = &hardware.chip;
     let main_function_sig = if chip.series == ChipSeries::Esp32 {
@@ -68,6 +112,8 @@ pub(crate) fn parse_split_peripheral_mod(
     };
 
     quote! {
+        #master_constants
+
         #main_function_sig {
             // ::defmt::info!("RMK start!");
             #main_function
@@ -75,10 +121,19 @@ pub(crate) fn parse_split_peripheral_mod(
     }
 }
 
+/// Config of a peripheral which selects its role by the USB presence at boot
+struct MasterConfig {
+    usb_detect_pin: String,
+    identity: Identity,
+    behavior: Behavior,
+    layout: Layout,
+}
+
 fn expand_bind_interrupt_for_split_peripheral(
     chip: &ChipModel,
     hardware: &Hardware,
     peripheral_id: usize,
+    extern_irqs: &[TokenStream2],
 ) -> TokenStream2 {
     let communication = &hardware.communication;
 
@@ -104,7 +159,44 @@ fn expand_bind_interrupt_for_split_peripheral(
     };
     let iqs5xx_interrupt = expand_iqs5xx_interrupts(&chip.series, &iqs5xx_config_for_irq);
 
+    let pinnacle_config_for_irq = match &hardware.board {
+        BoardConfig::Split(split_config) => split_config.peripheral[peripheral_id]
+            .input_device
+            .clone()
+            .unwrap_or(InputDeviceConfig::default())
+            .pinnacle
+            .unwrap_or(Vec::new()),
+        _ => Vec::new(),
+    };
+    let pinnacle_interrupt = expand_pinnacle_interrupts(&chip.series, &pinnacle_config_for_irq);
+
+    // The USB is used when the peripheral is master
+    let can_be_master = match &hardware.board {
+        BoardConfig::Split(split_config) => split_config.peripheral[peripheral_id]
+            .usb_detect_pin
+            .is_some(),
+        _ => false,
+    };
+
     match chip.series {
+        // Wired nRF peripherals don't run nrf-sdc
+        ChipSeries::Nrf52 if !communication.ble_enabled() => {
+            if display_interrupt.is_empty()
+                && iqs5xx_interrupt.is_empty()
+                && pinnacle_interrupt.is_empty()
+            {
+                quote! {}
+            } else {
+                quote! {
+                    use ::embassy_nrf::bind_interrupts;
+                    bind_interrupts!(struct Irqs {
+                        #iqs5xx_interrupt
+                        #pinnacle_interrupt
+                        #display_interrupt
+                    });
+                }
+            }
+        }
         ChipSeries::Nrf52 => {
             let ble_config = communication.get_ble_config().unwrap();
             let tx_power = if let Some(pwr) = ble_config.default_tx_power {
@@ -170,6 +262,7 @@ fn expand_bind_interrupt_for_split_peripheral(
                     RTC0 => ::nrf_sdc::mpsl::HighPrioInterruptHandler;
                     #pmw33xx_spi_interrupts
                     #iqs5xx_interrupt
+                    #pinnacle_interrupt
                     #display_interrupt
                 });
 
@@ -214,6 +307,7 @@ fn expand_bind_interrupt_for_split_peripheral(
                         PIO0_IRQ_0 => ::embassy_rp::pio::InterruptHandler<::embassy_rp::peripherals::PIO0>;
                         DMA_IRQ_0 => ::embassy_rp::dma::InterruptHandler<::embassy_rp::peripherals::DMA_CH0>, ::embassy_rp::dma::InterruptHandler<::embassy_rp::peripherals::DMA_CH1>;
                         #iqs5xx_interrupt
+                        #pinnacle_interrupt
                         #display_interrupt
                     });
                     #[::embassy_executor::task]
@@ -221,11 +315,29 @@ fn expand_bind_interrupt_for_split_peripheral(
                         runner.run().await
                     }
                 }
-            } else if !display_interrupt.is_empty() || !iqs5xx_interrupt.is_empty() {
+            } else if can_be_master
+                || !display_interrupt.is_empty()
+                || !iqs5xx_interrupt.is_empty()
+                || !pinnacle_interrupt.is_empty()
+            {
+                let usb_interrupt = if can_be_master {
+                    let usb_info = communication
+                        .get_usb_info()
+                        .expect("no usb info for the chip");
+                    let interrupt_name = format_ident!("{}", usb_info.interrupt_name);
+                    let peripheral_name = format_ident!("{}", usb_info.peripheral_name);
+                    quote! {
+                        #interrupt_name => ::embassy_rp::usb::InterruptHandler<::embassy_rp::peripherals::#peripheral_name>;
+                    }
+                } else {
+                    quote! {}
+                };
                 quote! {
                     use ::embassy_rp::bind_interrupts;
                     bind_interrupts!(struct Irqs {
+                        #usb_interrupt
                         #iqs5xx_interrupt
+                        #pinnacle_interrupt
                         #display_interrupt
                     });
                 }
@@ -233,7 +345,44 @@ fn expand_bind_interrupt_for_split_peripheral(
                 quote! {}
             }
         }
-        _ => quote! {},
+        ChipSeries::Stm32 => {
+            let split_config = match &hardware.board {
+                BoardConfig::Split(split_config) => split_config,
+                _ => panic!("Expected split configuration"),
+            };
+            let peripheral_config = &split_config.peripheral[peripheral_id];
+            let (split_interrupt, mut exti_pins) =
+                expand_stm32_split_interrupts(&split_config.connection, peripheral_config);
+            if is_feature_enabled(&get_rmk_features(), "async_matrix") {
+                exti_pins.extend(
+                    peripheral_config
+                        .matrix
+                        .row_pins
+                        .clone()
+                        .unwrap_or_default(),
+                );
+            }
+            let exti_interrupts = generate_stm32_exti_interrupts(&exti_pins);
+            // The interrupts of the DMA channels of the split I2C are added by `add_interrupt!`
+            if exti_interrupts.is_empty()
+                && split_interrupt.is_empty()
+                && display_interrupt.is_empty()
+                && extern_irqs.is_empty()
+            {
+                quote! {}
+            } else {
+                quote! {
+                    use ::embassy_stm32::bind_interrupts;
+                    bind_interrupts!(struct Irqs {
+                        #exti_interrupts
+                        #split_interrupt
+                        #display_interrupt
+                        #(#extern_irqs)*
+                    });
+                }
+            }
+        }
+        ChipSeries::Esp32 => quote! {},
     }
 }
 
@@ -242,6 +391,7 @@ fn expand_split_peripheral(
     hardware: &Hardware,
     item_mod: ItemMod,
     rmk_features: &Option<Vec<String>>,
+    master: Option<&MasterConfig>,
 ) -> TokenStream2 {
     // Check whether keyboard.toml contains split section
     let split_config = match &hardware.board {
@@ -256,8 +406,21 @@ fn expand_split_peripheral(
         .get(id)
         .expect("Missing peripheral config");
 
+    if master.is_some() && split_config.connection != "serial" {
+        panic!("`usb_detect_pin` is only supported by serial split peripherals");
+    }
+    // The USB interrupt of a master peripheral is only bound for RP2040,
+    // which is also the only chip with serial split
+    if master.is_some() && hardware.chip.series != ChipSeries::Rp2040 {
+        panic!(
+            "\n\u{274c} keyboard.toml: `usb_detect_pin` of split peripherals is only supported on RP2040, got {:?}",
+            hardware.chip.series
+        );
+    }
+
     let imports = expand_custom_imports(&item_mod);
     let mut chip_init = expand_chip_init(hardware, Some(id), &item_mod);
+    chip_init.extend(expand_firmware_build());
     if split_config.connection == "ble" {
         // Add storage when using BLE split
         let flash_init = expand_flash_init(hardware);
@@ -267,16 +430,11 @@ fn expand_split_peripheral(
         });
     }
 
-    // Debouncer config
-    let col = peripheral_config.cols;
-    let row = peripheral_config.rows;
-
     // Matrix config
     let async_matrix = is_feature_enabled(rmk_features, "async_matrix");
     let chip = &hardware.chip;
     let mut matrix_config = proc_macro2::TokenStream::new();
     let bootmagic = expand_bootmagic_check(&peripheral_config.matrix);
-    let debouncer_type = get_debouncer_type(&peripheral_config.matrix);
     match &peripheral_config.matrix.matrix_type {
         MatrixType::Normal => {
             matrix_config.extend(expand_matrix_input_output_pins(
@@ -294,15 +452,6 @@ fn expand_split_peripheral(
                 peripheral_config.matrix.row2col,
                 async_matrix,
             ));
-            let col2row = !peripheral_config.matrix.row2col;
-            let num_row = peripheral_config.rows;
-            let num_col = peripheral_config.cols;
-
-            matrix_config.extend(quote! {
-                #bootmagic
-                let debouncer = #debouncer_type::new();
-                let mut matrix = ::rmk::matrix::Matrix::<_, _, _, #num_row, #num_col, #col2row>::new(row_pins, col_pins, debouncer);
-            });
         }
         MatrixType::DirectPin => {
             matrix_config.extend(expand_matrix_direct_pins(
@@ -315,24 +464,15 @@ fn expand_split_peripheral(
                 async_matrix,
                 peripheral_config.matrix.direct_pin_low_active,
             ));
-            // `generic_arg_infer` is a nightly feature. Const arguments cannot yet be inferred with `_` in stable now.
-            // So we need to declaring them in advance.
-            let size = row * col;
-            let low_active = peripheral_config.matrix.direct_pin_low_active;
-
-            matrix_config.extend(quote! {
-                #bootmagic
-                let debouncer = #debouncer_type::new();
-                let mut matrix = ::rmk::matrix::direct_pin::DirectPinMatrix::<_, _, #row, #col, #size>::new(direct_pins, debouncer, #low_active);
-            });
         }
     }
+    matrix_config.extend(bootmagic);
 
     let output_config =
         expand_output_initialization(peripheral_config.output.clone().unwrap_or_default(), chip);
 
     // Get peripheral device and processor configuration
-    let (device_initialization, devices, processors) =
+    let (device_initialization, devices, processors, local_processors) =
         expand_peripheral_input_device_config(id, hardware);
 
     let needs_keymap = peripheral_config
@@ -389,10 +529,43 @@ fn expand_split_peripheral(
         chip,
         split_config,
         peripheral_config,
-        devices,
-        processors,
-        registered_processors,
+        devices.clone(),
+        processors.clone(),
+        registered_processors.clone(),
     );
+    let matrix = expand_peripheral_matrix(peripheral_config, false);
+    let run_rmk_peripheral = quote! {
+        let mut matrix = #matrix;
+        #run_rmk_peripheral
+    };
+
+    let run = if let Some(master) = master {
+        let usb_detect_pin =
+            convert_gpio_str_to_input_pin(chip, master.usb_detect_pin.clone(), false, Some(false));
+        let run_rmk_master = expand_split_peripheral_master(
+            hardware,
+            &item_mod,
+            master,
+            peripheral_config,
+            devices,
+            processors,
+            local_processors,
+            registered_processors,
+        );
+        quote! {
+            let mut usb_detect_pin = #usb_detect_pin;
+            match ::rmk::split::peripheral::detect_split_role(&mut usb_detect_pin).await {
+                ::rmk::split::peripheral::SplitRole::Master => {
+                    #run_rmk_master
+                }
+                ::rmk::split::peripheral::SplitRole::Slave => {
+                    #run_rmk_peripheral
+                }
+            }
+        }
+    } else {
+        run_rmk_peripheral
+    };
 
     quote! {
         #imports
@@ -404,7 +577,119 @@ fn expand_split_peripheral(
         #output_config
         #device_initialization
         #display_init
-        #run_rmk_peripheral
+        #run
+    }
+}
+
+/// Expression which creates the matrix of the peripheral.
+///
+/// The matrix of a master uses the offsets of the peripheral,
+/// so that its keys are at their positions in the keymap of the whole keyboard.
+fn expand_peripheral_matrix(
+    peripheral_config: &SplitBoardConfig,
+    with_offset: bool,
+) -> TokenStream2 {
+    let row = peripheral_config.rows;
+    let col = peripheral_config.cols;
+    let (row_offset, col_offset) = if with_offset {
+        (peripheral_config.row_offset, peripheral_config.col_offset)
+    } else {
+        (0, 0)
+    };
+    let debouncer_type = get_debouncer_type(&peripheral_config.matrix);
+    match &peripheral_config.matrix.matrix_type {
+        MatrixType::Normal => {
+            let col2row = !peripheral_config.matrix.row2col;
+            quote! {
+                {
+                    let debouncer = #debouncer_type::new();
+                    ::rmk::matrix::Matrix::<_, _, _, #row, #col, #col2row, #row_offset, #col_offset>::new(row_pins, col_pins, debouncer)
+                }
+            }
+        }
+        MatrixType::DirectPin => {
+            // `generic_arg_infer` is a nightly feature. Const arguments cannot yet be inferred with `_` in stable now.
+            // So we need to declaring them in advance.
+            let size = row * col;
+            let low_active = peripheral_config.matrix.direct_pin_low_active;
+            quote! {
+                {
+                    let debouncer = #debouncer_type::new();
+                    ::rmk::matrix::direct_pin::DirectPinMatrix::<_, _, #row, #col, #size, #row_offset, #col_offset>::new(direct_pins, debouncer, #low_active)
+                }
+            }
+        }
+    }
+}
+
+/// Run the peripheral as a standalone keyboard over its own USB, with the keymap of the whole keyboard.
+///
+/// Pointing devices of the peripheral are processed locally, with the processor config of the peripheral.
+#[allow(clippy::too_many_arguments)]
+fn expand_split_peripheral_master(
+    hardware: &Hardware,
+    item_mod: &ItemMod,
+    master: &MasterConfig,
+    peripheral_config: &SplitBoardConfig,
+    devices: Vec<TokenStream2>,
+    mut processors: Vec<TokenStream2>,
+    local_processors: Vec<Initializer>,
+    registered_processors: Vec<TokenStream2>,
+) -> TokenStream2 {
+    let usb_init = expand_usb_init(hardware, item_mod);
+    let behavior_config =
+        expand_behavior_config(&master.behavior, expand_pointing_cpi_config(hardware));
+    let keymap_data_init = expand_keymap_data_init(&master.layout);
+    let matrix = expand_peripheral_matrix(peripheral_config, true);
+
+    let mut local_processor_initializers = TokenStream2::new();
+    for initializer in local_processors {
+        local_processor_initializers.extend(initializer.initializer);
+        let processor_name = initializer.var_name;
+        processors.push(quote! { #processor_name });
+    }
+
+    let mut devs = devices;
+    devs.push(quote! {matrix});
+    let mut tasks = vec![
+        quote! {
+            ::rmk::run_all! (
+                #(#devs),*
+            )
+        },
+        quote! { keyboard.run() },
+        quote! { ::rmk::run_rmk(driver, rmk_config) },
+    ];
+    if !processors.is_empty() {
+        tasks.push(quote! {
+            ::rmk::run_all! (
+                #(#processors),*
+            )
+        });
+    }
+    tasks.extend(registered_processors);
+    let run_rmk_master = join_all_tasks(tasks);
+
+    quote! {
+        use ::rmk::core_traits::Runnable;
+        #usb_init
+        #behavior_config
+        #[allow(clippy::needless_update)]
+        let rmk_config = ::rmk::config::RmkConfig {
+            device_config: KEYBOARD_DEVICE_CONFIG,
+            ..Default::default()
+        };
+        let per_key_config = ::rmk::config::PositionalConfig::default();
+        #keymap_data_init
+        let keymap = ::rmk::initialize_keymap(
+            &mut keymap_data,
+            &mut behavior_config,
+            &per_key_config,
+        ).await;
+        let mut keyboard = ::rmk::keyboard::Keyboard::new(&keymap);
+        let mut matrix = #matrix;
+        #local_processor_initializers
+        #run_rmk_master
     }
 }
 
@@ -417,6 +702,10 @@ fn expand_split_peripheral_entry(
     processors: Vec<TokenStream2>,
     registered_processors: Vec<TokenStream2>,
 ) -> TokenStream2 {
+    // Matrix size of the peripheral, checked by the central in the handshake
+    let row = peripheral_config.rows;
+    let col = peripheral_config.cols;
+
     // Add matrix to devices, and run all devices
     let mut devs = devices.clone();
     devs.push(quote! {matrix});
@@ -442,7 +731,7 @@ fn expand_split_peripheral_entry(
 
     if split_config.connection == "ble" {
         let peripheral_run = quote! {
-            ::rmk::split::peripheral::run_rmk_split_peripheral(
+            ::rmk::split::peripheral::run_rmk_split_peripheral::<#row, #col, _>(
                 #id,
                 &stack,
             )
@@ -480,7 +769,7 @@ fn expand_split_peripheral_entry(
                 .to_lowercase()
         );
         let peripheral_run = quote! {
-            ::rmk::split::peripheral::run_rmk_split_peripheral(#uart_instance)
+            ::rmk::split::peripheral::run_rmk_split_peripheral::<#row, #col, _>(#uart_instance)
         };
         let mut tasks = vec![device_task, peripheral_run];
         tasks.extend(registered_processors);
@@ -489,19 +778,121 @@ fn expand_split_peripheral_entry(
             #serial_init
             #run_rmk_peripheral
         }
+    } else if split_config.connection == "i2c" {
+        let i2c_init = expand_i2c_target_init(
+            chip,
+            peripheral_config
+                .i2c
+                .as_ref()
+                .expect("Missing peripheral i2c config"),
+        );
+        let peripheral_run = quote! {
+            ::rmk::split::i2c::run_rmk_i2c_split_peripheral::<#row, #col, _>(split_i2c)
+        };
+        let mut tasks = vec![device_task, peripheral_run];
+        tasks.extend(registered_processors);
+        let run_rmk_peripheral = join_all_tasks(tasks);
+        quote! {
+            #i2c_init
+            #run_rmk_peripheral
+        }
+    } else if split_config.connection == "single_wire" {
+        let pins = peripheral_config
+            .single_wire
+            .clone()
+            .expect("Missing peripheral single_wire config");
+        if pins.len() != 1 {
+            panic!("Peripheral should have only one single_wire pin");
+        }
+        let single_wire_init = expand_single_wire_init(chip, pins);
+        let peripheral_run = quote! {
+            ::rmk::split::peripheral::run_rmk_split_peripheral::<#row, #col, _>(split_single_wire0)
+        };
+        let mut tasks = vec![device_task, peripheral_run];
+        tasks.extend(registered_processors);
+        let run_rmk_peripheral = join_all_tasks(tasks);
+        quote! {
+            #single_wire_init
+            #run_rmk_peripheral
+        }
     } else {
         panic!("Invalid split connection type: {}", split_config.connection);
     }
 }
 
-/// Returns (device initializations, device_names, processor_names)
+/// Initialize the I2C target of the peripheral, its address is the one in the peripheral's i2c config
+fn expand_i2c_target_init(chip: &ChipModel, i2c: &I2cConfig) -> TokenStream2 {
+    let instance = format_ident!("{}", i2c.instance);
+    let sda = format_ident!("{}", i2c.sda);
+    let scl = format_ident!("{}", i2c.scl);
+    let address = i2c.address;
+    match chip.series {
+        ChipSeries::Rp2040 => {
+            let address = address as u16;
+            let irq = format_ident!("{}_IRQ", i2c.instance);
+            quote! {
+                ::embassy_rp::bind_interrupts!(struct IrqsSplitI2c {
+                    #irq => ::embassy_rp::i2c::InterruptHandler<::embassy_rp::peripherals::#instance>;
+                });
+                let split_i2c = ::embassy_rp::i2c_slave::I2cSlave::new(
+                    p.#instance,
+                    p.#scl,
+                    p.#sda,
+                    IrqsSplitI2c,
+                    ::embassy_rp::i2c_slave::Config {
+                        addr: #address,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        ChipSeries::Nrf52 => quote! {
+            ::embassy_nrf::bind_interrupts!(struct IrqsSplitI2c {
+                #instance => ::embassy_nrf::twis::InterruptHandler<::embassy_nrf::peripherals::#instance>;
+            });
+            let split_i2c = ::embassy_nrf::twis::Twis::new(
+                p.#instance,
+                IrqsSplitI2c,
+                p.#sda,
+                p.#scl,
+                ::embassy_nrf::twis::Config {
+                    address0: #address,
+                    ..Default::default()
+                },
+            );
+        },
+        ChipSeries::Stm32 => {
+            let (tx_dma, rx_dma) = get_stm32_i2c_dma(i2c);
+            // The I2C interrupts are bound in `Irqs`, together with the interrupts of the DMA channels
+            quote! {
+                let split_i2c = ::embassy_stm32::i2c::I2c::new(
+                    p.#instance, p.#scl, p.#sda, p.#tx_dma, p.#rx_dma, Irqs, ::embassy_stm32::i2c::Config::default()
+                )
+                .into_slave_multimaster(::embassy_stm32::i2c::SlaveAddrConfig::basic(#address));
+            }
+        }
+        ChipSeries::Esp32 => {
+            panic!("\n\u{274c} keyboard.toml: `i2c` split isn't supported on ESP32")
+        }
+    }
+}
+
+/// Returns (device initializations, device_names, processor_names, local_processors)
+///
+/// The local processors, e.g. `PointingProcessor`s, are only initialized and run when the peripheral is master.
 pub(crate) fn expand_peripheral_input_device_config(
     id: usize,
     hardware: &Hardware,
-) -> (TokenStream2, Vec<TokenStream2>, Vec<TokenStream2>) {
+) -> (
+    TokenStream2,
+    Vec<TokenStream2>,
+    Vec<TokenStream2>,
+    Vec<Initializer>,
+) {
     let mut initializations = TokenStream2::new();
     let mut devices = Vec::new();
     let mut processors = Vec::new();
+    let mut local_processors = Vec::new();
 
     let communication = &hardware.communication;
     let ble_config = match communication {
@@ -544,6 +935,7 @@ pub(crate) fn expand_peripheral_input_device_config(
                 .joystick
                 .unwrap_or(Vec::new()),
             peripheral_ble_config,
+            &hardware.battery,
             chip.series.clone(),
         ),
         _ => (vec![], vec![]),
@@ -586,7 +978,7 @@ pub(crate) fn expand_peripheral_input_device_config(
     }
 
     // generate PMW3610 configuration
-    let (pmw3610_devices, _pmw3610_processors) = match board {
+    let (pmw3610_devices, pmw3610_processors) = match board {
         BoardConfig::Split(split_config) => expand_pmw3610_device(
             split_config.peripheral[id]
                 .input_device
@@ -604,9 +996,10 @@ pub(crate) fn expand_peripheral_input_device_config(
         let device_name = initializer.var_name;
         devices.push(quote! { #device_name });
     }
+    local_processors.extend(pmw3610_processors);
 
     // generate PMW33xx configuration
-    let (pmw33xx_devices, _pmw33xx_processors) = match board {
+    let (pmw33xx_devices, pmw33xx_processors) = match board {
         BoardConfig::Split(split_config) => expand_pmw33xx_device(
             split_config.peripheral[id]
                 .input_device
@@ -624,9 +1017,10 @@ pub(crate) fn expand_peripheral_input_device_config(
         let device_name = initializer.var_name;
         devices.push(quote! { #device_name });
     }
+    local_processors.extend(pmw33xx_processors);
 
     // generate IQS5xx configuration
-    let (iqs5xx_devices, _iqs5xx_processors) = match board {
+    let (iqs5xx_devices, iqs5xx_processors) = match board {
         BoardConfig::Split(split_config) => expand_iqs5xx_device(
             split_config.peripheral[id]
                 .input_device
@@ -644,6 +1038,28 @@ pub(crate) fn expand_peripheral_input_device_config(
         let device_name = initializer.var_name;
         devices.push(quote! { #device_name });
     }
+    local_processors.extend(iqs5xx_processors);
+
+    // generate Cirque Pinnacle configuration
+    let (pinnacle_devices, pinnacle_processors) = match board {
+        BoardConfig::Split(split_config) => expand_pinnacle_device(
+            split_config.peripheral[id]
+                .input_device
+                .clone()
+                .unwrap_or(InputDeviceConfig::default())
+                .pinnacle
+                .unwrap_or(Vec::new()),
+            chip,
+        ),
+        _ => (vec![], vec![]),
+    };
+
+    for initializer in pinnacle_devices {
+        initializations.extend(initializer.initializer);
+        let device_name = initializer.var_name;
+        devices.push(quote! { #device_name });
+    }
+    local_processors.extend(pinnacle_processors);
 
-    (initializations, devices, processors)
+    (initializations, devices, processors, local_processors)
 }
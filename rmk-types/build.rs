@@ -74,6 +74,14 @@ fn generate_constants(bc: &BuildConstants) -> String {
         "pub const SPLIT_CENTRAL_SLEEP_TIMEOUT_SECONDS: u32 = {};",
         bc.split_central_sleep_timeout_seconds
     ));
+    lines.push(format!(
+        "pub const SLEEP_TIMEOUT_SECONDS: u32 = {};",
+        bc.sleep_timeout_seconds
+    ));
+    lines.push(format!(
+        "pub const USB_SLEEP_TIMEOUT_SECONDS: u32 = {};",
+        bc.usb_sleep_timeout_seconds
+    ));
     lines.push(format!("pub const MORSE_MAX_NUM: usize = {};", bc.morse_max_num));
     lines.push(format!(
         "pub const MAX_PATTERNS_PER_KEY: usize = {};",
@@ -183,6 +191,10 @@ fn generate_constants(bc: &BuildConstants) -> String {
                 "pub const PASSKEY_ENTRY_TIMEOUT_SECS: u32 = {};",
                 passkey.timeout_secs
             ));
+            lines.push(format!(
+                "pub const PASSKEY_DISPLAY_ENABLED: bool = {};",
+                passkey.display
+            ));
         } else {
             // No [ble] section but passkey_entry feature enabled: use defaults
             lines.push("pub const PASSKEY_ENTRY_ENABLED: bool = false;".to_string());
@@ -190,9 +202,62 @@ fn generate_constants(bc: &BuildConstants) -> String {
                 "pub const PASSKEY_ENTRY_TIMEOUT_SECS: u32 = {};",
                 rmk_config::DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS
             ));
+            lines.push("pub const PASSKEY_DISPLAY_ENABLED: bool = false;".to_string());
         }
     }
 
+    // BLE advertising (feature-gated)
+    if let Some(adv) = &bc.advertising {
+        lines.push(format!("pub const BLE_ADV_INTERVAL_MS: u16 = {};", adv.interval_ms));
+        lines.push(format!("pub const BLE_USE_2M_PHY: bool = {};", adv.use_2m_phy));
+        lines.push(format!("pub const BLE_ADV_TX_POWER: i8 = {};", adv.tx_power));
+        lines.push(format!(
+            "pub const BLE_BONDED_ADVERTISING: crate::ble::BondedAdvertising = crate::ble::BondedAdvertising::{};",
+            adv.bonded
+        ));
+        lines.push(format!(
+            "pub const BLE_PAIRING_WINDOW_SECS: u32 = {};",
+            adv.pairing_window_secs
+        ));
+    }
+
+    // BLE connection parameters (feature-gated)
+    if let Some(params) = &bc.conn_params {
+        lines.push(format!("pub const BLE_CONN_INTERVAL_US: u32 = {};", params.interval_us));
+        lines.push(format!("pub const BLE_CONN_LATENCY: u16 = {};", params.latency));
+        lines.push(format!(
+            "pub const BLE_LOW_POWER_CONN_INTERVAL_US: u32 = {};",
+            params.low_power_interval_us
+        ));
+        lines.push(format!(
+            "pub const BLE_LOW_POWER_CONN_LATENCY: u16 = {};",
+            params.low_power_latency
+        ));
+        lines.push(format!(
+            "pub const BLE_LOW_POWER_IDLE_SECS: u32 = {};",
+            params.low_power_idle_secs
+        ));
+    }
+
+    lines.push(format!("pub const BLE_MULTI_HOST: bool = {};", bc.ble_multi_host));
+
+    // Battery Services of the split peripherals. Each service is a field of the GATT server, which is declared
+    // by a macro invoked with the ids and names of the peripherals.
+    let batteries: Vec<String> = bc
+        .peripheral_battery_names
+        .iter()
+        .enumerate()
+        .map(|(id, name)| format!("{id} => {name:?}"))
+        .collect();
+    lines.push(format!(
+        "/// Invoke `$m!` with the id and name of each split peripheral whose battery is reported over BLE, \
+         e.g. `$m!(0 => \"Right\")`\n\
+         #[doc(hidden)]\n\
+         #[macro_export]\n\
+         macro_rules! split_peripheral_batteries {{\n    ($m:ident) => {{\n        $m!({});\n    }};\n}}",
+        batteries.join(", ")
+    ));
+
     lines.join("\n")
 }
 
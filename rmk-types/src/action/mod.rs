@@ -10,22 +10,29 @@
 //! - [`EncoderAction`] - Rotary encoder actions
 //! - [`LightAction`] - Light control actions
 //! - [`KeyboardAction`] - Keyboard control actions (reboot, toggle features, etc.)
+//! - `PointingAction` - Pointing device control actions (CPI presets), with the `pointing_cpi` feature
 //! - [`crate::morse::MorseProfile`] / [`crate::morse::MorseMode`] - Morse/tap-hold timing configuration
 
 mod encoder;
 mod key_action;
 mod keyboard;
 mod light;
+#[cfg(feature = "pointing_cpi")]
+mod pointing;
 
 pub use encoder::EncoderAction;
 pub use key_action::KeyAction;
 pub use keyboard::KeyboardAction;
 pub use light::LightAction;
+#[cfg(feature = "pointing_cpi")]
+pub use pointing::PointingAction;
 use postcard::experimental::max_size::MaxSize;
 #[cfg(feature = "rmk_protocol")]
 use postcard_schema::Schema;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "gamepad")]
+use crate::gamepad::{GamepadButton, GamepadHat};
 use crate::keycode::{KeyCode, SpecialKey};
 use crate::modifier::ModifierCombination;
 #[cfg(feature = "steno")]
@@ -80,4 +87,13 @@ pub enum Action {
     /// sent to the host as a vendor HID report.
     #[cfg(feature = "steno")]
     Steno(StenoKey),
+    /// A gamepad button, sent to the host as a gamepad HID report.
+    #[cfg(feature = "gamepad")]
+    GamepadButton(GamepadButton),
+    /// A direction of the gamepad hat switch (d-pad).
+    #[cfg(feature = "gamepad")]
+    GamepadHat(GamepadHat),
+    /// Control pointing devices, e.g. switch the CPI preset.
+    #[cfg(feature = "pointing_cpi")]
+    Pointing(PointingAction),
 }
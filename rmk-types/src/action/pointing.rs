@@ -0,0 +1,66 @@
+//! Pointing device control actions.
+
+use postcard::experimental::max_size::MaxSize;
+#[cfg(feature = "rmk_protocol")]
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Actions for controlling pointing devices.
+///
+/// The first field is the id of the target pointing device, 255 targets all devices.
+/// CPI values come from the CPI presets configured for each device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[non_exhaustive]
+pub enum PointingAction {
+    /// Switch to the next CPI preset, stays at the last one
+    CpiUp(u8),
+    /// Switch to the previous CPI preset, stays at the first one
+    CpiDown(u8),
+    /// Switch to the next CPI preset, wraps around to the first one
+    CpiCycle(u8),
+    /// Switch to the CPI preset at the given index
+    CpiPreset(u8, u8),
+}
+
+impl PointingAction {
+    /// Id of the target pointing device
+    pub const fn device_id(&self) -> u8 {
+        match self {
+            Self::CpiUp(id) | Self::CpiDown(id) | Self::CpiCycle(id) | Self::CpiPreset(id, _) => *id,
+        }
+    }
+
+    /// Get the preset index after this action, `current` is the index in use and `len` is the number of presets
+    pub fn next_preset(&self, current: u8, len: u8) -> Option<u8> {
+        if len == 0 {
+            return None;
+        }
+        let last = len - 1;
+        match *self {
+            Self::CpiUp(_) => Some(current.saturating_add(1).min(last)),
+            Self::CpiDown(_) => Some(current.saturating_sub(1).min(last)),
+            Self::CpiCycle(_) => Some(if current >= last { 0 } else { current + 1 }),
+            Self::CpiPreset(_, preset) => (preset <= last).then_some(preset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_preset() {
+        assert_eq!(PointingAction::CpiUp(0).next_preset(1, 3), Some(2));
+        assert_eq!(PointingAction::CpiUp(0).next_preset(2, 3), Some(2));
+        assert_eq!(PointingAction::CpiDown(0).next_preset(1, 3), Some(0));
+        assert_eq!(PointingAction::CpiDown(0).next_preset(0, 3), Some(0));
+        assert_eq!(PointingAction::CpiCycle(0).next_preset(1, 3), Some(2));
+        assert_eq!(PointingAction::CpiCycle(0).next_preset(2, 3), Some(0));
+        assert_eq!(PointingAction::CpiPreset(0, 2).next_preset(0, 3), Some(2));
+        assert_eq!(PointingAction::CpiPreset(0, 3).next_preset(0, 3), None);
+        assert_eq!(PointingAction::CpiUp(0).next_preset(0, 0), None);
+    }
+}
@@ -1,10 +1,33 @@
-//! BLE status types.
+//! BLE status, bond and output profile types.
 
 use postcard::experimental::max_size::MaxSize;
 #[cfg(feature = "rmk_protocol")]
 use postcard_schema::Schema;
 use serde::{Deserialize, Serialize};
 
+use crate::action::KeyAction;
+
+/// Maximum length of a BLE profile label in bytes
+pub const PROFILE_LABEL_SIZE: usize = 16;
+
+/// Maximum number of CCCD entries in an exported bond
+pub const MAX_CCCD_ENTRIES: usize = 16;
+
+/// Maximum number of key overrides of an output profile
+pub const MAX_KEY_OVERRIDES: usize = 8;
+
+/// How a profile with a bonded host advertises, set with `bonded_advertising` in `[ble]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BondedAdvertising {
+    /// Any host can connect, and pair again
+    Undirected,
+    /// Only the bonded host can connect, other hosts still see the keyboard
+    Filtered,
+    /// Advertising is addressed to the bonded host, other hosts don't see the keyboard
+    Directed,
+}
+
 /// BLE state (what the BLE subsystem is currently doing).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
 #[cfg_attr(feature = "rmk_protocol", derive(Schema))]
@@ -36,9 +59,171 @@ impl Default for BleStatus {
     }
 }
 
+/// Security level of a BLE bond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BondSecurityLevel {
+    NoEncryption,
+    Encrypted,
+    EncryptedAuthenticated,
+}
+
+/// Kind of the identity address of a bonded host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BondAddressKind {
+    Public,
+    /// Random static address
+    Random,
+}
+
+/// A Client Characteristic Configuration Descriptor value saved for a bonded host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CccdEntry {
+    /// Attribute handle of the CCCD
+    pub handle: u16,
+    /// Raw CCCD value, e.g. notifications enabled
+    pub value: u16,
+}
+
+/// The bond of a BLE profile slot, used to back up and restore pairings.
+///
+/// It contains the long term key of the host, keep it private.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BleBond {
+    /// Profile slot of the bond
+    pub slot: u8,
+    /// Identity address of the host
+    pub address: [u8; 6],
+    pub address_kind: BondAddressKind,
+    /// Identity resolving key of the host
+    pub irk: Option<[u8; 16]>,
+    /// Long term key, little endian
+    pub ltk: [u8; 16],
+    pub security_level: BondSecurityLevel,
+    pub is_bonded: bool,
+    /// CCCD values, so that the host doesn't need to subscribe again
+    pub cccd_table: heapless::Vec<CccdEntry, MAX_CCCD_ENTRIES>,
+}
+
+impl MaxSize for BleBond {
+    const POSTCARD_MAX_SIZE: usize = u8::POSTCARD_MAX_SIZE
+        + <[u8; 6]>::POSTCARD_MAX_SIZE
+        + BondAddressKind::POSTCARD_MAX_SIZE
+        + <Option<[u8; 16]>>::POSTCARD_MAX_SIZE
+        + <[u8; 16]>::POSTCARD_MAX_SIZE
+        + BondSecurityLevel::POSTCARD_MAX_SIZE
+        + bool::POSTCARD_MAX_SIZE
+        + crate::heapless_vec_max_size::<CccdEntry, MAX_CCCD_ENTRIES>();
+}
+
+/// User label of a BLE profile slot, e.g. the name of the host. UTF-8, empty if not set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProfileLabel {
+    pub label: heapless::Vec<u8, PROFILE_LABEL_SIZE>,
+}
+
+impl MaxSize for ProfileLabel {
+    const POSTCARD_MAX_SIZE: usize = crate::heapless_vec_max_size::<u8, PROFILE_LABEL_SIZE>();
+}
+
+impl ProfileLabel {
+    /// Create a label, returns `None` if it's longer than [`PROFILE_LABEL_SIZE`] bytes
+    pub fn new(label: &str) -> Option<Self> {
+        heapless::Vec::from_slice(label.as_bytes())
+            .ok()
+            .map(|label| Self { label })
+    }
+
+    /// The label as a string, `None` if it isn't valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        core::str::from_utf8(&self.label).ok()
+    }
+}
+
+/// An output of the keyboard which has its own [`ProfileSettings`]: USB, or a BLE profile slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OutputProfile {
+    Usb,
+    Ble(u8),
+}
+
+/// Operating system of the host.
+///
+/// The keymap is written for Windows and Linux hosts: on macOS, Ctrl and Gui(Cmd) are swapped,
+/// so that the shortcuts stay on the same keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OsMode {
+    /// Nothing is changed
+    #[default]
+    Unspecified,
+    Windows,
+    MacOs,
+    Linux,
+}
+
+impl OsMode {
+    /// Whether Ctrl and Gui are swapped for this host
+    pub const fn swaps_ctrl_gui(self) -> bool {
+        matches!(self, OsMode::MacOs)
+    }
+}
+
+/// Action which replaces the keymap action of a key position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyOverride {
+    pub layer: u8,
+    pub row: u8,
+    pub col: u8,
+    pub action: KeyAction,
+}
+
+/// Settings applied when an output becomes active
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProfileSettings {
+    /// Default layer of the output, `None` keeps the current default layer
+    pub default_layer: Option<u8>,
+    pub os_mode: OsMode,
+    pub key_overrides: heapless::Vec<KeyOverride, MAX_KEY_OVERRIDES>,
+}
+
+impl MaxSize for ProfileSettings {
+    const POSTCARD_MAX_SIZE: usize = <Option<u8>>::POSTCARD_MAX_SIZE
+        + OsMode::POSTCARD_MAX_SIZE
+        + crate::heapless_vec_max_size::<KeyOverride, MAX_KEY_OVERRIDES>();
+}
+
+impl ProfileSettings {
+    /// Overridden action of the key position, if any
+    pub fn key_override(&self, layer: u8, row: u8, col: u8) -> Option<KeyAction> {
+        self.key_overrides
+            .iter()
+            .find(|o| o.layer == layer && o.row == row && o.col == col)
+            .map(|o| o.action)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{BleState, BleStatus};
+    use super::{BleState, BleStatus, KeyOverride, OsMode, ProfileLabel, ProfileSettings};
+    use crate::action::{Action, KeyAction};
+    use crate::keycode::{HidKeyCode, KeyCode};
 
     #[test]
     fn default_ble_status_is_profile_zero_and_inactive() {
@@ -73,4 +258,33 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn profile_label() {
+        assert_eq!(ProfileLabel::new("MacBook").unwrap().as_str(), Some("MacBook"));
+        assert_eq!(ProfileLabel::default().as_str(), Some(""));
+        assert!(ProfileLabel::new("a label which is too long").is_none());
+    }
+
+    #[test]
+    fn profile_settings_key_override() {
+        let action = KeyAction::Single(Action::Key(KeyCode::Hid(HidKeyCode::LGui)));
+        let mut settings = ProfileSettings {
+            os_mode: OsMode::MacOs,
+            ..Default::default()
+        };
+        settings
+            .key_overrides
+            .push(KeyOverride {
+                layer: 0,
+                row: 1,
+                col: 2,
+                action,
+            })
+            .unwrap();
+        assert_eq!(settings.key_override(0, 1, 2), Some(action));
+        assert_eq!(settings.key_override(1, 1, 2), None);
+        assert!(settings.os_mode.swaps_ctrl_gui());
+        assert!(!OsMode::default().swaps_ctrl_gui());
+    }
 }
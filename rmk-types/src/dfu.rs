@@ -0,0 +1,124 @@
+//! Firmware update (DFU) types.
+//!
+//! A firmware image is streamed in chunks into the DFU partition of the target:
+//! `Begin` erases the partition, `Write` chunks must arrive in order, and `Finish`
+//! verifies the CRC32 of the whole image before marking it for swap by the bootloader.
+//! The same requests are relayed by the central to split peripherals.
+
+use postcard::experimental::max_size::MaxSize;
+#[cfg(feature = "rmk_protocol")]
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of image bytes in a DFU chunk
+pub const DFU_CHUNK_SIZE: usize = 32;
+
+/// The keyboard part which is updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DfuTarget {
+    /// The device connected to the host, i.e. the central of a split keyboard
+    Local,
+    /// The split peripheral with the given id, relayed by the central
+    Peripheral(u8),
+}
+
+/// A chunk of the firmware image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DfuChunk {
+    /// Offset of the chunk in the image
+    pub offset: u32,
+    /// Number of valid bytes in `data`
+    pub len: u8,
+    pub data: [u8; DFU_CHUNK_SIZE],
+}
+
+impl DfuChunk {
+    /// Create a chunk, returns `None` if `bytes` is longer than [`DFU_CHUNK_SIZE`]
+    pub fn new(offset: u32, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > DFU_CHUNK_SIZE {
+            return None;
+        }
+        let mut data = [0; DFU_CHUNK_SIZE];
+        data[..bytes.len()].copy_from_slice(bytes);
+        Some(Self {
+            offset,
+            len: bytes.len() as u8,
+            data,
+        })
+    }
+
+    /// Valid bytes of the chunk, an invalid `len` is clamped to [`DFU_CHUNK_SIZE`]
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..(self.len as usize).min(DFU_CHUNK_SIZE)]
+    }
+}
+
+/// A step of the firmware update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DfuRequest {
+    /// Start an update with the image size and the CRC32 (IEEE) of the whole image
+    Begin { size: u32, crc32: u32 },
+    /// Write the next chunk of the image
+    Write(DfuChunk),
+    /// Verify the image, mark it for swap and reboot
+    Finish,
+    /// Cancel the update, the current firmware is kept
+    Abort,
+}
+
+/// Error of a DFU request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DfuError {
+    /// No update is in progress
+    NotStarted,
+    /// The image doesn't fit in the DFU partition
+    TooLarge,
+    /// The chunk isn't the next one, or it goes past the image size
+    InvalidOffset,
+    /// Not all bytes of the image are written
+    Incomplete,
+    /// Erasing, writing or reading the flash failed
+    Flash,
+    /// The CRC32 of the written image doesn't match
+    CrcMismatch,
+    /// The target is not available, e.g. the peripheral is disconnected or DFU is not enabled on it
+    Unreachable,
+}
+
+/// Result of a DFU request
+pub type DfuResult = Result<(), DfuError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_bytes() {
+        let chunk = DfuChunk::new(64, &[1, 2, 3]).unwrap();
+        assert_eq!(chunk.bytes(), &[1, 2, 3]);
+        assert!(DfuChunk::new(0, &[0; DFU_CHUNK_SIZE + 1]).is_none());
+
+        let broken = DfuChunk {
+            offset: 0,
+            len: u8::MAX,
+            data: [0; DFU_CHUNK_SIZE],
+        };
+        assert_eq!(broken.bytes().len(), DFU_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let request = DfuRequest::Write(DfuChunk::new(32, &[0xAA; DFU_CHUNK_SIZE]).unwrap());
+        let mut buf = [0u8; DfuRequest::POSTCARD_MAX_SIZE];
+        let bytes = postcard::to_slice(&request, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<DfuRequest>(bytes).unwrap(), request);
+    }
+}
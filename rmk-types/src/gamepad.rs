@@ -0,0 +1,42 @@
+//! Gamepad button and hat switch identifiers.
+//!
+//! The gamepad report has 32 buttons, a hat switch and 6 axes. Buttons are
+//! identified by their index (0..=31), which is `Button N+1` in the HID
+//! descriptor. The hat switch is driven by four directions so it can be
+//! mapped to keys like a d-pad.
+
+use postcard::experimental::max_size::MaxSize;
+#[cfg(feature = "rmk_protocol")]
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Number of buttons in the gamepad report
+pub const GAMEPAD_BUTTON_NUM: u8 = 32;
+
+/// A single gamepad button, identified by its index (0..=31).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+pub struct GamepadButton(pub u8);
+
+impl GamepadButton {
+    /// Returns the index (0..=31) of this button.
+    #[inline]
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+}
+
+/// A direction of the gamepad hat switch.
+///
+/// Pressing two adjacent directions gives a diagonal, opposite directions
+/// cancel each other out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+pub enum GamepadHat {
+    Up,
+    Down,
+    Left,
+    Right,
+}
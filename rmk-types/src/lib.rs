@@ -18,9 +18,12 @@
 //! - [`mouse_button`] — `MouseButtons` bitfield
 //! - [`led_indicator`] — `LedIndicator` bitfield
 //! - [`battery`] — `BatteryStatus`, `ChargeState`
-//! - [`ble`] — `BleStatus`, `BleState`
+//! - [`ble`] — `BleStatus`, `BleState`, `BleBond`, `ProfileLabel`, `ProfileSettings`
 //! - [`connection`] — `ConnectionType` (USB/BLE)
 //!
+//! ### Firmware update
+//! - [`dfu`] — `DfuRequest`, `DfuChunk`, `DfuTarget`, `DfuError` (feature-gated: `dfu`)
+//!
 //! ### Protocol
 //! - [`protocol::vial`] — Vial/Via protocol types
 //! - [`protocol::rmk`] — RMK native protocol ICD (feature-gated: `rmk_protocol`)
@@ -36,8 +39,12 @@ pub mod ble;
 pub mod combo;
 pub mod connection;
 pub mod constants;
+#[cfg(feature = "dfu")]
+pub mod dfu;
 pub mod fmt;
 pub mod fork;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
 pub mod keycode;
 pub mod led_indicator;
 pub mod modifier;
@@ -185,4 +185,41 @@ impl ModifierCombination {
             Self::from_bits(modifier_bits) // Use as left side
         }
     }
+
+    /// Swap Ctrl and Gui on both sides, e.g. for a macOS host
+    pub const fn swap_ctrl_gui(self) -> Self {
+        self.with_left_ctrl(self.left_gui())
+            .with_left_gui(self.left_ctrl())
+            .with_right_ctrl(self.right_gui())
+            .with_right_gui(self.right_ctrl())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_ctrl_gui() {
+        let lctrl_rgui = ModifierCombination::new().with_left_ctrl(true).with_right_gui(true);
+        let lgui_rctrl = ModifierCombination::new().with_left_gui(true).with_right_ctrl(true);
+        assert_eq!(lctrl_rgui.swap_ctrl_gui(), lgui_rctrl);
+        assert_eq!(lgui_rctrl.swap_ctrl_gui(), lctrl_rgui);
+
+        // Both Ctrl and Gui held on a side stay held
+        let lctrl_lgui = ModifierCombination::new().with_left_ctrl(true).with_left_gui(true);
+        assert_eq!(lctrl_lgui.swap_ctrl_gui(), lctrl_lgui);
+
+        // Shift and Alt are kept
+        let shift_alt_ctrl = ModifierCombination::new()
+            .with_left_shift(true)
+            .with_right_alt(true)
+            .with_right_ctrl(true);
+        let shift_alt_gui = ModifierCombination::new()
+            .with_left_shift(true)
+            .with_right_alt(true)
+            .with_right_gui(true);
+        assert_eq!(shift_alt_ctrl.swap_ctrl_gui(), shift_alt_gui);
+        assert_eq!(ModifierCombination::new().swap_ctrl_gui(), ModifierCombination::new());
+    }
 }
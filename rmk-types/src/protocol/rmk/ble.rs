@@ -0,0 +1,106 @@
+//! BLE profile endpoint types.
+
+use postcard::experimental::max_size::MaxSize;
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::ble::{BleBond, OutputProfile, ProfileLabel, ProfileSettings};
+
+/// Response of `ExportBleBond`, `None` if the slot has no bond.
+///
+/// A type alias because `endpoints!` only accepts a single token as the response type.
+pub type BleBondResponse = Option<BleBond>;
+
+/// Request payload for `SetBleProfileLabel`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, MaxSize, Schema)]
+pub struct SetProfileLabelRequest {
+    pub slot: u8,
+    pub label: ProfileLabel,
+}
+
+/// Request payload for `SetProfileSettings`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, MaxSize, Schema)]
+pub struct SetProfileSettingsRequest {
+    pub output: OutputProfile,
+    pub settings: ProfileSettings,
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+    use crate::action::{Action, KeyAction};
+    use crate::ble::{BondAddressKind, BondSecurityLevel, CccdEntry, KeyOverride, MAX_CCCD_ENTRIES, OsMode};
+    use crate::keycode::{HidKeyCode, KeyCode};
+    use crate::protocol::rmk::test_utils::{assert_max_size_bound, round_trip};
+
+    fn bond(cccd_entries: usize) -> BleBond {
+        let mut cccd_table = Vec::new();
+        for i in 0..cccd_entries {
+            cccd_table
+                .push(CccdEntry {
+                    handle: u16::MAX - i as u16,
+                    value: u16::MAX,
+                })
+                .unwrap();
+        }
+        BleBond {
+            slot: u8::MAX,
+            address: [0xFF; 6],
+            address_kind: BondAddressKind::Random,
+            irk: Some([0xFF; 16]),
+            ltk: [0xFF; 16],
+            security_level: BondSecurityLevel::EncryptedAuthenticated,
+            is_bonded: true,
+            cccd_table,
+        }
+    }
+
+    #[test]
+    fn round_trip_ble_bond() {
+        round_trip(&bond(0));
+        round_trip(&bond(3));
+        assert_max_size_bound(&bond(MAX_CCCD_ENTRIES));
+    }
+
+    #[test]
+    fn round_trip_profile_label() {
+        let request = SetProfileLabelRequest {
+            slot: 2,
+            label: ProfileLabel::new("iPad").unwrap(),
+        };
+        round_trip(&request);
+        assert_max_size_bound(&SetProfileLabelRequest {
+            slot: u8::MAX,
+            label: ProfileLabel::new("0123456789abcdef").unwrap(),
+        });
+    }
+
+    #[test]
+    fn round_trip_profile_settings() {
+        let override_at = |i: u8| KeyOverride {
+            layer: i,
+            row: i,
+            col: i,
+            action: KeyAction::Single(Action::Key(KeyCode::Hid(HidKeyCode::LCtrl))),
+        };
+        let mut settings = ProfileSettings {
+            default_layer: Some(1),
+            os_mode: OsMode::MacOs,
+            key_overrides: Vec::new(),
+        };
+        settings.key_overrides.push(override_at(0)).unwrap();
+        round_trip(&SetProfileSettingsRequest {
+            output: OutputProfile::Ble(1),
+            settings: settings.clone(),
+        });
+
+        while settings.key_overrides.push(override_at(u8::MAX)).is_ok() {}
+        settings.default_layer = Some(u8::MAX);
+        assert_max_size_bound(&SetProfileSettingsRequest {
+            output: OutputProfile::Ble(u8::MAX),
+            settings,
+        });
+    }
+}
@@ -0,0 +1,58 @@
+//! Firmware update endpoint types.
+
+use postcard::experimental::max_size::MaxSize;
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::dfu::{DfuChunk, DfuTarget};
+
+/// Request payload for `DfuBegin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize, Schema)]
+pub struct DfuBeginRequest {
+    pub target: DfuTarget,
+    /// Size of the image in bytes
+    pub size: u32,
+    /// CRC32 (IEEE) of the whole image
+    pub crc32: u32,
+}
+
+/// Request payload for `DfuWrite`.
+///
+/// Chunks must be written in order, starting from offset 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize, Schema)]
+pub struct DfuWriteRequest {
+    pub target: DfuTarget,
+    pub chunk: DfuChunk,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::rmk::test_utils::{assert_max_size_bound, round_trip};
+
+    #[test]
+    fn round_trip_dfu_requests() {
+        round_trip(&DfuBeginRequest {
+            target: DfuTarget::Peripheral(1),
+            size: 0x40000,
+            crc32: 0xCBF4_3926,
+        });
+        round_trip(&DfuWriteRequest {
+            target: DfuTarget::Local,
+            chunk: DfuChunk::new(32, &[0x55; 20]).unwrap(),
+        });
+    }
+
+    #[test]
+    fn dfu_requests_max_size() {
+        assert_max_size_bound(&DfuBeginRequest {
+            target: DfuTarget::Peripheral(u8::MAX),
+            size: u32::MAX,
+            crc32: u32::MAX,
+        });
+        assert_max_size_bound(&DfuWriteRequest {
+            target: DfuTarget::Peripheral(u8::MAX),
+            chunk: DfuChunk::new(u32::MAX, &[0xFF; crate::dfu::DFU_CHUNK_SIZE]).unwrap(),
+        });
+    }
+}
@@ -22,9 +22,11 @@ use crate::action::{EncoderAction, KeyAction};
 #[cfg(feature = "_ble")]
 use crate::battery::BatteryStatus;
 #[cfg(feature = "_ble")]
-use crate::ble::BleStatus;
+use crate::ble::{BleBond, BleStatus, OutputProfile, ProfileLabel, ProfileSettings};
 use crate::combo::Combo;
 use crate::connection::ConnectionType;
+#[cfg(feature = "dfu")]
+use crate::dfu::{DfuResult, DfuTarget};
 use crate::fork::Fork;
 use crate::morse::Morse;
 
@@ -192,6 +194,40 @@ pub const BLE_CONNECTION_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc
     endpoints: &[],
 };
 
+#[cfg(feature = "_ble")]
+endpoints! {
+    list = BLE_BOND_ENDPOINT_LIST;
+    omit_std = true;
+    | EndpointTy         | RequestTy              | ResponseTy      | Path                 |
+    | ----------         | ---------              | ----------      | ----                 |
+    | ExportBleBond      | u8                     | BleBondResponse | "conn/export_bond"   |
+    | ImportBleBond      | BleBond                | RmkResult       | "conn/import_bond"   |
+    | GetBleProfileLabel | u8                     | ProfileLabel    | "conn/ble_label"     |
+    | SetBleProfileLabel | SetProfileLabelRequest | RmkResult       | "conn/set_ble_label" |
+}
+
+#[cfg(not(feature = "_ble"))]
+pub const BLE_BOND_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc::EndpointMap {
+    types: &[],
+    endpoints: &[],
+};
+
+#[cfg(feature = "_ble")]
+endpoints! {
+    list = PROFILE_SETTINGS_ENDPOINT_LIST;
+    omit_std = true;
+    | EndpointTy         | RequestTy                 | ResponseTy      | Path                        |
+    | ----------         | ---------                 | ----------      | ----                        |
+    | GetProfileSettings | OutputProfile             | ProfileSettings | "conn/profile_settings"     |
+    | SetProfileSettings | SetProfileSettingsRequest | RmkResult       | "conn/set_profile_settings" |
+}
+
+#[cfg(not(feature = "_ble"))]
+pub const PROFILE_SETTINGS_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc::EndpointMap {
+    types: &[],
+    endpoints: &[],
+};
+
 #[cfg(feature = "_ble")]
 endpoints! {
     list = BLE_STATUS_ENDPOINT_LIST;
@@ -207,6 +243,21 @@ pub const BLE_STATUS_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc::En
     endpoints: &[],
 };
 
+#[cfg(feature = "_ble")]
+endpoints! {
+    list = BLE_LINK_ENDPOINT_LIST;
+    omit_std = true;
+    | EndpointTy       | RequestTy | ResponseTy    | Path                  |
+    | ----------       | --------- | ----------    | ----                  |
+    | GetBleLinkStatus | ()        | BleLinkStatus | "status/ble_link/get" |
+}
+
+#[cfg(not(feature = "_ble"))]
+pub const BLE_LINK_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc::EndpointMap {
+    types: &[],
+    endpoints: &[],
+};
+
 #[cfg(all(feature = "_ble", feature = "split"))]
 endpoints! {
     list = SPLIT_STATUS_ENDPOINT_LIST;
@@ -222,6 +273,24 @@ pub const SPLIT_STATUS_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc::
     endpoints: &[],
 };
 
+#[cfg(feature = "dfu")]
+endpoints! {
+    list = DFU_ENDPOINT_LIST;
+    omit_std = true;
+    | EndpointTy | RequestTy       | ResponseTy | Path         |
+    | ---------- | ---------       | ---------- | ----         |
+    | DfuBegin   | DfuBeginRequest | DfuResult  | "dfu/begin"  |
+    | DfuWrite   | DfuWriteRequest | DfuResult  | "dfu/write"  |
+    | DfuFinish  | DfuTarget       | DfuResult  | "dfu/finish" |
+    | DfuAbort   | DfuTarget       | DfuResult  | "dfu/abort"  |
+}
+
+#[cfg(not(feature = "dfu"))]
+pub const DFU_ENDPOINT_LIST: postcard_rpc::EndpointMap = postcard_rpc::EndpointMap {
+    types: &[],
+    endpoints: &[],
+};
+
 /// Build an `EndpointMap` from a list of endpoint group constants.
 ///
 /// Each argument must be a `postcard_rpc::EndpointList` (as produced by `endpoints!`).
@@ -266,7 +335,7 @@ macro_rules! build_endpoint_map {
 ///
 /// Assembled from smaller endpoint groups to avoid very large const-eval
 /// workloads in a single `endpoints!` invocation.
-/// Feature-gated groups (bulk, BLE, split) use empty fallback constants
+/// Feature-gated groups (bulk, BLE, split, DFU) use empty fallback constants
 /// when the corresponding feature is disabled.
 pub const ENDPOINT_LIST: postcard_rpc::EndpointMap = build_endpoint_map!(
     SYSTEM_ENDPOINT_LIST,
@@ -282,9 +351,13 @@ pub const ENDPOINT_LIST: postcard_rpc::EndpointMap = build_endpoint_map!(
     BEHAVIOR_ENDPOINT_LIST,
     CONNECTION_ENDPOINT_LIST,
     BLE_CONNECTION_ENDPOINT_LIST,
+    BLE_BOND_ENDPOINT_LIST,
+    PROFILE_SETTINGS_ENDPOINT_LIST,
     STATUS_ENDPOINT_LIST,
     BLE_STATUS_ENDPOINT_LIST,
+    BLE_LINK_ENDPOINT_LIST,
     SPLIT_STATUS_ENDPOINT_LIST,
+    DFU_ENDPOINT_LIST,
 );
 
 #[cfg(test)]
@@ -370,7 +443,10 @@ mod tests {
     fn endpoint_keys_ble_locked() {
         let entries = collect(&[
             BLE_CONNECTION_ENDPOINT_LIST.endpoints,
+            BLE_BOND_ENDPOINT_LIST.endpoints,
+            PROFILE_SETTINGS_ENDPOINT_LIST.endpoints,
             BLE_STATUS_ENDPOINT_LIST.endpoints,
+            BLE_LINK_ENDPOINT_LIST.endpoints,
         ]);
         let actual = snapshot::format_endpoint_keys("snapshots/endpoint_keys_ble.snap", &entries);
         snapshot::assert_snapshot("snapshots/endpoint_keys_ble.snap", actual);
@@ -384,6 +460,14 @@ mod tests {
         snapshot::assert_snapshot("snapshots/endpoint_keys_ble_split.snap", actual);
     }
 
+    #[cfg(feature = "dfu")]
+    #[test]
+    fn endpoint_keys_dfu_locked() {
+        let entries = collect(&[DFU_ENDPOINT_LIST.endpoints]);
+        let actual = snapshot::format_endpoint_keys("snapshots/endpoint_keys_dfu.snap", &entries);
+        snapshot::assert_snapshot("snapshots/endpoint_keys_dfu.snap", actual);
+    }
+
     /// Verify that every non-bulk endpoint is present in the combined ENDPOINT_LIST.
     /// This catches divergence when new endpoint groups are added to one cfg block
     /// but not the other.
@@ -404,9 +488,13 @@ mod tests {
             BEHAVIOR_ENDPOINT_LIST.endpoints,
             CONNECTION_ENDPOINT_LIST.endpoints,
             BLE_CONNECTION_ENDPOINT_LIST.endpoints,
+            BLE_BOND_ENDPOINT_LIST.endpoints,
+            PROFILE_SETTINGS_ENDPOINT_LIST.endpoints,
             STATUS_ENDPOINT_LIST.endpoints,
             BLE_STATUS_ENDPOINT_LIST.endpoints,
+            BLE_LINK_ENDPOINT_LIST.endpoints,
             SPLIT_STATUS_ENDPOINT_LIST.endpoints,
+            DFU_ENDPOINT_LIST.endpoints,
         ];
 
         for group in non_bulk_groups {
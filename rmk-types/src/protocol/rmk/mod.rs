@@ -13,7 +13,9 @@
 //! - [`topics`] — `topics!` macro invocations
 //! - [`system`] — handshake, lock/unlock, storage reset, behavior config
 //! - [`keymap`], [`encoder`], [`macro_data`], [`combo`], [`morse`], [`fork`] — per-domain request/response types
-//! - [`status`] — runtime status types (matrix state, peripheral status)
+//! - [`status`] — runtime status types (matrix state, peripheral status, BLE link status)
+//! - [`dfu`] — firmware update requests (feature-gated: `dfu`)
+//! - [`ble`] — BLE profile requests (feature-gated: `_ble`)
 //!
 //! ## Protocol Handshake
 //!
@@ -44,7 +46,11 @@
 //!   changing its shape is forbidden even across major bumps.
 //! - Neither: no wire change.
 
+#[cfg(feature = "_ble")]
+mod ble;
 mod combo;
+#[cfg(feature = "dfu")]
+mod dfu;
 mod encoder;
 mod endpoints;
 mod fork;
@@ -63,7 +69,11 @@ use serde::{Deserialize, Serialize};
 // convenient endpoint registration. Domain types (Combo, Morse, Fork, etc.)
 // are NOT re-exported here — import them from their canonical crate-root
 // modules instead.
+#[cfg(feature = "_ble")]
+pub use self::ble::*;
 pub use self::combo::*;
+#[cfg(feature = "dfu")]
+pub use self::dfu::*;
 pub use self::encoder::*;
 pub use self::endpoints::*;
 pub use self::fork::*;
@@ -380,7 +390,14 @@ mod tests {
                 GetBleStatus::REQ_KEY,
                 SwitchBleProfile::REQ_KEY,
                 ClearBleProfile::REQ_KEY,
+                ExportBleBond::REQ_KEY,
+                ImportBleBond::REQ_KEY,
+                GetBleProfileLabel::REQ_KEY,
+                SetBleProfileLabel::REQ_KEY,
+                GetProfileSettings::REQ_KEY,
+                SetProfileSettings::REQ_KEY,
                 GetBatteryStatus::REQ_KEY,
+                GetBleLinkStatus::REQ_KEY,
             ]);
         }
         #[cfg(all(feature = "_ble", feature = "split"))]
@@ -398,6 +415,15 @@ mod tests {
                 SetMorseBulk::REQ_KEY,
             ]);
         }
+        #[cfg(feature = "dfu")]
+        {
+            keys.extend_from_slice(&[
+                DfuBegin::REQ_KEY,
+                DfuWrite::REQ_KEY,
+                DfuFinish::REQ_KEY,
+                DfuAbort::REQ_KEY,
+            ]);
+        }
         keys
     }
 
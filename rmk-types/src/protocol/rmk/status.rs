@@ -29,6 +29,44 @@ impl MaxSize for MatrixState {
 pub struct PeripheralStatus {
     pub connected: bool,
     pub battery: crate::battery::BatteryStatus,
+    pub link: PeripheralLinkStatus,
+}
+
+/// Quality of the BLE link to a split peripheral, measured on the central.
+#[cfg(all(feature = "_ble", feature = "split"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, MaxSize, Schema)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PeripheralLinkStatus {
+    /// Received signal strength in -dBm, e.g. `60` means -60 dBm, a positive RSSI is reported as `0`.
+    /// `None` if it's not measured yet.
+    pub rssi: Option<u8>,
+    /// Estimated latency of a key event in milliseconds. `None` if it's not measured yet.
+    pub latency_ms: Option<u16>,
+    /// Percentage of the recent link probes which got no answer.
+    pub packet_loss: u8,
+}
+
+/// Quality of the BLE link to the host and the connection parameters in use.
+#[cfg(feature = "_ble")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, MaxSize, Schema)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BleLinkStatus {
+    /// Received signal strength in -dBm, e.g. `60` means -60 dBm, a positive RSSI is reported as `0`.
+    /// `None` if it's not measured yet.
+    pub rssi: Option<u8>,
+    /// Connection interval chosen by the host in microseconds. `None` if it's not known yet.
+    pub conn_interval_us: Option<u32>,
+    /// Number of connection events the keyboard can skip.
+    pub conn_latency: u16,
+    /// Whether the low power connection parameters are requested.
+    pub low_power: bool,
+    /// Percentage of the recent reports which missed their connection event.
+    ///
+    /// The controller doesn't report missed connection events, so it's estimated: a report which took longer than a
+    /// connection interval to be sent counts as missed.
+    pub missed_conn_events: u8,
+    /// Most reports waiting to be sent since the last update.
+    pub queued_reports: u8,
 }
 
 #[cfg(test)]
@@ -64,10 +102,32 @@ mod tests {
                 charge_state: ChargeState::Discharging,
                 level: Some(85),
             },
+            link: PeripheralLinkStatus {
+                rssi: Some(62),
+                latency_ms: Some(12),
+                packet_loss: 3,
+            },
         });
         round_trip(&PeripheralStatus {
             connected: false,
             battery: BatteryStatus::Unavailable,
+            link: PeripheralLinkStatus::default(),
         });
     }
+
+    #[cfg(feature = "_ble")]
+    #[test]
+    fn round_trip_ble_link_status() {
+        let status = BleLinkStatus {
+            rssi: Some(70),
+            conn_interval_us: Some(30_000),
+            conn_latency: 30,
+            low_power: true,
+            missed_conn_events: 12,
+            queued_reports: 4,
+        };
+        round_trip(&status);
+        assert_max_size_bound(&status);
+        round_trip(&BleLinkStatus::default());
+    }
 }
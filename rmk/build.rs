@@ -13,18 +13,31 @@ fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 
     // Compute build hash and write to constants.rs
-    let build_hash = compute_build_hash();
+    let commit_id = git_commit_id();
+    let build_hash = compute_build_hash(&commit_id);
+    // The first 7 hex digits of the commit, exchanged between split halves
+    let build_commit = commit_id
+        .get(..7)
+        .and_then(|c| u32::from_str_radix(c, 16).ok())
+        .map_or("None".to_string(), |c| format!("Some({c:#010x})"));
+    let version: Vec<u8> = ["CARGO_PKG_VERSION_MAJOR", "CARGO_PKG_VERSION_MINOR", "CARGO_PKG_VERSION_PATCH"]
+        .iter()
+        .map(|v| env::var(v).ok().and_then(|v| v.parse().ok()).unwrap_or(0))
+        .collect();
     let constants = format!(
-        "#[allow(clippy::redundant_static_lifetimes)]\npub(crate) const BUILD_HASH: u32 = {build_hash:#010x};\n"
+        "#[allow(clippy::redundant_static_lifetimes)]\npub(crate) const BUILD_HASH: u32 = {build_hash:#010x};\n\
+         pub(crate) const BUILD_COMMIT: Option<u32> = {build_commit};\n\
+         pub(crate) const BUILD_VERSION: [u8; 3] = {version:?};\n"
     );
 
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("constants.rs");
     fs::write(&dest_path, constants).expect("Failed to write constants.rs file");
 }
-fn compute_build_hash() -> u32 {
-    // Get the short hash of the latest Git commit. Use "unknown" if it fails
-    let commit_id = Command::new("git")
+
+/// Get the short hash of the latest Git commit. Use "unknown" if it fails
+fn git_commit_id() -> String {
+    Command::new("git")
         .args(["rev-parse", "--short", "HEAD"])
         .output()
         .ok()
@@ -35,8 +48,10 @@ fn compute_build_hash() -> u32 {
                 None
             }
         })
-        .unwrap_or_else(|| "unknown".to_string());
+        .unwrap_or_else(|| "unknown".to_string())
+}
 
+fn compute_build_hash(commit_id: &str) -> u32 {
     // Get and format current local time
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
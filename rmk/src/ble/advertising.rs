@@ -0,0 +1,271 @@
+//! Advertising of the active BLE profile.
+//!
+//! A profile without bond advertises openly, so that any host can pair. When `pairing_window_secs` is set in `[ble]`,
+//! an empty profile only advertises after the pairing window is opened, until the window times out.
+//!
+//! A profile with a bonded host advertises according to `bonded_advertising` in `[ble]`: undirected, undirected but
+//! only accepting connections from the bonded host, or directed to the bonded host. Opening the pairing window on
+//! such a profile advertises openly, so that another host can replace the bond.
+//!
+//! Hosts which use resolvable private addresses are recognized by the controller with the IRK of the bond, which is
+//! loaded into the resolving list. If the controller rejects the filter accept list or the resolving list commands,
+//! the profile advertises openly.
+
+use bt_hci::cmd::le::{
+    LeAddDeviceToFilterAcceptList, LeAddDeviceToResolvingList, LeClearFilterAcceptList, LeClearResolvingList,
+    LeSetAddrResolutionEnable,
+};
+use bt_hci::controller::ControllerCmdSync;
+use bt_hci::param::{AddrKind, AdvFilterPolicy};
+use embassy_sync::signal::Signal;
+use embassy_time::Duration;
+use rmk_types::ble::BondedAdvertising;
+use trouble_host::prelude::*;
+
+use super::profile::ProfileInfo;
+use crate::{BLE_ADV_INTERVAL_MS, BLE_ADV_TX_POWER, BLE_PAIRING_WINDOW_SECS, BLE_USE_2M_PHY, RawMutex};
+
+/// Advertising stops after this timeout, and the keyboard sleeps until a key is pressed
+pub(crate) const ADVERTISING_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Signaled by the pairing window action, taken by the advertising loop
+pub(crate) static PAIRING_WINDOW_REQUEST: Signal<RawMutex, ()> = Signal::new();
+
+/// Open the pairing window of the active profile, `User(N+6)` does the same
+pub fn open_pairing_window() {
+    PAIRING_WINDOW_REQUEST.signal(());
+}
+
+/// Duration of the pairing window
+pub(crate) fn pairing_window() -> Duration {
+    if BLE_PAIRING_WINDOW_SECS == 0 {
+        ADVERTISING_TIMEOUT
+    } else {
+        Duration::from_secs(BLE_PAIRING_WINDOW_SECS as u64)
+    }
+}
+
+/// Identity of the bonded host of a profile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BondedHost {
+    pub(crate) address: Address,
+    pub(crate) irk: Option<IdentityResolvingKey>,
+}
+
+impl BondedHost {
+    pub(crate) fn new(profile: &ProfileInfo) -> Self {
+        Self {
+            address: Address {
+                kind: profile.addr_kind,
+                addr: profile.info.identity.bd_addr,
+            },
+            irk: profile.info.identity.irk,
+        }
+    }
+}
+
+/// How the active profile advertises
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum AdvertisingMode {
+    /// Undirected, any host can connect
+    Open,
+    /// Undirected, only the bonded host can connect
+    Filtered(BondedHost),
+    /// Directed to the bonded host
+    Directed(BondedHost),
+    /// Empty profile whose pairing window is closed, nothing is advertised
+    Closed,
+}
+
+impl AdvertisingMode {
+    /// Select the mode from the bonded host of the active profile and whether the pairing window is open
+    pub(crate) fn select(
+        bonded_host: Option<BondedHost>,
+        pairing_window_open: bool,
+        bonded_advertising: BondedAdvertising,
+        pairing_window_secs: u32,
+    ) -> Self {
+        if pairing_window_open {
+            return Self::Open;
+        }
+        match bonded_host {
+            Some(host) => match bonded_advertising {
+                BondedAdvertising::Undirected => Self::Open,
+                BondedAdvertising::Filtered => Self::Filtered(host),
+                BondedAdvertising::Directed => Self::Directed(host),
+            },
+            None if pairing_window_secs == 0 => Self::Open,
+            None => Self::Closed,
+        }
+    }
+}
+
+/// Advertising parameters from `[ble]`, `filtered` only accepts connections from the filter accept list
+pub(crate) fn advertisement_parameters(filtered: bool) -> AdvertisementParameters {
+    let interval = Duration::from_millis(BLE_ADV_INTERVAL_MS as u64);
+    AdvertisementParameters {
+        primary_phy: PhyKind::Le1M,
+        secondary_phy: if BLE_USE_2M_PHY { PhyKind::Le2M } else { PhyKind::Le1M },
+        tx_power: tx_power(BLE_ADV_TX_POWER),
+        interval_min: interval,
+        interval_max: interval,
+        filter_policy: if filtered {
+            AdvFilterPolicy::FilterConn
+        } else {
+            AdvFilterPolicy::Unfiltered
+        },
+        ..Default::default()
+    }
+}
+
+/// Kind of the identity address of a host which connected with an address of `kind`.
+///
+/// The controller reports an address resolved with the resolving list as the identity address with a kind of its
+/// own. For a host with an IRK, the kind only identifies its entry of the resolving list.
+pub(crate) fn identity_addr_kind(kind: AddrKind) -> AddrKind {
+    if kind == AddrKind::RESOLVABLE_PRIVATE_OR_PUBLIC {
+        AddrKind::PUBLIC
+    } else if kind == AddrKind::RESOLVABLE_PRIVATE_OR_RANDOM {
+        AddrKind::RANDOM
+    } else {
+        kind
+    }
+}
+
+/// Load the bonded host into the controller before advertising to it.
+///
+/// The IRK of the host goes into the resolving list, so that the controller recognizes its resolvable private
+/// addresses. With `filtered`, the host is the only entry of the filter accept list.
+pub(crate) async fn load_bonded_host<C, P: PacketPool>(
+    stack: &Stack<'_, C, P>,
+    host: BondedHost,
+    filtered: bool,
+) -> Result<(), BleHostError<C::Error>>
+where
+    C: Controller
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>,
+{
+    // The resolving list can't be changed while the address resolution is enabled
+    stack.command(LeSetAddrResolutionEnable::new(false)).await?;
+    stack.command(LeClearResolvingList::new()).await?;
+    if let Some(irk) = host.irk {
+        // All-zero local IRK: the keyboard uses its random static address
+        stack
+            .command(LeAddDeviceToResolvingList::new(
+                host.address.kind,
+                host.address.addr,
+                irk.to_le_bytes(),
+                [0; 16],
+            ))
+            .await?;
+        stack.command(LeSetAddrResolutionEnable::new(true)).await?;
+    }
+    if filtered {
+        stack.command(LeClearFilterAcceptList::new()).await?;
+        stack
+            .command(LeAddDeviceToFilterAcceptList::new(host.address.kind, host.address.addr))
+            .await?;
+    }
+    Ok(())
+}
+
+/// The highest supported TX power which doesn't exceed `dbm`
+fn tx_power(dbm: i8) -> TxPower {
+    match dbm {
+        i8::MIN..=-21 => TxPower::Minus40dBm,
+        -20..=-17 => TxPower::Minus20dBm,
+        -16..=-13 => TxPower::Minus16dBm,
+        -12..=-9 => TxPower::Minus12dBm,
+        -8..=-5 => TxPower::Minus8dBm,
+        -4..=-1 => TxPower::Minus4dBm,
+        0..=1 => TxPower::ZerodBm,
+        2 => TxPower::Plus2dBm,
+        3 => TxPower::Plus3dBm,
+        4 => TxPower::Plus4dBm,
+        5 => TxPower::Plus5dBm,
+        6 => TxPower::Plus6dBm,
+        7 => TxPower::Plus7dBm,
+        8..=9 => TxPower::Plus8dBm,
+        10..=11 => TxPower::Plus10dBm,
+        12..=13 => TxPower::Plus12dBm,
+        14 => TxPower::Plus14dBm,
+        15 => TxPower::Plus15dBm,
+        16..=17 => TxPower::Plus16dBm,
+        18..=19 => TxPower::Plus18dBm,
+        20..=i8::MAX => TxPower::Plus20dBm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rmk_types::ble::{BleBond, BondAddressKind, BondSecurityLevel};
+
+    use super::*;
+
+    const HOST: [u8; 6] = [1, 2, 3, 4, 5, 0xC6];
+
+    #[test]
+    fn empty_profile_advertising() {
+        let mode = AdvertisingMode::select(None, false, BondedAdvertising::Directed, 0);
+        assert_eq!(mode, AdvertisingMode::Open);
+        // Only open after the pairing window action
+        let mode = AdvertisingMode::select(None, false, BondedAdvertising::Directed, 60);
+        assert_eq!(mode, AdvertisingMode::Closed);
+        let mode = AdvertisingMode::select(None, true, BondedAdvertising::Directed, 60);
+        assert_eq!(mode, AdvertisingMode::Open);
+    }
+
+    #[test]
+    fn bonded_profile_advertising() {
+        let host = BondedHost {
+            address: Address::random(HOST),
+            irk: None,
+        };
+        let mode = AdvertisingMode::select(Some(host), false, BondedAdvertising::Undirected, 0);
+        assert_eq!(mode, AdvertisingMode::Open);
+        let mode = AdvertisingMode::select(Some(host), false, BondedAdvertising::Filtered, 0);
+        assert_eq!(mode, AdvertisingMode::Filtered(host));
+        let mode = AdvertisingMode::select(Some(host), false, BondedAdvertising::Directed, 0);
+        assert_eq!(mode, AdvertisingMode::Directed(host));
+        // The pairing window allows another host to replace the bond
+        let mode = AdvertisingMode::select(Some(host), true, BondedAdvertising::Directed, 0);
+        assert_eq!(mode, AdvertisingMode::Open);
+    }
+
+    #[test]
+    fn bonded_host_of_profile() {
+        let bond = BleBond {
+            slot: 0,
+            address: HOST,
+            // A public address isn't guessed from its most significant bits
+            address_kind: BondAddressKind::Public,
+            irk: Some([0x5A; 16]),
+            ltk: [0; 16],
+            security_level: BondSecurityLevel::Encrypted,
+            is_bonded: true,
+            cccd_table: heapless::Vec::new(),
+        };
+        let host = BondedHost::new(&ProfileInfo::from_bond(&bond));
+        assert_eq!(host.address.kind, AddrKind::PUBLIC);
+        assert_eq!(host.address.addr, BdAddr::new(HOST));
+        assert_eq!(host.irk, Some(IdentityResolvingKey::from_le_bytes([0x5A; 16])));
+    }
+
+    #[test]
+    fn resolved_address_kind() {
+        assert_eq!(identity_addr_kind(AddrKind::PUBLIC), AddrKind::PUBLIC);
+        assert_eq!(identity_addr_kind(AddrKind::RANDOM), AddrKind::RANDOM);
+        assert_eq!(
+            identity_addr_kind(AddrKind::RESOLVABLE_PRIVATE_OR_PUBLIC),
+            AddrKind::PUBLIC
+        );
+        assert_eq!(
+            identity_addr_kind(AddrKind::RESOLVABLE_PRIVATE_OR_RANDOM),
+            AddrKind::RANDOM
+        );
+    }
+}
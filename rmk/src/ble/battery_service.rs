@@ -1,14 +1,22 @@
+#[cfg(feature = "split")]
+use core::cell::RefCell;
 use core::sync::atomic::Ordering;
 
 use embassy_futures::join::join;
 use embassy_futures::select::{Either, select};
+#[cfg(feature = "split")]
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::pubsub::Subscriber;
 use embassy_time::{Duration, Instant, Timer, with_timeout};
 use rmk_types::battery::BatteryStatus;
 use trouble_host::prelude::*;
 
 use super::ble_server::Server;
+#[cfg(feature = "split")]
+use crate::SPLIT_PERIPHERALS_NUM;
 use crate::ble::SLEEPING_STATE;
+#[cfg(feature = "split")]
+use crate::event::PeripheralBatteryEvent;
 use crate::event::{BatteryStatusEvent, SubscribableEvent};
 use crate::keyboard::LAST_KEY_TIMESTAMP;
 
@@ -21,6 +29,24 @@ pub(crate) struct BatteryService {
     pub(crate) level: u8,
 }
 
+/// Last battery levels of the split peripherals, restored when the host connects again
+#[cfg(feature = "split")]
+static PERIPHERAL_BATTERY_LEVELS: Mutex<crate::RawMutex, RefCell<[Option<u8>; SPLIT_PERIPHERALS_NUM]>> =
+    Mutex::new(RefCell::new([None; SPLIT_PERIPHERALS_NUM]));
+
+/// Whether the handle is the CCCD of the battery level of a split peripheral
+#[cfg(feature = "split")]
+pub(crate) fn is_peripheral_battery_cccd(server: &Server, handle: u16) -> bool {
+    (0..SPLIT_PERIPHERALS_NUM)
+        .filter_map(|id| server.peripheral_battery_level(id))
+        .any(|level| level.cccd_handle == Some(handle))
+}
+
+#[cfg(not(feature = "split"))]
+pub(crate) fn is_peripheral_battery_cccd(_server: &Server, _handle: u16) -> bool {
+    false
+}
+
 pub(crate) struct BleBatteryServer<'stack, 'server, 'conn, P: PacketPool> {
     pub(crate) battery_level: Characteristic<u8>,
     pub(crate) conn: &'conn GattConnection<'stack, 'server, P>,
@@ -118,3 +144,62 @@ impl<P: PacketPool> BleBatteryServer<'_, '_, '_, P> {
         }
     }
 }
+
+/// Reports the battery levels of the split peripherals, each one in its own Battery Service
+#[cfg(feature = "split")]
+pub(crate) struct BlePeripheralBatteryServer<'stack, 'server, 'conn, P: PacketPool> {
+    server: &'server Server<'server>,
+    conn: &'conn GattConnection<'stack, 'server, P>,
+    sub: Subscriber<
+        'static,
+        crate::RawMutex,
+        PeripheralBatteryEvent,
+        { crate::PERIPHERAL_BATTERY_EVENT_CHANNEL_SIZE },
+        { crate::PERIPHERAL_BATTERY_EVENT_SUB_SIZE },
+        { crate::PERIPHERAL_BATTERY_EVENT_PUB_SIZE },
+    >,
+}
+
+#[cfg(feature = "split")]
+impl<'stack, 'server, 'conn, P: PacketPool> BlePeripheralBatteryServer<'stack, 'server, 'conn, P> {
+    pub(crate) fn new(server: &'server Server<'server>, conn: &'conn GattConnection<'stack, 'server, P>) -> Self {
+        Self {
+            server,
+            conn,
+            sub: PeripheralBatteryEvent::subscriber(),
+        }
+    }
+
+    pub(crate) async fn run(&mut self) {
+        // Wait 2 seconds, ensure that gatt server has been started
+        Timer::after_secs(2).await;
+
+        // The host reads the levels known before it connected
+        let levels = PERIPHERAL_BATTERY_LEVELS.lock(|levels| *levels.borrow());
+        for (id, level) in levels.iter().enumerate() {
+            if let (Some(level), Some(characteristic)) = (level, self.server.peripheral_battery_level(id))
+                && let Err(e) = self.server.set(&characteristic, level)
+            {
+                error!("Failed to set battery level of peripheral {}: {:?}", id, e);
+            }
+        }
+
+        loop {
+            let event = self.sub.next_message_pure().await;
+            let BatteryStatus::Available { level: Some(level), .. } = event.state.0 else {
+                continue;
+            };
+            let Some(characteristic) = self.server.peripheral_battery_level(event.id) else {
+                continue;
+            };
+            PERIPHERAL_BATTERY_LEVELS.lock(|levels| levels.borrow_mut()[event.id] = Some(level));
+            // Don't wake up the host
+            if SLEEPING_STATE.load(Ordering::Acquire) {
+                continue;
+            }
+            if let Err(e) = characteristic.notify(self.conn, &level).await {
+                error!("Failed to notify battery level of peripheral {}: {:?}", event.id, e);
+            }
+        }
+    }
+}
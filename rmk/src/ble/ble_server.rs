@@ -1,9 +1,13 @@
+use embassy_time::Instant;
 use trouble_host::prelude::*;
 use usbd_hid::descriptor::{AsInputReport as _, SerializedDescriptor};
 
 use super::battery_service::BatteryService;
 use super::device_info::DeviceConfigurationService;
+use super::link_stats::record_report;
 use crate::channel::KEYBOARD_REPORT_CHANNEL;
+#[cfg(feature = "gamepad")]
+use crate::hid::GamepadReport;
 #[cfg(feature = "host")]
 use crate::hid::ViaReport;
 use crate::hid::{
@@ -13,22 +17,94 @@ use crate::hid::{
 // Used for saving the CCCD table
 pub(crate) const CCCD_TABLE_SIZE: usize = _CCCD_TABLE_SIZE;
 
-// GATT Server definition
-// NOTE: ideally we would conditionally add the `via_service` member, based on the
-// `vial` feature flag. But when doing that, rust still compiles the member as if
-// the flag was on, for some reason. I suspect it might have something to do with
-// the `gatt_server` macro, but I'm not sure. So we need 2 versions of the Server
-// struct, one with vial support, and one without.
-#[cfg(feature = "host")]
-#[gatt_server]
-pub(crate) struct Server {
-    pub(crate) battery_service: BatteryService,
-    pub(crate) hid_service: HidService,
-    pub(crate) host_service: VialService,
-    pub(crate) composite_service: CompositeService,
-    pub(crate) device_config_service: DeviceConfigurationService,
+/// Declare the GATT server, with a Battery Service for each split peripheral given as `id => "name"`.
+///
+/// The services of the split peripherals are appended after the other services,
+/// so that the attribute handles of the other services don't change.
+macro_rules! define_server {
+    ($($id:literal => $name:literal),* $(,)?) => {
+        paste::paste! {
+            $(
+                /// Battery service of a split peripheral, described by the name of the peripheral
+                #[gatt_service(uuid = service::BATTERY)]
+                pub(crate) struct [<PeripheralBatteryService $id>] {
+                    #[descriptor(uuid = descriptors::VALID_RANGE, read, value = [0, 100])]
+                    #[descriptor(uuid = "2901", read, value = $name)]
+                    #[characteristic(uuid = characteristic::BATTERY_LEVEL, read, notify)]
+                    pub(crate) level: u8,
+                }
+            )*
+
+            // GATT Server definition
+            // NOTE: ideally we would conditionally add the `via_service` member, based on the
+            // `vial` feature flag. But when doing that, rust still compiles the member as if
+            // the flag was on, for some reason. I suspect it might have something to do with
+            // the `gatt_server` macro, but I'm not sure. The same applies to the `gamepad_service`
+            // member. So we need 4 versions of the Server struct, one for each combination of
+            // vial and gamepad support.
+            #[cfg(all(feature = "host", not(feature = "gamepad")))]
+            #[gatt_server]
+            pub(crate) struct Server {
+                pub(crate) battery_service: BatteryService,
+                pub(crate) hid_service: HidService,
+                pub(crate) host_service: VialService,
+                pub(crate) composite_service: CompositeService,
+                pub(crate) device_config_service: DeviceConfigurationService,
+                $(pub(crate) [<peripheral_battery_service_ $id>]: [<PeripheralBatteryService $id>],)*
+            }
+
+            #[cfg(all(feature = "host", feature = "gamepad"))]
+            #[gatt_server]
+            pub(crate) struct Server {
+                pub(crate) battery_service: BatteryService,
+                pub(crate) hid_service: HidService,
+                pub(crate) host_service: VialService,
+                pub(crate) composite_service: CompositeService,
+                pub(crate) gamepad_service: GamepadService,
+                pub(crate) device_config_service: DeviceConfigurationService,
+                $(pub(crate) [<peripheral_battery_service_ $id>]: [<PeripheralBatteryService $id>],)*
+            }
+
+            #[cfg(all(not(feature = "host"), not(feature = "gamepad")))]
+            #[gatt_server]
+            pub(crate) struct Server {
+                pub(crate) battery_service: BatteryService,
+                pub(crate) hid_service: HidService,
+                pub(crate) composite_service: CompositeService,
+                pub(crate) device_config_service: DeviceConfigurationService,
+                $(pub(crate) [<peripheral_battery_service_ $id>]: [<PeripheralBatteryService $id>],)*
+            }
+
+            #[cfg(all(not(feature = "host"), feature = "gamepad"))]
+            #[gatt_server]
+            pub(crate) struct Server {
+                pub(crate) battery_service: BatteryService,
+                pub(crate) hid_service: HidService,
+                pub(crate) composite_service: CompositeService,
+                pub(crate) gamepad_service: GamepadService,
+                pub(crate) device_config_service: DeviceConfigurationService,
+                $(pub(crate) [<peripheral_battery_service_ $id>]: [<PeripheralBatteryService $id>],)*
+            }
+
+            #[cfg(feature = "split")]
+            impl Server<'_> {
+                /// Battery level characteristic of the split peripheral
+                pub(crate) fn peripheral_battery_level(&self, id: usize) -> Option<Characteristic<u8>> {
+                    match id {
+                        $($id => Some(self.[<peripheral_battery_service_ $id>].level),)*
+                        _ => None,
+                    }
+                }
+            }
+        }
+    };
 }
 
+#[cfg(feature = "split")]
+rmk_types::split_peripheral_batteries!(define_server);
+#[cfg(not(feature = "split"))]
+define_server!();
+
 /// GATT service exposing the Vial-over-HID protocol. The keyboard writes replies via
 /// `input_data` notify; hosts push requests through `output_data`. `gatt_events_task`
 /// forwards `output_data` writes into `HOST_REQUEST_CHANNEL`, and `host::run_ble_host`
@@ -52,15 +128,6 @@ pub(crate) struct VialService {
     pub(crate) output_data: [u8; 32],
 }
 
-#[cfg(not(feature = "host"))]
-#[gatt_server]
-pub(crate) struct Server {
-    pub(crate) battery_service: BatteryService,
-    pub(crate) hid_service: HidService,
-    pub(crate) composite_service: CompositeService,
-    pub(crate) device_config_service: DeviceConfigurationService,
-}
-
 #[gatt_service(uuid = service::HUMAN_INTERFACE_DEVICE)]
 pub(crate) struct HidService {
     #[characteristic(uuid = "2a4a", read, value = [0x01, 0x01, 0x00, 0x03])]
@@ -100,11 +167,30 @@ pub(crate) struct CompositeService {
     pub(crate) system_report: [u8; 1],
 }
 
+/// GATT service exposing the gamepad report, which has its own report map like the USB interface.
+#[cfg(feature = "gamepad")]
+#[gatt_service(uuid = service::HUMAN_INTERFACE_DEVICE)]
+pub(crate) struct GamepadService {
+    #[characteristic(uuid = "2a4a", read, value = [0x01, 0x01, 0x00, 0x03])]
+    pub(crate) hid_info: [u8; 4],
+    #[characteristic(uuid = "2a4b", read, value = GamepadReport::desc().try_into().expect("Failed to convert GamepadReport to [u8; 74]"))]
+    pub(crate) report_map: [u8; 74],
+    #[characteristic(uuid = "2a4c", write_without_response)]
+    pub(crate) hid_control_point: u8,
+    #[characteristic(uuid = "2a4e", read, write_without_response, value = 1)]
+    pub(crate) protocol_mode: u8,
+    #[descriptor(uuid = "2908", read, value = [0u8, 1u8])]
+    #[characteristic(uuid = "2a4d", read, notify)]
+    pub(crate) gamepad_report: [u8; 11],
+}
+
 pub(crate) struct BleHidServer<'stack, 'server, 'conn, P: PacketPool> {
     pub(crate) input_keyboard: Characteristic<[u8; 8]>,
     pub(crate) mouse_report: Characteristic<[u8; 5]>,
     pub(crate) media_report: Characteristic<[u8; 2]>,
     pub(crate) system_report: Characteristic<[u8; 1]>,
+    #[cfg(feature = "gamepad")]
+    pub(crate) gamepad_report: Characteristic<[u8; 11]>,
     pub(crate) conn: &'conn GattConnection<'stack, 'server, P>,
 }
 
@@ -115,6 +201,8 @@ impl<'stack, 'server, 'conn, P: PacketPool> BleHidServer<'stack, 'server, 'conn,
             mouse_report: server.composite_service.mouse_report,
             media_report: server.composite_service.media_report,
             system_report: server.composite_service.system_report,
+            #[cfg(feature = "gamepad")]
+            gamepad_report: server.gamepad_service.gamepad_report,
             conn,
         }
     }
@@ -124,6 +212,17 @@ impl<P: PacketPool> HidWriterTrait for BleHidServer<'_, '_, '_, P> {
     type ReportType = Report;
 
     async fn write_report(&mut self, report: Self::ReportType) -> Result<usize, HidError> {
+        // Reports still waiting in the channel show the backpressure of the notifications
+        let queued = KEYBOARD_REPORT_CHANNEL.len();
+        let start = Instant::now();
+        let n = self.notify_report(report).await?;
+        record_report(start.elapsed(), queued);
+        Ok(n)
+    }
+}
+
+impl<P: PacketPool> BleHidServer<'_, '_, '_, P> {
+    pub(crate) async fn notify_report(&mut self, report: Report) -> Result<usize, HidError> {
         match report {
             Report::KeyboardReport(keyboard_report) => {
                 let mut buf = [0u8; 8];
@@ -177,6 +276,18 @@ impl<P: PacketPool> HidWriterTrait for BleHidServer<'_, '_, '_, P> {
                 warn!("Steno chord dropped: Plover HID over BLE is not supported");
                 Ok(0)
             }
+            #[cfg(feature = "gamepad")]
+            Report::GamepadReport(gamepad_report) => {
+                let mut buf = [0u8; 11];
+                let n = gamepad_report
+                    .serialize(&mut buf)
+                    .map_err(|_| HidError::ReportSerializeError)?;
+                self.gamepad_report.notify(self.conn, &buf).await.map_err(|e| {
+                    error!("Failed to notify gamepad report: {:?}", e);
+                    HidError::BleError
+                })?;
+                Ok(n)
+            }
         }
     }
 }
@@ -0,0 +1,80 @@
+//! Backup and restore of BLE bonds, and labels of BLE profiles.
+//!
+//! A bond exported with [`export_bond`] can be imported with [`import_bond`] after the storage is erased,
+//! or on another keyboard, so that the host doesn't need to pair again.
+//! An exported bond contains the long term key of the connection, it must be kept secret.
+//!
+//! Labels give the profile slots a name, e.g. "MacBook". They are kept when the bond of the slot is cleared.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+pub use rmk_types::ble::{BleBond, ProfileLabel};
+
+use super::profile::{BleProfileAction, EXPORTED_BOND, ProfileInfo};
+use crate::channel::BLE_PROFILE_CHANNEL;
+#[cfg(feature = "storage")]
+use crate::channel::FLASH_CHANNEL;
+#[cfg(feature = "storage")]
+use crate::storage::FlashOperationMessage;
+use crate::{NUM_BLE_PROFILE, RawMutex};
+
+static PROFILE_LABELS: Mutex<RawMutex, RefCell<[ProfileLabel; NUM_BLE_PROFILE]>> = Mutex::new(RefCell::new(
+    [const {
+        ProfileLabel {
+            label: heapless::Vec::new(),
+        }
+    }; NUM_BLE_PROFILE],
+));
+
+/// Load the labels of all profiles from the storage
+#[cfg(feature = "storage")]
+pub(crate) async fn load_profile_labels() {
+    for slot_num in 0..NUM_BLE_PROFILE {
+        if let Some(label) = crate::storage::read_profile_label(slot_num as u8).await {
+            PROFILE_LABELS.lock(|labels| labels.borrow_mut()[slot_num] = label);
+        }
+    }
+}
+
+/// Label of the profile, `None` if the slot doesn't exist
+pub fn profile_label(slot_num: u8) -> Option<ProfileLabel> {
+    PROFILE_LABELS.lock(|labels| labels.borrow().get(slot_num as usize).cloned())
+}
+
+/// Set the label of the profile and save it to the storage.
+///
+/// Returns `false` if the slot doesn't exist.
+pub async fn set_profile_label(slot_num: u8, label: ProfileLabel) -> bool {
+    if slot_num as usize >= NUM_BLE_PROFILE {
+        warn!("Invalid profile slot: {}", slot_num);
+        return false;
+    }
+    PROFILE_LABELS.lock(|labels| labels.borrow_mut()[slot_num as usize] = label.clone());
+    #[cfg(feature = "storage")]
+    FLASH_CHANNEL
+        .send(FlashOperationMessage::ProfileLabel(slot_num, label))
+        .await;
+    true
+}
+
+/// Export the bond of the profile, `None` if the slot isn't bonded
+pub async fn export_bond(slot_num: u8) -> Option<BleBond> {
+    EXPORTED_BOND.reset();
+    BLE_PROFILE_CHANNEL.send(BleProfileAction::ExportBond(slot_num)).await;
+    EXPORTED_BOND.wait().await.map(|info| info.to_bond())
+}
+
+/// Import a bond into the slot given by [`BleBond::slot`], replacing the existing bond of the slot.
+///
+/// Returns `false` if the slot doesn't exist.
+pub async fn import_bond(bond: BleBond) -> bool {
+    if bond.slot as usize >= NUM_BLE_PROFILE {
+        warn!("Invalid profile slot: {}", bond.slot);
+        return false;
+    }
+    BLE_PROFILE_CHANNEL
+        .send(BleProfileAction::ImportBond(ProfileInfo::from_bond(&bond)))
+        .await;
+    true
+}
@@ -0,0 +1,198 @@
+//! Link statistics of the BLE connection to the host.
+//!
+//! The RSSI is read from the BLE controller periodically.
+//! Neither the HCI nor the BLE stack report the connection events missed by the controller, so they are estimated
+//! from the send delay, how long the BLE stack takes to accept a report: a report which takes longer than a connection
+//! interval counts as a missed connection event. The reports waiting in the report channel give the backpressure of
+//! the notifications.
+//! The statistics are published as [`BleLinkStatsEvent`] every few seconds and when the connection parameters change.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Duration;
+
+use crate::RawMutex;
+use crate::event::{BleLinkStatsEvent, publish_event};
+
+/// Number of recent reports used to estimate the missed connection events
+const REPORT_WINDOW: u32 = 32;
+
+#[derive(Clone, Copy)]
+struct LinkStats {
+    rssi: Option<i8>,
+    conn_interval: Option<Duration>,
+    conn_latency: u16,
+    low_power: bool,
+    /// Results of the recent reports, a set bit is a report which took longer than a connection interval to be sent
+    missed: u32,
+    /// Number of recent reports in `missed`
+    reports: u32,
+    /// Most reports waiting in the report channel since the last event
+    queued_reports: u8,
+}
+
+impl LinkStats {
+    const fn new() -> Self {
+        Self {
+            rssi: None,
+            conn_interval: None,
+            conn_latency: 0,
+            low_power: false,
+            missed: 0,
+            reports: 0,
+            queued_reports: 0,
+        }
+    }
+
+    /// Record a report which took `elapsed` to be sent, with `queued` reports waiting after it
+    fn record_report(&mut self, elapsed: Duration, queued: usize) {
+        // The connection interval is unknown until the host sets it, nothing is counted as missed before
+        let missed = self.conn_interval.is_some_and(|interval| elapsed > interval);
+        self.missed = (self.missed << 1) | missed as u32;
+        self.reports = (self.reports + 1).min(REPORT_WINDOW);
+        self.queued_reports = self.queued_reports.max(queued.min(u8::MAX as usize) as u8);
+    }
+
+    /// Percentage of the recent reports which missed their connection event
+    fn missed_conn_events(&self) -> u8 {
+        if self.reports == 0 {
+            0
+        } else {
+            (self.missed.count_ones() * 100 / self.reports) as u8
+        }
+    }
+
+    fn event(&self) -> BleLinkStatsEvent {
+        BleLinkStatsEvent {
+            rssi: self.rssi,
+            conn_interval_us: self
+                .conn_interval
+                .map(|interval| interval.as_micros().min(u32::MAX as u64) as u32),
+            conn_latency: self.conn_latency,
+            low_power: self.low_power,
+            missed_conn_events: self.missed_conn_events(),
+            queued_reports: self.queued_reports,
+        }
+    }
+}
+
+static LINK_STATS: Mutex<RawMutex, RefCell<LinkStats>> = Mutex::new(RefCell::new(LinkStats::new()));
+
+/// Signaled when a report is sent to the host, the connection parameters follow the typing activity with it
+pub(crate) static REPORT_SENT: Signal<RawMutex, ()> = Signal::new();
+
+fn update<R>(f: impl FnOnce(&mut LinkStats) -> R) -> R {
+    LINK_STATS.lock(|stats| f(&mut stats.borrow_mut()))
+}
+
+/// Record a report sent to the host
+pub(crate) fn record_report(elapsed: Duration, queued: usize) {
+    update(|stats| stats.record_report(elapsed, queued));
+    REPORT_SENT.signal(());
+}
+
+/// Save the RSSI of the connection, it's published with the next statistics
+pub(crate) fn set_rssi(rssi: i8) {
+    update(|stats| stats.rssi = Some(rssi));
+}
+
+/// Save the connection parameters set by the host and publish the stats
+pub(crate) fn set_conn_params(interval: Duration, latency: u16) {
+    publish_event(update(|stats| {
+        stats.conn_interval = Some(interval);
+        stats.conn_latency = latency;
+        stats.event()
+    }));
+}
+
+/// Save whether the low power connection parameters are requested and publish the stats
+pub(crate) fn set_low_power(low_power: bool) {
+    publish_event(update(|stats| {
+        stats.low_power = low_power;
+        stats.event()
+    }));
+}
+
+/// Publish the stats, the backpressure is measured again from now on
+pub(crate) fn publish_link_stats() {
+    publish_event(update(|stats| {
+        let event = stats.event();
+        stats.queued_reports = 0;
+        event
+    }));
+}
+
+/// Clear the stats when the connection changes and publish them
+pub(crate) fn reset_link_stats() {
+    publish_event(update(|stats| {
+        *stats = LinkStats::new();
+        stats.event()
+    }));
+}
+
+/// Get the link statistics of the current BLE connection
+pub fn ble_link_stats() -> BleLinkStatsEvent {
+    LINK_STATS.lock(|stats| stats.borrow().event())
+}
+
+#[cfg(feature = "rmk_protocol")]
+impl From<BleLinkStatsEvent> for rmk_types::protocol::rmk::BleLinkStatus {
+    fn from(e: BleLinkStatsEvent) -> Self {
+        Self {
+            // The RSSI is sent in -dBm, a positive one can't be represented
+            rssi: e.rssi.map(|rssi| rssi.min(0).unsigned_abs()),
+            conn_interval_us: e.conn_interval_us,
+            conn_latency: e.conn_latency,
+            low_power: e.low_power,
+            missed_conn_events: e.missed_conn_events,
+            queued_reports: e.queued_reports,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_reports_miss_conn_events() {
+        let mut stats = LinkStats::new();
+        // Unknown interval, nothing is missed
+        stats.record_report(Duration::from_millis(100), 0);
+        assert_eq!(stats.missed_conn_events(), 0);
+
+        stats.conn_interval = Some(Duration::from_micros(7500));
+        stats.record_report(Duration::from_millis(20), 3);
+        assert_eq!(stats.missed_conn_events(), 50);
+        assert_eq!(stats.event().queued_reports, 3);
+        assert_eq!(stats.event().conn_interval_us, Some(7500));
+
+        // Old reports leave the window
+        for _ in 0..REPORT_WINDOW {
+            stats.record_report(Duration::from_millis(1), 1);
+        }
+        assert_eq!(stats.missed_conn_events(), 0);
+        // The most queued reports are kept until they're published
+        assert_eq!(stats.queued_reports, 3);
+    }
+
+    #[cfg(feature = "rmk_protocol")]
+    #[test]
+    fn rssi_is_sent_in_negative_dbm() {
+        use rmk_types::protocol::rmk::BleLinkStatus;
+
+        let status = |rssi| {
+            BleLinkStatus::from(BleLinkStatsEvent {
+                rssi,
+                ..Default::default()
+            })
+            .rssi
+        };
+        assert_eq!(status(Some(-60)), Some(60));
+        assert_eq!(status(Some(-128)), Some(128));
+        assert_eq!(status(Some(5)), Some(0));
+        assert_eq!(status(None), None);
+    }
+}
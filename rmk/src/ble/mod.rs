@@ -1,16 +1,17 @@
 use core::cell::Cell;
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use bt_hci::cmd::le::{LeReadLocalSupportedFeatures, LeSetPhy};
+use bt_hci::cmd::le::{
+    LeAddDeviceToFilterAcceptList, LeAddDeviceToResolvingList, LeClearFilterAcceptList, LeClearResolvingList,
+    LeReadLocalSupportedFeatures, LeSetAddrResolutionEnable, LeSetPhy,
+};
+use bt_hci::cmd::status::ReadRssi;
 use bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync};
-use embassy_futures::join::join;
-#[cfg(any(not(feature = "_no_usb"), feature = "passkey_entry"))]
-use embassy_futures::select::Either;
-use embassy_futures::select::{Either3, select, select3};
+use embassy_futures::join::{join, join3};
+use embassy_futures::select::{Either, Either3, select, select3};
 use embassy_sync::blocking_mutex::Mutex;
-use embassy_time::{Duration, Timer, with_timeout};
-#[cfg(feature = "passkey_entry")]
-use embassy_time::{Instant, with_deadline};
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Timer, with_deadline, with_timeout};
 use rand_core::{CryptoRng, RngCore};
 use rmk_types::led_indicator::LedIndicator;
 use trouble_host::prelude::appearance::human_interface_device::KEYBOARD;
@@ -28,14 +29,23 @@ use {
 #[cfg(feature = "storage")]
 use {crate::state::CONNECTION_TYPE, crate::storage::StorageKey};
 
-use crate::ble::battery_service::BleBatteryServer;
+use crate::ble::advertising::{
+    ADVERTISING_TIMEOUT, AdvertisingMode, BondedHost, PAIRING_WINDOW_REQUEST, advertisement_parameters,
+};
+#[cfg(feature = "split")]
+use crate::ble::battery_service::BlePeripheralBatteryServer;
+use crate::ble::battery_service::{BleBatteryServer, is_peripheral_battery_cccd};
 use crate::ble::ble_server::{BleHidServer, Server};
 use crate::ble::device_info::{PnPID, VidSource};
 use crate::ble::led::BleLedReader;
+use crate::ble::link_stats::{REPORT_SENT, publish_link_stats, reset_link_stats, set_low_power};
+use crate::ble::multi_host::{HOST_SLOTS, PendingHost};
 use crate::ble::profile::{ProfileInfo, ProfileManager, UPDATED_CCCD_TABLE, UPDATED_PROFILE};
 use crate::channel::{KEYBOARD_REPORT_CHANNEL, LED_SIGNAL};
 use crate::config::RmkConfig;
 use crate::event::{BleStatusChangeEvent, ConnectionChangeEvent, ConnectionType, publish_event};
+#[cfg(all(not(feature = "_no_usb"), feature = "gamepad"))]
+use crate::hid::GamepadReport;
 #[cfg(all(not(feature = "_no_usb"), feature = "steno"))]
 use crate::hid::StenoReport;
 #[cfg(all(feature = "host", not(feature = "_no_usb")))]
@@ -47,13 +57,20 @@ use crate::state::ConnectionState;
 #[cfg(feature = "usb_log")]
 use crate::usb::add_usb_logger;
 use crate::{CONNECTION_STATE, run_keyboard};
+pub(crate) mod advertising;
 pub(crate) mod battery_service;
 pub(crate) mod ble_server;
+pub mod bond;
 pub(crate) mod device_info;
 pub(crate) mod led;
+pub mod link_stats;
+pub(crate) mod multi_host;
 #[cfg(feature = "passkey_entry")]
 pub mod passkey;
 pub(crate) mod profile;
+pub mod profile_settings;
+
+pub use advertising::open_pairing_window;
 
 use rmk_types::ble::{BleState, BleStatus};
 
@@ -86,16 +103,28 @@ pub(crate) fn set_ble_state(state: BleState) {
 /// - `false`: Indicates central is awake
 pub(crate) static SLEEPING_STATE: AtomicBool = AtomicBool::new(false);
 
+/// Max number of host connections, the host of each profile stays connected with `multi_host`
+const HOST_CONNECTIONS_MAX: usize = if crate::BLE_MULTI_HOST {
+    crate::NUM_BLE_PROFILE
+} else {
+    1
+};
+
 // TODO: Add documentation about how to define split peripheral num in Rust code
 /// Max number of connections
-pub(crate) const CONNECTIONS_MAX: usize = crate::SPLIT_PERIPHERALS_NUM + 1;
+pub(crate) const CONNECTIONS_MAX: usize = crate::SPLIT_PERIPHERALS_NUM + HOST_CONNECTIONS_MAX;
 
 /// Max number of L2CAP channels
 pub(crate) const L2CAP_CHANNELS_MAX: usize = CONNECTIONS_MAX * 4; // Signal + att + smp + hid
 
+/// Interval of reading the RSSI and publishing the link statistics of the host connection
+const LINK_STATS_INTERVAL: Duration = Duration::from_secs(3);
+
 #[cfg(feature = "passkey_entry")]
 struct PasskeyInputState {
     deadline: Option<Instant>,
+    /// Whether the keyboard waits for a numeric comparison instead of a typed passkey
+    confirm: bool,
     cleanup: Option<crate::ble::passkey::PasskeyCleanupGuard>,
 }
 
@@ -104,12 +133,14 @@ impl PasskeyInputState {
     const fn new() -> Self {
         Self {
             deadline: None,
+            confirm: false,
             cleanup: None,
         }
     }
 
     fn clear(&mut self) {
         self.deadline = None;
+        self.confirm = false;
         drop(self.cleanup.take());
     }
 
@@ -121,6 +152,27 @@ impl PasskeyInputState {
         self.cleanup = Some(PasskeyCleanupGuard::new());
         self.deadline = Some(Instant::now() + Duration::from_secs(crate::PASSKEY_ENTRY_TIMEOUT_SECS as u64));
     }
+
+    /// Show the passkey which the host asks the user to compare, and wait for the confirmation
+    fn begin_confirm(&mut self, passkey: u32) {
+        use crate::ble::passkey::{PasskeyCleanupGuard, begin_passkey_confirm_session, show_passkey};
+
+        self.clear();
+        begin_passkey_confirm_session();
+        self.cleanup = Some(PasskeyCleanupGuard::new());
+        show_passkey(passkey, true);
+        self.confirm = true;
+        self.deadline = Some(Instant::now() + Duration::from_secs(crate::PASSKEY_ENTRY_TIMEOUT_SECS as u64));
+    }
+
+    /// Show the passkey which the user types on the host, until the pairing ends
+    fn show(&mut self, passkey: u32) {
+        use crate::ble::passkey::{PasskeyCleanupGuard, show_passkey};
+
+        self.clear();
+        self.cleanup = Some(PasskeyCleanupGuard::new());
+        show_passkey(passkey, false);
+    }
 }
 
 #[cfg(feature = "passkey_entry")]
@@ -128,6 +180,41 @@ async fn next_gatt_event<'a, 'b>(
     conn: &GattConnection<'a, 'b, DefaultPacketPool>,
     passkey_state: &mut PasskeyInputState,
 ) -> Option<GattConnectionEvent<'a, 'b, DefaultPacketPool>> {
+    if let Some(deadline) = passkey_state.deadline
+        && passkey_state.confirm
+    {
+        use crate::ble::passkey::PASSKEY_CONFIRMATION;
+
+        return match select(conn.next(), with_deadline(deadline, PASSKEY_CONFIRMATION.wait())).await {
+            Either::First(event) => Some(event),
+            Either::Second(Ok(true)) => {
+                passkey_state.clear();
+
+                info!("[gatt] Passkey confirmed");
+                if let Err(e) = conn.raw().pass_key_confirm() {
+                    error!("[gatt] pass_key_confirm error: {:?}", e);
+                }
+                None
+            }
+            Either::Second(Ok(false)) => {
+                passkey_state.clear();
+
+                info!("[gatt] Passkey rejected");
+                if let Err(e) = conn.raw().pass_key_cancel() {
+                    error!("[gatt] pass_key_cancel error: {:?}", e);
+                }
+                None
+            }
+            Either::Second(Err(_)) => {
+                passkey_state.clear();
+
+                warn!("[gatt] Passkey confirmation timeout");
+                let _ = conn.raw().pass_key_cancel();
+                None
+            }
+        };
+    }
+
     if crate::PASSKEY_ENTRY_ENABLED
         && let Some(deadline) = passkey_state.deadline
     {
@@ -184,6 +271,32 @@ pub async fn build_ble_stack<
         .set_random_generator_seed(random_generator)
 }
 
+/// IO capabilities of the keyboard for BLE pairing, `None` keeps the default of the stack (no input, no output).
+///
+/// `display` tells whether a [`DisplayProcessor`](crate::display::DisplayProcessor) runs on this board.
+/// When the passkey can be shown on it (`passkey_display` in `[ble]`), numeric comparison is confirmed with Enter or Y.
+/// Boards which use the Rust API pass the result to `Stack::set_io_capabilities`.
+pub fn pairing_io_capabilities(display: bool) -> Option<IoCapabilities> {
+    let display = display && passkey_display_enabled();
+    match (passkey_entry_enabled(), display) {
+        (true, true) => Some(IoCapabilities::KeyboardDisplay),
+        (true, false) => Some(IoCapabilities::KeyboardOnly),
+        (false, true) => Some(IoCapabilities::DisplayYesNo),
+        (false, false) => None,
+    }
+}
+
+fn passkey_display_enabled() -> bool {
+    #[cfg(feature = "passkey_entry")]
+    {
+        crate::PASSKEY_DISPLAY_ENABLED
+    }
+    #[cfg(not(feature = "passkey_entry"))]
+    {
+        false
+    }
+}
+
 #[doc(hidden)]
 pub fn passkey_entry_enabled() -> bool {
     #[cfg(feature = "passkey_entry")]
@@ -199,7 +312,15 @@ pub fn passkey_entry_enabled() -> bool {
 /// Run the BLE stack.
 pub(crate) async fn run_ble<
     'b,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>
+        + ControllerCmdSync<ReadRssi>,
     #[cfg(not(feature = "_no_usb"))] D: Driver<'static>,
 >(
     #[cfg(not(feature = "_no_usb"))] usb_driver: D,
@@ -232,6 +353,9 @@ pub(crate) async fn run_ble<
     #[cfg(all(not(feature = "_no_usb"), feature = "steno"))]
     let mut steno_writer = add_usb_writer!(&mut _usb_builder, StenoReport, 9, 16);
 
+    #[cfg(all(not(feature = "_no_usb"), feature = "gamepad"))]
+    let mut gamepad_writer = add_usb_writer!(&mut _usb_builder, GamepadReport, 11, 16);
+
     #[cfg(all(not(feature = "_no_usb"), feature = "host"))]
     let mut host_reader_writer = add_usb_reader_writer!(&mut _usb_builder, ViaReport, 32, 32, 32);
 
@@ -307,6 +431,10 @@ pub(crate) async fn run_ble<
         )
         .unwrap();
 
+    // Connections accepted for the host of each profile, with `multi_host`
+    let pending_hosts: [PendingHost<'_, '_>; HOST_SLOTS] = [const { Channel::new() }; HOST_SLOTS];
+    let hosts_task = multi_host::run_host_slots(&pending_hosts, &server, stack);
+
     #[cfg(not(feature = "_no_usb"))]
     let usb_task = async {
         loop {
@@ -342,11 +470,25 @@ pub(crate) async fn run_ble<
     let background_task = ble_task(runner);
 
     // Main loop
-    join(background_task, async {
+    let name = rmk_config.device_config.product_name;
+    join3(background_task, hosts_task, async {
         loop {
-            // Advertising state
-            set_ble_state(BleState::Advertising);
-            let adv_fut = advertise(rmk_config.device_config.product_name, &mut peripheral, &server);
+            // Advertising state, unless the host of the active profile is still connected
+            if !multi_host::is_host_connected(get_current_profile()) {
+                set_ble_state(BleState::Advertising);
+            }
+            let bonded_host = profile_manager.active_bond_info().as_ref().map(BondedHost::new);
+            let adv_fut = async {
+                if crate::BLE_MULTI_HOST {
+                    multi_host::accept_hosts(name, stack, &mut peripheral, &server, &pending_hosts, true)
+                        .await
+                        .map(BleLink::Host)
+                } else {
+                    advertise(name, stack, &mut peripheral, &server, bonded_host, false)
+                        .await
+                        .map(|(conn, _)| BleLink::Single(conn))
+                }
+            };
             // USB + BLE dual mode
             #[cfg(not(feature = "_no_usb"))]
             {
@@ -377,6 +519,8 @@ pub(crate) async fn run_ble<
                                         &mut other_writer,
                                         #[cfg(feature = "steno")]
                                         &mut steno_writer,
+                                        #[cfg(feature = "gamepad")]
+                                        &mut gamepad_writer,
                                     ),
                                 );
                                 #[cfg(feature = "host")]
@@ -387,14 +531,17 @@ pub(crate) async fn run_ble<
                                 let usb_with_host_fut = usb_fut;
                                 select(usb_with_host_fut, profile_manager.update_profile()).await;
                             }
-                            Either4::Second(Ok(conn)) => {
+                            Either4::Second(Ok(link)) => {
                                 info!("No USB, BLE connected, run BLE keyboard");
                                 #[cfg(feature = "storage")]
                                 let active_bond_info = profile_manager.active_bond_info();
-                                let ble_fut = run_ble_keyboard(
-                                    &server,
-                                    &conn,
+                                let ble_fut = run_ble_link(
+                                    link,
+                                    name,
                                     stack,
+                                    &mut peripheral,
+                                    &server,
+                                    &pending_hosts,
                                     #[cfg(feature = "storage")]
                                     active_bond_info,
                                 );
@@ -413,7 +560,7 @@ pub(crate) async fn run_ble<
                                 CENTRAL_SLEEP.signal(true);
 
                                 // Wait for the keyboard report for wake the keyboard
-                                let _ = KEYBOARD_REPORT_CHANNEL.receive().await;
+                                wait_for_wake_up().await;
 
                                 // Quit from sleep mode
                                 #[cfg(feature = "split")]
@@ -433,6 +580,8 @@ pub(crate) async fn run_ble<
                                 &mut other_writer,
                                 #[cfg(feature = "steno")]
                                 &mut steno_writer,
+                                #[cfg(feature = "gamepad")]
+                                &mut gamepad_writer,
                             ),
                         );
                         #[cfg(feature = "host")]
@@ -442,15 +591,18 @@ pub(crate) async fn run_ble<
                         #[cfg(not(feature = "host"))]
                         let usb_with_host_fut = usb_fut;
                         match select3(adv_fut, usb_with_host_fut, profile_manager.update_profile()).await {
-                            Either3::First(Ok(conn)) => {
+                            Either3::First(Ok(link)) => {
                                 info!("BLE connected, running BLE keyboard");
                                 #[cfg(feature = "storage")]
                                 let active_bond_info = profile_manager.active_bond_info();
                                 select(
-                                    run_ble_keyboard(
-                                        &server,
-                                        &conn,
+                                    run_ble_link(
+                                        link,
+                                        name,
                                         stack,
+                                        &mut peripheral,
+                                        &server,
+                                        &pending_hosts,
                                         #[cfg(feature = "storage")]
                                         active_bond_info,
                                     ),
@@ -470,7 +622,7 @@ pub(crate) async fn run_ble<
                                 CENTRAL_SLEEP.signal(true);
 
                                 // Wait for the keyboard report for wake the keyboard
-                                let _ = KEYBOARD_REPORT_CHANNEL.receive().await;
+                                wait_for_wake_up().await;
 
                                 // Quit from sleep mode
                                 #[cfg(feature = "split")]
@@ -486,15 +638,18 @@ pub(crate) async fn run_ble<
 
             #[cfg(feature = "_no_usb")]
             match adv_fut.await {
-                Ok(conn) => {
+                Ok(link) => {
                     // BLE connected
                     #[cfg(feature = "storage")]
                     let active_bond_info = profile_manager.active_bond_info();
                     select(
-                        run_ble_keyboard(
+                        run_ble_link(
+                            link,
+                            name,
+                            stack,
+                            &mut peripheral,
                             &server,
-                            &conn,
-                            &stack,
+                            &pending_hosts,
                             #[cfg(feature = "storage")]
                             active_bond_info,
                         ),
@@ -514,7 +669,7 @@ pub(crate) async fn run_ble<
                     CENTRAL_SLEEP.signal(true);
 
                     // Wait for the keyboard report for wake the keyboard
-                    let _ = KEYBOARD_REPORT_CHANNEL.receive().await;
+                    wait_for_wake_up().await;
 
                     // Quit from sleep mode
                     #[cfg(feature = "split")]
@@ -565,7 +720,13 @@ pub(crate) async fn ble_task<C: Controller + ControllerCmdAsync<LeSetPhy>, P: Pa
 ///
 /// This function will handle the GATT events and process them.
 /// This is how we interact with read and write requests.
-async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, DefaultPacketPool>) -> Result<(), Error> {
+///
+/// `slot` is the profile of the host. With `multi_host`, the host of an idle profile doesn't update the keyboard state.
+async fn gatt_events_task(
+    server: &Server<'_>,
+    conn: &GattConnection<'_, '_, DefaultPacketPool>,
+    slot: u8,
+) -> Result<(), Error> {
     let level = server.battery_service.level;
     let output_keyboard = server.hid_service.output_keyboard;
     let hid_control_point = server.hid_service.hid_control_point;
@@ -581,8 +742,15 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
     let media = server.composite_service.media_report;
     let media_control_point = server.composite_service.hid_control_point;
     let system_control = server.composite_service.system_report;
-
-    CONNECTION_STATE.store(ConnectionState::Connected.into(), Ordering::Release);
+    #[cfg(feature = "gamepad")]
+    let gamepad_cccd = server.gamepad_service.gamepad_report.cccd_handle;
+    #[cfg(not(feature = "gamepad"))]
+    let gamepad_cccd: Option<u16> = None;
+    let active = || slot == get_current_profile();
+
+    if active() {
+        CONNECTION_STATE.store(ConnectionState::Connected.into(), Ordering::Release);
+    }
 
     let mut connected = false;
     let mut published_connected_state = false;
@@ -608,11 +776,11 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                 #[cfg(feature = "passkey_entry")]
                 passkey_state.clear();
                 info!("[gatt] pairing complete: {:?}", security_level);
-                let profile = get_current_profile();
                 if let Some(bond_info) = bond {
                     let profile_info = ProfileInfo {
-                        slot_num: profile,
+                        slot_num: slot,
                         info: bond_info,
+                        addr_kind: advertising::identity_addr_kind(conn.raw().peer_addr_kind()),
                         removed: false,
                         cccd_table: server.get_cccd_table(conn.raw()).unwrap(),
                     };
@@ -650,7 +818,10 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                             if event.data().len() == 1 {
                                 let led_indicator = LedIndicator::from_bits(event.data()[0]);
                                 debug!("Got keyboard state: {:?}", led_indicator);
-                                LED_SIGNAL.signal(led_indicator);
+                                multi_host::save_host_leds(slot, led_indicator);
+                                if active() {
+                                    LED_SIGNAL.signal(led_indicator);
+                                }
                             } else {
                                 warn!("Wrong keyboard state data: {:?}", event.data());
                             }
@@ -659,6 +830,8 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                             || event.handle() == media.cccd_handle.expect("No CCCD for media report")
                             || event.handle() == system_control.cccd_handle.expect("No CCCD for system report")
                             || event.handle() == battery_level.cccd_handle.expect("No CCCD for battery level")
+                            || Some(event.handle()) == gamepad_cccd
+                            || is_peripheral_battery_cccd(server, event.handle())
                         {
                             // CCCD write event
                             cccd_updated = true;
@@ -667,7 +840,7 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                         {
                             info!("Write GATT Event to Control Point: {:?}", event.handle());
                             #[cfg(feature = "split")]
-                            if event.data().len() == 1 {
+                            if event.data().len() == 1 && active() {
                                 let data = event.data()[0];
                                 if data == 0 {
                                     // Enter sleep mode
@@ -681,7 +854,9 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                             #[cfg(feature = "host")]
                             if event.handle() == output_host.handle {
                                 debug!("Got host packet: {:?}", event.data());
-                                if event.data().len() == 32 {
+                                if !active() {
+                                    warn!("Host packet from the host of an idle profile, dropped");
+                                } else if event.data().len() == 32 {
                                     use crate::channel::{HOST_REQUEST_CHANNEL, HostTransport};
 
                                     let mut data = [0u8; 32];
@@ -696,7 +871,7 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                             } else if event.handle() == host_control_point.handle {
                                 info!("Write GATT Event to Control Point: {:?}", event.handle());
                                 #[cfg(feature = "split")]
-                                if event.data().len() == 1 {
+                                if event.data().len() == 1 && active() {
                                     let data = event.data()[0];
                                     if data == 0 {
                                         // Enter sleep mode
@@ -740,10 +915,12 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                     // When macOS wakes up from sleep mode, it won't send EXIT SUSPEND command
                     // So we need to monitor the sleep state by using CCCD write event
                     #[cfg(feature = "split")]
-                    CENTRAL_SLEEP.signal(false);
+                    if active() {
+                        CENTRAL_SLEEP.signal(false);
+                    }
 
                     if let Some(table) = server.get_cccd_table(conn.raw()) {
-                        UPDATED_CCCD_TABLE.signal(table);
+                        UPDATED_CCCD_TABLE.signal((slot, table));
                     }
                 }
             }
@@ -764,6 +941,9 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                     peripheral_latency,
                     supervision_timeout.as_millis()
                 );
+                if active() {
+                    link_stats::set_conn_params(conn_interval, peripheral_latency);
+                }
             }
             GattConnectionEvent::RequestConnectionParams(req) => info!(
                 "[gatt] RequestConnectionParams: interval: ({:?}, {:?})ms, {:?}, {:?}ms",
@@ -819,8 +999,25 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                     supervision_timeout.as_millis()
                 );
             }
-            GattConnectionEvent::PassKeyDisplay(pass_key) => info!("[gatt] PassKeyDisplay: {:?}", pass_key),
-            GattConnectionEvent::PassKeyConfirm(pass_key) => info!("[gatt] PassKeyConfirm: {:?}", pass_key),
+            GattConnectionEvent::PassKeyDisplay(pass_key) => {
+                info!("[gatt] PassKeyDisplay: {:?}", pass_key);
+                #[cfg(feature = "passkey_entry")]
+                if crate::PASSKEY_DISPLAY_ENABLED {
+                    passkey_state.show(pass_key.value());
+                }
+            }
+            GattConnectionEvent::PassKeyConfirm(pass_key) => {
+                info!("[gatt] PassKeyConfirm: {:?}", pass_key);
+                #[cfg(feature = "passkey_entry")]
+                if crate::PASSKEY_DISPLAY_ENABLED {
+                    passkey_state.begin_confirm(pass_key.value());
+                } else {
+                    warn!("[gatt] PassKeyConfirm: disabled in config, cancelling pairing, this shouldn't happen");
+                    if let Err(e) = conn.raw().pass_key_cancel() {
+                        error!("[gatt] pass_key_cancel error: {:?}", e);
+                    }
+                }
+            }
             GattConnectionEvent::PassKeyInput => {
                 #[cfg(feature = "passkey_entry")]
                 if crate::PASSKEY_ENTRY_ENABLED {
@@ -840,7 +1037,7 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
 
         // Publish the BLE connected event
 
-        if connected && !published_connected_state {
+        if connected && !published_connected_state && active() {
             set_ble_state(BleState::Connected);
             published_connected_state = true;
         }
@@ -849,15 +1046,8 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
     Ok(())
 }
 
-/// Create an advertiser to use to connect to a BLE Central, and wait for it to connect.
-async fn advertise<'a, 'b, C: Controller>(
-    name: &'a str,
-    peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
-    server: &'b Server<'_>,
-) -> Result<GattConnection<'a, 'b, DefaultPacketPool>, BleHostError<C::Error>> {
-    // Wait for 10ms to ensure the USB is checked
-    embassy_time::Timer::after_millis(10).await;
-    let mut advertiser_data = [0; 31];
+/// Encode the advertising data of the keyboard
+fn encode_advertiser_data(name: &str, advertiser_data: &mut [u8; 31]) -> Result<usize, Error> {
     AdStructure::encode_slice(
         &[
             AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED),
@@ -869,39 +1059,139 @@ async fn advertise<'a, 'b, C: Controller>(
             },
         ],
         &mut advertiser_data[..],
-    )?;
-
-    let advertise_config = AdvertisementParameters {
-        primary_phy: PhyKind::Le2M,
-        secondary_phy: PhyKind::Le2M,
-        tx_power: TxPower::Plus8dBm,
-        interval_min: Duration::from_millis(200),
-        interval_max: Duration::from_millis(200),
-        ..Default::default()
+    )
+}
+
+/// Create an advertiser to use to connect to a BLE Central, and wait for it to connect.
+///
+/// The advertising mode depends on the bond of the active profile and the pairing window, see [`advertising`].
+/// With `other_hosts`, bonded hosts of other profiles are disconnected too, so the advertising is undirected whatever
+/// the mode is. Returns the connection, and whether the active profile accepts a new host.
+async fn advertise<
+    'a,
+    'b,
+    C: Controller
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>,
+>(
+    name: &'a str,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
+    server: &'b Server<'_>,
+    bonded_host: Option<BondedHost>,
+    other_hosts: bool,
+) -> Result<(GattConnection<'a, 'b, DefaultPacketPool>, bool), BleHostError<C::Error>> {
+    // Wait for 10ms to ensure the USB is checked
+    embassy_time::Timer::after_millis(10).await;
+    let mut advertiser_data = [0; 31];
+    encode_advertiser_data(name, &mut advertiser_data)?;
+    let undirected = || Advertisement::ConnectableScannableUndirected {
+        adv_data: &advertiser_data[..],
+        scan_data: &[],
     };
 
-    info!("[adv] advertising");
-    let advertiser = peripheral
-        .advertise(
-            &advertise_config,
-            Advertisement::ConnectableScannableUndirected {
-                adv_data: &advertiser_data[..],
-                scan_data: &[],
-            },
-        )
-        .await?;
-
-    // Timeout for advertising is 300s
-    match with_timeout(Duration::from_secs(300), advertiser.accept()).await {
-        Ok(conn_res) => {
-            let conn = conn_res?.with_attribute_server(server)?;
-            info!("[adv] connection established");
-            if let Err(e) = conn.raw().set_bondable(true) {
-                error!("Set bondable error: {:?}", e);
-            };
-            Ok(conn)
+    let mut pairing_deadline = None;
+    loop {
+        if PAIRING_WINDOW_REQUEST.try_take().is_some() {
+            info!("[adv] pairing window opened");
+            pairing_deadline = Some(Instant::now() + advertising::pairing_window());
+        }
+        let deadline = pairing_deadline.unwrap_or(Instant::now() + ADVERTISING_TIMEOUT);
+        let mut mode = AdvertisingMode::select(
+            bonded_host,
+            pairing_deadline.is_some(),
+            crate::BLE_BONDED_ADVERTISING,
+            crate::BLE_PAIRING_WINDOW_SECS,
+        );
+        if !other_hosts
+            && let AdvertisingMode::Filtered(host) | AdvertisingMode::Directed(host) = mode
+            && let Err(e) =
+                advertising::load_bonded_host(stack, host, matches!(mode, AdvertisingMode::Filtered(_))).await
+        {
+            #[cfg(feature = "defmt")]
+            let e = defmt::Debug2Format(&e);
+            warn!(
+                "[adv] failed to load the bonded host into the controller, advertising openly: {:?}",
+                e
+            );
+            mode = AdvertisingMode::Open;
         }
-        Err(_) => Err(BleHostError::BleHost(Error::Timeout)),
+        let accepts_new_host = mode == AdvertisingMode::Open;
+        let mode = if other_hosts { AdvertisingMode::Open } else { mode };
+
+        // Whether only the filter accept list can connect, and the advertisement
+        let advertisement = match mode {
+            AdvertisingMode::Open => {
+                info!("[adv] advertising");
+                Some((false, undirected()))
+            }
+            AdvertisingMode::Filtered(_) => {
+                info!("[adv] advertising to the bonded host only");
+                Some((true, undirected()))
+            }
+            AdvertisingMode::Directed(host) => {
+                info!("[adv] advertising directed to the bonded host");
+                Some((
+                    false,
+                    Advertisement::ConnectableNonscannableDirected { peer: host.address },
+                ))
+            }
+            AdvertisingMode::Closed => {
+                info!("[adv] empty profile, waiting for the pairing window");
+                set_ble_state(BleState::Inactive);
+                None
+            }
+        };
+        let advertiser = match advertisement {
+            Some((filtered, advertisement)) => Some(
+                peripheral
+                    .advertise(&advertisement_parameters(filtered), advertisement)
+                    .await?,
+            ),
+            None => None,
+        };
+        let accept = async {
+            match advertiser {
+                Some(advertiser) => advertiser.accept().await,
+                None => core::future::pending().await,
+            }
+        };
+
+        match select(with_deadline(deadline, accept), PAIRING_WINDOW_REQUEST.wait()).await {
+            Either::First(Ok(conn_res)) => {
+                let conn = conn_res?.with_attribute_server(server)?;
+                info!("[adv] connection established");
+                if let Err(e) = conn.raw().set_bondable(true) {
+                    error!("Set bondable error: {:?}", e);
+                };
+                return Ok((conn, accepts_new_host));
+            }
+            Either::First(Err(_)) => {
+                if pairing_deadline.take().is_none() {
+                    return Err(BleHostError::BleHost(Error::Timeout));
+                }
+                // The pairing window is closed, advertise according to the profile again
+                info!("[adv] pairing window closed");
+                set_ble_state(BleState::Advertising);
+            }
+            Either::Second(_) => {
+                info!("[adv] pairing window opened");
+                pairing_deadline = Some(Instant::now() + advertising::pairing_window());
+                set_ble_state(BleState::Advertising);
+            }
+        }
+    }
+}
+
+/// Wait for a key press after advertising timed out.
+///
+/// The pairing window action wakes the keyboard up too, the request is kept for the next advertising.
+async fn wait_for_wake_up() {
+    if let Either::Second(_) = select(KEYBOARD_REPORT_CHANNEL.receive(), PAIRING_WINDOW_REQUEST.wait()).await {
+        PAIRING_WINDOW_REQUEST.signal(());
     }
 }
 
@@ -921,6 +1211,15 @@ pub(crate) async fn set_conn_params<
 >(
     stack: &Stack<'_, C, P>,
     conn: &GattConnection<'a, 'b, P>,
+) {
+    initial_conn_params(stack, conn).await;
+    follow_typing_activity(stack, conn).await;
+}
+
+/// Set the connection parameters after connecting, ending with the parameters used while typing
+async fn initial_conn_params<C: Controller + ControllerCmdSync<LeReadLocalSupportedFeatures>, P: PacketPool>(
+    stack: &Stack<'_, C, P>,
+    conn: &GattConnection<'_, '_, P>,
 ) {
     // Wait for 5 seconds before setting connection parameters to avoid connection drop
     embassy_time::Timer::after_secs(5).await;
@@ -933,7 +1232,7 @@ pub(crate) async fn set_conn_params<
         &RequestedConnParams {
             min_connection_interval: Duration::from_millis(15),
             max_connection_interval: Duration::from_millis(15),
-            max_latency: 30,
+            max_latency: crate::BLE_CONN_LATENCY,
             min_event_length: Duration::from_secs(0),
             max_event_length: Duration::from_secs(0),
             supervision_timeout: Duration::from_secs(5),
@@ -944,39 +1243,144 @@ pub(crate) async fn set_conn_params<
     embassy_time::Timer::after_secs(5).await;
 
     // Setting the conn param the second time ensures that we have best performance on all platforms
-    update_conn_params(
-        stack,
-        conn.raw(),
-        &RequestedConnParams {
-            min_connection_interval: Duration::from_micros(7500),
-            max_connection_interval: Duration::from_micros(7500),
-            max_latency: 30,
-            min_event_length: Duration::from_secs(0),
-            max_event_length: Duration::from_secs(0),
-            supervision_timeout: Duration::from_secs(5),
-        },
-    )
-    .await;
+    update_conn_params(stack, conn.raw(), &typing_conn_params(false)).await;
+}
+
+/// Switch between the typing and the low power connection parameters following the reports sent to the host
+async fn follow_typing_activity<C: Controller + ControllerCmdSync<LeReadLocalSupportedFeatures>, P: PacketPool>(
+    stack: &Stack<'_, C, P>,
+    conn: &GattConnection<'_, '_, P>,
+) {
+    if crate::BLE_LOW_POWER_IDLE_SECS == 0 {
+        // Wait forever. This is because we want the conn params setting can be interrupted when the connection is lost.
+        // So this task shouldn't quit after setting the conn params.
+        core::future::pending::<()>().await;
+    }
+
+    let idle_timeout = Duration::from_secs(crate::BLE_LOW_POWER_IDLE_SECS as u64);
+    loop {
+        // Keep the low latency parameters while reports are sent
+        REPORT_SENT.reset();
+        while with_timeout(idle_timeout, REPORT_SENT.wait()).await.is_ok() {}
+
+        info!(
+            "No reports for {}s, requesting low power connection parameters",
+            crate::BLE_LOW_POWER_IDLE_SECS
+        );
+        set_low_power(true);
+        update_conn_params(stack, conn.raw(), &typing_conn_params(true)).await;
+
+        // Typing again
+        REPORT_SENT.wait().await;
+        info!("Typing, requesting low latency connection parameters");
+        set_low_power(false);
+        update_conn_params(stack, conn.raw(), &typing_conn_params(false)).await;
+    }
+}
+
+/// Connection parameters requested while typing, or in low power mode after being idle
+fn typing_conn_params(low_power: bool) -> RequestedConnParams {
+    let (interval, latency) = if low_power {
+        (crate::BLE_LOW_POWER_CONN_INTERVAL_US, crate::BLE_LOW_POWER_CONN_LATENCY)
+    } else {
+        (crate::BLE_CONN_INTERVAL_US, crate::BLE_CONN_LATENCY)
+    };
+    RequestedConnParams {
+        min_connection_interval: Duration::from_micros(interval as u64),
+        max_connection_interval: Duration::from_micros(interval as u64),
+        max_latency: latency,
+        min_event_length: Duration::from_secs(0),
+        max_event_length: Duration::from_secs(0),
+        supervision_timeout: Duration::from_secs(5),
+    }
+}
+
+/// Read the RSSI of the connection and publish the link statistics periodically
+async fn link_stats_task<C: Controller + ControllerCmdSync<ReadRssi>, P: PacketPool>(
+    stack: &Stack<'_, C, P>,
+    conn: &GattConnection<'_, '_, P>,
+) {
+    loop {
+        Timer::after(LINK_STATS_INTERVAL).await;
+        match conn.raw().rssi(stack).await {
+            Ok(rssi) => link_stats::set_rssi(rssi),
+            Err(e) => {
+                #[cfg(feature = "defmt")]
+                let e = defmt::Debug2Format(&e);
+                warn!("Read RSSI error: {:?}", e);
+            }
+        }
+        publish_link_stats();
+    }
+}
+
+/// Connection to the host which receives the reports
+enum BleLink<'a, 'b> {
+    /// The only host connection
+    Single(GattConnection<'a, 'b, DefaultPacketPool>),
+    /// The host of the profile is connected and runs in its slot, with `multi_host`
+    Host(u8),
+}
 
-    // Wait forever. This is because we want the conn params setting can be interrupted when the connection is lost.
-    // So this task shouldn't quit after setting the conn params.
-    core::future::pending::<()>().await;
+/// Run BLE keyboard with the connected host
+async fn run_ble_link<
+    'a,
+    'b,
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>
+        + ControllerCmdSync<ReadRssi>,
+>(
+    link: BleLink<'a, 'b>,
+    name: &'a str,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
+    server: &'b Server<'_>,
+    pending_hosts: &[PendingHost<'a, 'b>; HOST_SLOTS],
+    #[cfg(feature = "storage")] active_bond_info: Option<ProfileInfo>,
+) {
+    match link {
+        BleLink::Single(conn) => {
+            run_ble_keyboard(
+                server,
+                &conn,
+                stack,
+                #[cfg(feature = "storage")]
+                active_bond_info,
+            )
+            .await
+        }
+        BleLink::Host(slot) => {
+            multi_host::run_host_keyboard(slot, name, stack, peripheral, server, pending_hosts).await
+        }
+    }
 }
 
 /// Run BLE keyboard with connected device
 async fn run_ble_keyboard<
     'a,
     'b,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
 >(
     server: &'b Server<'_>,
     conn: &GattConnection<'a, 'b, DefaultPacketPool>,
     stack: &Stack<'_, C, DefaultPacketPool>,
     #[cfg(feature = "storage")] active_bond_info: Option<crate::ble::profile::ProfileInfo>,
 ) {
+    reset_link_stats();
     let ble_hid_server = BleHidServer::new(server, conn);
     let ble_led_reader = BleLedReader {};
     let mut ble_battery_server = BleBatteryServer::new(server, conn);
+    #[cfg(feature = "split")]
+    let mut ble_peripheral_battery_server = BlePeripheralBatteryServer::new(server, conn);
 
     // CCCD lookup uses cached bond info to avoid a cancellable flash read while
     // this future is racing other arms of an outer `select`.
@@ -991,11 +1395,18 @@ async fn run_ble_keyboard<
     // Use 2M Phy
     update_ble_phy(stack, conn.raw()).await;
 
+    let battery_task = async {
+        #[cfg(feature = "split")]
+        join(ble_battery_server.run(), ble_peripheral_battery_server.run()).await;
+        #[cfg(not(feature = "split"))]
+        ble_battery_server.run().await;
+    };
+
     let communication_task = async {
         if let Either3::First(e) = select3(
-            gatt_events_task(server, conn),
-            set_conn_params(stack, conn),
-            ble_battery_server.run(),
+            gatt_events_task(server, conn, get_current_profile()),
+            join(set_conn_params(stack, conn), link_stats_task(stack, conn)),
+            battery_task,
         )
         .await
         {
@@ -1009,6 +1420,8 @@ async fn run_ble_keyboard<
     select(kb_fut, crate::host::run_ble_host(server.host_service.input_data, conn)).await;
     #[cfg(not(feature = "host"))]
     kb_fut.await;
+
+    reset_link_stats();
 }
 
 #[cfg(test)]
@@ -1068,11 +1481,14 @@ mod tests {
     }
 }
 
-// Update the PHY to 2M
+// Update the PHY to 2M, unless `use_2m_phy` is disabled
 pub(crate) async fn update_ble_phy<P: PacketPool>(
     stack: &Stack<'_, impl Controller + ControllerCmdAsync<LeSetPhy>, P>,
     conn: &Connection<'_, P>,
 ) {
+    if !crate::BLE_USE_2M_PHY {
+        return;
+    }
     loop {
         match conn.set_phy(stack, PhyKind::Le2M).await {
             Err(BleHostError::BleHost(Error::Hci(error))) => {
@@ -0,0 +1,668 @@
+//! Connections to the hosts of all BLE profiles at once.
+//!
+//! With `multi_host = true` in `[ble]`, the keyboard stays connected to the bonded host of each profile, and the
+//! reports are routed to the host of the active profile. Switching profiles doesn't reconnect: the host which becomes
+//! idle gets the released keys and the low power connection parameters, and the reports go to the other host.
+//!
+//! Each profile runs the connection of its host in a slot, see [`run_host_slots`]. [`accept_hosts`] advertises while
+//! a bonded host is disconnected and hands the connections over to the slots. A host without bond only connects to
+//! the active profile, when the active profile accepts a new host. While the host of the active profile is connected,
+//! the advertising for the other hosts is undirected, `bonded_advertising` only applies until the active host connects.
+//! When that advertising times out, it starts again on the next key press or when a host disconnects.
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use bt_hci::cmd::le::{
+    LeAddDeviceToFilterAcceptList, LeAddDeviceToResolvingList, LeClearFilterAcceptList, LeClearResolvingList,
+    LeReadLocalSupportedFeatures, LeSetAddrResolutionEnable, LeSetPhy,
+};
+use bt_hci::cmd::status::ReadRssi;
+use bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync};
+use embassy_futures::join::{join, join_array};
+use embassy_futures::select::{Either, Either4, select, select4};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Timer, with_timeout};
+use rmk_types::ble::BleState;
+use rmk_types::led_indicator::LedIndicator;
+use trouble_host::prelude::*;
+use usbd_hid::descriptor::{MediaKeyboardReport, MouseReport, SystemControlReport};
+
+use super::advertising::{ADVERTISING_TIMEOUT, BondedHost, advertisement_parameters};
+use super::battery_service::BleBatteryServer;
+#[cfg(feature = "split")]
+use super::battery_service::BlePeripheralBatteryServer;
+use super::ble_server::{BleHidServer, Server};
+use super::led::BleLedReader;
+use super::link_stats::{reset_link_stats, set_low_power};
+use super::profile::ProfileInfo;
+use super::{
+    advertise, encode_advertiser_data, follow_typing_activity, gatt_events_task, get_current_profile,
+    initial_conn_params, link_stats_task, set_ble_state, typing_conn_params, update_ble_phy, update_conn_params,
+};
+use crate::channel::{KEYBOARD_REPORT_CHANNEL, LED_SIGNAL};
+#[cfg(feature = "gamepad")]
+use crate::hid::GamepadReport;
+use crate::hid::{HidError, HidWriterTrait, KeyboardReport, Report, RunnableHidWriter};
+use crate::{NUM_BLE_PROFILE, REPORT_CHANNEL_SIZE, RawMutex, run_keyboard};
+
+/// Number of host slots, one for each profile with `multi_host`
+pub(crate) const HOST_SLOTS: usize = if crate::BLE_MULTI_HOST { NUM_BLE_PROFILE } else { 0 };
+
+/// Connection accepted for the host of a profile, waiting for its slot
+pub(crate) type PendingHost<'a, 'b> = Channel<NoopRawMutex, GattConnection<'a, 'b, DefaultPacketPool>, 1>;
+
+/// State of the connection to the host of a profile
+struct HostSlot {
+    /// Reports routed to the host
+    reports: Channel<RawMutex, Report, REPORT_CHANNEL_SIZE>,
+    /// Signaled when the active profile changes
+    activation: Signal<RawMutex, ()>,
+    /// Signaled to drop the connection, when the bond of the profile is cleared
+    disconnect: Signal<RawMutex, ()>,
+    /// Signaled when the host disconnects
+    disconnected: Signal<RawMutex, ()>,
+    connected: AtomicBool,
+    /// Last LED state written by the host, restored when the profile becomes active
+    leds: AtomicU8,
+}
+
+impl HostSlot {
+    const fn new() -> Self {
+        Self {
+            reports: Channel::new(),
+            activation: Signal::new(),
+            disconnect: Signal::new(),
+            disconnected: Signal::new(),
+            connected: AtomicBool::new(false),
+            leds: AtomicU8::new(0),
+        }
+    }
+}
+
+static HOSTS: [HostSlot; HOST_SLOTS] = [const { HostSlot::new() }; HOST_SLOTS];
+
+/// Signaled when a host disconnects or the bonds change, so that the advertising is updated
+static HOSTS_CHANGED: Signal<RawMutex, ()> = Signal::new();
+
+/// Signaled when a report is routed to the host of the active profile, which restarts the timed out advertising
+static REPORT_ROUTED: Signal<RawMutex, ()> = Signal::new();
+
+/// Bonds of the profiles, kept in sync with the bonds in the stack
+static BONDED_HOSTS: Mutex<RawMutex, RefCell<heapless::Vec<ProfileInfo, NUM_BLE_PROFILE>>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// Update the bonds of the profiles, after they're updated in the stack
+pub(crate) fn set_bonded_hosts(bonds: &[ProfileInfo]) {
+    BONDED_HOSTS.lock(|hosts| {
+        let mut hosts = hosts.borrow_mut();
+        hosts.clear();
+        for bond in bonds.iter().filter(|bond| !bond.removed) {
+            if hosts.push(bond.clone()).is_err() {
+                error!("Too many bonded hosts");
+            }
+        }
+    });
+    HOSTS_CHANGED.signal(());
+}
+
+/// Bond of the profile
+fn profile_bond(slot: u8) -> Option<ProfileInfo> {
+    BONDED_HOSTS.lock(|hosts| hosts.borrow().iter().find(|bond| bond.slot_num == slot).cloned())
+}
+
+/// Profile which the host is bonded to
+fn bonded_slot(identity: &Identity) -> Option<u8> {
+    BONDED_HOSTS.lock(|hosts| {
+        hosts
+            .borrow()
+            .iter()
+            .find(|bond| bond.info.identity.match_identity(identity))
+            .map(|bond| bond.slot_num)
+    })
+}
+
+/// Whether a bonded host of a profile other than `active` is disconnected
+fn other_hosts_disconnected(active: u8) -> bool {
+    BONDED_HOSTS.lock(|hosts| {
+        hosts
+            .borrow()
+            .iter()
+            .any(|bond| bond.slot_num != active && !is_host_connected(bond.slot_num))
+    })
+}
+
+/// Whether the host of the profile is connected, always false without `multi_host`
+pub(crate) fn is_host_connected(slot: u8) -> bool {
+    HOSTS
+        .get(slot as usize)
+        .is_some_and(|host| host.connected.load(Ordering::Acquire))
+}
+
+/// Notify the slots that the active profile changed
+pub(crate) fn active_profile_changed() {
+    for host in HOSTS.iter() {
+        host.activation.signal(());
+    }
+}
+
+/// Drop the connection to the host of the profile
+pub(crate) fn disconnect_host(slot: u8) {
+    if let Some(host) = HOSTS.get(slot as usize) {
+        host.disconnect.signal(());
+    }
+}
+
+/// Save the LED state written by the host of the profile
+pub(crate) fn save_host_leds(slot: u8, leds: LedIndicator) {
+    if let Some(host) = HOSTS.get(slot as usize) {
+        host.leds.store(leds.into_bits(), Ordering::Release);
+    }
+}
+
+/// Wait until the profile of the slot is active, or idle
+async fn wait_active(slot: u8, active: bool) {
+    while (get_current_profile() == slot) != active {
+        HOSTS[slot as usize].activation.wait().await;
+    }
+}
+
+/// Wait until the host of the profile is disconnected
+async fn wait_disconnected(slot: u8) {
+    let host = &HOSTS[slot as usize];
+    while host.connected.load(Ordering::Acquire) {
+        host.disconnected.wait().await;
+    }
+}
+
+/// Routes the reports to the host of the active profile
+struct HostRouter<'a> {
+    hosts: &'a [HostSlot],
+}
+
+impl HidWriterTrait for HostRouter<'_> {
+    type ReportType = Report;
+
+    async fn write_report(&mut self, report: Self::ReportType) -> Result<usize, HidError> {
+        match self.hosts.get(get_current_profile() as usize) {
+            Some(host) if host.connected.load(Ordering::Acquire) => {
+                host.reports.send(report).await;
+                REPORT_ROUTED.signal(());
+                Ok(0)
+            }
+            _ => Err(HidError::BleError),
+        }
+    }
+}
+
+impl RunnableHidWriter for HostRouter<'_> {
+    async fn get_report(&mut self) -> Self::ReportType {
+        KEYBOARD_REPORT_CHANNEL.receive().await
+    }
+}
+
+/// Run the keyboard with the host of the active profile, the other hosts can connect meanwhile
+pub(crate) async fn run_host_keyboard<
+    'a,
+    'b,
+    C: Controller
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>,
+>(
+    slot: u8,
+    name: &'a str,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
+    server: &'b Server<'_>,
+    pending_hosts: &[PendingHost<'a, 'b>; HOST_SLOTS],
+) {
+    info!("[multi_host] sending reports to the host of profile {}", slot);
+    set_ble_state(BleState::Connected);
+    LED_SIGNAL.signal(LedIndicator::from_bits(
+        HOSTS[slot as usize].leds.load(Ordering::Acquire),
+    ));
+
+    let communication_task = async {
+        match select(
+            accept_hosts(name, stack, peripheral, server, pending_hosts, false),
+            wait_disconnected(slot),
+        )
+        .await
+        {
+            Either::First(Err(e)) => {
+                #[cfg(feature = "defmt")]
+                let e = defmt::Debug2Format(&e);
+                error!("[multi_host] advertise error: {:?}", e);
+            }
+            _ => info!("[multi_host] host of profile {} disconnected", slot),
+        }
+    };
+    run_keyboard(communication_task, BleLedReader {}, HostRouter { hosts: &HOSTS }).await;
+}
+
+/// Advertise and hand the connections over to the slots of the profiles.
+///
+/// With `until_active`, returns the active profile when its host is connected. Otherwise the host of the active
+/// profile is connected, and it keeps advertising for the other bonded hosts.
+pub(crate) async fn accept_hosts<
+    'a,
+    'b,
+    C: Controller
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>,
+>(
+    name: &'a str,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
+    server: &'b Server<'_>,
+    pending_hosts: &[PendingHost<'a, 'b>; HOST_SLOTS],
+    until_active: bool,
+) -> Result<u8, BleHostError<C::Error>> {
+    loop {
+        let active = get_current_profile();
+        let active_connected = is_host_connected(active);
+        if until_active && active_connected {
+            return Ok(active);
+        }
+        HOSTS_CHANGED.reset();
+        let other_hosts = other_hosts_disconnected(active);
+
+        let accepted = if !active_connected {
+            let bonded_host = profile_bond(active).as_ref().map(BondedHost::new);
+            select(
+                advertise(name, stack, peripheral, server, bonded_host, other_hosts),
+                HOSTS_CHANGED.wait(),
+            )
+            .await
+        } else if other_hosts {
+            // Only the bonded hosts can connect while the active host is connected
+            match select(
+                advertise_to_bonded_hosts(name, peripheral, server),
+                HOSTS_CHANGED.wait(),
+            )
+            .await
+            {
+                Either::First(Err(BleHostError::BleHost(Error::Timeout))) => {
+                    warn!("[multi_host] advertising timeout, waiting for a key press or a host to disconnect");
+                    REPORT_ROUTED.reset();
+                    select(REPORT_ROUTED.wait(), HOSTS_CHANGED.wait()).await;
+                    continue;
+                }
+                Either::First(result) => Either::First(result.map(|conn| (conn, false))),
+                Either::Second(_) => Either::Second(()),
+            }
+        } else {
+            HOSTS_CHANGED.wait().await;
+            continue;
+        };
+
+        let (conn, accepts_new_host) = match accepted {
+            Either::First(result) => result?,
+            // Advertise again for the hosts which are disconnected now
+            Either::Second(_) => continue,
+        };
+
+        let slot = match bonded_slot(&conn.raw().peer_identity()) {
+            Some(slot) => slot,
+            None if accepts_new_host && !is_host_connected(active) => active,
+            None => {
+                info!("[multi_host] the host isn't bonded to any profile, disconnecting");
+                conn.raw().disconnect();
+                continue;
+            }
+        };
+        if is_host_connected(slot) {
+            warn!(
+                "[multi_host] profile {} is connected to another host, disconnecting",
+                slot
+            );
+            conn.raw().disconnect();
+            continue;
+        }
+        info!("[multi_host] host of profile {} connected", slot);
+        HOSTS[slot as usize].connected.store(true, Ordering::Release);
+        pending_hosts[slot as usize].send(conn).await;
+    }
+}
+
+/// Advertise undirected for the bonded hosts of the other profiles, while the host of the active profile is connected
+async fn advertise_to_bonded_hosts<'a, 'b, C: Controller>(
+    name: &'a str,
+    peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
+    server: &'b Server<'_>,
+) -> Result<GattConnection<'a, 'b, DefaultPacketPool>, BleHostError<C::Error>> {
+    let mut advertiser_data = [0; 31];
+    encode_advertiser_data(name, &mut advertiser_data)?;
+    let advertiser = peripheral
+        .advertise(
+            &advertisement_parameters(false),
+            Advertisement::ConnectableScannableUndirected {
+                adv_data: &advertiser_data[..],
+                scan_data: &[],
+            },
+        )
+        .await?;
+    let conn = with_timeout(ADVERTISING_TIMEOUT, advertiser.accept())
+        .await
+        .map_err(|_| BleHostError::BleHost(Error::Timeout))??
+        .with_attribute_server(server)?;
+    if let Err(e) = conn.raw().set_bondable(true) {
+        error!("Set bondable error: {:?}", e);
+    };
+    Ok(conn)
+}
+
+/// Run the connection to the host of each profile
+pub(crate) async fn run_host_slots<
+    'a,
+    'b,
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
+>(
+    pending_hosts: &[PendingHost<'a, 'b>; HOST_SLOTS],
+    server: &'b Server<'_>,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+) {
+    join_array(core::array::from_fn::<_, HOST_SLOTS, _>(|slot| {
+        run_host_slot(slot as u8, &pending_hosts[slot], server, stack)
+    }))
+    .await;
+}
+
+/// Run the connections accepted for the host of the profile, one after another
+async fn run_host_slot<
+    'a,
+    'b,
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
+>(
+    slot: u8,
+    pending: &PendingHost<'a, 'b>,
+    server: &'b Server<'_>,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+) {
+    let host = &HOSTS[slot as usize];
+    loop {
+        let conn = pending.receive().await;
+        host.disconnect.reset();
+        host.reports.clear();
+        if let Some(bond) = profile_bond(slot)
+            && bond.info.identity.match_identity(&conn.raw().peer_identity())
+        {
+            info!("Loading CCCD table: {:?}", bond.cccd_table);
+            server.set_cccd_table(conn.raw(), bond.cccd_table);
+        }
+
+        // Use 2M Phy
+        update_ble_phy(stack, conn.raw()).await;
+
+        let mut ble_battery_server = BleBatteryServer::new(server, &conn);
+        #[cfg(feature = "split")]
+        let mut ble_peripheral_battery_server = BlePeripheralBatteryServer::new(server, &conn);
+        let battery_task = async {
+            #[cfg(feature = "split")]
+            join(ble_battery_server.run(), ble_peripheral_battery_server.run()).await;
+            #[cfg(not(feature = "split"))]
+            ble_battery_server.run().await;
+        };
+
+        match select4(
+            gatt_events_task(server, &conn, slot),
+            run_host(slot, server, &conn, stack),
+            battery_task,
+            host.disconnect.wait(),
+        )
+        .await
+        {
+            Either4::First(e) => error!("[gatt_events_task] end: {:?}", e),
+            Either4::Fourth(_) => info!("[multi_host] the bond of profile {} is cleared, disconnecting", slot),
+            _ => (),
+        }
+
+        host.connected.store(false, Ordering::Release);
+        host.disconnected.signal(());
+        HOSTS_CHANGED.signal(());
+        if get_current_profile() == slot {
+            reset_link_stats();
+        }
+    }
+}
+
+/// Send the reports routed to the host while its profile is active, and set the connection parameters
+async fn run_host<
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
+>(
+    slot: u8,
+    server: &Server<'_>,
+    conn: &GattConnection<'_, '_, DefaultPacketPool>,
+    stack: &Stack<'_, C, DefaultPacketPool>,
+) {
+    let host = &HOSTS[slot as usize];
+    let mut ble_hid_server = BleHidServer::new(server, conn);
+    let mut connecting = true;
+    loop {
+        if get_current_profile() != slot {
+            if connecting {
+                // Wait before setting connection parameters to avoid connection drop
+                select(Timer::after_secs(5), wait_active(slot, true)).await;
+            }
+            if get_current_profile() != slot {
+                update_conn_params(stack, conn.raw(), &typing_conn_params(true)).await;
+                wait_active(slot, true).await;
+            }
+        }
+        reset_link_stats();
+
+        let conn_params_task = async {
+            if connecting {
+                initial_conn_params(stack, conn).await;
+            } else {
+                set_low_power(false);
+                update_conn_params(stack, conn.raw(), &typing_conn_params(false)).await;
+            }
+            follow_typing_activity(stack, conn).await;
+        };
+        let writer_task = async {
+            loop {
+                let report = host.reports.receive().await;
+                if let Err(e) = ble_hid_server.write_report(report).await {
+                    error!("Failed to send report: {:?}", e);
+                }
+            }
+        };
+        let active_task = join(writer_task, join(conn_params_task, link_stats_task(stack, conn)));
+        #[cfg(feature = "host")]
+        let active_task = join(
+            active_task,
+            crate::host::run_ble_host(server.host_service.input_data, conn),
+        );
+        select(active_task, wait_active(slot, false)).await;
+        connecting = false;
+
+        info!("[multi_host] profile {} is idle, releasing the keys", slot);
+        go_idle(&host.reports, &mut ble_hid_server).await;
+    }
+}
+
+/// Sends the reports to the host of a profile
+trait HostReportWriter {
+    /// Send a report routed to the host
+    async fn send_report(&mut self, report: Report) -> Result<usize, HidError>;
+
+    /// Send a report which releases keys, it's not recorded in the link statistics which belong to the active host
+    async fn send_release(&mut self, report: Report) -> Result<usize, HidError>;
+}
+
+impl<P: PacketPool> HostReportWriter for BleHidServer<'_, '_, '_, P> {
+    async fn send_report(&mut self, report: Report) -> Result<usize, HidError> {
+        self.write_report(report).await
+    }
+
+    async fn send_release(&mut self, report: Report) -> Result<usize, HidError> {
+        self.notify_report(report).await
+    }
+}
+
+/// Send the reports routed before the profile became idle, then release the keys
+async fn go_idle(reports: &Channel<RawMutex, Report, REPORT_CHANNEL_SIZE>, writer: &mut impl HostReportWriter) {
+    while let Ok(report) = reports.try_receive() {
+        if let Err(e) = writer.send_report(report).await {
+            error!("Failed to send report: {:?}", e);
+        }
+    }
+    release_keys(writer).await;
+}
+
+/// Release all keys on the host, the keys held while switching profiles would stay pressed on the idle host
+async fn release_keys(writer: &mut impl HostReportWriter) {
+    let reports = [
+        Report::KeyboardReport(KeyboardReport::default()),
+        Report::MouseReport(MouseReport {
+            buttons: 0,
+            x: 0,
+            y: 0,
+            wheel: 0,
+            pan: 0,
+        }),
+        Report::MediaKeyboardReport(MediaKeyboardReport { usage_id: 0 }),
+        Report::SystemControlReport(SystemControlReport { usage_id: 0 }),
+        #[cfg(feature = "gamepad")]
+        Report::GamepadReport(GamepadReport::default()),
+    ];
+    for report in reports {
+        if let Err(e) = writer.send_release(report).await {
+            error!("Failed to release keys: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rmk_types::ble::BleStatus;
+
+    use super::*;
+    use crate::ble::BLE_STATUS;
+    use crate::test_support::test_block_on as block_on;
+
+    fn switch_profile(profile: u8) {
+        BLE_STATUS.lock(|c| {
+            c.set(BleStatus {
+                profile,
+                state: BleState::Connected,
+            })
+        });
+    }
+
+    fn key_report(key: u8) -> Report {
+        Report::KeyboardReport(KeyboardReport {
+            keycodes: [key, 0, 0, 0, 0, 0],
+            ..Default::default()
+        })
+    }
+
+    fn keycode(report: &Report) -> Option<u8> {
+        match report {
+            Report::KeyboardReport(report) => Some(report.keycodes[0]),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn reports_are_routed_to_the_active_host() {
+        let hosts = [HostSlot::new(), HostSlot::new()];
+        let mut router = HostRouter { hosts: &hosts };
+        hosts[0].connected.store(true, Ordering::Release);
+        hosts[1].connected.store(true, Ordering::Release);
+
+        block_on(async {
+            switch_profile(1);
+            assert!(router.write_report(key_report(0x04)).await.is_ok());
+            switch_profile(0);
+            assert!(router.write_report(key_report(0x05)).await.is_ok());
+        });
+
+        assert_eq!(keycode(&hosts[0].reports.try_receive().unwrap()), Some(0x05));
+        assert!(hosts[0].reports.try_receive().is_err());
+        assert_eq!(keycode(&hosts[1].reports.try_receive().unwrap()), Some(0x04));
+        assert!(hosts[1].reports.try_receive().is_err());
+    }
+
+    #[test]
+    fn reports_are_dropped_when_the_active_host_is_disconnected() {
+        let hosts = [HostSlot::new(), HostSlot::new()];
+        let mut router = HostRouter { hosts: &hosts };
+        hosts[1].connected.store(true, Ordering::Release);
+
+        block_on(async {
+            switch_profile(0);
+            assert!(matches!(
+                router.write_report(key_report(0x04)).await,
+                Err(HidError::BleError)
+            ));
+            // The profile doesn't have a slot
+            switch_profile(2);
+            assert!(router.write_report(key_report(0x04)).await.is_err());
+        });
+
+        assert!(hosts[0].reports.try_receive().is_err());
+        assert!(hosts[1].reports.try_receive().is_err());
+    }
+
+    /// Records the reports sent to a host, and whether they release keys
+    #[derive(Default)]
+    struct RecordingWriter {
+        sent: Vec<(Report, bool)>,
+    }
+
+    impl HostReportWriter for RecordingWriter {
+        async fn send_report(&mut self, report: Report) -> Result<usize, HidError> {
+            self.sent.push((report, false));
+            Ok(0)
+        }
+
+        async fn send_release(&mut self, report: Report) -> Result<usize, HidError> {
+            self.sent.push((report, true));
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn idle_host_gets_the_routed_reports_before_the_release() {
+        let host = HostSlot::new();
+        let mut writer = RecordingWriter::default();
+        host.reports.try_send(key_report(0x04)).unwrap();
+        host.reports.try_send(key_report(0x05)).unwrap();
+
+        block_on(go_idle(&host.reports, &mut writer));
+
+        assert!(host.reports.try_receive().is_err());
+        let (routed, released) = writer.sent.split_at(2);
+        assert!(routed.iter().all(|(_, release)| !release));
+        assert_eq!(keycode(&routed[0].0), Some(0x04));
+        assert_eq!(keycode(&routed[1].0), Some(0x05));
+
+        assert!(released.iter().all(|(_, release)| *release));
+        assert!(matches!(released[0].0, Report::KeyboardReport(ref r) if r.modifier == 0 && r.keycodes == [0; 6]));
+        assert!(matches!(released[1].0, Report::MouseReport(ref r) if r.buttons == 0));
+        assert!(matches!(released[2].0, Report::MediaKeyboardReport(ref r) if r.usage_id == 0));
+        assert!(matches!(released[3].0, Report::SystemControlReport(ref r) if r.usage_id == 0));
+        #[cfg(feature = "gamepad")]
+        assert!(matches!(released[4].0, Report::GamepadReport(_)));
+    }
+}
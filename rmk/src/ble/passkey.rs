@@ -3,6 +3,8 @@ use core::sync::atomic::{AtomicBool, Ordering};
 use embassy_sync::signal::Signal;
 use rmk_types::keycode::HidKeyCode;
 
+use crate::event::{PasskeyDisplayEvent, publish_event};
+
 /// Maximum number of digits in a BLE passkey.
 pub const PASSKEY_LENGTH: usize = 6;
 
@@ -10,10 +12,21 @@ pub const PASSKEY_LENGTH: usize = 6;
 /// Set when `PassKeyInput` event arrives, cleared on submit/cancel/timeout.
 pub static PASSKEY_ENTRY_MODE: AtomicBool = AtomicBool::new(false);
 
+/// Global flag indicating the passkey shown by the host must be confirmed instead of typed.
+/// Set together with `PASSKEY_ENTRY_MODE` when `PassKeyConfirm` event arrives.
+pub static PASSKEY_CONFIRM_MODE: AtomicBool = AtomicBool::new(false);
+
 /// Signal to carry the passkey result from the keyboard task back to the GATT task.
 /// `Some(passkey)` = submit, `None` = cancel.
 pub static PASSKEY_RESPONSE: Signal<crate::RawMutex, Option<u32>> = Signal::new();
 
+/// Signal to carry the numeric comparison result from the keyboard task back to the GATT task.
+/// `true` = the passkeys match, `false` = reject.
+pub static PASSKEY_CONFIRMATION: Signal<crate::RawMutex, bool> = Signal::new();
+
+/// Whether a passkey is shown through [`PasskeyDisplayEvent`], so that ending the session hides it.
+static PASSKEY_SHOWN: AtomicBool = AtomicBool::new(false);
+
 /// Start a new passkey entry session.
 ///
 /// IMPORTANT: reset the response signal before enabling passkey mode, so an
@@ -23,9 +36,31 @@ pub fn begin_passkey_entry_session() {
     PASSKEY_ENTRY_MODE.store(true, Ordering::Release);
 }
 
+/// Start a numeric comparison session, the passkey must be shown with [`show_passkey`].
+///
+/// The confirm flag is set before the entry flag, so the keyboard never sees
+/// an active session in the wrong mode.
+pub fn begin_passkey_confirm_session() {
+    PASSKEY_CONFIRMATION.reset();
+    PASSKEY_CONFIRM_MODE.store(true, Ordering::Release);
+    PASSKEY_ENTRY_MODE.store(true, Ordering::Release);
+}
+
+/// Show a passkey on the display until the session ends.
+///
+/// `confirm` asks the user to confirm that it matches the passkey shown by the host.
+pub fn show_passkey(passkey: u32, confirm: bool) {
+    PASSKEY_SHOWN.store(true, Ordering::Release);
+    publish_event(PasskeyDisplayEvent::new(passkey, confirm));
+}
+
 /// End the current passkey entry session.
 pub fn end_passkey_entry_session() {
     PASSKEY_ENTRY_MODE.store(false, Ordering::Release);
+    PASSKEY_CONFIRM_MODE.store(false, Ordering::Release);
+    if PASSKEY_SHOWN.swap(false, Ordering::AcqRel) {
+        publish_event(PasskeyDisplayEvent::hidden());
+    }
 }
 
 /// Drop guard that clears passkey mode whenever the surrounding task exits.
@@ -64,6 +99,10 @@ pub enum PasskeyAction {
     BufferFull,
     /// Enter pressed but fewer than PASSKEY_LENGTH digits entered.
     Incomplete,
+    /// The user confirmed that the passkeys match (Enter or Y), numeric comparison only.
+    Confirmed,
+    /// The user rejected the passkey (Escape or N), numeric comparison only.
+    Rejected,
     /// Key was not a passkey-relevant key (silently consumed).
     Ignored,
 }
@@ -73,6 +112,8 @@ pub struct PasskeyEntryState {
     digits: [u8; PASSKEY_LENGTH],
     count: usize,
     active: bool,
+    /// Whether the active session is a numeric comparison instead of digit entry.
+    confirm: bool,
 }
 
 impl Default for PasskeyEntryState {
@@ -87,6 +128,7 @@ impl PasskeyEntryState {
             digits: [0; PASSKEY_LENGTH],
             count: 0,
             active: false,
+            confirm: false,
         }
     }
 
@@ -94,21 +136,42 @@ impl PasskeyEntryState {
         self.active
     }
 
+    /// Whether the active session is a numeric comparison.
+    pub fn is_confirming(&self) -> bool {
+        self.active && self.confirm
+    }
+
     pub fn activate(&mut self) {
         self.active = true;
+        self.confirm = false;
         self.reset();
     }
 
+    /// Activate a numeric comparison session.
+    pub fn activate_confirm(&mut self) {
+        self.activate();
+        self.confirm = true;
+    }
+
     pub fn deactivate(&mut self) {
         self.active = false;
+        self.confirm = false;
     }
 
     pub fn check_mode_transition(&mut self) {
         use core::sync::atomic::Ordering;
         let passkey_active = crate::ble::passkey::PASSKEY_ENTRY_MODE.load(Ordering::Acquire);
-        if passkey_active && !self.is_active() {
-            self.activate();
-        } else if !passkey_active && self.is_active() {
+        if passkey_active {
+            let confirm = crate::ble::passkey::PASSKEY_CONFIRM_MODE.load(Ordering::Acquire);
+            // A new session can start in the other mode before the old one was observed as ended
+            if !self.is_active() || self.confirm != confirm {
+                if confirm {
+                    self.activate_confirm();
+                } else {
+                    self.activate();
+                }
+            }
+        } else if self.is_active() {
             self.deactivate();
         }
     }
@@ -164,7 +227,16 @@ impl PasskeyEntryState {
     ///
     /// Encapsulates all passkey entry logic: digit entry, Enter/submit,
     /// Escape/cancel, Backspace/delete, and ignoring irrelevant keys.
+    /// In a numeric comparison only Enter/Y and Escape/N are handled.
     pub fn handle_key(&mut self, key: HidKeyCode) -> PasskeyAction {
+        if self.confirm {
+            return match key {
+                HidKeyCode::Enter | HidKeyCode::KpEnter | HidKeyCode::Y => PasskeyAction::Confirmed,
+                HidKeyCode::Escape | HidKeyCode::N => PasskeyAction::Rejected,
+                _ => PasskeyAction::Ignored,
+            };
+        }
+
         if let Some(digit) = hid_keycode_to_digit(key) {
             if self.add_digit(digit) {
                 PasskeyAction::DigitAdded(digit)
@@ -374,6 +446,38 @@ mod tests {
         assert_eq!(state.handle_key(HidKeyCode::KpEnter), PasskeyAction::Submitted(123456));
     }
 
+    #[test]
+    fn test_handle_key_confirm() {
+        let mut state = PasskeyEntryState::new();
+        state.activate_confirm();
+        assert!(state.is_confirming());
+        assert_eq!(state.handle_key(HidKeyCode::Enter), PasskeyAction::Confirmed);
+        assert_eq!(state.handle_key(HidKeyCode::KpEnter), PasskeyAction::Confirmed);
+        assert_eq!(state.handle_key(HidKeyCode::Y), PasskeyAction::Confirmed);
+    }
+
+    #[test]
+    fn test_handle_key_reject() {
+        let mut state = PasskeyEntryState::new();
+        state.activate_confirm();
+        assert_eq!(state.handle_key(HidKeyCode::Escape), PasskeyAction::Rejected);
+        assert_eq!(state.handle_key(HidKeyCode::N), PasskeyAction::Rejected);
+    }
+
+    #[test]
+    fn test_handle_key_confirm_ignores_digits() {
+        let mut state = PasskeyEntryState::new();
+        state.activate_confirm();
+        assert_eq!(state.handle_key(HidKeyCode::Kc1), PasskeyAction::Ignored);
+        assert_eq!(state.handle_key(HidKeyCode::Backspace), PasskeyAction::Ignored);
+        assert_eq!(state.digit_count(), 0);
+
+        // Switching back to digit entry leaves the numeric comparison
+        state.activate();
+        assert!(!state.is_confirming());
+        assert_eq!(state.handle_key(HidKeyCode::Kc1), PasskeyAction::DigitAdded(1));
+    }
+
     #[test]
     fn test_hid_keycode_to_digit_all() {
         let keycodes = [
@@ -2,11 +2,12 @@
 
 use core::sync::atomic::Ordering;
 
+use bt_hci::param::AddrKind;
 #[cfg(feature = "_ble")]
 use bt_hci::{cmd::le::LeSetPhy, controller::ControllerCmdAsync};
 use embassy_futures::select::{Either3, select3};
 use embassy_sync::signal::Signal;
-use rmk_types::ble::{BleState, BleStatus};
+use rmk_types::ble::{BleBond, BleState, BleStatus, BondAddressKind, BondSecurityLevel, CccdEntry};
 use trouble_host::prelude::*;
 use trouble_host::{BondInformation, LongTermKey};
 #[cfg(feature = "storage")]
@@ -16,17 +17,24 @@ use {
 };
 
 use super::ble_server::CCCD_TABLE_SIZE;
-use super::{get_current_profile, set_ble_status};
+use super::profile_settings::PROFILE_SETTINGS_CHANGED;
+use super::{get_current_profile, multi_host, set_ble_status};
 use crate::NUM_BLE_PROFILE;
 use crate::channel::BLE_PROFILE_CHANNEL;
 use crate::event::{ConnectionChangeEvent, ConnectionType, publish_event};
 use crate::state::CONNECTION_TYPE;
 
 pub(crate) static UPDATED_PROFILE: Signal<crate::RawMutex, ProfileInfo> = Signal::new();
-pub(crate) static UPDATED_CCCD_TABLE: Signal<crate::RawMutex, CccdTable<CCCD_TABLE_SIZE>> = Signal::new();
+/// CCCD table written by the host of a profile
+pub(crate) static UPDATED_CCCD_TABLE: Signal<crate::RawMutex, (u8, CccdTable<CCCD_TABLE_SIZE>)> = Signal::new();
+/// Response of `BleProfileAction::ExportBond`
+pub(crate) static EXPORTED_BOND: Signal<crate::RawMutex, Option<ProfileInfo>> = Signal::new();
 
 /// BLE profile info
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+///
+/// The address kind was added after the other fields, so it's serialized last. Records saved before don't have it,
+/// see the `Deserialize` implementation.
+#[derive(Clone, Debug, serde::Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ProfileInfo {
     pub(crate) slot_num: u8,
@@ -35,6 +43,69 @@ pub struct ProfileInfo {
     pub(crate) info: BondInformation,
     #[serde(with = "cccd_table_serde")]
     pub(crate) cccd_table: CccdTable<CCCD_TABLE_SIZE>,
+    /// Kind of the identity address in `info`, public or random
+    #[serde(with = "addr_kind_serde")]
+    pub(crate) addr_kind: AddrKind,
+}
+
+impl<'de> serde::Deserialize<'de> for ProfileInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{Error, SeqAccess, Visitor};
+
+        #[derive(serde::Deserialize)]
+        struct Info(#[serde(with = "bond_info_serde")] BondInformation);
+        #[derive(serde::Deserialize)]
+        struct Cccd(#[serde(with = "cccd_table_serde")] CccdTable<CCCD_TABLE_SIZE>);
+        #[derive(serde::Deserialize)]
+        struct Kind(#[serde(with = "addr_kind_serde")] AddrKind);
+
+        struct ProfileInfoVisitor;
+
+        impl<'de> Visitor<'de> for ProfileInfoVisitor {
+            type Value = ProfileInfo;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                formatter.write_str("struct ProfileInfo")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ProfileInfo, A::Error> {
+                let slot_num = seq.next_element()?.ok_or_else(|| Error::invalid_length(0, &self))?;
+                let removed = seq.next_element()?.ok_or_else(|| Error::invalid_length(1, &self))?;
+                let Info(info) = seq.next_element()?.ok_or_else(|| Error::invalid_length(2, &self))?;
+                let Cccd(cccd_table) = seq.next_element()?.ok_or_else(|| Error::invalid_length(3, &self))?;
+                // Legacy records end here, postcard reports the end of the record as an error.
+                // The kind of their address is derived from the address itself.
+                let addr_kind = match seq.next_element::<Kind>() {
+                    Ok(Some(Kind(kind))) => kind,
+                    _ => legacy_addr_kind(&info.identity.bd_addr),
+                };
+                Ok(ProfileInfo {
+                    slot_num,
+                    removed,
+                    info,
+                    cccd_table,
+                    addr_kind,
+                })
+            }
+        }
+
+        const FIELDS: &[&str] = &["slot_num", "removed", "info", "cccd_table", "addr_kind"];
+        deserializer.deserialize_struct("ProfileInfo", FIELDS, ProfileInfoVisitor)
+    }
+}
+
+/// Kind of an address saved without it: random static addresses have the two most significant bits set.
+///
+/// Public addresses with these bits set are taken as random, the host has to pair again in that case.
+fn legacy_addr_kind(addr: &BdAddr) -> AddrKind {
+    if addr.raw()[5] >> 6 == 0b11 {
+        AddrKind::RANDOM
+    } else {
+        AddrKind::PUBLIC
+    }
 }
 
 // Custom serde module for BondInformation
@@ -84,6 +155,28 @@ pub(crate) mod bond_info_serde {
     }
 }
 
+// Custom serde module for AddrKind
+pub(crate) mod addr_kind_serde {
+    use serde::{Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    pub fn serialize<S>(kind: &AddrKind, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        kind.as_raw().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<AddrKind, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let kind: u8 = serde::Deserialize::deserialize(deserializer)?;
+        Ok(AddrKind::new(kind))
+    }
+}
+
 // Custom serde module for CccdTable
 pub(crate) mod cccd_table_serde {
     use serde::{Deserializer, Serialize, Serializer};
@@ -152,17 +245,92 @@ impl Default for ProfileInfo {
                 false,
             ),
             cccd_table: CccdTable::<CCCD_TABLE_SIZE>::default(),
+            addr_kind: AddrKind::PUBLIC,
+        }
+    }
+}
+
+impl ProfileInfo {
+    /// Convert to the bond record exchanged with the host
+    pub(crate) fn to_bond(&self) -> BleBond {
+        let mut cccd_table = heapless::Vec::new();
+        // Unused entries have handle 0
+        for (handle, cccd) in self.cccd_table.inner().iter().filter(|(handle, _)| *handle != 0) {
+            let entry = CccdEntry {
+                handle: *handle,
+                value: cccd.raw(),
+            };
+            if cccd_table.push(entry).is_err() {
+                warn!("Too many CCCD entries, the rest are not exported");
+                break;
+            }
+        }
+        BleBond {
+            slot: self.slot_num,
+            address: self.info.identity.bd_addr.into_inner(),
+            address_kind: if self.addr_kind == AddrKind::RANDOM {
+                BondAddressKind::Random
+            } else {
+                BondAddressKind::Public
+            },
+            irk: self.info.identity.irk.map(|k| k.to_le_bytes()),
+            ltk: self.info.ltk.to_le_bytes(),
+            security_level: match self.info.security_level {
+                SecurityLevel::NoEncryption => BondSecurityLevel::NoEncryption,
+                SecurityLevel::Encrypted => BondSecurityLevel::Encrypted,
+                SecurityLevel::EncryptedAuthenticated => BondSecurityLevel::EncryptedAuthenticated,
+            },
+            is_bonded: self.info.is_bonded,
+            cccd_table,
+        }
+    }
+
+    /// Create from a bond record imported from the host
+    pub(crate) fn from_bond(bond: &BleBond) -> Self {
+        let mut cccd_values = [(0u16, CCCD::default()); CCCD_TABLE_SIZE];
+        if bond.cccd_table.len() > CCCD_TABLE_SIZE {
+            warn!("Too many CCCD entries, the rest are not imported");
+        }
+        for (value, entry) in cccd_values.iter_mut().zip(bond.cccd_table.iter()) {
+            *value = (entry.handle, entry.value.into());
+        }
+        Self {
+            slot_num: bond.slot,
+            removed: false,
+            info: BondInformation::new(
+                Identity {
+                    bd_addr: BdAddr::new(bond.address),
+                    irk: bond.irk.map(IdentityResolvingKey::from_le_bytes),
+                },
+                LongTermKey::from_le_bytes(bond.ltk),
+                match bond.security_level {
+                    BondSecurityLevel::NoEncryption => SecurityLevel::NoEncryption,
+                    BondSecurityLevel::Encrypted => SecurityLevel::Encrypted,
+                    BondSecurityLevel::EncryptedAuthenticated => SecurityLevel::EncryptedAuthenticated,
+                },
+                bond.is_bonded,
+            ),
+            cccd_table: CccdTable::new(cccd_values),
+            addr_kind: match bond.address_kind {
+                BondAddressKind::Public => AddrKind::PUBLIC,
+                BondAddressKind::Random => AddrKind::RANDOM,
+            },
         }
     }
 }
 
 /// BLE profile switch action
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum BleProfileAction {
     SwitchProfile(u8),
     PreviousProfile,
     NextProfile,
     ClearProfile,
     ToggleConnection,
+    /// Read the bond of the slot, answered via `EXPORTED_BOND`
+    ExportBond(u8),
+    /// Save a bond restored by the host
+    ImportBond(ProfileInfo),
 }
 
 /// Manage BLE profiles and bonding information
@@ -170,7 +338,7 @@ pub(crate) enum BleProfileAction {
 /// ProfileManager is responsible for:
 /// 1. Managing multiple BLE profiles, allowing users to switch between multiple devices
 /// 2. Storing and loading bonding information for each profile
-/// 3. Updating the bonding information of the active profile to the BLE stack
+/// 3. Updating the bonding information of the active profile to the BLE stack, or of all profiles with `multi_host`
 /// 4. Handling profile switch, clear, and save operations
 #[cfg(feature = "_ble")]
 pub struct ProfileManager<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> {
@@ -205,6 +373,8 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
             }
         }
         debug!("Loaded {} bond info", self.bonded_devices.len());
+        super::bond::load_profile_labels().await;
+        super::profile_settings::load_profile_settings().await;
 
         // Load current active profile, save to `BLE_STATUS`
         let profile = if let Some(profile) = read_setting(StorageKey::ActiveBleProfile).await {
@@ -233,6 +403,11 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
 
     /// Update bonding information in the stack according to the current active profile
     pub fn update_stack_bonds(&self) {
+        if crate::BLE_MULTI_HOST {
+            self.update_all_stack_bonds();
+            return;
+        }
+
         let current_bond_info = self.stack.get_bond_information();
         for bond in current_bond_info {
             if let Err(e) = self.stack.remove_bond_information(bond.identity) {
@@ -248,6 +423,29 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
         }
     }
 
+    /// Update the bonds of all profiles in the stack, with `multi_host`
+    ///
+    /// The hosts of the other profiles stay connected, so the bonds which didn't change are kept in the stack.
+    fn update_all_stack_bonds(&self) {
+        let bonds = || self.bonded_devices.iter().filter(|info| !info.removed);
+        for bond in self.stack.get_bond_information() {
+            if !bonds().any(|info| info.info == bond)
+                && let Err(e) = self.stack.remove_bond_information(bond.identity)
+            {
+                debug!("Remove bond info error: {:?}", e);
+            }
+        }
+
+        let current_bond_info = self.stack.get_bond_information();
+        for info in bonds().filter(|info| !current_bond_info.iter().any(|bond| *bond == info.info)) {
+            debug!("Add bond info of profile {}: {:?}", info.slot_num, info);
+            if let Err(e) = self.stack.add_bond_information(info.info.clone()) {
+                debug!("Add bond info error: {:?}", e);
+            }
+        }
+        multi_host::set_bonded_hosts(&self.bonded_devices);
+    }
+
     /// Add/update bonding information
     pub async fn add_profile_info(&mut self, profile_info: ProfileInfo) {
         // Update profile information in memory
@@ -278,21 +476,14 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
             .await;
     }
 
-    /// Update CCCD table in the stack
-    pub async fn update_profile_cccd_table(&mut self, table: CccdTable<CCCD_TABLE_SIZE>) {
-        // Get current active profile
-        let active_profile = get_current_profile();
-
+    /// Update CCCD table of the profile
+    pub async fn update_profile_cccd_table(&mut self, profile: u8, table: CccdTable<CCCD_TABLE_SIZE>) {
         // Update profile information in memory
-        if let Some(index) = self
-            .bonded_devices
-            .iter()
-            .position(|info| info.slot_num == active_profile)
-        {
+        if let Some(index) = self.bonded_devices.iter().position(|info| info.slot_num == profile) {
             // Check whether the CCCD table is the same as the current one
             debug!(
                 "Updating profile {} CCCD table: {:?} from {:?}",
-                active_profile,
+                profile,
                 table,
                 self.bonded_devices[index].cccd_table.inner()
             );
@@ -301,7 +492,7 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
                 return;
             }
 
-            debug!("Updating profile {} CCCD table: {:?}", active_profile, table);
+            debug!("Updating profile {} CCCD table: {:?}", profile, table);
             let mut profile_info = self.bonded_devices[index].clone();
             profile_info.cccd_table = table;
             self.bonded_devices[index] = profile_info.clone();
@@ -328,6 +519,8 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
 
         // Update the active bonding information in the stack
         self.update_stack_bonds();
+        // The host of the profile may still be connected in its slot
+        multi_host::disconnect_host(slot_num);
 
         #[cfg(feature = "storage")]
         // Send the clear slot message to the flash task
@@ -348,8 +541,13 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
             state: BleState::Inactive,
         });
 
-        // Update the active bonding information in the stack
-        self.update_stack_bonds();
+        // Update the active bonding information in the stack, the bonds of all profiles are kept with `multi_host`
+        if crate::BLE_MULTI_HOST {
+            multi_host::active_profile_changed();
+        } else {
+            self.update_stack_bonds();
+        }
+        PROFILE_SETTINGS_CHANGED.signal(());
 
         #[cfg(feature = "storage")]
         FLASH_CHANNEL
@@ -376,6 +574,19 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
             )
             .await
             {
+                // Bond backup and restore don't change the active profile
+                Either3::First(BleProfileAction::ExportBond(slot_num)) => {
+                    let bond = self
+                        .bonded_devices
+                        .iter()
+                        .find(|info| !info.removed && info.slot_num == slot_num)
+                        .cloned();
+                    EXPORTED_BOND.signal(bond);
+                }
+                Either3::First(BleProfileAction::ImportBond(profile_info)) => {
+                    info!("Importing bond of profile {}", profile_info.slot_num);
+                    self.add_profile_info(profile_info).await;
+                }
                 Either3::First(action) => {
                     #[cfg(feature = "storage")]
                     if FLASH_OPERATION_FINISHED.signaled() {
@@ -408,6 +619,7 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
                             let profile = get_current_profile();
                             self.clear_bond(profile).await;
                         }
+                        BleProfileAction::ExportBond(_) | BleProfileAction::ImportBond(_) => (),
                         BleProfileAction::ToggleConnection => {
                             let current: ConnectionType = CONNECTION_TYPE.load(Ordering::SeqCst).into();
                             let updated = match current {
@@ -419,6 +631,7 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
                             info!("Switching connection type to: {:?}", updated);
 
                             publish_event(ConnectionChangeEvent::new(updated));
+                            PROFILE_SETTINGS_CHANGED.signal(());
 
                             #[cfg(feature = "storage")]
                             FLASH_CHANNEL
@@ -434,10 +647,101 @@ impl<'a, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> ProfileMan
                 Either3::Second(profile_info) => {
                     self.add_profile_info(profile_info).await;
                 }
-                Either3::Third(table) => {
-                    self.update_profile_cccd_table(table).await;
+                Either3::Third((profile, table)) => {
+                    self.update_profile_cccd_table(profile, table).await;
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rmk_types::ble::{BleBond, BondAddressKind, BondSecurityLevel, CccdEntry};
+    use trouble_host::BondInformation;
+    use trouble_host::prelude::*;
+
+    use super::{CCCD_TABLE_SIZE, ProfileInfo, bond_info_serde, cccd_table_serde};
+
+    #[test]
+    fn bond_round_trip() {
+        let bond = BleBond {
+            slot: 2,
+            address: [0x11, 0x22, 0x33, 0x44, 0x55, 0xc6],
+            address_kind: BondAddressKind::Random,
+            irk: Some([0xa5; 16]),
+            ltk: [0x5a; 16],
+            security_level: BondSecurityLevel::EncryptedAuthenticated,
+            is_bonded: true,
+            cccd_table: heapless::Vec::from_slice(&[
+                CccdEntry { handle: 12, value: 1 },
+                CccdEntry { handle: 15, value: 2 },
+            ])
+            .unwrap(),
+        };
+
+        let profile = ProfileInfo::from_bond(&bond);
+        assert_eq!(profile.slot_num, 2);
+        assert!(!profile.removed);
+        assert_eq!(profile.to_bond(), bond);
+
+        let no_irk = BleBond {
+            address_kind: BondAddressKind::Public,
+            irk: None,
+            security_level: BondSecurityLevel::NoEncryption,
+            cccd_table: heapless::Vec::new(),
+            ..bond
+        };
+        assert_eq!(ProfileInfo::from_bond(&no_irk).to_bond(), no_irk);
+    }
+
+    /// Layout of the records saved before the address kind was added
+    #[derive(serde::Serialize)]
+    struct LegacyProfileInfo {
+        slot_num: u8,
+        removed: bool,
+        #[serde(with = "bond_info_serde")]
+        info: BondInformation,
+        #[serde(with = "cccd_table_serde")]
+        cccd_table: CccdTable<CCCD_TABLE_SIZE>,
+    }
+
+    #[test]
+    fn legacy_record_is_deserialized() {
+        let bond = BleBond {
+            slot: 1,
+            address: [0x11, 0x22, 0x33, 0x44, 0x55, 0xc6],
+            address_kind: BondAddressKind::Random,
+            irk: Some([0xa5; 16]),
+            ltk: [0x5a; 16],
+            security_level: BondSecurityLevel::Encrypted,
+            is_bonded: true,
+            cccd_table: heapless::Vec::from_slice(&[CccdEntry { handle: 12, value: 1 }]).unwrap(),
+        };
+        let mut buf = [0; 256];
+        for bond in [
+            bond.clone(),
+            BleBond {
+                address: [0x11, 0x22, 0x33, 0x44, 0x55, 0x06],
+                address_kind: BondAddressKind::Public,
+                ..bond
+            },
+        ] {
+            let profile = ProfileInfo::from_bond(&bond);
+            let legacy = LegacyProfileInfo {
+                slot_num: profile.slot_num,
+                removed: profile.removed,
+                info: profile.info.clone(),
+                cccd_table: profile.cccd_table.clone(),
+            };
+            let record = postcard::to_slice(&legacy, &mut buf).unwrap();
+            let restored: ProfileInfo = postcard::from_bytes(record).unwrap();
+            assert_eq!(restored.to_bond(), bond);
+
+            // The current layout keeps the address kind
+            let record = postcard::to_slice(&profile, &mut buf).unwrap();
+            let restored: ProfileInfo = postcard::from_bytes(record).unwrap();
+            assert_eq!(restored.to_bond(), bond);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Settings of the outputs: USB, and each BLE profile.
+//!
+//! When the active BLE profile or the connection type changes, the keyboard applies the settings of the new output:
+//! its default layer is set, its key overrides replace the keymap actions, and its OS mode swaps Ctrl and Gui
+//! for a macOS host.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use rmk_types::action::KeyAction;
+pub use rmk_types::ble::{KeyOverride, OsMode, OutputProfile, ProfileSettings};
+
+use super::get_current_profile;
+#[cfg(feature = "storage")]
+use crate::channel::FLASH_CHANNEL;
+use crate::event::ConnectionType;
+use crate::state::get_connection_type;
+#[cfg(feature = "storage")]
+use crate::storage::FlashOperationMessage;
+use crate::{NUM_BLE_PROFILE, RawMutex};
+
+/// Settings of USB, followed by the settings of each BLE profile
+static PROFILE_SETTINGS: Mutex<RawMutex, RefCell<[ProfileSettings; NUM_BLE_PROFILE + 1]>> = Mutex::new(RefCell::new(
+    [const {
+        ProfileSettings {
+            default_layer: None,
+            os_mode: OsMode::Unspecified,
+            key_overrides: heapless::Vec::new(),
+        }
+    }; NUM_BLE_PROFILE + 1],
+));
+
+/// Signaled when the active output or its settings change, the keyboard applies the settings of the active output
+pub(crate) static PROFILE_SETTINGS_CHANGED: Signal<RawMutex, ()> = Signal::new();
+
+fn settings_index(output: OutputProfile) -> Option<usize> {
+    match output {
+        OutputProfile::Usb => Some(0),
+        OutputProfile::Ble(slot_num) if (slot_num as usize) < NUM_BLE_PROFILE => Some(slot_num as usize + 1),
+        OutputProfile::Ble(_) => None,
+    }
+}
+
+/// Load the settings of all outputs from the storage
+#[cfg(feature = "storage")]
+pub(crate) async fn load_profile_settings() {
+    let outputs = core::iter::once(OutputProfile::Usb).chain((0..NUM_BLE_PROFILE as u8).map(OutputProfile::Ble));
+    for output in outputs {
+        if let Some(settings) = crate::storage::read_profile_settings(output).await
+            && let Some(idx) = settings_index(output)
+        {
+            PROFILE_SETTINGS.lock(|s| s.borrow_mut()[idx] = settings);
+        }
+    }
+    PROFILE_SETTINGS_CHANGED.signal(());
+}
+
+/// The output which receives the reports
+pub fn active_output() -> OutputProfile {
+    match get_connection_type() {
+        ConnectionType::Usb => OutputProfile::Usb,
+        ConnectionType::Ble => OutputProfile::Ble(get_current_profile()),
+    }
+}
+
+/// Settings of the output, `None` if the BLE profile slot doesn't exist
+pub fn profile_settings(output: OutputProfile) -> Option<ProfileSettings> {
+    let idx = settings_index(output)?;
+    Some(PROFILE_SETTINGS.lock(|s| s.borrow()[idx].clone()))
+}
+
+/// Set the settings of the output and save them to the storage.
+///
+/// Returns `false` if the BLE profile slot doesn't exist.
+pub async fn set_profile_settings(output: OutputProfile, settings: ProfileSettings) -> bool {
+    let Some(idx) = settings_index(output) else {
+        warn!("Invalid output profile: {:?}", output);
+        return false;
+    };
+    PROFILE_SETTINGS.lock(|s| s.borrow_mut()[idx] = settings.clone());
+    if output == active_output() {
+        PROFILE_SETTINGS_CHANGED.signal(());
+    }
+    #[cfg(feature = "storage")]
+    FLASH_CHANNEL
+        .send(FlashOperationMessage::ProfileSettings(output, settings))
+        .await;
+    true
+}
+
+fn with_active_settings<R>(f: impl FnOnce(&ProfileSettings) -> R) -> Option<R> {
+    let idx = settings_index(active_output())?;
+    Some(PROFILE_SETTINGS.lock(|s| f(&s.borrow()[idx])))
+}
+
+/// Default layer of the active output
+pub(crate) fn active_default_layer() -> Option<u8> {
+    with_active_settings(|s| s.default_layer).flatten()
+}
+
+/// OS mode of the active output
+pub(crate) fn active_os_mode() -> OsMode {
+    with_active_settings(|s| s.os_mode).unwrap_or_default()
+}
+
+/// Key override of the active output at the key position
+pub(crate) fn key_override(layer: u8, row: u8, col: u8) -> Option<KeyAction> {
+    with_active_settings(|s| s.key_override(layer, row, col)).flatten()
+}
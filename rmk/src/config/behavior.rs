@@ -18,6 +18,7 @@ pub struct BehaviorConfig {
     pub morse: MorsesConfig,
     pub keyboard_macros: KeyboardMacrosConfig,
     pub mouse_key: MouseKeyConfig,
+    pub pointing_cpi: PointingCpiConfig,
 }
 
 /// Configurations for tap behavior
@@ -95,6 +96,71 @@ impl Default for CombosConfig {
     }
 }
 
+/// Maximum number of pointing devices which have CPI presets
+pub const POINTING_CPI_DEVICE_MAX_NUM: usize = 8;
+
+/// CPI presets of a pointing device
+#[derive(Clone, Copy, Debug)]
+pub struct PointingCpiPresets {
+    /// Id of the pointing device
+    pub device_id: u8,
+    /// Available CPI values, switched by CPI actions
+    pub presets: &'static [u16],
+    /// Index of the current preset
+    pub current: u8,
+}
+
+impl PointingCpiPresets {
+    /// CPI of the current preset
+    pub fn cpi(&self) -> Option<u16> {
+        self.presets.get(self.current as usize).copied()
+    }
+
+    /// Set the CPI of the device to the current preset
+    #[cfg(feature = "pointing_cpi")]
+    pub(crate) fn publish(&self) {
+        if let Some(cpi) = self.cpi() {
+            crate::input_device::pointing::set_pointing_cpi(crate::event::PointingSetCpiEvent {
+                device_id: self.device_id,
+                cpi,
+            });
+        }
+    }
+}
+
+/// Config for CPI presets of pointing devices
+#[derive(Clone, Debug, Default)]
+pub struct PointingCpiConfig {
+    pub devices: Vec<PointingCpiPresets, POINTING_CPI_DEVICE_MAX_NUM>,
+}
+
+#[cfg(feature = "pointing_cpi")]
+impl PointingCpiConfig {
+    /// Switch the preset of the devices targeted by `action`, returns the devices whose preset is changed
+    pub(crate) fn update(
+        &mut self,
+        action: rmk_types::action::PointingAction,
+    ) -> Vec<PointingCpiPresets, POINTING_CPI_DEVICE_MAX_NUM> {
+        let target = action.device_id();
+        let mut changed = Vec::new();
+        for device in self
+            .devices
+            .iter_mut()
+            .filter(|d| target == crate::input_device::pointing::ALL_POINTING_DEVICES || d.device_id == target)
+        {
+            let len = device.presets.len().min(u8::MAX as usize) as u8;
+            if let Some(preset) = action.next_preset(device.current, len)
+                && preset != device.current
+            {
+                device.current = preset;
+                // `changed` has the same capacity as `devices`
+                let _ = changed.push(*device);
+            }
+        }
+        changed
+    }
+}
+
 /// Config for fork behavior
 #[derive(Clone, Debug)]
 pub struct ForksConfig {
@@ -8,7 +8,7 @@ mod vial;
 
 pub use behavior::{
     BehaviorConfig, CombosConfig, ForksConfig, KeyboardMacrosConfig, MorsesConfig, MouseKeyConfig, OneShotConfig,
-    OneShotModifiersConfig, TapConfig,
+    OneShotModifiersConfig, POINTING_CPI_DEVICE_MAX_NUM, PointingCpiConfig, PointingCpiPresets, TapConfig,
 };
 #[cfg(feature = "_ble")]
 pub use ble_battery::BleBatteryConfig;
@@ -0,0 +1,463 @@
+//! Firmware update into a DFU partition.
+//!
+//! [`DfuUpdater`] owns two flash partitions with the layout of an embassy-boot style bootloader:
+//! the DFU partition which receives the new image, and the bootloader state partition.
+//! A firmware update goes through [`DfuRequest`]s:
+//!
+//! 1. `Begin` erases the DFU partition.
+//! 2. `Write` chunks are written in order. Every chunk except the last one must be full.
+//! 3. `Finish` reads the image back and checks its CRC32, then writes the swap magic into the state partition
+//!    and reboots. The bootloader swaps the image into the active partition on the next boot.
+//!
+//! The new firmware must confirm the swap by writing the boot magic into the state partition with
+//! [`DfuUpdater::mark_booted`], which [`DfuUpdater`] does when it starts running.
+//! Otherwise the bootloader reverts to the previous image on the next reboot.
+//!
+//! Requests are sent with [`request`], from the host protocol or from the split link.
+//! On a split keyboard, the central relays the requests for `DfuTarget::Peripheral` to the peripheral,
+//! which must run its own [`DfuUpdater`].
+
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Timer, with_timeout};
+use embedded_storage_async::nor_flash::NorFlash;
+pub use rmk_types::dfu::{DFU_CHUNK_SIZE, DfuChunk, DfuError, DfuRequest, DfuResult, DfuTarget};
+
+use crate::RawMutex;
+use crate::core_traits::Runnable;
+
+/// Value written into the bootloader state partition to request the swap of the DFU image
+pub const SWAP_MAGIC: u8 = 0xF0;
+
+/// Value written into the bootloader state partition to confirm that the running image boots
+pub const BOOT_MAGIC: u8 = 0xD0;
+
+/// Time to wait for the response of a request, erasing the DFU partition in `Begin` takes a while
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time given to the response of `Finish` to be sent before rebooting
+const REBOOT_DELAY: Duration = Duration::from_millis(500);
+
+/// Requests to the local [`DfuUpdater`]
+static REQUESTS: Channel<RawMutex, DfuRequest, 1> = Channel::new();
+
+/// Responses of the local [`DfuUpdater`]
+static RESPONSES: Channel<RawMutex, DfuResult, 1> = Channel::new();
+
+/// Only one request is processed at a time
+static REQUEST_LOCK: Mutex<RawMutex, ()> = Mutex::new(());
+
+/// Send a DFU request to the target and wait for its result.
+///
+/// Returns `DfuError::Unreachable` if the target doesn't answer, e.g. no [`DfuUpdater`] is running on it.
+pub async fn request(target: DfuTarget, request: DfuRequest) -> DfuResult {
+    let _lock = REQUEST_LOCK.lock().await;
+    match target {
+        DfuTarget::Local => {
+            REQUESTS.send(request).await;
+            let result = with_timeout(RESPONSE_TIMEOUT, RESPONSES.receive()).await;
+            result.unwrap_or_else(|_| {
+                error!("No response from the DFU updater, is it running?");
+                REQUESTS.clear();
+                Err(DfuError::Unreachable)
+            })
+        }
+        #[cfg(feature = "split")]
+        DfuTarget::Peripheral(id) => relay::request(id as usize, request).await,
+        #[cfg(not(feature = "split"))]
+        DfuTarget::Peripheral(_) => Err(DfuError::Unreachable),
+    }
+}
+
+/// Relay of DFU requests from the central to split peripherals
+#[cfg(feature = "split")]
+pub(crate) mod relay {
+    use embassy_sync::channel::Channel;
+    use embassy_time::with_timeout;
+
+    use super::{DfuError, DfuRequest, DfuResult, RESPONSE_TIMEOUT};
+    use crate::{RawMutex, SPLIT_PERIPHERALS_NUM};
+
+    /// Requests to each peripheral, sent by the split driver
+    static REQUESTS: [Channel<RawMutex, DfuRequest, 1>; SPLIT_PERIPHERALS_NUM] =
+        [const { Channel::new() }; SPLIT_PERIPHERALS_NUM];
+
+    /// Responses from each peripheral
+    static RESPONSES: [Channel<RawMutex, DfuResult, 1>; SPLIT_PERIPHERALS_NUM] =
+        [const { Channel::new() }; SPLIT_PERIPHERALS_NUM];
+
+    pub(super) async fn request(id: usize, request: DfuRequest) -> DfuResult {
+        let (Some(requests), Some(responses)) = (REQUESTS.get(id), RESPONSES.get(id)) else {
+            warn!("DFU request to invalid peripheral {}", id);
+            return Err(DfuError::Unreachable);
+        };
+        // Drop the late response of a timed out request
+        responses.clear();
+        requests.send(request).await;
+        with_timeout(RESPONSE_TIMEOUT, responses.receive())
+            .await
+            .unwrap_or_else(|_| {
+                warn!("No DFU response from peripheral {}", id);
+                requests.clear();
+                Err(DfuError::Unreachable)
+            })
+    }
+
+    /// Wait for the next DFU request to the peripheral with the given id
+    pub(crate) async fn next_request(id: usize) -> DfuRequest {
+        match REQUESTS.get(id) {
+            Some(channel) => channel.receive().await,
+            None => core::future::pending().await,
+        }
+    }
+
+    /// Deliver the DFU response from the peripheral with the given id
+    pub(crate) fn respond(id: usize, result: DfuResult) {
+        if let Some(channel) = RESPONSES.get(id)
+            && channel.try_send(result).is_err()
+        {
+            warn!("Unexpected DFU response from peripheral {}", id);
+        }
+    }
+}
+
+/// CRC32 (IEEE), computed incrementally
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                self.0 = if self.0 & 1 != 0 {
+                    (self.0 >> 1) ^ 0xEDB8_8320
+                } else {
+                    self.0 >> 1
+                };
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+/// An update in progress
+struct Transfer {
+    /// Size of the image
+    size: u32,
+    /// Expected CRC32 of the image
+    crc32: u32,
+    /// Number of bytes written
+    written: u32,
+}
+
+/// Writes firmware images into the DFU partition, see the [module docs](self).
+///
+/// `DFU` is the DFU partition and `STATE` is the bootloader state partition,
+/// e.g. `embassy_embedded_hal::flash::partition::Partition`s of the internal flash.
+pub struct DfuUpdater<DFU: NorFlash, STATE: NorFlash> {
+    dfu: DFU,
+    state: STATE,
+    transfer: Option<Transfer>,
+}
+
+impl<DFU: NorFlash, STATE: NorFlash> DfuUpdater<DFU, STATE> {
+    pub fn new(dfu: DFU, state: STATE) -> Self {
+        const {
+            assert!(
+                DFU_CHUNK_SIZE % DFU::WRITE_SIZE == 0 && DFU_CHUNK_SIZE % DFU::READ_SIZE == 0,
+                "DFU chunks must be aligned to the write and read size of the flash"
+            );
+            assert!(STATE::WRITE_SIZE <= DFU_CHUNK_SIZE && STATE::WRITE_SIZE % STATE::READ_SIZE == 0);
+        }
+        Self {
+            dfu,
+            state,
+            transfer: None,
+        }
+    }
+
+    /// Process a request, `Finish` only marks the image for swap and doesn't reboot
+    pub(crate) async fn process(&mut self, request: DfuRequest) -> DfuResult {
+        let result = match request {
+            DfuRequest::Begin { size, crc32 } => self.begin(size, crc32).await,
+            DfuRequest::Write(chunk) => self.write(&chunk).await,
+            DfuRequest::Finish => self.finish().await,
+            DfuRequest::Abort => {
+                info!("DFU aborted");
+                self.transfer = None;
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            error!("DFU {:?} failed: {:?}", request, e);
+        }
+        result
+    }
+
+    async fn begin(&mut self, size: u32, crc32: u32) -> DfuResult {
+        self.transfer = None;
+        let capacity = (self.dfu.capacity() - self.dfu.capacity() % DFU::ERASE_SIZE) as u32;
+        if size == 0 || size > capacity {
+            return Err(DfuError::TooLarge);
+        }
+        info!("DFU started, image size: {}", size);
+        self.dfu.erase(0, capacity).await.map_err(|_| DfuError::Flash)?;
+        self.transfer = Some(Transfer {
+            size,
+            crc32,
+            written: 0,
+        });
+        Ok(())
+    }
+
+    async fn write(&mut self, chunk: &DfuChunk) -> DfuResult {
+        let transfer = self.transfer.as_mut().ok_or(DfuError::NotStarted)?;
+        let bytes = chunk.bytes();
+        let end = chunk.offset + bytes.len() as u32;
+        // Chunks are written in order and only the last one can be partial, so that every write is aligned
+        if chunk.offset != transfer.written
+            || end > transfer.size
+            || (bytes.len() < DFU_CHUNK_SIZE && end != transfer.size)
+        {
+            return Err(DfuError::InvalidOffset);
+        }
+
+        // Pad the last chunk with the erased value to the write size
+        let mut buf = [0xFF; DFU_CHUNK_SIZE];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        let len = bytes.len().div_ceil(DFU::WRITE_SIZE) * DFU::WRITE_SIZE;
+        self.dfu
+            .write(chunk.offset, &buf[..len])
+            .await
+            .map_err(|_| DfuError::Flash)?;
+        transfer.written = end;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> DfuResult {
+        let transfer = self.transfer.take().ok_or(DfuError::NotStarted)?;
+        if transfer.written != transfer.size {
+            return Err(DfuError::Incomplete);
+        }
+
+        // Verify what is actually in the flash
+        let mut crc = Crc32::new();
+        let mut buf = [0; DFU_CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < transfer.size {
+            let len = (transfer.size - offset).min(DFU_CHUNK_SIZE as u32) as usize;
+            let read_len = len.div_ceil(DFU::READ_SIZE) * DFU::READ_SIZE;
+            self.dfu
+                .read(offset, &mut buf[..read_len])
+                .await
+                .map_err(|_| DfuError::Flash)?;
+            crc.update(&buf[..len]);
+            offset += len as u32;
+        }
+        if crc.finish() != transfer.crc32 {
+            return Err(DfuError::CrcMismatch);
+        }
+
+        // Request the bootloader to swap the DFU image into the active partition on the next boot
+        self.write_magic(SWAP_MAGIC).await?;
+        info!("DFU image verified and marked for swap");
+        Ok(())
+    }
+
+    /// Confirm that the running image boots, so that the bootloader doesn't revert a swapped image.
+    ///
+    /// It's called when the updater starts running. The state partition is only written when it doesn't hold the
+    /// boot magic yet.
+    pub async fn mark_booted(&mut self) -> DfuResult {
+        let mut magic = [0; DFU_CHUNK_SIZE];
+        self.state
+            .read(0, &mut magic[..STATE::WRITE_SIZE])
+            .await
+            .map_err(|_| DfuError::Flash)?;
+        if magic[..STATE::WRITE_SIZE].iter().all(|&b| b == BOOT_MAGIC) {
+            return Ok(());
+        }
+        self.write_magic(BOOT_MAGIC).await
+    }
+
+    async fn write_magic(&mut self, magic: u8) -> DfuResult {
+        let capacity = (self.state.capacity() - self.state.capacity() % STATE::ERASE_SIZE) as u32;
+        self.state.erase(0, capacity).await.map_err(|_| DfuError::Flash)?;
+        let magic = [magic; DFU_CHUNK_SIZE];
+        self.state
+            .write(0, &magic[..STATE::WRITE_SIZE])
+            .await
+            .map_err(|_| DfuError::Flash)
+    }
+}
+
+impl<DFU: NorFlash, STATE: NorFlash> Runnable for DfuUpdater<DFU, STATE> {
+    async fn run(&mut self) -> ! {
+        if let Err(e) = self.mark_booted().await {
+            error!("Failed to mark the firmware as booted: {:?}", e);
+        }
+        loop {
+            let request = REQUESTS.receive().await;
+            let result = self.process(request).await;
+            RESPONSES.send(result).await;
+            if request == DfuRequest::Finish && result.is_ok() {
+                Timer::after(REBOOT_DELAY).await;
+                crate::boot::reboot_keyboard();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+
+    use super::*;
+    use crate::storage::dummy_flash::TestFlash;
+    use crate::test_support::test_block_on as block_on;
+
+    type Updater = DfuUpdater<TestFlash<1024, 256, 4>, TestFlash<256, 256, 4>>;
+
+    fn updater() -> Updater {
+        DfuUpdater::new(TestFlash::new(), TestFlash::new())
+    }
+
+    fn image(size: usize) -> ([u8; 256], u32) {
+        let mut image = [0; 256];
+        for (i, b) in image[..size].iter_mut().enumerate() {
+            *b = (i * 7) as u8;
+        }
+        let mut crc = Crc32::new();
+        crc.update(&image[..size]);
+        (image, crc.finish())
+    }
+
+    async fn write_image(updater: &mut Updater, image: &[u8]) -> DfuResult {
+        for (i, bytes) in image.chunks(DFU_CHUNK_SIZE).enumerate() {
+            let chunk = DfuChunk::new((i * DFU_CHUNK_SIZE) as u32, bytes).unwrap();
+            updater.process(DfuRequest::Write(chunk)).await?;
+        }
+        Ok(())
+    }
+
+    fn state_magic(updater: &mut Updater) -> [u8; 4] {
+        let mut magic = [0; 4];
+        block_on(updater.state.read(0, &mut magic)).unwrap();
+        magic
+    }
+
+    fn swap_marked(updater: &mut Updater) -> bool {
+        state_magic(updater) == [SWAP_MAGIC; 4]
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn image_is_written_and_marked() {
+        block_on(async {
+            let mut updater = updater();
+            // Partial last chunk, which isn't aligned to the write size
+            let (image, crc32) = image(203);
+            updater.process(DfuRequest::Begin { size: 203, crc32 }).await.unwrap();
+            write_image(&mut updater, &image[..203]).await.unwrap();
+            assert_eq!(updater.process(DfuRequest::Finish).await, Ok(()));
+
+            let mut written = [0; 203];
+            updater.dfu.read(0, &mut written).await.unwrap();
+            assert_eq!(written, image[..203]);
+            assert!(swap_marked(&mut updater));
+        });
+    }
+
+    #[test]
+    fn swapped_image_is_marked_booted() {
+        block_on(async {
+            let mut updater = updater();
+            let (image, crc32) = image(64);
+            updater.process(DfuRequest::Begin { size: 64, crc32 }).await.unwrap();
+            write_image(&mut updater, &image[..64]).await.unwrap();
+            updater.process(DfuRequest::Finish).await.unwrap();
+            assert!(swap_marked(&mut updater));
+
+            // The new image starts
+            assert_eq!(updater.mark_booted().await, Ok(()));
+            assert_eq!(state_magic(&mut updater), [BOOT_MAGIC; 4]);
+            // Marking again keeps the boot magic
+            assert_eq!(updater.mark_booted().await, Ok(()));
+            assert_eq!(state_magic(&mut updater), [BOOT_MAGIC; 4]);
+        });
+    }
+
+    #[test]
+    fn crc_mismatch_is_not_marked() {
+        block_on(async {
+            let mut updater = updater();
+            let (image, crc32) = image(64);
+            updater
+                .process(DfuRequest::Begin {
+                    size: 64,
+                    crc32: crc32 ^ 1,
+                })
+                .await
+                .unwrap();
+            write_image(&mut updater, &image[..64]).await.unwrap();
+            assert_eq!(updater.process(DfuRequest::Finish).await, Err(DfuError::CrcMismatch));
+            assert!(!swap_marked(&mut updater));
+            // The transfer is over
+            assert_eq!(updater.process(DfuRequest::Finish).await, Err(DfuError::NotStarted));
+        });
+    }
+
+    #[test]
+    fn invalid_transfers_are_refused() {
+        block_on(async {
+            let mut updater = updater();
+            let chunk = DfuChunk::new(0, &[0; DFU_CHUNK_SIZE]).unwrap();
+            assert_eq!(
+                updater.process(DfuRequest::Write(chunk)).await,
+                Err(DfuError::NotStarted)
+            );
+            assert_eq!(
+                updater.process(DfuRequest::Begin { size: 2048, crc32: 0 }).await,
+                Err(DfuError::TooLarge)
+            );
+
+            updater.process(DfuRequest::Begin { size: 96, crc32: 0 }).await.unwrap();
+            // Out of order
+            let chunk = DfuChunk::new(32, &[0; DFU_CHUNK_SIZE]).unwrap();
+            assert_eq!(
+                updater.process(DfuRequest::Write(chunk)).await,
+                Err(DfuError::InvalidOffset)
+            );
+            // Partial chunk in the middle of the image
+            let chunk = DfuChunk::new(0, &[0; 16]).unwrap();
+            assert_eq!(
+                updater.process(DfuRequest::Write(chunk)).await,
+                Err(DfuError::InvalidOffset)
+            );
+            let chunk = DfuChunk::new(0, &[0; DFU_CHUNK_SIZE]).unwrap();
+            updater.process(DfuRequest::Write(chunk)).await.unwrap();
+            assert_eq!(updater.process(DfuRequest::Finish).await, Err(DfuError::Incomplete));
+
+            updater.process(DfuRequest::Begin { size: 32, crc32: 0 }).await.unwrap();
+            updater.process(DfuRequest::Abort).await.unwrap();
+            assert_eq!(
+                updater.process(DfuRequest::Write(chunk)).await,
+                Err(DfuError::NotStarted)
+            );
+        });
+    }
+}
@@ -74,14 +74,16 @@ use rmk_types::modifier::ModifierCombination;
 pub use ssd1306;
 
 use crate::core_traits::Runnable;
-#[cfg(feature = "_ble")]
-use crate::event::BleStatusChangeEvent;
-#[cfg(all(feature = "split", feature = "_ble"))]
-use crate::event::PeripheralBatteryEvent;
+#[cfg(feature = "passkey_entry")]
+use crate::event::PasskeyDisplayEvent;
 use crate::event::{
-    BatteryStatusEvent, KeyboardEvent, LayerChangeEvent, LedIndicatorEvent, ModifierEvent, SleepStateEvent,
-    WpmUpdateEvent,
+    BatteryStatusEvent, KeyboardEvent, LayerChangeEvent, LedIndicatorEvent, LowBatteryEvent, ModifierEvent,
+    SleepStateEvent, WpmUpdateEvent,
 };
+#[cfg(feature = "_ble")]
+use crate::event::{BleLinkStatsEvent, BleStatusChangeEvent};
+#[cfg(all(feature = "split", feature = "_ble"))]
+use crate::event::{CentralBatteryEvent, PeripheralBatteryEvent};
 #[cfg(feature = "split")]
 use crate::event::{CentralConnectedEvent, PeripheralConnectedEvent};
 use crate::processor::Processor;
@@ -92,9 +94,10 @@ use crate::processor::Processor;
 ///
 /// Some fields are only available when specific RMK features are enabled:
 ///
-/// - `ble_status` — requires the `_ble` feature
-/// - `central_connected`, `peripherals_connected` — require the `split` feature
-/// - `peripheral_batteries` — requires both `split` and `_ble` features
+/// - `ble_status`, `ble_link` — require the `_ble` feature
+/// - `passkey` — requires the `passkey_entry` feature
+/// - `central_connected`, `peripherals_connected`, `peripherals_build_mismatch` — require the `split` feature
+/// - `peripheral_batteries`, `central_battery` — require both `split` and `_ble` features
 ///
 /// Third-party renderers that access these fields must enable the
 /// corresponding features in their `Cargo.toml` dependency on `rmk`,
@@ -110,20 +113,34 @@ pub struct RenderContext {
     pub num_lock: bool,
     /// Current battery status.
     pub battery: BatteryStatusEvent,
+    /// Whether the battery level is at or below the low battery threshold.
+    pub battery_low: bool,
     /// Whether the keyboard is sleeping.
     pub sleeping: bool,
     /// Current BLE connection status (profile + state).
     #[cfg(feature = "_ble")]
     pub ble_status: BleStatus,
+    /// Link statistics of the BLE connection to the host.
+    #[cfg(feature = "_ble")]
+    pub ble_link: BleLinkStatsEvent,
+    /// Passkey to show while pairing with a BLE host, renderers should draw it instead of the usual content.
+    #[cfg(feature = "passkey_entry")]
+    pub passkey: PasskeyDisplayEvent,
     /// Whether the central is connected (only meaningful on peripherals).
     #[cfg(feature = "split")]
     pub central_connected: bool,
     /// Per-peripheral connection state, indexed by peripheral id.
     #[cfg(feature = "split")]
     pub peripherals_connected: [bool; crate::SPLIT_PERIPHERALS_NUM],
+    /// Whether each peripheral runs a different firmware build than the central, indexed by peripheral id.
+    #[cfg(feature = "split")]
+    pub peripherals_build_mismatch: [bool; crate::SPLIT_PERIPHERALS_NUM],
     /// Per-peripheral battery status, indexed by peripheral id.
     #[cfg(all(feature = "split", feature = "_ble"))]
     pub peripheral_batteries: [BatteryStatusEvent; crate::SPLIT_PERIPHERALS_NUM],
+    /// Battery status of the central (only meaningful on peripherals).
+    #[cfg(all(feature = "split", feature = "_ble"))]
+    pub central_battery: BatteryStatusEvent,
     /// Currently active modifier keys (Shift, Ctrl, Alt, GUI).
     pub modifiers: ModifierCombination,
     /// Whether a key is currently held down.
@@ -143,16 +160,25 @@ impl Default for RenderContext {
             caps_lock: false,
             num_lock: false,
             battery: BatteryStatusEvent(rmk_types::battery::BatteryStatus::Unavailable),
+            battery_low: false,
             sleeping: false,
             #[cfg(feature = "_ble")]
             ble_status: BleStatus::default(),
+            #[cfg(feature = "_ble")]
+            ble_link: BleLinkStatsEvent::default(),
+            #[cfg(feature = "passkey_entry")]
+            passkey: PasskeyDisplayEvent::hidden(),
             #[cfg(feature = "split")]
             central_connected: false,
             #[cfg(feature = "split")]
             peripherals_connected: [false; crate::SPLIT_PERIPHERALS_NUM],
+            #[cfg(feature = "split")]
+            peripherals_build_mismatch: [false; crate::SPLIT_PERIPHERALS_NUM],
             #[cfg(all(feature = "split", feature = "_ble"))]
             peripheral_batteries: [BatteryStatusEvent(rmk_types::battery::BatteryStatus::Unavailable);
                 crate::SPLIT_PERIPHERALS_NUM],
+            #[cfg(all(feature = "split", feature = "_ble"))]
+            central_battery: BatteryStatusEvent(rmk_types::battery::BatteryStatus::Unavailable),
             modifiers: ModifierCombination::new(),
             key_pressed: false,
             key_press_latch: false,
@@ -213,7 +239,7 @@ pub trait DisplayRenderer<C: PixelColor> {
 /// Processor that renders keyboard state on a display.
 ///
 /// Subscribes to [`LayerChangeEvent`], [`WpmUpdateEvent`], [`LedIndicatorEvent`],
-/// and [`BatteryStatusEvent`], redrawing the screen whenever any of these change.
+/// [`BatteryStatusEvent`] and [`LowBatteryEvent`], redrawing the screen whenever any of these change.
 ///
 /// The rendering is delegated to a [`DisplayRenderer`].  Use [`new`](Self::new)
 /// for the built-in [`LogoRenderer`], or [`with_renderer`](Self::with_renderer)
@@ -223,10 +249,11 @@ pub trait DisplayRenderer<C: PixelColor> {
 ///
 /// - `D` — display driver, must implement [`DisplayDriver`].
 /// - `R` — the renderer, defaults to [`LogoRenderer`].
-#[processor(subscribe = [KeyboardEvent, LayerChangeEvent, WpmUpdateEvent, LedIndicatorEvent, ModifierEvent, BatteryStatusEvent, SleepStateEvent])]
-#[cfg_attr(feature = "_ble", processor(subscribe = [BleStatusChangeEvent]))]
+#[processor(subscribe = [KeyboardEvent, LayerChangeEvent, WpmUpdateEvent, LedIndicatorEvent, ModifierEvent, BatteryStatusEvent, LowBatteryEvent, SleepStateEvent])]
+#[cfg_attr(feature = "_ble", processor(subscribe = [BleStatusChangeEvent, BleLinkStatsEvent]))]
+#[cfg_attr(feature = "passkey_entry", processor(subscribe = [PasskeyDisplayEvent]))]
 #[cfg_attr(feature = "split", processor(subscribe = [PeripheralConnectedEvent, CentralConnectedEvent]))]
-#[cfg_attr(all(feature = "split", feature = "_ble"), processor(subscribe = [PeripheralBatteryEvent]))]
+#[cfg_attr(all(feature = "split", feature = "_ble"), processor(subscribe = [PeripheralBatteryEvent, CentralBatteryEvent]))]
 #[::rmk::macros::runnable_generated]
 pub struct DisplayProcessor<D, R = LogoRenderer>
 where
@@ -362,6 +389,11 @@ where
         self.render().await;
     }
 
+    async fn on_low_battery_event(&mut self, event: LowBatteryEvent) {
+        self.ctx.battery_low = event.low;
+        self.render().await;
+    }
+
     async fn on_keyboard_event(&mut self, event: KeyboardEvent) {
         self.ctx.key_pressed = event.pressed;
         if event.pressed {
@@ -385,11 +417,26 @@ where
         self.render().await;
     }
 
+    #[cfg(feature = "_ble")]
+    async fn on_ble_link_stats_event(&mut self, event: BleLinkStatsEvent) {
+        self.ctx.ble_link = event;
+        self.render().await;
+    }
+
+    #[cfg(feature = "passkey_entry")]
+    async fn on_passkey_display_event(&mut self, event: PasskeyDisplayEvent) {
+        self.ctx.passkey = event;
+        self.render().await;
+    }
+
     #[cfg(feature = "split")]
     async fn on_peripheral_connected_event(&mut self, event: PeripheralConnectedEvent) {
         if let Some(slot) = self.ctx.peripherals_connected.get_mut(event.id) {
             *slot = event.connected;
         }
+        if let Some(slot) = self.ctx.peripherals_build_mismatch.get_mut(event.id) {
+            *slot = event.build_mismatch;
+        }
         self.render().await;
     }
 
@@ -406,6 +453,12 @@ where
         }
         self.render().await;
     }
+
+    #[cfg(all(feature = "split", feature = "_ble"))]
+    async fn on_central_battery_event(&mut self, event: CentralBatteryEvent) {
+        self.ctx.central_battery = event.state;
+        self.render().await;
+    }
 }
 
 impl<D, R> Runnable for DisplayProcessor<D, R>
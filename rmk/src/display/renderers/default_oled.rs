@@ -3,6 +3,8 @@ use core::fmt::Write as _;
 use embedded_graphics::image::{Image, ImageRaw};
 use embedded_graphics::mono_font::MonoTextStyle;
 use embedded_graphics::mono_font::ascii::FONT_5X8;
+#[cfg(feature = "passkey_entry")]
+use embedded_graphics::mono_font::ascii::FONT_10X20;
 #[cfg(feature = "_ble")]
 use embedded_graphics::mono_font::iso_8859_1::FONT_6X9;
 use embedded_graphics::pixelcolor::BinaryColor;
@@ -11,6 +13,8 @@ use embedded_graphics::prelude::*;
 use embedded_graphics::primitives::Rectangle;
 use embedded_graphics::primitives::{Circle, Line, PrimitiveStyle};
 use embedded_graphics::text::Text;
+#[cfg(feature = "passkey_entry")]
+use embedded_graphics::text::{Alignment, Baseline, TextStyle, TextStyleBuilder};
 #[cfg(feature = "_ble")]
 use rmk_types::battery::{BatteryStatus, ChargeState};
 
@@ -101,10 +105,18 @@ pub struct OledRenderer;
 impl DisplayRenderer<BinaryColor> for OledRenderer {
     fn render<D: DrawTarget<Color = BinaryColor>>(&mut self, ctx: &RenderContext, display: &mut D) {
         display.clear(BinaryColor::Off).ok();
+        let layout = Layout::from_display(display);
+
+        // The pairing prompt replaces everything else, even while sleeping
+        #[cfg(feature = "passkey_entry")]
+        if let Some(passkey) = ctx.passkey.passkey {
+            draw_passkey_prompt(passkey, ctx.passkey.confirm, display, &layout);
+            return;
+        }
+
         if ctx.sleeping {
             return;
         }
-        let layout = Layout::from_display(display);
 
         draw_info_zone(ctx, display, &layout);
         draw_modifier_zones(ctx, display, &layout);
@@ -205,7 +217,7 @@ fn draw_status_zone<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, dis
 
     // Battery (right side, only for BLE)
     #[cfg(feature = "_ble")]
-    draw_battery_icon(ctx.battery, display, layout);
+    draw_battery_icon(ctx.battery, ctx.battery_low, display, layout);
 }
 
 fn draw_lock_dots<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, display: &mut D, x: i32, y: i32) {
@@ -226,6 +238,7 @@ fn draw_lock_dots<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, displ
 #[cfg(feature = "_ble")]
 fn draw_battery_icon<D: DrawTarget<Color = BinaryColor>>(
     battery: BatteryStatusEvent,
+    low: bool,
     display: &mut D,
     layout: &Layout,
 ) {
@@ -295,6 +308,7 @@ fn draw_battery_icon<D: DrawTarget<Color = BinaryColor>>(
                 charge_state: ChargeState::Charging,
                 level: None,
             } => write!(label, "CHG").ok(),
+            BatteryStatus::Available { level: Some(pct), .. } if low => write!(label, "{}%!", pct).ok(),
             BatteryStatus::Available { level: Some(pct), .. } => write!(label, "{}%", pct).ok(),
             BatteryStatus::Available { level: None, .. } => write!(label, "FULL").ok(),
             BatteryStatus::Unavailable => write!(label, "N/A").ok(),
@@ -315,20 +329,31 @@ fn draw_connection_indicator<D: DrawTarget<Color = BinaryColor>>(
     _layout: &Layout,
 ) {
     let _connected = is_connected(ctx);
+    // A peripheral which runs a different firmware build is shown instead of the connection state
+    #[cfg(feature = "split")]
+    let _mismatch = ctx.peripherals_build_mismatch.iter().any(|&mismatch| mismatch);
+    #[cfg(not(feature = "split"))]
+    let _mismatch = false;
 
     #[cfg(feature = "_ble")]
     {
-        draw_ble_indicator(_connected, _display, _layout);
+        draw_ble_indicator(_connected, _mismatch, ctx.ble_link.rssi, _display, _layout);
     }
 
     #[cfg(all(not(feature = "_ble"), feature = "split"))]
     {
-        draw_status_mark(_connected, _display, _layout.w - 7, 4);
+        draw_status_mark(_connected, _mismatch, _display, _layout.w - 7, 4);
     }
 }
 
 #[cfg(feature = "_ble")]
-fn draw_ble_indicator<D: DrawTarget<Color = BinaryColor>>(connected: bool, display: &mut D, layout: &Layout) {
+fn draw_ble_indicator<D: DrawTarget<Color = BinaryColor>>(
+    connected: bool,
+    mismatch: bool,
+    rssi: Option<i8>,
+    display: &mut D,
+    layout: &Layout,
+) {
     const BT_W: i32 = icons::BT_ICON_W as i32;
     const BT_H: i32 = icons::BT_ICON_H as i32;
     const STATUS_SZ: i32 = 5;
@@ -347,12 +372,51 @@ fn draw_ble_indicator<D: DrawTarget<Color = BinaryColor>>(connected: bool, displ
         (bt_x - STATUS_SZ - GAP, bt_y + (BT_H - STATUS_SZ) / 2)
     };
 
-    draw_status_mark(connected, display, status_x, status_y);
+    draw_status_mark(connected, mismatch, display, status_x, status_y);
+
+    // Signal strength, left of the icons. Portrait displays have no room for it
+    if let (true, Some(rssi), Orientation::Landscape) = (connected, rssi, &layout.orientation) {
+        let bars_x = bt_x.min(status_x) - SIGNAL_BARS_W - GAP;
+        draw_signal_bars(rssi, display, bars_x, bt_y + BT_H - 1);
+    }
 }
 
-/// Draw a small 5x5 checkmark (connected) or cross (disconnected).
-fn draw_status_mark<D: DrawTarget<Color = BinaryColor>>(connected: bool, display: &mut D, x: i32, y: i32) {
-    if connected {
+#[cfg(feature = "_ble")]
+const SIGNAL_BARS_W: i32 = 5;
+
+/// Draw 3 bars of the signal strength, `bottom` is the y of their bottom row
+#[cfg(feature = "_ble")]
+fn draw_signal_bars<D: DrawTarget<Color = BinaryColor>>(rssi: i8, display: &mut D, x: i32, bottom: i32) {
+    let bars = match rssi {
+        -60.. => 3,
+        -75..=-61 => 2,
+        _ => 1,
+    };
+    for i in 0..bars {
+        let bar_x = x + i * 2;
+        let height = 2 + i * 2;
+        Line::new(Point::new(bar_x, bottom), Point::new(bar_x, bottom - height + 1))
+            .into_styled(STROKE)
+            .draw(display)
+            .ok();
+    }
+}
+
+/// Draw a small 5x5 checkmark (connected), cross (disconnected) or exclamation mark (build mismatch).
+fn draw_status_mark<D: DrawTarget<Color = BinaryColor>>(
+    connected: bool,
+    mismatch: bool,
+    display: &mut D,
+    x: i32,
+    y: i32,
+) {
+    if mismatch {
+        Line::new(Point::new(x + 2, y), Point::new(x + 2, y + 2))
+            .into_styled(STROKE)
+            .draw(display)
+            .ok();
+        Pixel(Point::new(x + 2, y + 4), BinaryColor::On).draw(display).ok();
+    } else if connected {
         Line::new(Point::new(x, y + 2), Point::new(x + 2, y + 4))
             .into_styled(STROKE)
             .draw(display)
@@ -373,6 +437,61 @@ fn draw_status_mark<D: DrawTarget<Color = BinaryColor>>(connected: bool, display
     }
 }
 
+#[cfg(feature = "passkey_entry")]
+const CENTERED: TextStyle = TextStyleBuilder::new()
+    .alignment(Alignment::Center)
+    .baseline(Baseline::Middle)
+    .build();
+
+/// Draw the passkey of a BLE pairing, with the keys to accept or reject it in a numeric comparison.
+#[cfg(feature = "passkey_entry")]
+fn draw_passkey_prompt<D: DrawTarget<Color = BinaryColor>>(
+    passkey: u32,
+    confirm: bool,
+    display: &mut D,
+    layout: &Layout,
+) {
+    let digits_style = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    let mut digits: heapless::String<8> = heapless::String::new();
+    write!(digits, "{:06}", passkey).ok();
+    let cx = layout.w / 2;
+
+    match layout.orientation {
+        Orientation::Landscape => {
+            let title = if confirm { "Match? Ent:Y Esc:N" } else { "Passkey" };
+            Text::with_text_style(title, Point::new(cx, 4), FONT_STYLE, CENTERED)
+                .draw(display)
+                .ok();
+            Text::with_text_style(&digits, Point::new(cx, layout.h / 2 + 3), digits_style, CENTERED)
+                .draw(display)
+                .ok();
+        }
+        Orientation::Portrait => {
+            // 6 digits don't fit in the width, draw them in 2 rows of 3
+            let title = if confirm { "Match" } else { "Key" };
+            Text::with_text_style(title, Point::new(cx, 4), FONT_STYLE, CENTERED)
+                .draw(display)
+                .ok();
+            let (first, second) = digits.split_at(3);
+            Text::with_text_style(first, Point::new(cx, layout.zone_center_y(1)), digits_style, CENTERED)
+                .draw(display)
+                .ok();
+            Text::with_text_style(second, Point::new(cx, layout.zone_center_y(2)), digits_style, CENTERED)
+                .draw(display)
+                .ok();
+            if confirm {
+                let y = layout.zone_top(3);
+                Text::with_text_style("Ent:Y", Point::new(cx, y + 8), FONT_STYLE, CENTERED)
+                    .draw(display)
+                    .ok();
+                Text::with_text_style("Esc:N", Point::new(cx, y + 18), FONT_STYLE, CENTERED)
+                    .draw(display)
+                    .ok();
+            }
+        }
+    }
+}
+
 /// Returns true when the keyboard considers itself connected.
 fn is_connected(_ctx: &RenderContext) -> bool {
     // Split + BLE:
@@ -1,9 +1,11 @@
 use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
 
-#[cfg(feature = "_nrf_ble")]
+#[cfg(feature = "nrf")]
 pub mod nrf;
 #[cfg(feature = "rp2040")]
 pub mod rp;
+#[cfg(feature = "stm32")]
+pub mod stm32;
 
 /// Pin that can be switched between input and output.
 pub trait FlexPin: ErrorType + InputPin + OutputPin {
@@ -0,0 +1,98 @@
+use core::convert::Infallible;
+
+use embassy_stm32::Peri;
+use embassy_stm32::exti::{Channel, ExtiInput, InterruptHandler};
+use embassy_stm32::gpio::{Flex, Level, Pin, Pull, Speed};
+use embassy_stm32::interrupt::typelevel::Binding;
+use embassy_stm32::mode::Async;
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+use embedded_hal_async::digital::Wait;
+
+use crate::driver::flex_pin::FlexPin;
+
+/// Flex pin of STM32 which can wait for edges.
+///
+/// The `Flex` of embassy-stm32 can't wait, so the pin is also held by an `ExtiInput` on its EXTI line,
+/// which is only used for waiting.
+pub struct ExtiFlex<'d> {
+    flex: Flex<'d>,
+    exti: ExtiInput<'d, Async>,
+}
+
+impl<'d> ExtiFlex<'d> {
+    pub fn new<C: Channel>(
+        pin: Peri<'d, impl Pin>,
+        ch: Peri<'d, C>,
+        irq: impl Binding<C::IRQ, InterruptHandler<C::IRQ>>,
+    ) -> Self {
+        let mut flex = Flex::new(pin);
+        flex.set_as_input(Pull::Down);
+        // Safety: both handles drive the same pin, the `ExtiInput` never changes the mode of the pin
+        let exti = ExtiInput::from_flex(unsafe { flex.clone_unchecked() }, ch, irq);
+        Self { flex, exti }
+    }
+}
+
+impl ErrorType for ExtiFlex<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for ExtiFlex<'_> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.flex.is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.flex.is_low())
+    }
+}
+
+impl OutputPin for ExtiFlex<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.flex.set_low();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.flex.set_high();
+        Ok(())
+    }
+}
+
+impl Wait for ExtiFlex<'_> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.exti.wait_for_high().await;
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.exti.wait_for_low().await;
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.exti.wait_for_rising_edge().await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.exti.wait_for_falling_edge().await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.exti.wait_for_any_edge().await;
+        Ok(())
+    }
+}
+
+impl FlexPin for ExtiFlex<'_> {
+    fn set_as_input(&mut self) {
+        self.flex.set_as_input(Pull::Down);
+    }
+
+    fn set_as_output(&mut self) {
+        self.flex.set_level(Level::Low);
+        self.flex.set_as_output(Speed::VeryHigh);
+    }
+}
@@ -4,6 +4,7 @@
 //! - Battery ADC reading events
 //! - Charging state events
 //! - Battery status events (computed from ADC and charging state)
+//! - Low battery events
 
 use postcard::experimental::max_size::MaxSize;
 use rmk_macro::event;
@@ -39,3 +40,18 @@ pub struct ChargingStateEvent {
 pub struct BatteryStatusEvent(pub BatteryStatus);
 
 impl_payload_wrapper!(BatteryStatusEvent, BatteryStatus);
+
+/// Low battery state changed event.
+///
+/// Published when the battery level drops to the low battery threshold,
+/// and again when it recovers or charging starts.
+#[event(
+    channel_size = crate::LOW_BATTERY_EVENT_CHANNEL_SIZE,
+    pubs = crate::LOW_BATTERY_EVENT_PUB_SIZE,
+    subs = crate::LOW_BATTERY_EVENT_SUB_SIZE
+)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LowBatteryEvent {
+    pub low: bool,
+}
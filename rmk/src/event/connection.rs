@@ -3,6 +3,8 @@
 //! This module contains all connection-related events:
 //! - Connection type change events (USB/BLE)
 //! - BLE status change events
+//! - BLE link statistics events
+//! - BLE passkey display events
 
 use rmk_macro::event;
 #[cfg(feature = "_ble")]
@@ -40,3 +42,50 @@ pub struct BleStatusChangeEvent(pub BleStatus);
 
 #[cfg(feature = "_ble")]
 impl_payload_wrapper!(BleStatusChangeEvent, BleStatus);
+
+/// Link statistics of the BLE connection to the host, published every few seconds and when the connection parameters change
+#[cfg(feature = "_ble")]
+#[event(channel_size = crate::BLE_LINK_STATS_EVENT_CHANNEL_SIZE, pubs = crate::BLE_LINK_STATS_EVENT_PUB_SIZE, subs = crate::BLE_LINK_STATS_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BleLinkStatsEvent {
+    /// Received signal strength in dBm, `None` until it's read from the controller
+    pub rssi: Option<i8>,
+    /// Connection interval chosen by the host in microseconds, `None` until the host sets the connection parameters
+    pub conn_interval_us: Option<u32>,
+    /// Number of connection events the keyboard can skip
+    pub conn_latency: u16,
+    /// Whether the low power connection parameters are requested
+    pub low_power: bool,
+    /// Percentage of the recent reports which missed their connection event, estimated from the send delay:
+    /// a report which waited longer than a connection interval to be sent counts as missed
+    pub missed_conn_events: u8,
+    /// Most reports waiting in the report channel since the last event
+    pub queued_reports: u8,
+}
+
+/// Passkey to show during BLE pairing, published when the passkey appears and again when it should be hidden
+#[cfg(feature = "passkey_entry")]
+#[event(channel_size = crate::PASSKEY_DISPLAY_EVENT_CHANNEL_SIZE, pubs = crate::PASSKEY_DISPLAY_EVENT_PUB_SIZE, subs = crate::PASSKEY_DISPLAY_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PasskeyDisplayEvent {
+    /// Passkey to show, `None` when the prompt should be hidden
+    pub passkey: Option<u32>,
+    /// Whether the user must confirm that the passkey matches the one shown by the host (numeric comparison)
+    pub confirm: bool,
+}
+
+#[cfg(feature = "passkey_entry")]
+impl PasskeyDisplayEvent {
+    pub fn new(passkey: u32, confirm: bool) -> Self {
+        Self {
+            passkey: Some(passkey),
+            confirm,
+        }
+    }
+
+    pub fn hidden() -> Self {
+        Self::default()
+    }
+}
@@ -144,11 +144,31 @@ pub enum Axis {
 }
 
 /// Set the CPI (Resolution) of the pointing device
-/// TODO: Make the channel size configurable
-#[event(channel_size = 8, pubs = 2, subs = 2)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// `device_id` can be `ALL_POINTING_DEVICES` to target every pointing device.
+#[event(
+    channel_size = crate::POINTING_SET_CPI_EVENT_CHANNEL_SIZE,
+    pubs = crate::POINTING_SET_CPI_EVENT_PUB_SIZE,
+    subs = crate::POINTING_SET_CPI_EVENT_SUB_SIZE
+)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, MaxSize, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PointingSetCpiEvent {
     pub device_id: u8,
     pub cpi: u16,
 }
+
+/// Mouse buttons pressed by a pointing device itself, e.g. tap to click of a trackpad
+///
+/// `buttons` has the same bit layout as the buttons of the HID mouse report.
+#[event(
+    channel_size = crate::POINTING_BUTTON_EVENT_CHANNEL_SIZE,
+    pubs = crate::POINTING_BUTTON_EVENT_PUB_SIZE,
+    subs = crate::POINTING_BUTTON_EVENT_SUB_SIZE
+)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, MaxSize, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PointingButtonEvent {
+    pub device_id: u8,
+    pub buttons: u8,
+}
@@ -11,7 +11,7 @@
 //!
 //! - `input`: Input events (keyboard, modifier, pointing device)
 //! - `state`: Keyboard state events (layer, WPM, LED indicator, sleep)
-//! - `battery`: Battery events (ADC, charging, battery status)
+//! - `battery`: Battery events (ADC, charging, battery status, low battery)
 //! - `connection`: Connection events (USB/BLE, BLE status)
 //! - `split`: Split keyboard events (peripheral/central connection)
 
@@ -53,19 +53,23 @@ mod split;
 mod state;
 
 pub use action::ActionEvent;
-pub use battery::{BatteryAdcEvent, BatteryStatusEvent, ChargingStateEvent};
+pub use battery::{BatteryAdcEvent, BatteryStatusEvent, ChargingStateEvent, LowBatteryEvent};
+#[cfg(feature = "passkey_entry")]
+pub use connection::PasskeyDisplayEvent;
 #[cfg(feature = "_ble")]
-pub use connection::BleStatusChangeEvent;
+pub use connection::{BleLinkStatsEvent, BleStatusChangeEvent};
 pub use connection::{ConnectionChangeEvent, ConnectionType};
 pub use input::{
-    Axis, AxisEvent, AxisValType, KeyPos, KeyboardEvent, KeyboardEventPos, ModifierEvent, PointingEvent,
-    PointingSetCpiEvent, RotaryEncoderPos,
+    Axis, AxisEvent, AxisValType, KeyPos, KeyboardEvent, KeyboardEventPos, ModifierEvent, PointingButtonEvent,
+    PointingEvent, PointingSetCpiEvent, RotaryEncoderPos,
 };
-#[cfg(feature = "split")]
-pub use split::{CentralConnectedEvent, PeripheralConnectedEvent};
+#[cfg(all(feature = "split", feature = "split_reliable"))]
+pub use split::SplitLinkQualityEvent;
 #[cfg(all(feature = "split", feature = "_ble"))]
-pub use split::{ClearPeerEvent, PeripheralBatteryEvent};
-pub use state::{LayerChangeEvent, LedIndicatorEvent, SleepStateEvent, WpmUpdateEvent};
+pub use split::{CentralBatteryEvent, ClearPeerEvent, PeripheralBatteryEvent, PeripheralLinkStatsEvent};
+#[cfg(feature = "split")]
+pub use split::{CentralConnectedEvent, PeripheralConnectedEvent, SplitCustomEvent, SplitMismatchEvent};
+pub use state::{DefaultLayerChangeEvent, LayerChangeEvent, LedIndicatorEvent, SleepStateEvent, WpmUpdateEvent};
 
 /// Trait for event publishers
 pub trait EventPublisher {
@@ -3,14 +3,20 @@
 use rmk_macro::event;
 
 use super::battery::BatteryStatusEvent;
+use crate::split::SplitMismatch;
+use crate::split::custom::SPLIT_CUSTOM_MESSAGE_SIZE;
 
 /// Peripheral connected state changed event
+///
+/// `connected` is set after the handshake and the firmware builds of both halves are compared.
 #[event(channel_size = crate::PERIPHERAL_CONNECTED_EVENT_CHANNEL_SIZE, pubs = crate::PERIPHERAL_CONNECTED_EVENT_PUB_SIZE, subs = crate::PERIPHERAL_CONNECTED_EVENT_SUB_SIZE)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PeripheralConnectedEvent {
     pub id: usize,
     pub connected: bool,
+    /// The peripheral runs a different firmware build than the central
+    pub build_mismatch: bool,
 }
 
 /// Connected to central state changed event
@@ -30,6 +36,71 @@ pub struct PeripheralBatteryEvent {
     pub state: BatteryStatusEvent,
 }
 
+/// Battery status of the central, published on peripherals
+#[cfg(feature = "_ble")]
+#[event(channel_size = crate::CENTRAL_BATTERY_EVENT_CHANNEL_SIZE, pubs = crate::CENTRAL_BATTERY_EVENT_PUB_SIZE, subs = crate::CENTRAL_BATTERY_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CentralBatteryEvent {
+    pub state: BatteryStatusEvent,
+}
+
+/// Incompatibility between the central and a peripheral, found in the split handshake
+///
+/// `id` is the peripheral id on the central, and is always 0 on a peripheral.
+#[event(channel_size = crate::SPLIT_MISMATCH_EVENT_CHANNEL_SIZE, pubs = crate::SPLIT_MISMATCH_EVENT_PUB_SIZE, subs = crate::SPLIT_MISMATCH_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitMismatchEvent {
+    pub id: usize,
+    pub mismatch: SplitMismatch,
+}
+
+/// Custom message received from the other half, see [`send_custom_message`](crate::split::custom::send_custom_message)
+///
+/// `id` is the id of the sender peripheral on the central, and is always 0 on a peripheral.
+#[event(channel_size = crate::SPLIT_CUSTOM_EVENT_CHANNEL_SIZE, pubs = crate::SPLIT_CUSTOM_EVENT_PUB_SIZE, subs = crate::SPLIT_CUSTOM_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitCustomEvent {
+    pub id: usize,
+    pub data: [u8; SPLIT_CUSTOM_MESSAGE_SIZE],
+}
+
+/// Link quality of a serial split connection, published when one of the counters changes
+///
+/// `id` is the peripheral id on the central, and is always 0 on a peripheral.
+#[cfg(feature = "split_reliable")]
+#[event(channel_size = crate::SPLIT_LINK_QUALITY_EVENT_CHANNEL_SIZE, pubs = crate::SPLIT_LINK_QUALITY_EVENT_PUB_SIZE, subs = crate::SPLIT_LINK_QUALITY_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitLinkQualityEvent {
+    pub id: usize,
+    /// Received frames which are broken
+    pub corrupted: u32,
+    /// Frames which never arrived, found by the gaps in sequence numbers
+    pub lost: u32,
+    /// Frames sent again because their ACK didn't arrive in time
+    pub retransmitted: u32,
+    /// Frames given up after all retransmits
+    pub failed: u32,
+}
+
+/// Link statistics of a BLE split peripheral, measured on the central and published every few seconds
+#[cfg(feature = "_ble")]
+#[event(channel_size = crate::PERIPHERAL_LINK_STATS_EVENT_CHANNEL_SIZE, pubs = crate::PERIPHERAL_LINK_STATS_EVENT_PUB_SIZE, subs = crate::PERIPHERAL_LINK_STATS_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PeripheralLinkStatsEvent {
+    pub id: usize,
+    /// Received signal strength in dBm, `None` until it's read from the controller
+    pub rssi: Option<i8>,
+    /// Estimated latency of a key event in ms, half of the round trip time of the last answered probe
+    pub latency_ms: Option<u16>,
+    /// Percentage of the recent probes which got no answer
+    pub packet_loss: u8,
+}
+
 /// Clear BLE peer information event
 #[cfg(feature = "_ble")]
 #[event(channel_size = crate::CLEAR_PEER_EVENT_CHANNEL_SIZE, pubs = crate::CLEAR_PEER_EVENT_PUB_SIZE, subs = crate::CLEAR_PEER_EVENT_SUB_SIZE)]
@@ -17,6 +17,20 @@ impl LayerChangeEvent {
 
 impl_payload_wrapper!(LayerChangeEvent, u8);
 
+/// Default layer changed event
+#[event(channel_size = crate::DEFAULT_LAYER_CHANGE_EVENT_CHANNEL_SIZE, pubs = crate::DEFAULT_LAYER_CHANGE_EVENT_PUB_SIZE, subs = crate::DEFAULT_LAYER_CHANGE_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DefaultLayerChangeEvent(pub u8);
+
+impl DefaultLayerChangeEvent {
+    pub fn new(layer: u8) -> Self {
+        Self(layer)
+    }
+}
+
+impl_payload_wrapper!(DefaultLayerChangeEvent, u8);
+
 /// WPM updated event
 #[event(channel_size = crate::WPM_UPDATE_EVENT_CHANNEL_SIZE, pubs = crate::WPM_UPDATE_EVENT_PUB_SIZE, subs = crate::WPM_UPDATE_EVENT_SUB_SIZE)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -152,6 +152,130 @@ mod steno_tests {
     }
 }
 
+/// Gamepad HID report, sent through its own interface.
+///
+/// The report has 32 buttons, a hat switch and 6 axes (X, Y, Z, Rx, Ry, Rz).
+/// Wire format is 11 bytes without report id: `[buttons (u32, LE), hat, x, y, z, rx, ry, rz]`.
+/// The hat switch uses the low 4 bits, `0` is up and each step is 45° clockwise,
+/// any value out of `0..=7` means centered.
+#[cfg(feature = "gamepad")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GamepadReport {
+    pub buttons: u32,
+    pub hat: u8,
+    pub axes: [i8; 6],
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadReport {
+    /// Centered value of the hat switch
+    pub const HAT_CENTERED: u8 = 0x08;
+
+    /// Length of the serialized report
+    pub const LEN: usize = 11;
+}
+
+// `gen_hid_descriptor` can't generate hat switches with the null state, so the
+// descriptor is written by hand.
+#[cfg(feature = "gamepad")]
+const GAMEPAD_REPORT_DESCRIPTOR: [u8; 74] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x05, // Usage (Game Pad)
+    0xa1, 0x01, // Collection (Application)
+    0x05, 0x09, //   Usage Page (Button)
+    0x19, 0x01, //   Usage Minimum (Button 1)
+    0x29, 0x20, //   Usage Maximum (Button 32)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x20, //   Report Count (32)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x05, 0x01, //   Usage Page (Generic Desktop)
+    0x09, 0x39, //   Usage (Hat Switch)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x07, //   Logical Maximum (7)
+    0x35, 0x00, //   Physical Minimum (0)
+    0x46, 0x3b, 0x01, //   Physical Maximum (315)
+    0x65, 0x14, //   Unit (Degrees)
+    0x75, 0x04, //   Report Size (4)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x42, //   Input (Data, Variable, Absolute, Null State)
+    0x65, 0x00, //   Unit (None)
+    0x45, 0x00, //   Physical Maximum (0)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x03, //   Input (Constant), 4 bits padding
+    0x09, 0x30, //   Usage (X)
+    0x09, 0x31, //   Usage (Y)
+    0x09, 0x32, //   Usage (Z)
+    0x09, 0x33, //   Usage (Rx)
+    0x09, 0x34, //   Usage (Ry)
+    0x09, 0x35, //   Usage (Rz)
+    0x15, 0x81, //   Logical Minimum (-127)
+    0x25, 0x7f, //   Logical Maximum (127)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x06, //   Report Count (6)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xc0, // End Collection
+];
+
+#[cfg(feature = "gamepad")]
+impl usbd_hid::descriptor::SerializedDescriptor for GamepadReport {
+    fn desc() -> &'static [u8] {
+        &GAMEPAD_REPORT_DESCRIPTOR
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl AsInputReport for GamepadReport {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<usize, usbd_hid::descriptor::BufferOverflow> {
+        if buffer.len() < Self::LEN {
+            return Err(usbd_hid::descriptor::BufferOverflow);
+        }
+        buffer[0..4].copy_from_slice(&self.buttons.to_le_bytes());
+        buffer[4] = self.hat & 0x0f;
+        for (b, a) in buffer[5..Self::LEN].iter_mut().zip(self.axes.iter()) {
+            *b = *a as u8;
+        }
+        Ok(Self::LEN)
+    }
+}
+
+#[cfg(all(test, feature = "gamepad"))]
+mod gamepad_tests {
+    use usbd_hid::descriptor::{AsInputReport, SerializedDescriptor};
+
+    use super::GamepadReport;
+
+    #[test]
+    fn descriptor_is_a_gamepad() {
+        let desc = GamepadReport::desc();
+        fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+            haystack.windows(needle.len()).any(|w| w == needle)
+        }
+        assert_eq!(desc.len(), 74);
+        assert!(contains(desc, &[0x09, 0x05, 0xa1, 0x01]), "missing Game Pad collection");
+        assert!(contains(desc, &[0x29, 0x20]), "missing UsageMax Button 32");
+        assert!(contains(desc, &[0x09, 0x39]), "missing Hat Switch");
+        assert!(contains(desc, &[0x81, 0x42]), "missing null state of the hat switch");
+        assert!(contains(desc, &[0x95, 0x06]), "missing 6 axes");
+        assert_eq!(desc.last(), Some(&0xc0));
+    }
+
+    #[test]
+    fn serialize_report() {
+        let report = GamepadReport {
+            buttons: 0x8000_0001,
+            hat: GamepadReport::HAT_CENTERED,
+            axes: [1, -1, 127, -127, 0, 0],
+        };
+        let mut buf = [0u8; 11];
+        assert_eq!(report.serialize(&mut buf), Ok(11));
+        assert_eq!(buf, [0x01, 0x00, 0x00, 0x80, 0x08, 0x01, 0xff, 0x7f, 0x81, 0x00, 0x00]);
+        assert!(report.serialize(&mut [0u8; 10]).is_err());
+    }
+}
+
 /// A composite hid report which contains mouse, consumer, system reports.
 /// Report id is used to distinguish from them.
 #[gen_hid_descriptor(
@@ -219,6 +343,9 @@ pub enum Report {
     /// Plover HID stenography chord report
     #[cfg(feature = "steno")]
     StenoReport(StenoReport),
+    /// Gamepad report
+    #[cfg(feature = "gamepad")]
+    GamepadReport(GamepadReport),
 }
 
 impl AsInputReport for Report {
@@ -230,6 +357,8 @@ impl AsInputReport for Report {
             Report::SystemControlReport(r) => r.serialize(buffer),
             #[cfg(feature = "steno")]
             Report::StenoReport(r) => r.serialize(buffer),
+            #[cfg(feature = "gamepad")]
+            Report::GamepadReport(r) => r.serialize(buffer),
         }
     }
 }
@@ -3,15 +3,88 @@ pub mod nrf;
 
 #[cfg(feature = "_nrf_ble")]
 pub use nrf::*;
+#[cfg(any(feature = "rp2040", feature = "pico_w_ble"))]
+pub mod rp;
+#[cfg(any(feature = "rp2040", feature = "pico_w_ble"))]
+pub use rp::*;
+
+#[cfg(feature = "_ble")]
+use embassy_time::{Duration, Timer};
+#[cfg(feature = "_ble")]
+use rmk_macro::input_device;
+
+#[cfg(feature = "_ble")]
+use crate::event::BatteryAdcEvent;
 
 pub enum AnalogEventType {
     Joystick(u8),
     Battery,
 }
 
+/// Reads all channels of an ADC at once.
+///
+/// Implemented for the ADC peripherals of the supported chips, used by analog inputs such as
+/// [`AnalogMatrix`](crate::matrix::analog_matrix::AnalogMatrix).
+pub trait AdcReader<const N: usize> {
+    /// Sample every channel, the result is the raw value of each channel
+    async fn read(&mut self) -> [u16; N];
+}
+
 #[derive(PartialEq)]
 pub enum AdcState {
     Active,
     LightSleep,
     // DeepSleep,
 }
+
+/// Reader of a single ADC channel from a non-blocking read function, which returns `None` while the conversion is
+/// in progress.
+///
+/// It adapts ADCs without an async API, e.g. the one-shot read of the esp-hal ADC:
+/// `PollAdc(move || adc.read_oneshot(&mut pin).ok())`.
+pub struct PollAdc<F: FnMut() -> Option<u16>>(pub F);
+
+impl<F: FnMut() -> Option<u16>> AdcReader<1> for PollAdc<F> {
+    async fn read(&mut self) -> [u16; 1] {
+        loop {
+            if let Some(value) = (self.0)() {
+                return [value];
+            }
+            embassy_futures::yield_now().await;
+        }
+    }
+}
+
+/// Reads the battery voltage from a single ADC channel and publishes the raw value as `BatteryAdcEvent`.
+///
+/// It works with the ADC of any chip, the raw value is converted to millivolts by
+/// [`BatteryProcessor`](crate::input_device::battery::BatteryProcessor) with the
+/// [`AdcConfig`](crate::input_device::battery::AdcConfig) of the chip.
+#[cfg(feature = "_ble")]
+#[input_device(publish = BatteryAdcEvent)]
+pub struct BatteryAdc<A: AdcReader<1>> {
+    adc: A,
+    polling_interval: Duration,
+    /// The first reading isn't delayed
+    started: bool,
+}
+
+#[cfg(feature = "_ble")]
+impl<A: AdcReader<1>> BatteryAdc<A> {
+    pub fn new(adc: A, polling_interval: Duration) -> Self {
+        Self {
+            adc,
+            polling_interval,
+            started: false,
+        }
+    }
+
+    async fn read_battery_adc_event(&mut self) -> BatteryAdcEvent {
+        if self.started {
+            Timer::after(self.polling_interval).await;
+        }
+        self.started = true;
+        let [value] = self.adc.read().await;
+        BatteryAdcEvent(value)
+    }
+}
@@ -2,7 +2,7 @@ use embassy_nrf::saadc::Saadc;
 use embassy_time::{Duration, Instant};
 use rmk_macro::{Event, input_device};
 
-use super::{AdcState, AnalogEventType};
+use super::{AdcReader, AdcState, AnalogEventType};
 use crate::event::{Axis, AxisEvent, AxisValType, BatteryAdcEvent, PointingEvent};
 
 /// Events produced by NrfAdc.
@@ -12,6 +12,15 @@ pub enum NrfAdcEvent {
     Battery(BatteryAdcEvent),
 }
 
+impl<'a, const N: usize> AdcReader<N> for Saadc<'a, N> {
+    async fn read(&mut self) -> [u16; N] {
+        let mut buf = [0i16; N];
+        self.sample(&mut buf).await;
+        // Single-ended samples may be slightly negative due to noise
+        buf.map(|v| v.max(0) as u16)
+    }
+}
+
 #[input_device(publish = NrfAdcEvent)]
 pub struct NrfAdc<'a, const PIN_NUM: usize, const EVENT_NUM: usize> {
     saadc: Saadc<'a, PIN_NUM>,
@@ -0,0 +1,27 @@
+use embassy_rp::adc::{Adc, Async, Channel};
+
+use super::AdcReader;
+
+/// A single channel of the RP2040 ADC
+pub struct RpAdc<'d> {
+    adc: Adc<'d, Async>,
+    channel: Channel<'d>,
+}
+
+impl<'d> RpAdc<'d> {
+    pub fn new(adc: Adc<'d, Async>, channel: Channel<'d>) -> Self {
+        Self { adc, channel }
+    }
+}
+
+impl AdcReader<1> for RpAdc<'_> {
+    async fn read(&mut self) -> [u16; 1] {
+        match self.adc.read(&mut self.channel).await {
+            Ok(value) => [value],
+            Err(_) => {
+                error!("Failed to read the RP2040 ADC");
+                [0]
+            }
+        }
+    }
+}
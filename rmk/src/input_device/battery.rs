@@ -3,9 +3,9 @@ use rmk_macro::{input_device, processor};
 #[cfg(feature = "_ble")]
 use rmk_types::battery::{BatteryStatus, ChargeState};
 
-#[cfg(feature = "_ble")]
-use crate::event::BatteryStatusEvent;
 use crate::event::{BatteryAdcEvent, ChargingStateEvent, publish_event};
+#[cfg(feature = "_ble")]
+use crate::event::{BatteryStatusEvent, LowBatteryEvent};
 
 /// Reads charging state from a GPIO pin and publishes ChargingStateEvent.
 ///
@@ -74,58 +74,272 @@ impl<I: InputPin> ChargingStateReader<I> {
     }
 }
 
+/// Number of voltage samples averaged by [`BatteryProcessor`]
+const SMOOTHING_WINDOW: usize = 8;
+
+/// Minimum change of the battery level, in percent, that is reported
+const LEVEL_HYSTERESIS: u8 = 2;
+
+/// Default battery level, in percent, at or below which the battery is low
+pub const DEFAULT_LOW_BATTERY_THRESHOLD: u8 = 10;
+
+/// Conversion from raw ADC readings to the battery voltage.
+///
+/// The battery voltage is `raw * reference / gain / 2^resolution * total / measured`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdcConfig {
+    /// Reference voltage of the ADC, in millivolts
+    pub reference_mv: u32,
+    /// Input gain of the ADC, as `(numerator, denominator)`
+    pub gain: (u32, u32),
+    /// Resolution of the ADC, in bits
+    pub resolution: u8,
+    /// Resistance of the divider between the ADC pin and ground
+    pub divider_measured: u32,
+    /// Total resistance of the divider
+    pub divider_total: u32,
+}
+
+impl AdcConfig {
+    /// nRF52 SAADC with the default settings: 0.6V internal reference, 1/6 gain, 12 bits
+    pub const NRF52: Self = Self {
+        reference_mv: 600,
+        gain: (1, 6),
+        resolution: 12,
+        divider_measured: 1,
+        divider_total: 1,
+    };
+
+    /// RP2040 ADC: 3.3V reference, 12 bits
+    pub const RP2040: Self = Self {
+        reference_mv: 3300,
+        gain: (1, 1),
+        resolution: 12,
+        divider_measured: 1,
+        divider_total: 1,
+    };
+
+    /// ESP32 ADC with 11dB attenuation: about 3.1V full scale, 12 bits.
+    ///
+    /// The full scale of the ESP32-C3 is lower, about 2.5V.
+    pub const ESP32: Self = Self {
+        reference_mv: 3100,
+        gain: (1, 1),
+        resolution: 12,
+        divider_measured: 1,
+        divider_total: 1,
+    };
+
+    /// Set the voltage divider between the battery and the ADC pin
+    pub const fn with_divider(mut self, measured: u32, total: u32) -> Self {
+        self.divider_measured = measured;
+        self.divider_total = total;
+        self
+    }
+
+    /// Convert a raw ADC reading to the battery voltage in millivolts
+    pub fn millivolts(&self, raw: u16) -> u32 {
+        let num = raw as u64 * self.reference_mv as u64 * self.gain.1 as u64 * self.divider_total as u64;
+        let den = (self.gain.0 as u64 * self.divider_measured as u64) << self.resolution;
+        if den == 0 { 0 } else { (num / den) as u32 }
+    }
+}
+
+/// Discharge curve of a battery, maps the battery voltage to the remaining capacity.
+///
+/// Points are `(millivolts, percent)`, sorted by descending voltage.
+/// The percentage between two points is interpolated linearly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatteryCurve(&'static [(u16, u8)]);
+
+impl BatteryCurve {
+    /// Single cell lithium polymer / lithium ion battery
+    pub const LIPO: Self = Self(&[
+        (4200, 100),
+        (4100, 90),
+        (4000, 80),
+        (3920, 70),
+        (3850, 60),
+        (3800, 50),
+        (3760, 40),
+        (3720, 30),
+        (3680, 20),
+        (3620, 10),
+        (3500, 5),
+        (3300, 0),
+    ]);
+
+    /// Single cell lithium iron phosphate battery
+    pub const LIFEPO4: Self = Self(&[
+        (3400, 100),
+        (3350, 90),
+        (3320, 70),
+        (3300, 50),
+        (3270, 30),
+        (3200, 20),
+        (3000, 10),
+        (2500, 0),
+    ]);
+
+    /// Two alkaline AA cells in series
+    pub const ALKALINE_2AA: Self = Self(&[
+        (3200, 100),
+        (2900, 80),
+        (2700, 60),
+        (2550, 40),
+        (2400, 20),
+        (2200, 10),
+        (2000, 0),
+    ]);
+
+    /// Create a custom curve from `(millivolts, percent)` points, sorted by descending voltage
+    pub const fn new(points: &'static [(u16, u8)]) -> Self {
+        Self(points)
+    }
+
+    /// Get the battery level in percent for the given voltage
+    pub fn percent(&self, mv: u32) -> u8 {
+        let Some(&(top_mv, top_percent)) = self.0.first() else {
+            return 0;
+        };
+        if mv >= top_mv as u32 {
+            return top_percent;
+        }
+        for pair in self.0.windows(2) {
+            let (high_mv, high_percent) = pair[0];
+            let (low_mv, low_percent) = pair[1];
+            if mv >= low_mv as u32 {
+                let span = high_mv.saturating_sub(low_mv) as u32;
+                if span == 0 {
+                    return low_percent;
+                }
+                let delta = high_percent.saturating_sub(low_percent) as u32;
+                return low_percent + ((mv - low_mv as u32) * delta / span) as u8;
+            }
+        }
+        self.0.last().map(|&(_, percent)| percent).unwrap_or(0)
+    }
+}
+
+/// Moving average of the recent battery voltages
+#[derive(Default)]
+struct VoltageFilter {
+    samples: [u32; SMOOTHING_WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl VoltageFilter {
+    /// Add a sample, returns the average of the samples in the window
+    fn push(&mut self, mv: u32) -> u32 {
+        self.samples[self.next] = mv;
+        self.next = (self.next + 1) % SMOOTHING_WINDOW;
+        self.len = (self.len + 1).min(SMOOTHING_WINDOW);
+        self.samples[..self.len].iter().sum::<u32>() / self.len as u32
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
 /// BatteryProcessor processes battery adc value and charging state,
-/// emits `BatteryStatusEvent` when battery status changes.
+/// emits `BatteryStatusEvent` when battery status changes and
+/// `LowBatteryEvent` when the battery becomes low or recovers.
+///
+/// ADC readings are converted to millivolts with [`AdcConfig`], averaged,
+/// then mapped to a percentage with a [`BatteryCurve`].
 #[processor(subscribe = [BatteryAdcEvent, ChargingStateEvent])]
 pub struct BatteryProcessor {
-    adc_divider_measured: u32,
-    adc_divider_total: u32,
+    adc: AdcConfig,
+    curve: BatteryCurve,
+    filter: VoltageFilter,
+    low_battery_threshold: u8,
+    /// Whether a low battery is reported
+    low_battery: bool,
     /// Current battery status
     battery_status: BatteryStatus,
 }
 
 impl BatteryProcessor {
+    /// Create a processor for a LiPo battery measured by the nRF52 SAADC through a voltage divider
     pub fn new(adc_divider_measured: u32, adc_divider_total: u32) -> Self {
         BatteryProcessor {
-            adc_divider_measured,
-            adc_divider_total,
+            adc: AdcConfig::NRF52.with_divider(adc_divider_measured, adc_divider_total),
+            curve: BatteryCurve::LIPO,
+            filter: VoltageFilter::default(),
+            low_battery_threshold: DEFAULT_LOW_BATTERY_THRESHOLD,
+            low_battery: false,
             battery_status: BatteryStatus::Unavailable,
         }
     }
 
-    #[cfg(feature = "_ble")]
-    fn get_battery_percent(&self, val: u16) -> u8 {
-        // Avoid overflow
-        let val = val as i32;
-
-        // According to nRF52840's datasheet, for single_ended saadc:
-        // val = v_adc * (gain / reference) * 2^(resolution)
-        //
-        // When using default setting, gain = 1/6, reference = 0.6v, resolution = 12bits, so:
-        // val = v_adc * 1137.8
-        //
-        // For example, rmk-ble-keyboard uses two resistors 820K and 2M adjusting the v_adc, then,
-        // v_adc = v_bat * measured / total => val = v_bat * 1137.8 * measured / total
-        //
-        // If the battery voltage range is 3.6v ~ 4.2v, the adc val range should be (4096 ~ 4755) * measured / total
-        let mut measured = self.adc_divider_measured as i32;
-        let mut total = self.adc_divider_total as i32;
-        if 500 < val && val < 1000 {
-            // Thing becomes different when using vddh as reference
-            // The adc value for vddh pin is actually vddh/5,
-            // so we use this rough range to detect vddh
-            measured = 1;
-            total = 5;
+    /// Set the ADC conversion, replaces the divider given in [`BatteryProcessor::new`]
+    pub fn with_adc(mut self, adc: AdcConfig) -> Self {
+        self.adc = adc;
+        self
+    }
+
+    /// Set the discharge curve of the battery
+    pub fn with_curve(mut self, curve: BatteryCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Set the battery level, in percent, at or below which `LowBatteryEvent` is published
+    pub fn with_low_battery_threshold(mut self, threshold: u8) -> Self {
+        self.low_battery_threshold = threshold;
+        self
+    }
+
+    /// Get the smoothed battery level, `reported` is the last reported level
+    fn battery_percent(&mut self, val: u16, reported: Option<u8>) -> u8 {
+        let mv = self.filter.push(self.adc.millivolts(val));
+        let percent = self.curve.percent(mv);
+        match reported {
+            // Ignore small changes so that the level doesn't jitter
+            Some(level) if level.abs_diff(percent) < LEVEL_HYSTERESIS && percent != 0 => level,
+            _ => percent,
         }
-        if val > 4755_i32 * measured / total {
-            // 4755 ~= 4.2v * 1137.8
-            100_u8
-        } else if val < 4055_i32 * measured / total {
-            // 4096 ~= 3.6v * 1137.8
-            // To simplify the calculation, we use 4055 here
-            0_u8
-        } else {
-            ((val * total / measured - 4055) / 7) as u8
+    }
+}
+
+#[cfg(feature = "_ble")]
+impl BatteryProcessor {
+    fn publish_battery_status(&mut self) {
+        let state = BatteryStatusEvent::from(self.battery_status);
+        // Kept for split peripherals connected later
+        #[cfg(feature = "split")]
+        crate::split::snapshot::set_battery(state);
+        publish_event(state);
+        self.update_low_battery();
+    }
+
+    /// Publish `LowBatteryEvent` when the low battery state changes
+    fn update_low_battery(&mut self) {
+        let low = match self.battery_status {
+            BatteryStatus::Available {
+                charge_state: ChargeState::Charging,
+                ..
+            } => false,
+            BatteryStatus::Available { level: Some(level), .. } => {
+                if self.low_battery {
+                    // Recover only when the level is clearly above the threshold
+                    level <= self.low_battery_threshold.saturating_add(LEVEL_HYSTERESIS)
+                } else {
+                    level <= self.low_battery_threshold
+                }
+            }
+            _ => self.low_battery,
+        };
+        if low != self.low_battery {
+            self.low_battery = low;
+            if low {
+                warn!("Battery is low");
+            }
+            publish_event(LowBatteryEvent { low });
         }
     }
 }
@@ -144,23 +358,23 @@ impl BatteryProcessor {
             } => {}
             // Not charging: update level if changed
             BatteryStatus::Available { charge_state, level } => {
-                let battery_percent = self.get_battery_percent(val);
+                let battery_percent = self.battery_percent(val, level);
                 if level != Some(battery_percent) {
                     self.battery_status = BatteryStatus::Available {
                         charge_state,
                         level: Some(battery_percent),
                     };
-                    publish_event(BatteryStatusEvent::from(self.battery_status));
+                    self.publish_battery_status();
                 }
             }
             // First ADC reading: transition from Unavailable
             BatteryStatus::Unavailable => {
-                let battery_percent = self.get_battery_percent(val);
+                let battery_percent = self.battery_percent(val, None);
                 self.battery_status = BatteryStatus::Available {
                     charge_state: ChargeState::Unknown,
                     level: Some(battery_percent),
                 };
-                publish_event(BatteryStatusEvent::from(self.battery_status));
+                self.publish_battery_status();
             }
         }
     }
@@ -169,6 +383,9 @@ impl BatteryProcessor {
         let charging = event.charging;
         info!("Charging state changed: {:?}", charging);
 
+        // The voltage jumps when the charger is plugged or unplugged, start averaging again
+        self.filter.reset();
+
         #[cfg(feature = "_ble")]
         {
             if charging {
@@ -189,7 +406,66 @@ impl BatteryProcessor {
                 };
             }
 
-            publish_event(BatteryStatusEvent::from(self.battery_status));
+            self.publish_battery_status();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adc_to_millivolts() {
+        // nRF52 SAADC measuring VDDH / 5
+        let adc = AdcConfig::NRF52.with_divider(1, 5);
+        assert_eq!(adc.millivolts(956), 4201);
+        // 820K / 2.82M divider
+        let adc = AdcConfig::NRF52.with_divider(2000, 2806);
+        assert_eq!(adc.millivolts(3406), 4199);
+        // 3.3V reference, 12 bits, no gain, half divider
+        let adc = AdcConfig {
+            reference_mv: 3300,
+            gain: (1, 1),
+            resolution: 12,
+            divider_measured: 1,
+            divider_total: 2,
+        };
+        assert_eq!(adc.millivolts(2048), 3300);
+        // Pico W measuring VSYS / 3
+        let adc = AdcConfig::RP2040.with_divider(1, 3);
+        assert_eq!(adc.millivolts(1737), 4198);
+    }
+
+    #[test]
+    fn curve_interpolation() {
+        let curve = BatteryCurve::LIPO;
+        assert_eq!(curve.percent(4300), 100);
+        assert_eq!(curve.percent(4200), 100);
+        assert_eq!(curve.percent(4150), 95);
+        assert_eq!(curve.percent(3800), 50);
+        assert_eq!(curve.percent(3650), 15);
+        assert_eq!(curve.percent(3300), 0);
+        assert_eq!(curve.percent(3000), 0);
+        assert_eq!(BatteryCurve::new(&[]).percent(4000), 0);
+    }
+
+    #[test]
+    fn smoothing_and_hysteresis() {
+        let mut processor = BatteryProcessor::new(1, 1);
+        let raw = |mv: u32| (mv * 4096 / 3600) as u16;
+        // Fill the window at 50%
+        let mut level = None;
+        for _ in 0..SMOOTHING_WINDOW {
+            level = Some(processor.battery_percent(raw(3801), level));
+        }
+        assert_eq!(level, Some(50));
+        // A single outlier barely moves the average
+        assert_eq!(processor.battery_percent(raw(3780), level), 50);
+        // A sustained drop is reported
+        for _ in 0..SMOOTHING_WINDOW {
+            level = Some(processor.battery_percent(raw(3721), level));
         }
+        assert_eq!(level, Some(30));
     }
 }
@@ -2,10 +2,73 @@ use rmk_macro::processor;
 use usbd_hid::descriptor::MouseReport;
 
 use crate::channel::KEYBOARD_REPORT_CHANNEL;
-use crate::event::PointingEvent;
+use crate::event::{KeyPos, KeyboardEvent, PointingEvent, publish_event_async};
 use crate::hid::Report;
 use crate::keymap::KeyMap;
 
+/// Output mode of the joystick
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JoystickMode {
+    /// Move the mouse cursor
+    #[default]
+    Mouse,
+    /// Scroll, y axis for the wheel and x axis for the pan
+    Scroll,
+    /// Trigger the keys at [`JoystickKeys::positions`]
+    Keys,
+    /// Gamepad axes, x, y and z are mapped to the X, Y and Z axes of the gamepad report
+    #[cfg(feature = "gamepad")]
+    Gamepad,
+}
+
+/// Digital direction output of the joystick
+///
+/// Each direction triggers a key position in the keymap, so the actual `KeyAction`
+/// comes from the keymap and follows the active layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct JoystickKeys {
+    /// Key positions of up, down, left and right
+    pub positions: [KeyPos; 4],
+    /// A direction is pressed when the axis value is beyond the dead zone
+    pub dead_zone: u16,
+    /// A pressed direction is released when the axis value is below `dead_zone - hysteresis`
+    pub hysteresis: u16,
+    /// Allow diagonals by pressing two directions at the same time
+    pub eight_way: bool,
+}
+
+impl JoystickKeys {
+    /// Get the next pressed state of up, down, left and right
+    fn next_state(&self, x: i16, y: i16, pressed: [bool; 4]) -> [bool; 4] {
+        let press = self.dead_zone.min(i16::MAX as u16) as i16;
+        let hysteresis = self.hysteresis.min(i16::MAX as u16) as i16;
+        let release = press.saturating_sub(hysteresis);
+        let values = [y.saturating_neg(), y, x.saturating_neg(), x];
+
+        let mut next = [false; 4];
+        if self.eight_way {
+            for (n, (v, p)) in next.iter_mut().zip(values.iter().zip(pressed.iter())) {
+                *n = *v > if *p { release } else { press };
+            }
+        } else {
+            let strongest = (0..4).max_by_key(|&i| values[i]).unwrap_or(0);
+            // Keep the held direction until it's released or another direction is clearly stronger
+            let held = pressed
+                .iter()
+                .position(|p| *p)
+                .filter(|&i| values[i] > release && values[strongest] <= values[i].saturating_add(hysteresis));
+            if let Some(i) = held {
+                next[i] = true;
+            } else if values[strongest] > press {
+                next[strongest] = true;
+            }
+        }
+        next
+    }
+}
+
 #[processor(subscribe = [PointingEvent])]
 pub struct JoystickProcessor<'a, const N: usize> {
     transform: [[i16; N]; N],
@@ -13,6 +76,13 @@ pub struct JoystickProcessor<'a, const N: usize> {
     keymap: &'a KeyMap<'a>,
     record: [i16; N],
     resolution: u16,
+    /// Output mode of each layer, layers out of range use the first one
+    modes: &'a [JoystickMode],
+    /// Axis value per wheel/pan step in scroll mode
+    scroll_divisor: i16,
+    scroll_remainder: [i16; 2],
+    keys: Option<JoystickKeys>,
+    keys_pressed: [bool; 4],
 }
 
 impl<'a, const N: usize> JoystickProcessor<'a, N> {
@@ -23,9 +93,43 @@ impl<'a, const N: usize> JoystickProcessor<'a, N> {
             resolution,
             keymap,
             record: [0; N],
+            modes: &[],
+            scroll_divisor: 8,
+            scroll_remainder: [0; 2],
+            keys: None,
+            keys_pressed: [false; 4],
         }
     }
 
+    /// Set the output mode of each layer.
+    ///
+    /// `modes[i]` is used on layer `i`, layers without an entry use `modes[0]`.
+    pub fn with_modes(mut self, modes: &'a [JoystickMode]) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    /// Set how much the axis value has to accumulate for one wheel/pan step in scroll mode
+    pub fn with_scroll_divisor(mut self, scroll_divisor: u16) -> Self {
+        self.scroll_divisor = scroll_divisor.clamp(1, i16::MAX as u16) as i16;
+        self
+    }
+
+    /// Set the key positions and thresholds used in keys mode
+    pub fn with_keys(mut self, keys: JoystickKeys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    fn current_mode(&self) -> JoystickMode {
+        let layer = self.keymap.active_layer() as usize;
+        self.modes
+            .get(layer)
+            .or(self.modes.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
     async fn on_pointing_event(&mut self, event: PointingEvent) {
         for (rec, e) in self.record.iter_mut().zip(event.0.iter()) {
             *rec = e.value;
@@ -54,17 +158,160 @@ impl<'a, const N: usize> JoystickProcessor<'a, N> {
         }
 
         debug!("JoystickProcessor::generate_report: report = {:?}", report);
+        let mode = self.current_mode();
+        if mode != JoystickMode::Keys {
+            // Don't leave keys pressed after switching to another mode
+            self.update_keys([false; 4]).await;
+        }
+        if mode != JoystickMode::Scroll {
+            self.scroll_remainder = [0; 2];
+        }
+        #[cfg(feature = "gamepad")]
+        if mode != JoystickMode::Gamepad {
+            // Center the gamepad axes after switching to another mode
+            self.update_gamepad_axes(&[0; N]).await;
+        }
+
+        match mode {
+            JoystickMode::Mouse => self.send_mouse_report(report[0], report[1], 0, 0).await,
+            JoystickMode::Scroll => {
+                let (x, y) = (report[0], report[1]);
+                self.scroll_remainder[0] = self.scroll_remainder[0].saturating_add(x);
+                self.scroll_remainder[1] = self.scroll_remainder[1].saturating_add(y);
+                let pan = self.scroll_remainder[0] / self.scroll_divisor;
+                let wheel = self.scroll_remainder[1] / self.scroll_divisor;
+                self.scroll_remainder[0] %= self.scroll_divisor;
+                self.scroll_remainder[1] %= self.scroll_divisor;
+                if pan != 0 || wheel != 0 {
+                    // Pushing the stick up scrolls up, which is a positive wheel value
+                    self.send_mouse_report(0, 0, wheel.saturating_neg(), pan).await;
+                }
+            }
+            JoystickMode::Keys => {
+                if let Some(keys) = self.keys {
+                    let next = keys.next_state(report[0], report[1], self.keys_pressed);
+                    self.update_keys(next).await;
+                }
+            }
+            #[cfg(feature = "gamepad")]
+            JoystickMode::Gamepad => self.update_gamepad_axes(&report).await,
+        }
+    }
+
+    #[cfg(feature = "gamepad")]
+    async fn update_gamepad_axes(&self, values: &[i16; N]) {
+        let mut axes = [0i8; 3];
+        for (axis, v) in axes.iter_mut().zip(values.iter()) {
+            // -128 isn't in the logical range of the gamepad axes
+            *axis = (*v).clamp(-(i8::MAX as i16), i8::MAX as i16) as i8;
+        }
+        let len = N.min(axes.len());
+        if let Some(report) = crate::keyboard::gamepad::update(|s| s.set_axes(0, &axes[..len])) {
+            KEYBOARD_REPORT_CHANNEL.send(report).await;
+        }
+    }
+
+    async fn send_mouse_report(&self, x: i16, y: i16, wheel: i16, pan: i16) {
+        let clamp = |v: i16| v.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
         // map to mouse
         let buttons = self.keymap.mouse_buttons();
         let mouse_report = MouseReport {
             buttons,
-            x: (report[0].clamp(i8::MIN as i16, i8::MAX as i16)) as i8,
-            y: (report[1].clamp(i8::MIN as i16, i8::MAX as i16)) as i8,
-            wheel: 0,
-            pan: 0,
+            x: clamp(x),
+            y: clamp(y),
+            wheel: clamp(wheel),
+            pan: clamp(pan),
         };
 
         // Send mouse report directly
         KEYBOARD_REPORT_CHANNEL.send(Report::MouseReport(mouse_report)).await;
     }
+
+    /// Publish the changed directions, releases first so that a 4-way switch never presses two keys
+    async fn update_keys(&mut self, next: [bool; 4]) {
+        let Some(keys) = self.keys else {
+            return;
+        };
+        for pressed in [false, true] {
+            for i in 0..4 {
+                if self.keys_pressed[i] != next[i] && next[i] == pressed {
+                    let pos = keys.positions[i];
+                    publish_event_async(KeyboardEvent::key(pos.row, pos.col, pressed)).await;
+                    self.keys_pressed[i] = pressed;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: [bool; 4] = [true, false, false, false];
+    const DOWN: [bool; 4] = [false, true, false, false];
+    const LEFT: [bool; 4] = [false, false, true, false];
+    const RIGHT: [bool; 4] = [false, false, false, true];
+    const UP_RIGHT: [bool; 4] = [true, false, false, true];
+    const NONE: [bool; 4] = [false; 4];
+
+    fn keys(eight_way: bool) -> JoystickKeys {
+        JoystickKeys {
+            positions: [
+                KeyPos { row: 0, col: 0 },
+                KeyPos { row: 0, col: 1 },
+                KeyPos { row: 0, col: 2 },
+                KeyPos { row: 0, col: 3 },
+            ],
+            dead_zone: 20,
+            hysteresis: 5,
+            eight_way,
+        }
+    }
+
+    #[test]
+    fn test_keys_dead_zone() {
+        let keys = keys(false);
+        assert_eq!(keys.next_state(0, 0, NONE), NONE);
+        assert_eq!(keys.next_state(20, 0, NONE), NONE);
+        assert_eq!(keys.next_state(21, 0, NONE), RIGHT);
+        assert_eq!(keys.next_state(0, -21, NONE), UP);
+        assert_eq!(keys.next_state(0, 21, NONE), DOWN);
+    }
+
+    #[test]
+    fn test_keys_hysteresis() {
+        let keys = keys(false);
+        // Stays pressed until the value drops to `dead_zone - hysteresis`
+        assert_eq!(keys.next_state(16, 0, RIGHT), RIGHT);
+        assert_eq!(keys.next_state(15, 0, RIGHT), NONE);
+        // Not pressed again until it's beyond the dead zone
+        assert_eq!(keys.next_state(18, 0, NONE), NONE);
+    }
+
+    #[test]
+    fn test_keys_four_way() {
+        let keys = keys(false);
+        // Only the strongest direction is pressed
+        assert_eq!(keys.next_state(30, 25, NONE), RIGHT);
+        // The held direction is kept while the other axis isn't clearly stronger
+        assert_eq!(keys.next_state(30, 35, RIGHT), RIGHT);
+        assert_eq!(keys.next_state(30, 36, RIGHT), DOWN);
+    }
+
+    #[test]
+    fn test_keys_eight_way() {
+        let keys = keys(true);
+        assert_eq!(keys.next_state(30, -25, NONE), UP_RIGHT);
+        assert_eq!(keys.next_state(30, -16, UP_RIGHT), UP_RIGHT);
+        assert_eq!(keys.next_state(30, -15, UP_RIGHT), RIGHT);
+        assert_eq!(keys.next_state(-30, 0, RIGHT), LEFT);
+    }
+
+    #[test]
+    fn test_keys_extreme_values() {
+        let keys = keys(false);
+        assert_eq!(keys.next_state(0, i16::MIN, NONE), UP);
+        assert_eq!(keys.next_state(i16::MAX, 0, NONE), RIGHT);
+    }
 }
@@ -0,0 +1,1097 @@
+//! Cirque Pinnacle (1CA027) Trackpad Driver
+//!
+//! The Pinnacle ASIC is used by Cirque's GlidePoint circle trackpads (TM0xx0xx).
+//! Its registers are accessed through the Register Access Protocol (RAP), either over SPI (mode 1)
+//! or over I2C. The analog front-end tuning lives in extended registers, which are reached
+//! indirectly through the ERA window registers.
+//!
+//! Both data modes of the ASIC are supported:
+//! - Relative mode: the ASIC reports PS/2 style deltas and detects taps by itself.
+//! - Absolute mode: the ASIC reports the finger position. The driver converts consecutive positions
+//!   to deltas scaled to the configured CPI, and detects taps in software.
+//!
+//! Taps are published as `PointingButtonEvent`, which is merged into the mouse report by `PointingProcessor`.
+//!
+//! Reference:
+//! https://github.com/cirque-corp/Cirque_Pinnacle_1CA027
+
+use embassy_time::{Duration, Instant};
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::i2c::I2c;
+use embedded_hal_async::spi::SpiBus;
+
+use crate::event::{PointingButtonEvent, publish_event};
+use crate::input_device::pointing::{InitState, MotionData, PointingDevice, PointingDriver, PointingDriverError};
+
+// ============================================================================
+// Registers
+// ============================================================================
+const PINNACLE_FIRMWARE_ID: u8 = 0x00;
+const PINNACLE_STATUS1: u8 = 0x02;
+const PINNACLE_SYS_CONFIG1: u8 = 0x03;
+const PINNACLE_FEED_CONFIG1: u8 = 0x04;
+const PINNACLE_FEED_CONFIG2: u8 = 0x05;
+const PINNACLE_Z_IDLE: u8 = 0x0a;
+const PINNACLE_PACKET_BYTE0: u8 = 0x12;
+const PINNACLE_ERA_VALUE: u8 = 0x1b;
+const PINNACLE_ERA_ADDR_HIGH: u8 = 0x1c;
+const PINNACLE_ERA_ADDR_LOW: u8 = 0x1d;
+const PINNACLE_ERA_CONTROL: u8 = 0x1e;
+
+// ============================================================================
+// Extended registers
+// ============================================================================
+const ERA_TRACK_ADC_CONFIG: u16 = 0x0187;
+const ERA_X_AXIS_WIDE_Z_MIN: u16 = 0x0149;
+const ERA_Y_AXIS_WIDE_Z_MIN: u16 = 0x0168;
+
+// ============================================================================
+// Constants
+// ============================================================================
+/// Default I2C address of the Pinnacle
+pub const PINNACLE_I2C_ADDRESS: u8 = 0x2a;
+
+const RAP_READ: u8 = 0xa0;
+const RAP_WRITE: u8 = 0x80;
+/// SPI filler byte, clocked out while reading with auto-increment
+const RAP_FILLER: u8 = 0xfc;
+/// SPI filler byte for the last byte of a read
+const RAP_FILLER_LAST: u8 = 0xfb;
+
+const FIRMWARE_ID_PINNACLE: u8 = 0x07;
+const STATUS1_SW_DR: u8 = 1 << 2;
+const SYS_CONFIG1_SLEEP_ENABLE: u8 = 1 << 2;
+const FEED_CONFIG1_FEED_ENABLE: u8 = 1 << 0;
+const FEED_CONFIG1_ABSOLUTE_MODE: u8 = 1 << 1;
+const FEED_CONFIG2_ALL_TAPS_DISABLE: u8 = 1 << 1;
+const FEED_CONFIG2_SECONDARY_TAP_DISABLE: u8 = 1 << 2;
+const FEED_CONFIG2_SCROLL_DISABLE: u8 = 1 << 3;
+const FEED_CONFIG2_GLIDE_EXTEND_DISABLE: u8 = 1 << 4;
+const ERA_CONTROL_READ: u8 = 0x01;
+const ERA_CONTROL_WRITE: u8 = 0x02;
+const TRACK_ADC_ATTENUATION_MASK: u8 = 0xc0;
+/// Lower finger detection thresholds at the pad edges, where a curved overlay is farther from the sensor
+const WIDE_Z_MIN_X_CURVED: u8 = 0x04;
+const WIDE_Z_MIN_Y_CURVED: u8 = 0x03;
+
+const RELATIVE_PACKET_LEN: usize = 3;
+const RELATIVE_BUTTONS_MASK: u8 = 0x07;
+const RELATIVE_X_SIGN: u8 = 1 << 4;
+const RELATIVE_Y_SIGN: u8 = 1 << 5;
+
+const ABSOLUTE_PACKET_LEN: usize = 6;
+const ABS_X_MIN: u16 = 127;
+const ABS_X_MAX: u16 = 1919;
+const ABS_Y_MIN: u16 = 63;
+const ABS_Y_MAX: u16 = 1471;
+/// Number of all-zero packets sent after the finger is lifted, used to detect the lift in absolute mode
+const ABS_Z_IDLE_PACKETS: u8 = 5;
+
+/// Maximum finger travel of a tap in absolute mode, in sensor counts
+const TAP_MAX_TRAVEL: u16 = 48;
+/// Button reported for a tap
+const TAP_BUTTON: u8 = 0x01;
+
+/// Maximum number of polls of the ERA control register before giving up
+const ERA_MAX_POLLS: u8 = 100;
+
+/// Data mode of the Pinnacle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinnacleDataMode {
+    /// Relative deltas, taps are detected by the ASIC
+    Relative,
+    /// Absolute position, deltas and taps are computed by the driver
+    Absolute,
+}
+
+/// ADC attenuation of the sensor
+///
+/// Higher attenuation means lower sensitivity. Thick or curved overlays usually need less attenuation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinnacleAttenuation {
+    X1,
+    X2,
+    X3,
+    X4,
+}
+
+impl PinnacleAttenuation {
+    fn bits(self) -> u8 {
+        match self {
+            PinnacleAttenuation::X1 => 0x00,
+            PinnacleAttenuation::X2 => 0x40,
+            PinnacleAttenuation::X3 => 0x80,
+            PinnacleAttenuation::X4 => 0xc0,
+        }
+    }
+}
+
+/// Pinnacle configuration
+#[derive(Clone)]
+pub struct PinnacleConfig {
+    /// Data mode
+    pub mode: PinnacleDataMode,
+    /// Output resolution in absolute mode, in counts per inch
+    pub cpi: u16,
+    /// Diameter of the trackpad in millimeters, used to scale absolute positions to `cpi`
+    pub diameter_mm: u8,
+    /// ADC attenuation
+    pub attenuation: PinnacleAttenuation,
+    /// Apply the tuning for curved overlays
+    pub curved_overlay: bool,
+    /// Enable tap to click
+    pub tap: bool,
+    /// Enable secondary tap (right click by tapping the upper right corner), relative mode only
+    pub secondary_tap: bool,
+    /// Maximum touch duration of a tap in absolute mode, in milliseconds
+    pub tap_term_ms: u16,
+    /// Invert X axis
+    pub invert_x: bool,
+    /// Invert Y axis
+    pub invert_y: bool,
+    /// Swap X and Y axes
+    pub swap_xy: bool,
+    /// Enable the low power sleep mode of the ASIC
+    pub sleep: bool,
+}
+
+impl Default for PinnacleConfig {
+    fn default() -> Self {
+        Self {
+            mode: PinnacleDataMode::Absolute,
+            cpi: 800,
+            diameter_mm: 35,
+            attenuation: PinnacleAttenuation::X4,
+            curved_overlay: false,
+            tap: true,
+            secondary_tap: false,
+            tap_term_ms: 200,
+            invert_x: false,
+            invert_y: false,
+            swap_xy: false,
+            sleep: false,
+        }
+    }
+}
+
+/// Pinnacle error types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinnacleError {
+    /// SPI communication error
+    Spi,
+    /// I2C communication error
+    I2c,
+    /// Invalid firmware ID detected
+    InvalidFirmwareId(u8),
+    /// Extended register access didn't complete
+    EraTimeout,
+}
+
+impl From<PinnacleError> for PointingDriverError {
+    fn from(err: PinnacleError) -> Self {
+        match err {
+            PinnacleError::Spi => PointingDriverError::Spi,
+            PinnacleError::I2c => PointingDriverError::I2c,
+            PinnacleError::InvalidFirmwareId(id) => PointingDriverError::InvalidProductId(id),
+            PinnacleError::EraTimeout => PointingDriverError::InitFailed,
+        }
+    }
+}
+
+/// Register access of the Pinnacle, implemented for SPI and I2C
+pub trait PinnacleInterface {
+    /// Read consecutive registers starting from `addr`
+    async fn read_regs(&mut self, addr: u8, data: &mut [u8]) -> Result<(), PinnacleError>;
+    /// Write a single register
+    async fn write_reg(&mut self, addr: u8, value: u8) -> Result<(), PinnacleError>;
+}
+
+/// SPI interface of the Pinnacle, the bus should be configured as SPI mode 1
+pub struct PinnacleSpi<SPI: SpiBus, CS: OutputPin> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI: SpiBus, CS: OutputPin> PinnacleSpi<SPI, CS> {
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        Self { spi, cs }
+    }
+}
+
+impl<SPI: SpiBus, CS: OutputPin> PinnacleInterface for PinnacleSpi<SPI, CS> {
+    async fn read_regs(&mut self, addr: u8, data: &mut [u8]) -> Result<(), PinnacleError> {
+        data.fill(RAP_FILLER);
+        if let Some(last) = data.last_mut() {
+            *last = RAP_FILLER_LAST;
+        }
+
+        let _ = self.cs.set_low();
+        // The first two bytes clocked after the command are dummy bytes
+        let result = match self.spi.write(&[RAP_READ | addr, RAP_FILLER, RAP_FILLER]).await {
+            Ok(()) => self.spi.transfer_in_place(data).await,
+            Err(e) => Err(e),
+        };
+        let _ = self.cs.set_high();
+
+        result.map_err(|_| PinnacleError::Spi)
+    }
+
+    async fn write_reg(&mut self, addr: u8, value: u8) -> Result<(), PinnacleError> {
+        let _ = self.cs.set_low();
+        let result = self.spi.write(&[RAP_WRITE | addr, value]).await;
+        let _ = self.cs.set_high();
+
+        result.map_err(|_| PinnacleError::Spi)
+    }
+}
+
+/// I2C interface of the Pinnacle
+pub struct PinnacleI2c<I2C: I2c> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C: I2c> PinnacleI2c<I2C> {
+    /// Create the interface with the default address
+    pub fn new(i2c: I2C) -> Self {
+        Self::with_address(i2c, PINNACLE_I2C_ADDRESS)
+    }
+
+    pub fn with_address(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+}
+
+impl<I2C: I2c> PinnacleInterface for PinnacleI2c<I2C> {
+    async fn read_regs(&mut self, addr: u8, data: &mut [u8]) -> Result<(), PinnacleError> {
+        self.i2c
+            .write_read(self.address, &[RAP_READ | addr], data)
+            .await
+            .map_err(|_| PinnacleError::I2c)
+    }
+
+    async fn write_reg(&mut self, addr: u8, value: u8) -> Result<(), PinnacleError> {
+        self.i2c
+            .write(self.address, &[RAP_WRITE | addr, value])
+            .await
+            .map_err(|_| PinnacleError::I2c)
+    }
+}
+
+/// Data ready (HW_DR) pin of the Pinnacle
+///
+/// HW_DR is driven high while a packet is available, but `PointingDevice` expects an active low motion pin,
+/// so this wrapper inverts the pin level.
+pub struct PinnacleDataReady<P: InputPin + Wait>(P);
+
+impl<P: InputPin + Wait> ErrorType for PinnacleDataReady<P> {
+    type Error = P::Error;
+}
+
+impl<P: InputPin + Wait> InputPin for PinnacleDataReady<P> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_low()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_high()
+    }
+}
+
+impl<P: InputPin + Wait> Wait for PinnacleDataReady<P> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_low().await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_high().await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_falling_edge().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_rising_edge().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_any_edge().await
+    }
+}
+
+/// Decoded absolute mode packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AbsolutePosition {
+    x: u16,
+    y: u16,
+    z: u8,
+}
+
+/// State of the current touch in absolute mode
+struct Touch {
+    start: Instant,
+    last_x: u16,
+    last_y: u16,
+    /// Total travel since touch down, in sensor counts
+    travel: u16,
+}
+
+/// Pinnacle driver
+pub struct Pinnacle<IF: PinnacleInterface, DR: InputPin + Wait> {
+    id: u8,
+    interface: IF,
+    data_ready: Option<PinnacleDataReady<DR>>,
+    config: PinnacleConfig,
+    /// Buttons last reported in relative mode
+    buttons: u8,
+    touch: Option<Touch>,
+    remainder_x: i64,
+    remainder_y: i64,
+}
+
+impl<IF: PinnacleInterface, DR: InputPin + Wait> Pinnacle<IF, DR> {
+    /// Create a new Pinnacle driver instance
+    pub fn new(id: u8, interface: IF, data_ready: Option<DR>, config: PinnacleConfig) -> Self {
+        Self {
+            id,
+            interface,
+            data_ready: data_ready.map(PinnacleDataReady),
+            config,
+            buttons: 0,
+            touch: None,
+            remainder_x: 0,
+            remainder_y: 0,
+        }
+    }
+
+    /// Clear the data ready and command complete flags
+    async fn clear_flags(&mut self) -> Result<(), PinnacleError> {
+        self.interface.write_reg(PINNACLE_STATUS1, 0x00).await
+    }
+
+    async fn era_wait(&mut self) -> Result<(), PinnacleError> {
+        for _ in 0..ERA_MAX_POLLS {
+            let mut control = [0u8];
+            self.interface.read_regs(PINNACLE_ERA_CONTROL, &mut control).await?;
+            if control[0] == 0x00 {
+                return Ok(());
+            }
+        }
+        Err(PinnacleError::EraTimeout)
+    }
+
+    /// Read an extended register, the feed must be disabled
+    async fn era_read(&mut self, addr: u16) -> Result<u8, PinnacleError> {
+        self.interface
+            .write_reg(PINNACLE_ERA_ADDR_HIGH, (addr >> 8) as u8)
+            .await?;
+        self.interface
+            .write_reg(PINNACLE_ERA_ADDR_LOW, (addr & 0xff) as u8)
+            .await?;
+        self.interface.write_reg(PINNACLE_ERA_CONTROL, ERA_CONTROL_READ).await?;
+        self.era_wait().await?;
+
+        let mut value = [0u8];
+        self.interface.read_regs(PINNACLE_ERA_VALUE, &mut value).await?;
+        self.clear_flags().await?;
+
+        Ok(value[0])
+    }
+
+    /// Write an extended register, the feed must be disabled
+    async fn era_write(&mut self, addr: u16, value: u8) -> Result<(), PinnacleError> {
+        self.interface.write_reg(PINNACLE_ERA_VALUE, value).await?;
+        self.interface
+            .write_reg(PINNACLE_ERA_ADDR_HIGH, (addr >> 8) as u8)
+            .await?;
+        self.interface
+            .write_reg(PINNACLE_ERA_ADDR_LOW, (addr & 0xff) as u8)
+            .await?;
+        self.interface
+            .write_reg(PINNACLE_ERA_CONTROL, ERA_CONTROL_WRITE)
+            .await?;
+        self.era_wait().await?;
+        self.clear_flags().await
+    }
+
+    fn feed_config2(&self) -> u8 {
+        let mut val = FEED_CONFIG2_SCROLL_DISABLE | FEED_CONFIG2_GLIDE_EXTEND_DISABLE;
+        // Taps of the ASIC are only reported in relative mode
+        if !self.config.tap || self.config.mode == PinnacleDataMode::Absolute {
+            val |= FEED_CONFIG2_ALL_TAPS_DISABLE;
+        }
+        if !self.config.secondary_tap {
+            val |= FEED_CONFIG2_SECONDARY_TAP_DISABLE;
+        }
+        val
+    }
+
+    fn decode_relative(packet: &[u8; RELATIVE_PACKET_LEN]) -> (i16, i16, u8) {
+        let mut dx = packet[1] as i16;
+        if packet[0] & RELATIVE_X_SIGN != 0 {
+            dx -= 256;
+        }
+        let mut dy = packet[2] as i16;
+        if packet[0] & RELATIVE_Y_SIGN != 0 {
+            dy -= 256;
+        }
+
+        // Relative packets follow the PS/2 convention, where positive Y points up
+        (dx, -dy, packet[0] & RELATIVE_BUTTONS_MASK)
+    }
+
+    fn decode_absolute(packet: &[u8; ABSOLUTE_PACKET_LEN]) -> AbsolutePosition {
+        AbsolutePosition {
+            x: packet[2] as u16 | ((packet[4] as u16 & 0x0f) << 8),
+            y: packet[3] as u16 | ((packet[4] as u16 & 0xf0) << 4),
+            z: packet[5] & 0x3f,
+        }
+    }
+
+    /// Scale a delta in sensor counts to the configured CPI.
+    ///
+    /// The remainder is carried over to the next packet, so that slow movements are not lost.
+    fn scale(delta: i32, range: u16, cpi: u16, diameter_mm: u8, remainder: &mut i64) -> i16 {
+        // Sensor counts per inch = range / (diameter_mm / 25.4)
+        let num = delta as i64 * cpi as i64 * diameter_mm as i64 * 10 + *remainder;
+        let den = range as i64 * 254;
+        *remainder = num % den;
+        (num / den).clamp(i16::MIN as i64, i16::MAX as i64) as i16
+    }
+
+    /// Track the touch in absolute mode, returns the scaled motion
+    fn process_absolute(&mut self, pos: AbsolutePosition) -> (i16, i16) {
+        if pos.x == 0 && pos.y == 0 {
+            // The finger is lifted, the ASIC sends Z-idle packets with all zero coordinates
+            let is_tap = self.touch.take().is_some_and(|touch| {
+                touch.travel <= TAP_MAX_TRAVEL
+                    && touch.start.elapsed() <= Duration::from_millis(self.config.tap_term_ms as u64)
+            });
+            if self.config.tap && is_tap {
+                debug!("Pinnacle {}: Tap detected", self.id);
+                publish_event(PointingButtonEvent {
+                    device_id: self.id,
+                    buttons: TAP_BUTTON,
+                });
+                publish_event(PointingButtonEvent {
+                    device_id: self.id,
+                    buttons: 0,
+                });
+            }
+            return (0, 0);
+        }
+
+        let x = pos.x.clamp(ABS_X_MIN, ABS_X_MAX);
+        let y = pos.y.clamp(ABS_Y_MIN, ABS_Y_MAX);
+
+        let (dx, dy) = match self.touch.as_mut() {
+            Some(touch) => {
+                let dx = x as i32 - touch.last_x as i32;
+                let dy = y as i32 - touch.last_y as i32;
+                touch.last_x = x;
+                touch.last_y = y;
+                touch.travel = touch
+                    .travel
+                    .saturating_add((dx.unsigned_abs() + dy.unsigned_abs()) as u16);
+                (dx, dy)
+            }
+            None => {
+                // Touch down, there's no previous position to compute the motion from
+                self.touch = Some(Touch {
+                    start: Instant::now(),
+                    last_x: x,
+                    last_y: y,
+                    travel: 0,
+                });
+                self.remainder_x = 0;
+                self.remainder_y = 0;
+                return (0, 0);
+            }
+        };
+
+        (
+            Self::scale(
+                dx,
+                ABS_X_MAX - ABS_X_MIN,
+                self.config.cpi,
+                self.config.diameter_mm,
+                &mut self.remainder_x,
+            ),
+            Self::scale(
+                dy,
+                ABS_Y_MAX - ABS_Y_MIN,
+                self.config.cpi,
+                self.config.diameter_mm,
+                &mut self.remainder_y,
+            ),
+        )
+    }
+
+    /// Publish the buttons reported by the ASIC in relative mode, if changed
+    fn update_buttons(&mut self, buttons: u8) {
+        if buttons != self.buttons {
+            self.buttons = buttons;
+            publish_event(PointingButtonEvent {
+                device_id: self.id,
+                buttons,
+            });
+        }
+    }
+}
+
+impl<IF: PinnacleInterface, DR: InputPin + Wait> PointingDriver for Pinnacle<IF, DR> {
+    type MOTION = PinnacleDataReady<DR>;
+
+    async fn init(&mut self) -> Result<(), PointingDriverError> {
+        let mut fw_id = [0u8];
+        self.interface.read_regs(PINNACLE_FIRMWARE_ID, &mut fw_id).await?;
+        if fw_id[0] != FIRMWARE_ID_PINNACLE {
+            error!("Pinnacle {}: Invalid firmware ID: 0x{:02x}", self.id, fw_id[0]);
+            return Err(PinnacleError::InvalidFirmwareId(fw_id[0]).into());
+        }
+
+        self.clear_flags().await?;
+
+        let sys_config = if self.config.sleep {
+            SYS_CONFIG1_SLEEP_ENABLE
+        } else {
+            0x00
+        };
+        self.interface.write_reg(PINNACLE_SYS_CONFIG1, sys_config).await?;
+
+        // The feed must be disabled while accessing extended registers
+        self.interface.write_reg(PINNACLE_FEED_CONFIG1, 0x00).await?;
+
+        let adc_config = self.era_read(ERA_TRACK_ADC_CONFIG).await?;
+        self.era_write(
+            ERA_TRACK_ADC_CONFIG,
+            (adc_config & !TRACK_ADC_ATTENUATION_MASK) | self.config.attenuation.bits(),
+        )
+        .await?;
+
+        if self.config.curved_overlay {
+            self.era_write(ERA_X_AXIS_WIDE_Z_MIN, WIDE_Z_MIN_X_CURVED).await?;
+            self.era_write(ERA_Y_AXIS_WIDE_Z_MIN, WIDE_Z_MIN_Y_CURVED).await?;
+        }
+
+        let (feed_config1, z_idle) = match self.config.mode {
+            PinnacleDataMode::Relative => (FEED_CONFIG1_FEED_ENABLE, 0),
+            PinnacleDataMode::Absolute => (
+                FEED_CONFIG1_FEED_ENABLE | FEED_CONFIG1_ABSOLUTE_MODE,
+                ABS_Z_IDLE_PACKETS,
+            ),
+        };
+        self.interface.write_reg(PINNACLE_Z_IDLE, z_idle).await?;
+        self.interface
+            .write_reg(PINNACLE_FEED_CONFIG2, self.feed_config2())
+            .await?;
+        self.interface.write_reg(PINNACLE_FEED_CONFIG1, feed_config1).await?;
+        self.clear_flags().await?;
+
+        self.buttons = 0;
+        self.touch = None;
+
+        info!("Pinnacle {}: Initialized in {:?} mode", self.id, self.config.mode);
+        Ok(())
+    }
+
+    async fn read_motion(&mut self) -> Result<MotionData, PointingDriverError> {
+        // Without the HW_DR pin, check the software data ready flag
+        if self.data_ready.is_none() {
+            let mut status = [0u8];
+            self.interface.read_regs(PINNACLE_STATUS1, &mut status).await?;
+            if status[0] & STATUS1_SW_DR == 0 {
+                return Ok(MotionData::default());
+            }
+        }
+
+        let (mut dx, mut dy) = match self.config.mode {
+            PinnacleDataMode::Relative => {
+                let mut packet = [0u8; RELATIVE_PACKET_LEN];
+                self.interface.read_regs(PINNACLE_PACKET_BYTE0, &mut packet).await?;
+                self.clear_flags().await?;
+
+                let (dx, dy, buttons) = Self::decode_relative(&packet);
+                if self.config.tap {
+                    self.update_buttons(buttons);
+                }
+                (dx, dy)
+            }
+            PinnacleDataMode::Absolute => {
+                let mut packet = [0u8; ABSOLUTE_PACKET_LEN];
+                self.interface.read_regs(PINNACLE_PACKET_BYTE0, &mut packet).await?;
+                self.clear_flags().await?;
+
+                self.process_absolute(Self::decode_absolute(&packet))
+            }
+        };
+
+        if self.config.invert_x {
+            dx = -dx;
+        }
+        if self.config.invert_y {
+            dy = -dy;
+        }
+        if self.config.swap_xy {
+            (dx, dy) = (dy, dx);
+        }
+
+        Ok(MotionData { dx, dy })
+    }
+
+    /// Check if data is ready (HW_DR is active high, inverted by `PinnacleDataReady`)
+    fn motion_pending(&mut self) -> bool {
+        match &mut self.data_ready {
+            Some(gpio) => gpio.is_low().unwrap_or(true),
+            None => true,
+        }
+    }
+
+    fn motion_gpio(&mut self) -> Option<&mut Self::MOTION> {
+        self.data_ready.as_mut()
+    }
+
+    /// Set the output resolution in CPI, absolute mode only
+    async fn set_resolution(&mut self, cpi: u16) -> Result<(), PointingDriverError> {
+        if self.config.mode != PinnacleDataMode::Absolute {
+            debug!("Pinnacle {}: Resolution can only be set in absolute mode", self.id);
+            return Err(PointingDriverError::NotImplementedError);
+        }
+        if cpi == 0 {
+            return Err(PointingDriverError::InvalidCpi);
+        }
+
+        self.config.cpi = cpi;
+        self.remainder_x = 0;
+        self.remainder_y = 0;
+        debug!("Pinnacle {}: Resolution set to {} CPI", self.id, cpi);
+        Ok(())
+    }
+}
+
+impl<IF, DR> PointingDevice<Pinnacle<IF, DR>>
+where
+    IF: PinnacleInterface,
+    DR: InputPin + Wait,
+{
+    const DEFAULT_POLL_INTERVAL_US: u64 = 500;
+    const DEFAULT_REPORT_HZ: u16 = 125;
+
+    /// Create a new Pinnacle device
+    pub fn new(id: u8, interface: IF, data_ready: Option<DR>, sensor_config: PinnacleConfig) -> Self {
+        Self::with_poll_interval_and_report_hz(
+            id,
+            interface,
+            data_ready,
+            sensor_config,
+            Self::DEFAULT_POLL_INTERVAL_US,
+            Self::DEFAULT_REPORT_HZ,
+        )
+    }
+
+    /// Create a new Pinnacle device with custom report rate (Hz)
+    pub fn with_report_hz(
+        id: u8,
+        interface: IF,
+        data_ready: Option<DR>,
+        sensor_config: PinnacleConfig,
+        report_hz: u16,
+    ) -> Self {
+        Self::with_poll_interval_and_report_hz(
+            id,
+            interface,
+            data_ready,
+            sensor_config,
+            Self::DEFAULT_POLL_INTERVAL_US,
+            report_hz,
+        )
+    }
+
+    /// Create a new Pinnacle device with custom poll interval
+    pub fn with_poll_interval(
+        id: u8,
+        interface: IF,
+        data_ready: Option<DR>,
+        sensor_config: PinnacleConfig,
+        poll_interval_us: u64,
+    ) -> Self {
+        Self::with_poll_interval_and_report_hz(
+            id,
+            interface,
+            data_ready,
+            sensor_config,
+            poll_interval_us,
+            Self::DEFAULT_REPORT_HZ,
+        )
+    }
+
+    /// Create a new Pinnacle device with custom poll interval and report rate
+    pub fn with_poll_interval_and_report_hz(
+        id: u8,
+        interface: IF,
+        data_ready: Option<DR>,
+        sensor_config: PinnacleConfig,
+        poll_interval_us: u64,
+        report_hz: u16,
+    ) -> Self {
+        let report_interval = Duration::from_hz(report_hz as u64);
+
+        // Polling should be more frequent than reporting
+        let poll_interval = Duration::from_micros(poll_interval_us).min(report_interval);
+
+        Self {
+            id,
+            sensor: Pinnacle::new(id, interface, data_ready, sensor_config),
+            init_state: InitState::Pending,
+            poll_interval,
+            report_interval,
+            last_poll: Instant::MIN,
+            last_report: Instant::MIN,
+            accumulated_x: 0,
+            accumulated_y: 0,
+            pending_cpi: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State as PinState, Transaction as PinTrans};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTrans};
+
+    use super::*;
+    use crate::event::SubscribableEvent;
+    use crate::test_support::test_block_on as block_on;
+
+    // Init logger for tests
+    #[ctor::ctor]
+    fn init_log() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Debug)
+            .is_test(true)
+            .try_init();
+    }
+
+    fn reg_read(addr: u8, data: Vec<u8>) -> I2cTrans {
+        I2cTrans::write_read(PINNACLE_I2C_ADDRESS, vec![RAP_READ | addr], data)
+    }
+
+    fn reg_write(addr: u8, value: u8) -> I2cTrans {
+        I2cTrans::write(PINNACLE_I2C_ADDRESS, vec![RAP_WRITE | addr, value])
+    }
+
+    fn driver(expectations: &[I2cTrans], config: PinnacleConfig) -> Pinnacle<PinnacleI2c<I2cMock>, PinMock> {
+        Pinnacle::new(0, PinnacleI2c::new(I2cMock::new(expectations)), None, config)
+    }
+
+    fn absolute_packet(x: u16, y: u16, z: u8) -> Vec<u8> {
+        vec![
+            0x00,
+            0x00,
+            (x & 0xff) as u8,
+            (y & 0xff) as u8,
+            ((x >> 8) as u8 & 0x0f) | (((y >> 8) as u8 & 0x0f) << 4),
+            z,
+        ]
+    }
+
+    #[test]
+    fn test_init_absolute() {
+        let expectations = [
+            reg_read(PINNACLE_FIRMWARE_ID, vec![FIRMWARE_ID_PINNACLE]),
+            reg_write(PINNACLE_STATUS1, 0x00),
+            reg_write(PINNACLE_SYS_CONFIG1, 0x00),
+            reg_write(PINNACLE_FEED_CONFIG1, 0x00),
+            // ERA read of the ADC config
+            reg_write(PINNACLE_ERA_ADDR_HIGH, 0x01),
+            reg_write(PINNACLE_ERA_ADDR_LOW, 0x87),
+            reg_write(PINNACLE_ERA_CONTROL, ERA_CONTROL_READ),
+            reg_read(PINNACLE_ERA_CONTROL, vec![ERA_CONTROL_READ]),
+            reg_read(PINNACLE_ERA_CONTROL, vec![0x00]),
+            reg_read(PINNACLE_ERA_VALUE, vec![0x1e]),
+            reg_write(PINNACLE_STATUS1, 0x00),
+            // ERA write of the attenuation
+            reg_write(PINNACLE_ERA_VALUE, 0xde),
+            reg_write(PINNACLE_ERA_ADDR_HIGH, 0x01),
+            reg_write(PINNACLE_ERA_ADDR_LOW, 0x87),
+            reg_write(PINNACLE_ERA_CONTROL, ERA_CONTROL_WRITE),
+            reg_read(PINNACLE_ERA_CONTROL, vec![0x00]),
+            reg_write(PINNACLE_STATUS1, 0x00),
+            reg_write(PINNACLE_Z_IDLE, ABS_Z_IDLE_PACKETS),
+            reg_write(
+                PINNACLE_FEED_CONFIG2,
+                FEED_CONFIG2_SCROLL_DISABLE
+                    | FEED_CONFIG2_GLIDE_EXTEND_DISABLE
+                    | FEED_CONFIG2_ALL_TAPS_DISABLE
+                    | FEED_CONFIG2_SECONDARY_TAP_DISABLE,
+            ),
+            reg_write(
+                PINNACLE_FEED_CONFIG1,
+                FEED_CONFIG1_FEED_ENABLE | FEED_CONFIG1_ABSOLUTE_MODE,
+            ),
+            reg_write(PINNACLE_STATUS1, 0x00),
+        ];
+
+        let mut device = driver(&expectations, PinnacleConfig::default());
+        block_on(device.init()).unwrap();
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_init_relative_curved_overlay() {
+        let era_write = |addr: u16, value: u8| {
+            vec![
+                reg_write(PINNACLE_ERA_VALUE, value),
+                reg_write(PINNACLE_ERA_ADDR_HIGH, (addr >> 8) as u8),
+                reg_write(PINNACLE_ERA_ADDR_LOW, (addr & 0xff) as u8),
+                reg_write(PINNACLE_ERA_CONTROL, ERA_CONTROL_WRITE),
+                reg_read(PINNACLE_ERA_CONTROL, vec![0x00]),
+                reg_write(PINNACLE_STATUS1, 0x00),
+            ]
+        };
+
+        let mut expectations = vec![
+            reg_read(PINNACLE_FIRMWARE_ID, vec![FIRMWARE_ID_PINNACLE]),
+            reg_write(PINNACLE_STATUS1, 0x00),
+            reg_write(PINNACLE_SYS_CONFIG1, SYS_CONFIG1_SLEEP_ENABLE),
+            reg_write(PINNACLE_FEED_CONFIG1, 0x00),
+            reg_write(PINNACLE_ERA_ADDR_HIGH, 0x01),
+            reg_write(PINNACLE_ERA_ADDR_LOW, 0x87),
+            reg_write(PINNACLE_ERA_CONTROL, ERA_CONTROL_READ),
+            reg_read(PINNACLE_ERA_CONTROL, vec![0x00]),
+            reg_read(PINNACLE_ERA_VALUE, vec![0xde]),
+            reg_write(PINNACLE_STATUS1, 0x00),
+        ];
+        expectations.extend(era_write(ERA_TRACK_ADC_CONFIG, 0x5e));
+        expectations.extend(era_write(ERA_X_AXIS_WIDE_Z_MIN, WIDE_Z_MIN_X_CURVED));
+        expectations.extend(era_write(ERA_Y_AXIS_WIDE_Z_MIN, WIDE_Z_MIN_Y_CURVED));
+        expectations.extend([
+            reg_write(PINNACLE_Z_IDLE, 0),
+            reg_write(
+                PINNACLE_FEED_CONFIG2,
+                FEED_CONFIG2_SCROLL_DISABLE | FEED_CONFIG2_GLIDE_EXTEND_DISABLE,
+            ),
+            reg_write(PINNACLE_FEED_CONFIG1, FEED_CONFIG1_FEED_ENABLE),
+            reg_write(PINNACLE_STATUS1, 0x00),
+        ]);
+
+        let config = PinnacleConfig {
+            mode: PinnacleDataMode::Relative,
+            attenuation: PinnacleAttenuation::X2,
+            curved_overlay: true,
+            secondary_tap: true,
+            sleep: true,
+            ..Default::default()
+        };
+        let mut device = driver(&expectations, config);
+        block_on(device.init()).unwrap();
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_init_invalid_firmware_id() {
+        let expectations = [reg_read(PINNACLE_FIRMWARE_ID, vec![0x0e])];
+
+        let mut device = driver(&expectations, PinnacleConfig::default());
+        let result = block_on(device.init());
+        assert_eq!(result, Err(PointingDriverError::InvalidProductId(0x0e)));
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_era_timeout() {
+        let mut expectations = vec![
+            reg_write(PINNACLE_ERA_ADDR_HIGH, 0x01),
+            reg_write(PINNACLE_ERA_ADDR_LOW, 0x87),
+            reg_write(PINNACLE_ERA_CONTROL, ERA_CONTROL_READ),
+        ];
+        for _ in 0..ERA_MAX_POLLS {
+            expectations.push(reg_read(PINNACLE_ERA_CONTROL, vec![ERA_CONTROL_READ]));
+        }
+
+        let mut device = driver(&expectations, PinnacleConfig::default());
+        let result = block_on(device.era_read(ERA_TRACK_ADC_CONFIG));
+        assert_eq!(result, Err(PinnacleError::EraTimeout));
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_read_relative_motion() {
+        let expectations = [
+            reg_read(PINNACLE_STATUS1, vec![STATUS1_SW_DR]),
+            // Primary button, negative X
+            reg_read(PINNACLE_PACKET_BYTE0, vec![RELATIVE_X_SIGN | 0x01, 0xf6, 0x05]),
+            reg_write(PINNACLE_STATUS1, 0x00),
+        ];
+        let config = PinnacleConfig {
+            mode: PinnacleDataMode::Relative,
+            ..Default::default()
+        };
+
+        let mut device = driver(&expectations, config);
+        let mut sub = PointingButtonEvent::subscriber();
+        let motion = block_on(device.read_motion()).unwrap();
+        assert_eq!(motion.dx, -10);
+        assert_eq!(motion.dy, -5);
+        assert_eq!(
+            sub.try_next_message_pure(),
+            Some(PointingButtonEvent {
+                device_id: 0,
+                buttons: 0x01
+            })
+        );
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_read_without_data_ready() {
+        let expectations = [reg_read(PINNACLE_STATUS1, vec![0x00])];
+
+        let mut device = driver(&expectations, PinnacleConfig::default());
+        let motion = block_on(device.read_motion()).unwrap();
+        assert_eq!(motion.dx, 0);
+        assert_eq!(motion.dy, 0);
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_read_absolute_motion_and_tap() {
+        let expectations = [
+            reg_read(PINNACLE_STATUS1, vec![STATUS1_SW_DR]),
+            reg_read(PINNACLE_PACKET_BYTE0, absolute_packet(1000, 700, 20)),
+            reg_write(PINNACLE_STATUS1, 0x00),
+            reg_read(PINNACLE_STATUS1, vec![STATUS1_SW_DR]),
+            reg_read(PINNACLE_PACKET_BYTE0, absolute_packet(1020, 690, 20)),
+            reg_write(PINNACLE_STATUS1, 0x00),
+            reg_read(PINNACLE_STATUS1, vec![STATUS1_SW_DR]),
+            reg_read(PINNACLE_PACKET_BYTE0, absolute_packet(0, 0, 0)),
+            reg_write(PINNACLE_STATUS1, 0x00),
+        ];
+
+        let mut device = driver(&expectations, PinnacleConfig::default());
+        let mut sub = PointingButtonEvent::subscriber();
+
+        // Touch down doesn't generate motion
+        let motion = block_on(device.read_motion()).unwrap();
+        assert_eq!((motion.dx, motion.dy), (0, 0));
+
+        // 20 counts * 800 CPI / (1792 counts / (35mm / 25.4mm per inch)) = 12.3
+        let motion = block_on(device.read_motion()).unwrap();
+        assert_eq!(motion.dx, 12);
+        assert_eq!(motion.dy, -7);
+
+        // Short touch with little travel is a tap
+        let motion = block_on(device.read_motion()).unwrap();
+        assert_eq!((motion.dx, motion.dy), (0, 0));
+        assert_eq!(
+            sub.try_next_message_pure(),
+            Some(PointingButtonEvent {
+                device_id: 0,
+                buttons: TAP_BUTTON
+            })
+        );
+        assert_eq!(
+            sub.try_next_message_pure(),
+            Some(PointingButtonEvent {
+                device_id: 0,
+                buttons: 0
+            })
+        );
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_absolute_drag_is_not_tap() {
+        let mut device = driver(&[], PinnacleConfig::default());
+        let mut sub = PointingButtonEvent::subscriber();
+
+        let pos = |x, y| AbsolutePosition { x, y, z: 20 };
+        assert_eq!(device.process_absolute(pos(1000, 700)), (0, 0));
+        device.process_absolute(pos(1100, 700));
+        device.process_absolute(AbsolutePosition { x: 0, y: 0, z: 0 });
+
+        assert_eq!(sub.try_next_message_pure(), None);
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_scale_carries_remainder() {
+        let mut remainder = 0;
+        let mut total = 0;
+        // One count is less than one output count at 800 CPI
+        for _ in 0..10 {
+            total +=
+                Pinnacle::<PinnacleI2c<I2cMock>, PinMock>::scale(1, ABS_X_MAX - ABS_X_MIN, 800, 35, &mut remainder);
+        }
+        assert_eq!(total, 6);
+    }
+
+    #[test]
+    fn test_set_resolution() {
+        let mut device = driver(&[], PinnacleConfig::default());
+        block_on(device.set_resolution(1600)).unwrap();
+        assert_eq!(device.config.cpi, 1600);
+        assert_eq!(block_on(device.set_resolution(0)), Err(PointingDriverError::InvalidCpi));
+        device.interface.i2c.done();
+
+        let config = PinnacleConfig {
+            mode: PinnacleDataMode::Relative,
+            ..Default::default()
+        };
+        let mut device = driver(&[], config);
+        assert_eq!(
+            block_on(device.set_resolution(1600)),
+            Err(PointingDriverError::NotImplementedError)
+        );
+        device.interface.i2c.done();
+    }
+
+    #[test]
+    fn test_spi_read_framing() {
+        let spi_expectations = [
+            SpiTrans::write_vec(vec![RAP_READ | PINNACLE_PACKET_BYTE0, RAP_FILLER, RAP_FILLER]),
+            SpiTrans::transfer_in_place(vec![RAP_FILLER, RAP_FILLER, RAP_FILLER_LAST], vec![0x01, 0x02, 0x03]),
+        ];
+        let cs = PinMock::new(&[PinTrans::set(PinState::Low), PinTrans::set(PinState::High)]);
+
+        let mut interface = PinnacleSpi::new(SpiMock::new(&spi_expectations), cs);
+        let mut data = [0u8; 3];
+        block_on(interface.read_regs(PINNACLE_PACKET_BYTE0, &mut data)).unwrap();
+        assert_eq!(data, [0x01, 0x02, 0x03]);
+        interface.spi.done();
+        interface.cs.done();
+    }
+
+    #[test]
+    fn test_spi_write() {
+        let spi_expectations = [SpiTrans::write_vec(vec![RAP_WRITE | PINNACLE_Z_IDLE, 0x05])];
+        let cs = PinMock::new(&[PinTrans::set(PinState::Low), PinTrans::set(PinState::High)]);
+
+        let mut interface = PinnacleSpi::new(SpiMock::new(&spi_expectations), cs);
+        block_on(interface.write_reg(PINNACLE_Z_IDLE, 0x05)).unwrap();
+        interface.spi.done();
+        interface.cs.done();
+    }
+
+    #[test]
+    fn test_data_ready_is_inverted() {
+        let pin = PinMock::new(&[PinTrans::get(PinState::High), PinTrans::get(PinState::Low)]);
+        let mut device = Pinnacle::new(
+            0,
+            PinnacleI2c::new(I2cMock::new(&[])),
+            Some(pin),
+            PinnacleConfig::default(),
+        );
+
+        assert!(device.motion_pending());
+        assert!(!device.motion_pending());
+        device.data_ready.as_mut().unwrap().0.done();
+        device.interface.i2c.done();
+    }
+}
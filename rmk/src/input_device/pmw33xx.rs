@@ -751,6 +751,7 @@ where
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         }
     }
 
@@ -783,6 +784,7 @@ where
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         }
     }
 }
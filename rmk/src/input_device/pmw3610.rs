@@ -541,6 +541,7 @@ where
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         }
     }
 }
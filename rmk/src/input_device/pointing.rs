@@ -1,5 +1,10 @@
 //! Common functionality across pointing devices
 
+#[cfg(feature = "pointing_cpi")]
+use core::cell::RefCell;
+
+#[cfg(feature = "pointing_cpi")]
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_time::{Duration, Instant, Timer};
 use embedded_hal::digital::InputPin;
 use embedded_hal_async::digital::Wait;
@@ -7,13 +12,69 @@ use futures::future::pending;
 use rmk_macro::{input_device, processor};
 use usbd_hid::descriptor::MouseReport;
 
+#[cfg(feature = "pointing_cpi")]
+use crate::RawMutex;
 use crate::channel::KEYBOARD_REPORT_CHANNEL;
-use crate::event::{Axis, AxisEvent, AxisValType, PointingEvent, PointingSetCpiEvent};
+#[cfg(feature = "pointing_cpi")]
+use crate::config::POINTING_CPI_DEVICE_MAX_NUM;
+#[cfg(feature = "pointing_cpi")]
+use crate::event::publish_event;
+use crate::event::{Axis, AxisEvent, AxisValType, PointingButtonEvent, PointingEvent, PointingSetCpiEvent};
 use crate::hid::Report;
 use crate::keymap::KeyMap;
 
+/// Device id which targets all pointing devices, e.g. in `PointingSetCpiEvent`
 pub const ALL_POINTING_DEVICES: u8 = 255;
 
+/// Last CPI set for each pointing device.
+///
+/// Pointing devices which start after the CPI is published, and split peripherals which (re)connect,
+/// get the CPI from here.
+#[cfg(feature = "pointing_cpi")]
+static POINTING_CPI: Mutex<RawMutex, RefCell<heapless::Vec<PointingSetCpiEvent, POINTING_CPI_DEVICE_MAX_NUM>>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// Save the CPI of pointing devices and publish it as `PointingSetCpiEvent`
+#[cfg(feature = "pointing_cpi")]
+pub(crate) fn set_pointing_cpi(event: PointingSetCpiEvent) {
+    POINTING_CPI.lock(|saved| {
+        let mut saved = saved.borrow_mut();
+        if event.device_id == ALL_POINTING_DEVICES {
+            saved.clear();
+        }
+        match saved.iter_mut().find(|e| e.device_id == event.device_id) {
+            Some(e) => *e = event,
+            None => {
+                if saved.push(event).is_err() {
+                    warn!(
+                        "Too many pointing devices, CPI of device {} isn't saved",
+                        event.device_id
+                    );
+                }
+            }
+        }
+    });
+    publish_event(event);
+}
+
+/// All saved CPI of pointing devices
+#[cfg(feature = "pointing_cpi")]
+pub(crate) fn saved_pointing_cpi() -> heapless::Vec<PointingSetCpiEvent, POINTING_CPI_DEVICE_MAX_NUM> {
+    POINTING_CPI.lock(|saved| saved.borrow().clone())
+}
+
+#[cfg(feature = "pointing_cpi")]
+fn saved_cpi_of(device_id: u8) -> Option<u16> {
+    POINTING_CPI.lock(|saved| {
+        let saved = saved.borrow();
+        saved
+            .iter()
+            .find(|e| e.device_id == device_id)
+            .or_else(|| saved.iter().find(|e| e.device_id == ALL_POINTING_DEVICES))
+            .map(|e| e.cpi)
+    })
+}
+
 /// Motion data from the sensor
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MotionData {
@@ -27,6 +88,8 @@ pub struct MotionData {
 pub enum PointingDriverError {
     /// SPI communication error
     Spi,
+    /// I2C communication error
+    I2c,
     /// Invalid product ID detected
     InvalidProductId(u8),
     /// Initialization failed
@@ -78,6 +141,8 @@ pub struct PointingDevice<S: PointingDriver> {
     pub last_report: Instant,
     pub accumulated_x: i32,
     pub accumulated_y: i32,
+    /// CPI received before the sensor is initialized, applied once it's ready
+    pub pending_cpi: Option<u16>,
 }
 
 impl<S: PointingDriver> PointingDevice<S> {
@@ -104,6 +169,13 @@ impl<S: PointingDriver> PointingDevice<S> {
                 Ok(()) => {
                     info!("PointingDevice {}: Sensor initialized successfully", self.id);
                     self.init_state = InitState::Ready;
+                    #[cfg(feature = "pointing_cpi")]
+                    if self.pending_cpi.is_none() {
+                        self.pending_cpi = saved_cpi_of(self.id);
+                    }
+                    if let Some(cpi) = self.pending_cpi.take() {
+                        self.set_resolution(cpi).await;
+                    }
                     return true;
                 }
                 Err(e) => {
@@ -176,11 +248,21 @@ impl<S: PointingDriver> PointingDevice<S> {
 
 impl<S: PointingDriver> PointingDevice<S> {
     async fn on_pointing_set_cpi_event(&mut self, e: PointingSetCpiEvent) {
-        if e.device_id == self.id {
-            info!("PointingDevice {}: Setting resolution to {}", self.id, e.cpi);
-            if let Err(err) = self.sensor.set_resolution(e.cpi).await {
-                debug!("PointingDevice {}: Setting resolution failed: {:?}", self.id, err);
-            }
+        if e.device_id != self.id && e.device_id != ALL_POINTING_DEVICES {
+            return;
+        }
+        if self.init_state == InitState::Ready {
+            self.set_resolution(e.cpi).await;
+        } else {
+            // The sensor can't be configured yet, e.g. the CPI restored from storage at startup
+            self.pending_cpi = Some(e.cpi);
+        }
+    }
+
+    async fn set_resolution(&mut self, cpi: u16) {
+        info!("PointingDevice {}: Setting resolution to {}", self.id, cpi);
+        if let Err(err) = self.sensor.set_resolution(cpi).await {
+            debug!("PointingDevice {}: Setting resolution failed: {:?}", self.id, err);
         }
     }
 
@@ -249,6 +331,33 @@ impl<S: PointingDriver> PointingDevice<S> {
     }
 }
 
+/// Max number of pointing devices whose own buttons are tracked at the same time
+const BUTTON_DEVICES_MAX: usize = 8;
+
+/// Buttons pressed by each pointing device itself, keyed by the device id
+#[derive(Default)]
+struct DeviceButtons {
+    pressed: heapless::Vec<PointingButtonEvent, BUTTON_DEVICES_MAX>,
+}
+
+impl DeviceButtons {
+    /// Replace the buttons of `event.device_id`, devices without pressed buttons aren't kept
+    fn update(&mut self, event: PointingButtonEvent) {
+        self.pressed.retain(|e| e.device_id != event.device_id);
+        if event.buttons != 0 && self.pressed.push(event).is_err() {
+            warn!(
+                "Too many pointing devices, buttons of device {} are ignored",
+                event.device_id
+            );
+        }
+    }
+
+    /// Buttons pressed by any device
+    fn buttons(&self) -> u8 {
+        self.pressed.iter().fold(0, |buttons, e| buttons | e.buttons)
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct PointingProcessorConfig {
     /// Invert X axis
@@ -260,20 +369,41 @@ pub struct PointingProcessorConfig {
 }
 
 /// PointingProcessor that converts motion events to mouse reports
-#[processor(subscribe = [PointingEvent])]
+#[processor(subscribe = [PointingEvent, PointingButtonEvent])]
 pub struct PointingProcessor<'a> {
     /// Reference to the keymap
     keymap: &'a KeyMap<'a>,
     config: PointingProcessorConfig,
+    /// Buttons pressed by the pointing devices themselves, e.g. trackpad taps
+    device_buttons: DeviceButtons,
 }
 
 impl<'a> PointingProcessor<'a> {
     /// Create a new pointing processor with default settings
     pub fn new(keymap: &'a KeyMap<'a>, config: PointingProcessorConfig) -> Self {
-        Self { keymap, config }
+        Self {
+            keymap,
+            config,
+            device_buttons: DeviceButtons::default(),
+        }
+    }
+
+    async fn on_pointing_button_event(&mut self, event: PointingButtonEvent) {
+        self.device_buttons.update(event);
+        let mouse_report = MouseReport {
+            buttons: self.keymap.mouse_buttons() | self.device_buttons.buttons(),
+            x: 0,
+            y: 0,
+            wheel: 0,
+            pan: 0,
+        };
+        KEYBOARD_REPORT_CHANNEL.send(Report::MouseReport(mouse_report)).await;
     }
 
     async fn on_pointing_event(&mut self, event: PointingEvent) {
+        #[cfg(feature = "_ble")]
+        crate::power::notify_activity();
+
         let mut x = 0i16;
         let mut y = 0i16;
 
@@ -295,7 +425,7 @@ impl<'a> PointingProcessor<'a> {
             (x, y) = (y, x);
         }
 
-        let buttons = self.keymap.mouse_buttons();
+        let buttons = self.keymap.mouse_buttons() | self.device_buttons.buttons();
         let mouse_report = MouseReport {
             buttons,
             x: x.clamp(i8::MIN as i16, i8::MAX as i16) as i8,
@@ -335,6 +465,7 @@ mod tests {
         pub fails_init: bool,
         pub motion_gpio: Option<DummyMotionPin>,
         pub read_called: bool,
+        pub cpi: Option<u16>,
     }
 
     impl PointingDriver for DummyDriver {
@@ -358,6 +489,11 @@ mod tests {
             self.motion_pending
         }
 
+        async fn set_resolution(&mut self, cpi: u16) -> Result<(), PointingDriverError> {
+            self.cpi = Some(cpi);
+            Ok(())
+        }
+
         fn motion_gpio(&mut self) -> Option<&mut Self::MOTION> {
             self.motion_gpio.as_mut()
         }
@@ -430,6 +566,7 @@ mod tests {
             fails_init: true,
             motion_gpio: None,
             read_called: false,
+            cpi: None,
         };
 
         let mut device = PointingDevice {
@@ -443,6 +580,7 @@ mod tests {
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         };
 
         let mut result = false;
@@ -472,6 +610,7 @@ mod tests {
             fails_init: false,
             motion_gpio: None,
             read_called: false,
+            cpi: None,
         };
 
         let mut device = PointingDevice {
@@ -485,6 +624,7 @@ mod tests {
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         };
 
         // Run the async try_init
@@ -494,6 +634,85 @@ mod tests {
         assert!(device.sensor.init_called, "Driver init should be called");
     }
 
+    #[test]
+    fn test_set_cpi_before_init_is_applied_after_init() {
+        let driver = DummyDriver {
+            motion_pending: false,
+            motion: MotionData { dx: 0, dy: 0 },
+            init_called: false,
+            fails_init: false,
+            motion_gpio: None,
+            read_called: false,
+            cpi: None,
+        };
+
+        let mut device = PointingDevice {
+            sensor: driver,
+            init_state: InitState::Pending,
+            poll_interval: Duration::from_millis(1),
+            id: 1,
+
+            report_interval: Duration::from_millis(1),
+            last_poll: Instant::MIN,
+            last_report: Instant::MIN,
+            accumulated_x: 0,
+            accumulated_y: 0,
+            pending_cpi: None,
+        };
+
+        // Events for other devices are ignored
+        block_on(device.on_pointing_set_cpi_event(PointingSetCpiEvent { device_id: 2, cpi: 800 }));
+        assert_eq!(device.pending_cpi, None);
+
+        block_on(device.on_pointing_set_cpi_event(PointingSetCpiEvent {
+            device_id: ALL_POINTING_DEVICES,
+            cpi: 1600,
+        }));
+        assert_eq!(device.pending_cpi, Some(1600));
+        assert_eq!(device.sensor.cpi, None);
+
+        assert!(block_on(device.try_init()));
+        assert_eq!(device.pending_cpi, None);
+        assert_eq!(device.sensor.cpi, Some(1600));
+
+        // Once ready, the CPI is applied immediately
+        block_on(device.on_pointing_set_cpi_event(PointingSetCpiEvent { device_id: 1, cpi: 400 }));
+        assert_eq!(device.sensor.cpi, Some(400));
+    }
+
+    #[test]
+    fn test_device_buttons_are_kept_per_device() {
+        let mut buttons = DeviceButtons::default();
+        buttons.update(PointingButtonEvent {
+            device_id: 0,
+            buttons: 0b01,
+        });
+        buttons.update(PointingButtonEvent {
+            device_id: 1,
+            buttons: 0b10,
+        });
+        assert_eq!(buttons.buttons(), 0b11);
+
+        // Releasing the buttons of one device keeps the buttons of the other
+        buttons.update(PointingButtonEvent {
+            device_id: 0,
+            buttons: 0,
+        });
+        assert_eq!(buttons.buttons(), 0b10);
+
+        // A new state of a device replaces its previous one
+        buttons.update(PointingButtonEvent {
+            device_id: 1,
+            buttons: 0b100,
+        });
+        assert_eq!(buttons.buttons(), 0b100);
+        buttons.update(PointingButtonEvent {
+            device_id: 1,
+            buttons: 0,
+        });
+        assert_eq!(buttons.buttons(), 0);
+    }
+
     #[test]
     fn test_poll_once_accumulate_motion() {
         let motion_pin = DummyMotionPin::new();
@@ -505,6 +724,7 @@ mod tests {
             fails_init: false,
             motion_gpio: Some(motion_pin),
             read_called: false,
+            cpi: None,
         };
 
         let mut device = PointingDevice {
@@ -518,6 +738,7 @@ mod tests {
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         };
 
         let inited = block_on(device.try_init());
@@ -537,6 +758,7 @@ mod tests {
             motion_pending: true,
             motion: MotionData { dx: 3, dy: -2 },
             read_called: false,
+            cpi: None,
             init_called: true,
             fails_init: false,
             motion_gpio: None,
@@ -551,6 +773,7 @@ mod tests {
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
             id: 1,
         };
 
@@ -574,6 +797,7 @@ mod tests {
             fails_init: false,
             motion_gpio: Some(motion_pin),
             read_called: false,
+            cpi: None,
         };
 
         let mut device = PointingDevice {
@@ -587,6 +811,7 @@ mod tests {
             last_report: Instant::MIN,
             accumulated_x: 0,
             accumulated_y: 0,
+            pending_cpi: None,
         };
 
         let start = Instant::now();
@@ -1,12 +1,14 @@
 use core::fmt::Debug;
 
-#[cfg(all(feature = "split", feature = "_ble"))]
+#[cfg(feature = "_ble")]
 use embassy_futures::select::{Either, select};
 use embassy_futures::yield_now;
 #[cfg(feature = "_ble")]
 use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Instant, Timer, with_deadline};
 use heapless::Vec;
+#[cfg(feature = "pointing_cpi")]
+use rmk_types::action::PointingAction;
 use rmk_types::action::{Action, KeyAction, KeyboardAction};
 use rmk_types::fork::StateBits;
 use rmk_types::keycode::{ConsumerKey, HidKeyCode, KeyCode, SpecialKey, SystemControlKey};
@@ -16,6 +18,8 @@ use rmk_types::morse::{MorseMode, MorsePattern, TAP};
 use rmk_types::mouse_button::MouseButtons;
 use usbd_hid::descriptor::{MediaKeyboardReport, SystemControlReport};
 
+#[cfg(feature = "_ble")]
+use crate::ble::profile_settings::{PROFILE_SETTINGS_CHANGED, active_default_layer, active_os_mode};
 use crate::channel::KEYBOARD_REPORT_CHANNEL;
 use crate::config::Hand;
 use crate::core_traits::Runnable;
@@ -38,6 +42,8 @@ use crate::{COMBO_MAX_NUM, FORK_MAX_NUM, MACRO_SPACE_SIZE, boot};
 
 pub mod combo;
 pub(crate) mod fork;
+#[cfg(feature = "gamepad")]
+pub(crate) mod gamepad;
 pub(crate) mod held_buffer;
 pub(crate) mod morse;
 pub(crate) mod mouse;
@@ -132,6 +138,11 @@ impl Runnable for Keyboard<'_> {
     /// Main keyboard processing task, it receives input devices result, processes keys.
     /// The report is sent using `send_report`.
     async fn run(&mut self) -> ! {
+        // Apply the CPI presets restored from storage
+        #[cfg(feature = "pointing_cpi")]
+        self.keymap
+            .with_pointing_cpi_mut(|config| config.devices.iter().for_each(|d| d.publish()));
+
         loop {
             // TODO: Now the unprocessed_events is only used in one-shot keys and clear peer key.
             // Maybe it can be removed in the future?
@@ -146,7 +157,7 @@ impl Runnable for Keyboard<'_> {
             } else {
                 // If mouse repeat is pending, race subscriber against deadline
                 let event = if let Some(deadline) = self.mouse.next_deadline() {
-                    match with_deadline(deadline, self.keyboard_event_subscriber.next_message_pure()).await {
+                    match with_deadline(deadline, self.next_keyboard_event()).await {
                         Ok(event) => event,
                         Err(_) => {
                             // Repeat deadline expired, fire repeat
@@ -156,7 +167,7 @@ impl Runnable for Keyboard<'_> {
                     }
                 } else {
                     // No repeat pending, wait indefinitely
-                    self.keyboard_event_subscriber.next_message_pure().await
+                    self.next_keyboard_event().await
                 };
                 self.process_inner(event).await
             };
@@ -241,6 +252,10 @@ pub struct Keyboard<'a> {
     /// Passkey entry state for BLE pairing
     #[cfg(feature = "passkey_entry")]
     passkey_entry_state: crate::ble::passkey::PasskeyEntryState,
+
+    /// Default layer of the keyboard while the active output overrides it, restored when the output has no default layer
+    #[cfg(feature = "_ble")]
+    own_default_layer: Option<u8>,
 }
 
 impl<'a> Keyboard<'a> {
@@ -271,6 +286,55 @@ impl<'a> Keyboard<'a> {
             steno: crate::keyboard::steno::StenoChord::new(),
             #[cfg(feature = "passkey_entry")]
             passkey_entry_state: crate::ble::passkey::PasskeyEntryState::new(),
+            #[cfg(feature = "_ble")]
+            own_default_layer: None,
+        }
+    }
+
+    /// Wait for the next keyboard event.
+    ///
+    /// The settings of the active output are applied when it changes while waiting.
+    async fn next_keyboard_event(&mut self) -> KeyboardEvent {
+        #[cfg(feature = "_ble")]
+        loop {
+            match select(
+                self.keyboard_event_subscriber.next_message_pure(),
+                PROFILE_SETTINGS_CHANGED.wait(),
+            )
+            .await
+            {
+                Either::First(event) => return event,
+                Either::Second(_) => self.apply_profile_settings(),
+            }
+        }
+
+        #[cfg(not(feature = "_ble"))]
+        self.keyboard_event_subscriber.next_message_pure().await
+    }
+
+    /// Apply the default layer of the active output.
+    /// Key overrides and the OS mode are looked up when keys are processed.
+    #[cfg(feature = "_ble")]
+    fn apply_profile_settings(&mut self) {
+        match active_default_layer() {
+            Some(layer) => {
+                let (_, _, num_layer) = self.keymap.get_keymap_config();
+                if (layer as usize) < num_layer {
+                    if self.own_default_layer.is_none() {
+                        self.own_default_layer = Some(self.keymap.get_default_layer());
+                    }
+                    info!("Set default layer of the active output: {}", layer);
+                    self.keymap.set_default_layer(layer);
+                } else {
+                    warn!("Invalid default layer of the active output: {}", layer);
+                }
+            }
+            None => {
+                if let Some(layer) = self.own_default_layer.take() {
+                    info!("Restore the default layer of the keyboard: {}", layer);
+                    self.keymap.set_default_layer(layer);
+                }
+            }
         }
     }
 
@@ -371,6 +435,8 @@ impl<'a> Keyboard<'a> {
         // Update activity time for BLE split central sleep management
         #[cfg(all(feature = "split", feature = "_ble"))]
         update_activity_time();
+        #[cfg(feature = "_ble")]
+        crate::power::notify_activity();
 
         // Capture the event time once per event and thread it through.
         let event_time = Instant::now();
@@ -1277,10 +1343,44 @@ impl<'a> Keyboard<'a> {
                     crate::keyboard::steno::try_send(report);
                 }
             }
+            #[cfg(feature = "gamepad")]
+            Action::GamepadButton(button) => {
+                if let Some(report) = crate::keyboard::gamepad::update(|s| s.set_button(button, event.pressed)) {
+                    self.send_report(report).await;
+                }
+            }
+            #[cfg(feature = "gamepad")]
+            Action::GamepadHat(direction) => {
+                if let Some(report) = crate::keyboard::gamepad::update(|s| s.set_hat(direction, event.pressed)) {
+                    self.send_report(report).await;
+                }
+            }
+            #[cfg(feature = "pointing_cpi")]
+            Action::Pointing(pointing_action) => {
+                if event.pressed {
+                    self.process_action_pointing(pointing_action).await;
+                }
+            }
             _ => warn!("Action variant not supported: {:?}", action),
         }
     }
 
+    /// Switch the CPI preset of pointing devices, the new preset is saved to storage
+    #[cfg(feature = "pointing_cpi")]
+    async fn process_action_pointing(&mut self, action: PointingAction) {
+        let changed = self.keymap.with_pointing_cpi_mut(|config| config.update(action));
+        for device in changed.iter() {
+            device.publish();
+            #[cfg(feature = "storage")]
+            crate::channel::FLASH_CHANNEL
+                .send(crate::storage::FlashOperationMessage::PointingCpi {
+                    device_id: device.device_id,
+                    preset: device.current,
+                })
+                .await;
+        }
+    }
+
     /// Tap action, send a key when the key is pressed, then release the key.
     async fn process_key_action_tap(&mut self, action: Action, mut event: KeyboardEvent) {
         debug!("TAP action: {:?}, {:?}", action, event);
@@ -1377,7 +1477,7 @@ impl<'a> Keyboard<'a> {
     async fn process_hid_keycode(&mut self, key: HidKeyCode, event: KeyboardEvent) {
         #[cfg(feature = "passkey_entry")]
         if self.passkey_entry_state.is_active() {
-            use crate::ble::passkey::{PASSKEY_RESPONSE, PasskeyAction};
+            use crate::ble::passkey::{PASSKEY_CONFIRMATION, PASSKEY_RESPONSE, PasskeyAction};
 
             // In passkey mode: capture on release only (prevents Enter release leaking)
             if !event.pressed {
@@ -1390,6 +1490,14 @@ impl<'a> Keyboard<'a> {
                         info!("[passkey] Cancelled");
                         PASSKEY_RESPONSE.signal(None);
                     }
+                    PasskeyAction::Confirmed => {
+                        info!("[passkey] Confirmed");
+                        PASSKEY_CONFIRMATION.signal(true);
+                    }
+                    PasskeyAction::Rejected => {
+                        info!("[passkey] Rejected");
+                        PASSKEY_CONFIRMATION.signal(false);
+                    }
                     _ => {
                         // Ignore other states
                     }
@@ -1623,6 +1731,13 @@ impl<'a> Keyboard<'a> {
                 } else if id == NUM_BLE_PROFILE as u8 + 3 {
                     // User11:
                     BLE_PROFILE_CHANNEL.send(BleProfileAction::ToggleConnection).await;
+                } else if id == NUM_BLE_PROFILE as u8 + 5 {
+                    // User13: Forget the disconnected split peripherals and pair new ones
+                    #[cfg(feature = "split")]
+                    crate::split::ble::central::pair_new_peripheral();
+                } else if id == NUM_BLE_PROFILE as u8 + 6 {
+                    // User14: Open the pairing window of the current profile
+                    crate::ble::open_pairing_window();
                 }
             }
         }
@@ -1706,6 +1821,13 @@ impl<'a> Keyboard<'a> {
     pub(crate) async fn send_keyboard_report_with_resolved_modifiers(&mut self, pressed: bool) {
         // all modifier related effects are combined here to be sent with the hid report:
         let modifiers = self.resolve_modifiers(pressed);
+        // Cmd of a macOS host is on the Ctrl key
+        #[cfg(feature = "_ble")]
+        let modifiers = if active_os_mode().swaps_ctrl_gui() {
+            modifiers.swap_ctrl_gui()
+        } else {
+            modifiers
+        };
         info!(
             "Sending keyboard report, modifiers: {:?}, keycodes: {:?}",
             modifiers, &self.held_keycodes,
@@ -1816,9 +1938,7 @@ impl<'a> Keyboard<'a> {
     fn register_modifier_key(&mut self, key: HidKeyCode) {
         self.held_modifiers |= key.to_hid_modifiers();
 
-        publish_event(ModifierEvent {
-            modifier: self.held_modifiers,
-        });
+        self.publish_held_modifiers();
 
         // if a modifier key arrives after fork activation, it should be kept
         self.fork_keep_mask |= key.to_hid_modifiers();
@@ -1828,18 +1948,14 @@ impl<'a> Keyboard<'a> {
     fn unregister_modifier_key(&mut self, key: HidKeyCode) {
         self.held_modifiers &= !key.to_hid_modifiers();
 
-        publish_event(ModifierEvent {
-            modifier: self.held_modifiers,
-        });
+        self.publish_held_modifiers();
     }
 
     /// Register a modifier combination to be sent in hid report.
     fn register_modifiers(&mut self, modifiers: ModifierCombination) {
         self.held_modifiers |= modifiers;
 
-        publish_event(ModifierEvent {
-            modifier: self.held_modifiers,
-        });
+        self.publish_held_modifiers();
 
         // if a modifier key arrives after fork activation, it should be kept
         self.fork_keep_mask |= modifiers;
@@ -1849,6 +1965,13 @@ impl<'a> Keyboard<'a> {
     fn unregister_modifiers(&mut self, modifiers: ModifierCombination) {
         self.held_modifiers &= !modifiers;
 
+        self.publish_held_modifiers();
+    }
+
+    /// Publish the held modifiers, they're also kept for split peripherals connected later
+    fn publish_held_modifiers(&self) {
+        #[cfg(feature = "split")]
+        crate::split::snapshot::set_modifiers(self.held_modifiers.into_bits());
         publish_event(ModifierEvent {
             modifier: self.held_modifiers,
         });
@@ -2440,4 +2563,67 @@ mod test {
 
         block_on(main);
     }
+
+    #[cfg(feature = "_ble")]
+    #[test]
+    fn test_apply_profile_settings_on_profile_switch() {
+        use core::sync::atomic::Ordering;
+
+        use rmk_types::ble::{BleState, BleStatus};
+
+        use crate::ble::BLE_STATUS;
+        use crate::ble::profile_settings::{OutputProfile, ProfileSettings, set_profile_settings};
+        use crate::event::ConnectionType;
+        use crate::state::CONNECTION_TYPE;
+
+        fn switch_profile(profile: u8) {
+            BLE_STATUS.lock(|c| {
+                c.set(BleStatus {
+                    profile,
+                    state: BleState::Inactive,
+                })
+            });
+        }
+
+        let main = async {
+            let mut keyboard = create_test_keyboard();
+            CONNECTION_TYPE.store(ConnectionType::Ble.into(), Ordering::SeqCst);
+            switch_profile(0);
+
+            let settings = |default_layer| ProfileSettings {
+                default_layer,
+                ..Default::default()
+            };
+            assert!(set_profile_settings(OutputProfile::Ble(0), settings(Some(0))).await);
+            assert!(set_profile_settings(OutputProfile::Ble(1), settings(Some(1))).await);
+            // The layer doesn't exist in the keymap
+            assert!(set_profile_settings(OutputProfile::Ble(2), settings(Some(5))).await);
+
+            // Default layer of the keyboard
+            keyboard.keymap.set_default_layer(1);
+            keyboard.apply_profile_settings();
+            assert_eq!(keyboard.keymap.get_default_layer(), 0);
+
+            switch_profile(1);
+            keyboard.apply_profile_settings();
+            assert_eq!(keyboard.keymap.get_default_layer(), 1);
+
+            // An invalid default layer is ignored
+            switch_profile(2);
+            keyboard.apply_profile_settings();
+            assert_eq!(keyboard.keymap.get_default_layer(), 1);
+
+            switch_profile(0);
+            keyboard.apply_profile_settings();
+            assert_eq!(keyboard.keymap.get_default_layer(), 0);
+
+            // The default layer of the keyboard is restored on a profile without default layer
+            assert!(set_profile_settings(OutputProfile::Ble(2), settings(None)).await);
+            switch_profile(2);
+            keyboard.apply_profile_settings();
+            assert_eq!(keyboard.keymap.get_default_layer(), 1);
+        };
+
+        block_on(main);
+    }
 }
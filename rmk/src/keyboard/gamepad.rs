@@ -0,0 +1,160 @@
+//! Gamepad state shared by keys and analog inputs.
+//!
+//! Buttons and the hat switch are driven by `Action::GamepadButton` and
+//! `Action::GamepadHat` in the keymap, axes are fed by input processors, e.g.
+//! `JoystickProcessor` in gamepad mode. Every change sends the full state
+//! to the host, so the state lives in a global shared by both sides.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use rmk_types::gamepad::{GAMEPAD_BUTTON_NUM, GamepadButton, GamepadHat};
+
+use crate::RawMutex;
+use crate::hid::{GamepadReport, Report};
+
+static GAMEPAD_STATE: Mutex<RawMutex, Cell<GamepadState>> = Mutex::new(Cell::new(GamepadState::new()));
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct GamepadState {
+    buttons: u32,
+    /// Bitmap of the pressed hat directions, indexed by `GamepadHat`
+    hat: u8,
+    axes: [i8; 6],
+}
+
+impl GamepadState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buttons: 0,
+            hat: 0,
+            axes: [0; 6],
+        }
+    }
+
+    /// Update a button, buttons out of range are ignored
+    pub(crate) fn set_button(&mut self, button: GamepadButton, pressed: bool) {
+        let idx = button.index();
+        if idx >= GAMEPAD_BUTTON_NUM {
+            return;
+        }
+        if pressed {
+            self.buttons |= 1 << idx;
+        } else {
+            self.buttons &= !(1 << idx);
+        }
+    }
+
+    pub(crate) fn set_hat(&mut self, direction: GamepadHat, pressed: bool) {
+        let mask = 1 << direction as u8;
+        if pressed {
+            self.hat |= mask;
+        } else {
+            self.hat &= !mask;
+        }
+    }
+
+    /// Set the axes from index `start`, values out of the array are ignored
+    pub(crate) fn set_axes(&mut self, start: usize, values: &[i8]) {
+        for (axis, v) in self.axes.iter_mut().skip(start).zip(values.iter()) {
+            *axis = *v;
+        }
+    }
+
+    /// Get the hat switch value. Opposite directions cancel each other out.
+    fn hat_value(&self) -> u8 {
+        let pressed = |d: GamepadHat| self.hat & (1 << d as u8) != 0;
+        let vertical = pressed(GamepadHat::Down) as i8 - pressed(GamepadHat::Up) as i8;
+        let horizontal = pressed(GamepadHat::Right) as i8 - pressed(GamepadHat::Left) as i8;
+        match (vertical, horizontal) {
+            (-1, 0) => 0,
+            (-1, 1) => 1,
+            (0, 1) => 2,
+            (1, 1) => 3,
+            (1, 0) => 4,
+            (1, -1) => 5,
+            (0, -1) => 6,
+            (-1, -1) => 7,
+            _ => GamepadReport::HAT_CENTERED,
+        }
+    }
+
+    pub(crate) fn report(&self) -> GamepadReport {
+        GamepadReport {
+            buttons: self.buttons,
+            hat: self.hat_value(),
+            axes: self.axes,
+        }
+    }
+}
+
+/// Update the global gamepad state, returns the report of the new state if it's changed
+pub(crate) fn update(f: impl FnOnce(&mut GamepadState)) -> Option<Report> {
+    GAMEPAD_STATE.lock(|state| {
+        let last = state.get();
+        let mut s = last;
+        f(&mut s);
+        state.set(s);
+        (s.report() != last.report()).then(|| Report::GamepadReport(s.report()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hat(directions: &[GamepadHat]) -> u8 {
+        let mut s = GamepadState::new();
+        for d in directions {
+            s.set_hat(*d, true);
+        }
+        s.report().hat
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut s = GamepadState::new();
+        s.set_button(GamepadButton(0), true);
+        s.set_button(GamepadButton(31), true);
+        assert_eq!(s.report().buttons, 0x8000_0001);
+        s.set_button(GamepadButton(0), false);
+        assert_eq!(s.report().buttons, 0x8000_0000);
+        s.set_button(GamepadButton(32), true);
+        assert_eq!(s.report().buttons, 0x8000_0000);
+    }
+
+    #[test]
+    fn test_hat() {
+        use GamepadHat::*;
+        assert_eq!(hat(&[]), GamepadReport::HAT_CENTERED);
+        assert_eq!(hat(&[Up]), 0);
+        assert_eq!(hat(&[Up, Right]), 1);
+        assert_eq!(hat(&[Right]), 2);
+        assert_eq!(hat(&[Down, Right]), 3);
+        assert_eq!(hat(&[Down]), 4);
+        assert_eq!(hat(&[Down, Left]), 5);
+        assert_eq!(hat(&[Left]), 6);
+        assert_eq!(hat(&[Up, Left]), 7);
+        // Opposite directions cancel each other out
+        assert_eq!(hat(&[Up, Down]), GamepadReport::HAT_CENTERED);
+        assert_eq!(hat(&[Up, Down, Left]), 6);
+        assert_eq!(hat(&[Up, Down, Left, Right]), GamepadReport::HAT_CENTERED);
+    }
+
+    #[test]
+    fn test_hat_release() {
+        let mut s = GamepadState::new();
+        s.set_hat(GamepadHat::Up, true);
+        s.set_hat(GamepadHat::Left, true);
+        s.set_hat(GamepadHat::Up, false);
+        assert_eq!(s.report().hat, 6);
+    }
+
+    #[test]
+    fn test_axes() {
+        let mut s = GamepadState::new();
+        s.set_axes(0, &[1, 2]);
+        s.set_axes(4, &[5, 6, 7]);
+        assert_eq!(s.report().axes, [1, 2, 0, 0, 5, 6]);
+    }
+}
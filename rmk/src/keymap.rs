@@ -11,8 +11,10 @@ use {
 };
 
 use crate::MACRO_SPACE_SIZE;
+#[cfg(feature = "pointing_cpi")]
+use crate::config::PointingCpiConfig;
 use crate::config::{BehaviorConfig, Hand, MouseKeyConfig, OneShotModifiersConfig, PositionalConfig};
-use crate::event::{KeyboardEvent, KeyboardEventPos, LayerChangeEvent, publish_event};
+use crate::event::{DefaultLayerChangeEvent, KeyboardEvent, KeyboardEventPos, LayerChangeEvent, publish_event};
 use crate::input_device::rotary_encoder::Direction;
 use crate::keyboard::combo::Combo;
 use crate::keyboard_macros::MacroOperation;
@@ -73,6 +75,13 @@ pub(crate) fn fill_vec<T: Default + Clone, const N: usize>(vector: &mut heapless
         .expect("impossible error, as we resize to the capacity of the vector!");
 }
 
+/// Publish the activated layer, it's also kept for split peripherals connected later
+fn publish_layer(layer: u8) {
+    #[cfg(feature = "split")]
+    crate::split::snapshot::set_layer(layer);
+    publish_event(LayerChangeEvent::new(layer));
+}
+
 /// KeyMap with hidden interior mutability.
 ///
 /// Consumers use `&KeyMap` with plain method calls — no generics needed.
@@ -146,6 +155,9 @@ impl KeyMapInner<'_> {
 
     fn set_default_layer(&mut self, layer_num: u8) {
         self.default_layer = layer_num;
+        #[cfg(feature = "split")]
+        crate::split::snapshot::set_default_layer(layer_num);
+        publish_event(DefaultLayerChangeEvent::new(layer_num));
     }
 
     fn get_action_at(&self, pos: KeyboardEventPos, layer_num: usize) -> KeyAction {
@@ -196,15 +208,26 @@ impl KeyMapInner<'_> {
         }
     }
 
+    /// Action at the position, with the key overrides of the active output applied
+    fn get_effective_action_at(&self, pos: KeyboardEventPos, layer_num: usize) -> KeyAction {
+        #[cfg(feature = "_ble")]
+        if let KeyboardEventPos::Key(key_pos) = pos
+            && let Some(action) = crate::ble::profile_settings::key_override(layer_num as u8, key_pos.row, key_pos.col)
+        {
+            return action;
+        }
+        self.get_action_at(pos, layer_num)
+    }
+
     fn get_action_with_layer_cache(&mut self, event: KeyboardEvent) -> KeyAction {
         if !event.pressed {
             let layer = self.pop_layer_from_cache(event.pos);
-            return self.get_action_at(event.pos, layer as usize);
+            return self.get_effective_action_at(event.pos, layer as usize);
         }
 
         for layer_idx in (0..self.num_layer).rev() {
             if self.layer_state[layer_idx] || layer_idx as u8 == self.default_layer {
-                let action = self.get_action_at(event.pos, layer_idx);
+                let action = self.get_effective_action_at(event.pos, layer_idx);
                 if action == KeyAction::Transparent {
                     continue;
                 }
@@ -275,7 +298,7 @@ impl KeyMapInner<'_> {
         if self.num_layer > 3 {
             self.layer_state[3] = self.layer_state[1] && self.layer_state[2];
             let layer = self.get_activated_layer();
-            publish_event(LayerChangeEvent::new(layer));
+            publish_layer(layer);
         }
     }
 
@@ -285,7 +308,7 @@ impl KeyMapInner<'_> {
                 self.layer_state[tri_layer[0] as usize] && self.layer_state[tri_layer[1] as usize];
         }
         let layer = self.get_activated_layer();
-        publish_event(LayerChangeEvent::new(layer));
+        publish_layer(layer);
     }
 
     fn activate_layer(&mut self, layer_num: u8) {
@@ -597,6 +620,12 @@ impl<'a> KeyMap<'a> {
         f(&mut inner.behavior.combo.combos)
     }
 
+    #[cfg(feature = "pointing_cpi")]
+    pub(crate) fn with_pointing_cpi_mut<R>(&self, f: impl FnOnce(&mut PointingCpiConfig) -> R) -> R {
+        let mut inner = self.inner.borrow_mut();
+        f(&mut inner.behavior.pointing_cpi)
+    }
+
     // ── Macros ──
 
     pub(crate) fn get_macro_sequence_start(&self, idx: u8) -> Option<usize> {
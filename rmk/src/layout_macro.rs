@@ -677,3 +677,78 @@ macro_rules! steno {
         ))
     };
 }
+
+/// Create a gamepad button action, the button index is 0-31
+///
+/// Example:
+/// ```ignore
+/// gamepad!(0)
+/// ```
+#[cfg(feature = "gamepad")]
+#[macro_export]
+macro_rules! gamepad {
+    ($button: expr) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::GamepadButton(
+            $crate::types::gamepad::GamepadButton($button),
+        ))
+    };
+}
+
+/// Create a gamepad hat switch action, the direction is one of `Up`, `Down`, `Left` and `Right`
+///
+/// Example:
+/// ```ignore
+/// hat!(Up)
+/// ```
+#[cfg(feature = "gamepad")]
+#[macro_export]
+macro_rules! hat {
+    ($direction: ident) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::GamepadHat(
+            $crate::types::gamepad::GamepadHat::$direction,
+        ))
+    };
+}
+
+/// Create a CPI control action for pointing devices, the operation is one of `Up`, `Down`, `Cycle` and `Preset`.
+///
+/// The device id is optional, all pointing devices are targeted when it's omitted.
+///
+/// Example:
+/// ```ignore
+/// // Next CPI preset of all pointing devices
+/// cpi!(Up)
+/// // Previous CPI preset of the pointing device 1
+/// cpi!(Down, 1)
+/// // CPI preset 2 of the pointing device 0
+/// cpi!(Preset, 2, 0)
+/// ```
+#[cfg(feature = "pointing_cpi")]
+#[macro_export]
+macro_rules! cpi {
+    (Preset, $preset: expr) => {
+        $crate::cpi!(Preset, $preset, $crate::input_device::pointing::ALL_POINTING_DEVICES)
+    };
+    (Preset, $preset: expr, $device: expr) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::Pointing(
+            $crate::types::action::PointingAction::CpiPreset($device, $preset),
+        ))
+    };
+    (Up $(, $device: expr)?) => {
+        $crate::cpi!(@single CpiUp $(, $device)?)
+    };
+    (Down $(, $device: expr)?) => {
+        $crate::cpi!(@single CpiDown $(, $device)?)
+    };
+    (Cycle $(, $device: expr)?) => {
+        $crate::cpi!(@single CpiCycle $(, $device)?)
+    };
+    (@single $variant: ident) => {
+        $crate::cpi!(@single $variant, $crate::input_device::pointing::ALL_POINTING_DEVICES)
+    };
+    (@single $variant: ident, $device: expr) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::Pointing(
+            $crate::types::action::PointingAction::$variant($device),
+        ))
+    };
+}
@@ -37,7 +37,11 @@ use core::sync::atomic::Ordering;
 
 #[cfg(feature = "_ble")]
 use bt_hci::{
-    cmd::le::{LeReadLocalSupportedFeatures, LeSetPhy},
+    cmd::le::{
+        LeAddDeviceToFilterAcceptList, LeAddDeviceToResolvingList, LeClearFilterAcceptList, LeClearResolvingList,
+        LeReadLocalSupportedFeatures, LeSetAddrResolutionEnable, LeSetPhy,
+    },
+    cmd::status::ReadRssi,
     controller::{ControllerCmdAsync, ControllerCmdSync},
 };
 use config::RmkConfig;
@@ -51,6 +55,8 @@ use embassy_usb::driver::Driver;
 pub use futures;
 use futures::FutureExt;
 pub use heapless;
+#[cfg(all(not(feature = "_ble"), feature = "gamepad"))]
+use hid::GamepadReport;
 #[cfg(all(not(feature = "_ble"), feature = "steno"))]
 use hid::StenoReport;
 #[cfg(all(feature = "host", not(feature = "_no_usb"), not(feature = "_ble")))]
@@ -90,6 +96,8 @@ pub mod channel;
 pub mod config;
 pub mod core_traits;
 pub mod debounce;
+#[cfg(feature = "dfu")]
+pub mod dfu;
 #[cfg(feature = "display")]
 pub mod display;
 pub mod driver;
@@ -105,6 +113,8 @@ pub mod keymap;
 pub mod layout_macro;
 pub mod light;
 pub mod matrix;
+#[cfg(feature = "_ble")]
+pub mod power;
 pub mod processor;
 #[cfg(feature = "split")]
 pub mod split;
@@ -160,6 +170,8 @@ pub async fn initialize_keymap_and_storage<
             };
             Storage::new(flash, &data.keymap, &encoder_opt, storage_config, behavior_config).await
         };
+        #[cfg(feature = "pointing_cpi")]
+        storage.read_pointing_cpi(&mut behavior_config.pointing_cpi).await;
 
         let keymap = KeyMap::new_from_storage(data, Some(&mut storage), behavior_config, positional_config).await;
         (keymap, storage)
@@ -167,7 +179,10 @@ pub async fn initialize_keymap_and_storage<
 
     #[cfg(not(feature = "host"))]
     {
-        let storage = Storage::new(flash, storage_config, behavior_config).await;
+        #[cfg_attr(not(feature = "pointing_cpi"), allow(unused_mut))]
+        let mut storage = Storage::new(flash, storage_config, behavior_config).await;
+        #[cfg(feature = "pointing_cpi")]
+        storage.read_pointing_cpi(&mut behavior_config.pointing_cpi).await;
         let keymap = KeyMap::new(data, behavior_config, positional_config).await;
         (keymap, storage)
     }
@@ -176,7 +191,15 @@ pub async fn initialize_keymap_and_storage<
 #[allow(unreachable_code)]
 pub async fn run_rmk<
     #[cfg(feature = "_ble")] 'b,
-    #[cfg(feature = "_ble")] C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    #[cfg(feature = "_ble")] C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<LeClearFilterAcceptList>
+        + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+        + ControllerCmdSync<LeClearResolvingList>
+        + ControllerCmdSync<LeAddDeviceToResolvingList>
+        + ControllerCmdSync<LeSetAddrResolutionEnable>
+        + ControllerCmdSync<ReadRssi>,
     #[cfg(not(feature = "_no_usb"))] D: Driver<'static>,
 >(
     #[cfg(not(feature = "_no_usb"))] usb_driver: D,
@@ -210,6 +233,8 @@ pub async fn run_rmk<
         let mut other_writer = add_usb_writer!(&mut usb_builder, CompositeReport, 9, 16);
         #[cfg(feature = "steno")]
         let mut steno_writer = add_usb_writer!(&mut usb_builder, StenoReport, 9, 16);
+        #[cfg(feature = "gamepad")]
+        let mut gamepad_writer = add_usb_writer!(&mut usb_builder, GamepadReport, 11, 16);
         #[cfg(feature = "host")]
         let mut host_reader_writer = add_usb_reader_writer!(&mut usb_builder, ViaReport, 32, 32, 32);
 
@@ -262,6 +287,8 @@ pub async fn run_rmk<
                         &mut other_writer,
                         #[cfg(feature = "steno")]
                         &mut steno_writer,
+                        #[cfg(feature = "gamepad")]
+                        &mut gamepad_writer,
                     ),
                 );
 
@@ -14,6 +14,7 @@ use crate::debounce::{DebounceState, DebouncerTrait};
 use crate::event::{KeyboardEvent, publish_event_async};
 use crate::input_device::InputDevice;
 use crate::state::ConnectionState;
+pub mod analog_matrix;
 pub mod bidirectional_matrix;
 pub mod direct_pin;
 pub mod hc595_matrix;
@@ -512,8 +513,12 @@ where
             out.set_high().ok();
         }
 
-        // Wait for any key press
+        // Wait for any key press, the input pins wake the chip up from deep sleep meanwhile
+        #[cfg(feature = "_ble")]
+        crate::power::set_wake_armed(true);
         self.wait_input_pins().await;
+        #[cfg(feature = "_ble")]
+        crate::power::set_wake_armed(false);
 
         // Set all output pins back to low
         for out in self.get_output_pins_mut().iter_mut() {
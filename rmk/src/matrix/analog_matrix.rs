@@ -0,0 +1,609 @@
+//! Analog (Hall-effect) Matrix
+//!
+//! A keyboard matrix for magnetic switches. Each key is a Hall-effect sensor
+//! whose output voltage changes with the key travel. The sensors are connected
+//! to the ADC channels through analog multiplexers, the multiplexer channel is
+//! selected by GPIO outputs.
+//!
+//! Every key has its own actuation point and rapid trigger sensitivity, see
+//! [`AnalogKeyConfig`]. All distances are key travel in 0.01 mm.
+//!
+//! # Wiring
+//!
+//! ```text
+//!   MCU                   Multiplexer (xADC)    Sensors
+//!   ---------------------------------------------------
+//!   GPIO Out  -------->  S0..S(SEL-1)
+//!   ADC In    <--------  COM               <--  CH 0..CHANNEL
+//! ```
+//!
+//! # Calibration
+//!
+//! The rest level of a key is sampled in the first scan, so keys must not be
+//! pressed at power up. Until the key is pressed all the way down, the
+//! bottom-out level is assumed to be `bottom_out_delta` away from the rest
+//! level. Reading beyond the bottom-out level extends it. Updated calibration
+//! is saved to the storage when the key is released, and can be loaded at
+//! startup with `Storage::read_analog_calibration`.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use rmk::matrix::analog_matrix::{AnalogKeyConfig, AnalogMatrix};
+//!
+//! let calibration = storage.read_analog_calibration::<4, 16>().await;
+//! let mut matrix = AnalogMatrix::<_, _, 4, 4, 16, 4, 16>::new(
+//!     adc,                       // impl AdcReader<4>
+//!     select_pins,               // [impl OutputPin; 4]
+//!     positions,                 // [[Option<KeyPos>; 4]; 16]
+//!     AnalogKeyConfig::default(),
+//! )
+//! .with_calibration(calibration);
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal::digital::OutputPin;
+use heapless::Deque;
+use postcard::experimental::max_size::MaxSize;
+use rmk_macro::input_device;
+use serde::{Deserialize, Serialize};
+
+use crate::event::{KeyPos, KeyboardEvent};
+use crate::input_device::adc::AdcReader;
+use crate::matrix::MatrixTrait;
+
+/// Settle time of the multiplexer output after switching the channel
+const MUX_SETTLE_US: u64 = 10;
+/// The calibration is saved again only if the bottom-out level moved more than this
+const CALIBRATION_SAVE_THRESHOLD: u16 = 16;
+const EVENT_QUEUE_SIZE: usize = 16;
+
+/// Raw ADC levels of a key at rest and bottomed out.
+///
+/// `bottom` can be either larger or smaller than `rest`, depending on the polarity of the magnet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnalogKeyCalibration {
+    pub rest: u16,
+    pub bottom: u16,
+}
+
+impl AnalogKeyCalibration {
+    /// Convert the raw ADC value to key travel, in the range of `0..=full_travel`
+    fn travel(&self, raw: u16, full_travel: u16) -> u16 {
+        let range = self.bottom as i32 - self.rest as i32;
+        if range == 0 {
+            return 0;
+        }
+        let travel = (raw as i32 - self.rest as i32) * full_travel as i32 / range;
+        travel.clamp(0, full_travel as i32) as u16
+    }
+
+    /// Extend the bottom-out level if the raw value is beyond it
+    fn extend(&mut self, raw: u16) {
+        if (self.bottom > self.rest && raw > self.bottom) || (self.bottom < self.rest && raw < self.bottom) {
+            self.bottom = raw;
+        }
+    }
+}
+
+/// Actuation settings of an analog key, all values are key travel in 0.01 mm
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnalogKeyConfig {
+    /// The key is pressed when the travel reaches the actuation point
+    pub actuation: u16,
+    /// The key is released when the travel is below `actuation - release_hysteresis`
+    pub release_hysteresis: u16,
+    /// Rapid trigger sensitivity, 0 to disable it.
+    ///
+    /// When enabled, the key is released as soon as it moves up by this distance, and pressed
+    /// again as soon as it moves down by this distance, regardless of the actuation point.
+    pub rapid_trigger: u16,
+}
+
+impl Default for AnalogKeyConfig {
+    fn default() -> Self {
+        Self {
+            actuation: 150,
+            release_hysteresis: 10,
+            rapid_trigger: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct AnalogKey {
+    config: AnalogKeyConfig,
+    calibration: Option<AnalogKeyCalibration>,
+    /// Last calibration loaded from or saved to the storage
+    saved: Option<AnalogKeyCalibration>,
+    pressed: bool,
+    /// Deepest travel while pressed, shallowest travel while released
+    extreme: u16,
+    /// Rapid trigger is active until the key goes back above the release point
+    rt_armed: bool,
+}
+
+impl AnalogKey {
+    fn new(config: AnalogKeyConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Update the key with the new travel, returns the new pressed state if it's changed
+    fn update_travel(&mut self, travel: u16) -> Option<bool> {
+        let rt = self.config.rapid_trigger;
+        let release_point = self.config.actuation.saturating_sub(self.config.release_hysteresis);
+        if self.pressed {
+            self.extreme = self.extreme.max(travel);
+            let rt_release = rt > 0 && travel.saturating_add(rt) <= self.extreme;
+            if travel < release_point || rt_release {
+                self.pressed = false;
+                self.extreme = travel;
+                self.rt_armed = rt > 0 && travel >= release_point;
+                return Some(false);
+            }
+        } else {
+            if travel < release_point {
+                self.rt_armed = false;
+            }
+            let press = if self.rt_armed {
+                self.extreme = self.extreme.min(travel);
+                travel >= self.extreme.saturating_add(rt)
+            } else {
+                travel >= self.config.actuation
+            };
+            if press {
+                self.pressed = true;
+                self.extreme = travel;
+                return Some(true);
+            }
+        }
+        None
+    }
+}
+
+#[input_device(publish = KeyboardEvent)]
+pub struct AnalogMatrix<
+    A: AdcReader<ADC>,
+    S: OutputPin,
+    const SEL: usize,
+    const ADC: usize,
+    const CHANNEL: usize,
+    const ROW: usize,
+    const COL: usize,
+> {
+    adc: A,
+    /// Channel select pins of the multiplexers, the first one is the least significant bit
+    select_pins: [S; SEL],
+    /// Key position of each multiplexer channel and ADC input
+    positions: [[Option<KeyPos>; ADC]; CHANNEL],
+    keys: [[AnalogKey; COL]; ROW],
+    /// Key travel of a bottomed out key, in 0.01 mm
+    full_travel: u16,
+    /// Assumed raw difference between rest and bottom-out before the key is calibrated
+    bottom_out_delta: i16,
+    scan_interval: Duration,
+    events: Deque<KeyboardEvent, EVENT_QUEUE_SIZE>,
+}
+
+impl<
+    A: AdcReader<ADC>,
+    S: OutputPin,
+    const SEL: usize,
+    const ADC: usize,
+    const CHANNEL: usize,
+    const ROW: usize,
+    const COL: usize,
+> AnalogMatrix<A, S, SEL, ADC, CHANNEL, ROW, COL>
+{
+    pub fn new(
+        adc: A,
+        select_pins: [S; SEL],
+        positions: [[Option<KeyPos>; ADC]; CHANNEL],
+        config: AnalogKeyConfig,
+    ) -> Self {
+        const {
+            assert!(
+                SEL < usize::BITS as usize && CHANNEL <= 1 << SEL,
+                "AnalogMatrix has more channels than the select pins can address"
+            )
+        };
+        Self {
+            adc,
+            select_pins,
+            positions,
+            keys: [[AnalogKey::new(config); COL]; ROW],
+            full_travel: 400,
+            bottom_out_delta: 1000,
+            scan_interval: Duration::from_millis(1),
+            events: Deque::new(),
+        }
+    }
+
+    /// Set the actuation settings of a single key
+    pub fn with_key_config(mut self, row: usize, col: usize, config: AnalogKeyConfig) -> Self {
+        if let Some(key) = self.keys.get_mut(row).and_then(|r| r.get_mut(col)) {
+            key.config = config;
+        }
+        self
+    }
+
+    /// Use the saved calibration, keys without calibration are calibrated at runtime
+    pub fn with_calibration(mut self, calibration: [[Option<AnalogKeyCalibration>; COL]; ROW]) -> Self {
+        for (keys, cals) in self.keys.iter_mut().zip(calibration.iter()) {
+            for (key, cal) in keys.iter_mut().zip(cals.iter()) {
+                key.calibration = *cal;
+                key.saved = *cal;
+            }
+        }
+        self
+    }
+
+    /// Set the full key travel in 0.01 mm, 400 by default
+    pub fn with_full_travel(mut self, full_travel: u16) -> Self {
+        self.full_travel = full_travel.max(1);
+        self
+    }
+
+    /// Set the assumed raw difference between the rest and bottom-out levels of uncalibrated keys.
+    ///
+    /// Use a negative value if the ADC value decreases when the key is pressed.
+    pub fn with_bottom_out_delta(mut self, bottom_out_delta: i16) -> Self {
+        self.bottom_out_delta = bottom_out_delta;
+        self
+    }
+
+    /// Set the interval between two full scans, 1ms by default
+    pub fn with_scan_interval(mut self, scan_interval: Duration) -> Self {
+        self.scan_interval = scan_interval;
+        self
+    }
+
+    fn select_channel(&mut self, channel: usize) {
+        for (i, pin) in self.select_pins.iter_mut().enumerate() {
+            if channel & (1 << i) != 0 {
+                pin.set_high().ok();
+            } else {
+                pin.set_low().ok();
+            }
+        }
+    }
+
+    /// Scan all channels once, changed keys are pushed to the event queue
+    async fn scan(&mut self) {
+        for channel in 0..CHANNEL {
+            if SEL > 0 {
+                self.select_channel(channel);
+                Timer::after_micros(MUX_SETTLE_US).await;
+            }
+            let values = self.adc.read().await;
+            for (pos, raw) in self.positions[channel].iter().zip(values.iter()) {
+                if let Some(pos) = pos {
+                    self.update_key(*pos, *raw);
+                }
+            }
+        }
+    }
+
+    fn update_key(&mut self, pos: KeyPos, raw: u16) {
+        if self.events.is_full() {
+            // Check it again in the next scan
+            return;
+        }
+        let (full_travel, bottom_out_delta) = (self.full_travel, self.bottom_out_delta);
+        let Some(key) = self
+            .keys
+            .get_mut(pos.row as usize)
+            .and_then(|r| r.get_mut(pos.col as usize))
+        else {
+            warn!("Analog key out of bounds: ({}, {})", pos.row, pos.col);
+            return;
+        };
+        let calibration = key.calibration.get_or_insert_with(|| AnalogKeyCalibration {
+            rest: raw,
+            bottom: (raw as i32 + bottom_out_delta as i32).clamp(0, u16::MAX as i32) as u16,
+        });
+        calibration.extend(raw);
+        let travel = calibration.travel(raw, full_travel);
+
+        if let Some(pressed) = key.update_travel(travel) {
+            self.events
+                .push_back(KeyboardEvent::key(pos.row, pos.col, pressed))
+                .ok();
+            if !pressed {
+                Self::save_calibration(key, pos);
+            }
+        }
+    }
+
+    /// Save the calibration of the key if it's changed noticeably since the last save
+    fn save_calibration(key: &mut AnalogKey, pos: KeyPos) {
+        let Some(calibration) = key.calibration else {
+            return;
+        };
+        let changed = key.saved.is_none_or(|saved| {
+            saved.rest != calibration.rest || saved.bottom.abs_diff(calibration.bottom) > CALIBRATION_SAVE_THRESHOLD
+        });
+        if !changed {
+            return;
+        }
+        #[cfg(feature = "storage")]
+        {
+            use crate::channel::FLASH_CHANNEL;
+            use crate::storage::FlashOperationMessage;
+
+            // Never block the scanning, the calibration is saved again at the next release
+            if FLASH_CHANNEL
+                .try_send(FlashOperationMessage::AnalogCalibration {
+                    row: pos.row,
+                    col: pos.col,
+                    calibration,
+                })
+                .is_err()
+            {
+                warn!("Flash channel is full, analog calibration isn't saved");
+                return;
+            }
+        }
+        #[cfg(not(feature = "storage"))]
+        let _ = pos;
+        key.saved = Some(calibration);
+    }
+
+    async fn read_keyboard_event(&mut self) -> KeyboardEvent {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return event;
+            }
+            self.scan().await;
+            if self.events.is_empty() {
+                Timer::after(self.scan_interval).await;
+            }
+        }
+    }
+}
+
+impl<
+    A: AdcReader<ADC>,
+    S: OutputPin,
+    const SEL: usize,
+    const ADC: usize,
+    const CHANNEL: usize,
+    const ROW: usize,
+    const COL: usize,
+> MatrixTrait<ROW, COL> for AnalogMatrix<A, S, SEL, ADC, CHANNEL, ROW, COL>
+{
+    #[cfg(feature = "async_matrix")]
+    async fn wait_for_key(&mut self) {
+        // Analog keys have no interrupt, keep polling
+        Timer::after(self.scan_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use embedded_hal::digital::ErrorType;
+
+    use super::*;
+    use crate::event::KeyboardEventPos;
+    use crate::test_support::test_block_on as block_on;
+
+    /// Select pin that writes its bit to the shared channel number
+    struct MockSelectPin<'a> {
+        bit: usize,
+        channel: &'a Cell<usize>,
+    }
+
+    impl ErrorType for MockSelectPin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for MockSelectPin<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.channel.set(self.channel.get() & !(1 << self.bit));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.channel.set(self.channel.get() | (1 << self.bit));
+            Ok(())
+        }
+    }
+
+    /// Two ADC inputs, each behind a 4-channel multiplexer
+    struct MockAdc<'a> {
+        channel: &'a Cell<usize>,
+        values: &'a Cell<[[u16; 2]; 4]>,
+    }
+
+    impl AdcReader<2> for MockAdc<'_> {
+        async fn read(&mut self) -> [u16; 2] {
+            self.values.get()[self.channel.get()]
+        }
+    }
+
+    fn key(actuation: u16, release_hysteresis: u16, rapid_trigger: u16) -> AnalogKey {
+        AnalogKey::new(AnalogKeyConfig {
+            actuation,
+            release_hysteresis,
+            rapid_trigger,
+        })
+    }
+
+    fn key_event(event: KeyboardEvent) -> (u8, u8, bool) {
+        match event.pos {
+            KeyboardEventPos::Key(pos) => (pos.row, pos.col, event.pressed),
+            _ => panic!("Not a key event"),
+        }
+    }
+
+    #[test]
+    fn test_travel() {
+        let cal = AnalogKeyCalibration {
+            rest: 1000,
+            bottom: 2000,
+        };
+        assert_eq!(cal.travel(1000, 400), 0);
+        assert_eq!(cal.travel(1500, 400), 200);
+        assert_eq!(cal.travel(2500, 400), 400);
+        assert_eq!(cal.travel(900, 400), 0);
+
+        // Decreasing ADC value when pressed
+        let cal = AnalogKeyCalibration {
+            rest: 2000,
+            bottom: 1000,
+        };
+        assert_eq!(cal.travel(1750, 400), 100);
+        assert_eq!(cal.travel(500, 400), 400);
+    }
+
+    #[test]
+    fn test_calibration_extend() {
+        let mut cal = AnalogKeyCalibration {
+            rest: 1000,
+            bottom: 2000,
+        };
+        cal.extend(1900);
+        assert_eq!(cal.bottom, 2000);
+        cal.extend(2200);
+        assert_eq!(cal.bottom, 2200);
+        cal.extend(500);
+        assert_eq!(
+            cal,
+            AnalogKeyCalibration {
+                rest: 1000,
+                bottom: 2200
+            }
+        );
+
+        let mut cal = AnalogKeyCalibration {
+            rest: 2000,
+            bottom: 1000,
+        };
+        cal.extend(800);
+        assert_eq!(cal.bottom, 800);
+    }
+
+    #[test]
+    fn test_actuation_and_hysteresis() {
+        let mut k = key(150, 20, 0);
+        assert_eq!(k.update_travel(149), None);
+        assert_eq!(k.update_travel(150), Some(true));
+        assert_eq!(k.update_travel(400), None);
+        // Going up but not below the release point
+        assert_eq!(k.update_travel(130), None);
+        assert_eq!(k.update_travel(129), Some(false));
+        assert_eq!(k.update_travel(140), None);
+        assert_eq!(k.update_travel(150), Some(true));
+    }
+
+    #[test]
+    fn test_rapid_trigger() {
+        let mut k = key(150, 20, 30);
+        assert_eq!(k.update_travel(200), Some(true));
+        assert_eq!(k.update_travel(300), None);
+        // Released after moving up by the sensitivity, above the actuation point
+        assert_eq!(k.update_travel(271), None);
+        assert_eq!(k.update_travel(270), Some(false));
+        assert_eq!(k.update_travel(250), None);
+        // Pressed again after moving down by the sensitivity from the highest point
+        assert_eq!(k.update_travel(279), None);
+        assert_eq!(k.update_travel(280), Some(true));
+        // Released below the release point even without rapid trigger distance
+        assert_eq!(k.update_travel(129), Some(false));
+        // Rapid trigger is disarmed, the actuation point is used again
+        assert_eq!(k.update_travel(0), None);
+        assert_eq!(k.update_travel(100), None);
+        assert_eq!(k.update_travel(150), Some(true));
+    }
+
+    #[test]
+    fn test_scan_through_multiplexer() {
+        block_on(async {
+            let channel = Cell::new(0);
+            let values = Cell::new([[1000; 2]; 4]);
+            let adc = MockAdc {
+                channel: &channel,
+                values: &values,
+            };
+            let select_pins = [
+                MockSelectPin {
+                    bit: 0,
+                    channel: &channel,
+                },
+                MockSelectPin {
+                    bit: 1,
+                    channel: &channel,
+                },
+            ];
+            let mut positions = [[None; 2]; 4];
+            positions[2][1] = Some(KeyPos { row: 1, col: 3 });
+            positions[3][0] = Some(KeyPos { row: 0, col: 0 });
+            let mut matrix =
+                AnalogMatrix::<_, _, 2, 2, 4, 2, 4>::new(adc, select_pins, positions, AnalogKeyConfig::default());
+
+            // The first scan samples the rest level
+            matrix.scan().await;
+            assert!(matrix.events.is_empty());
+
+            // 1000 + 1000 * 150 / 400 is the default actuation point
+            let mut v = values.get();
+            v[2][1] = 1375;
+            values.set(v);
+            assert_eq!(key_event(matrix.read_keyboard_event().await), (1, 3, true));
+
+            v[2][1] = 1000;
+            v[3][0] = 2000;
+            values.set(v);
+            assert_eq!(key_event(matrix.read_keyboard_event().await), (1, 3, false));
+            assert_eq!(key_event(matrix.read_keyboard_event().await), (0, 0, true));
+
+            // Bottoming out beyond the assumed level extends the calibration
+            v[3][0] = 2600;
+            values.set(v);
+            matrix.scan().await;
+            assert_eq!(
+                matrix.keys[0][0].calibration,
+                Some(AnalogKeyCalibration {
+                    rest: 1000,
+                    bottom: 2600
+                })
+            );
+            // 1500 was beyond the actuation point before the calibration is extended
+            v[3][0] = 1500;
+            values.set(v);
+            assert_eq!(key_event(matrix.read_keyboard_event().await), (0, 0, false));
+        });
+    }
+
+    #[test]
+    fn test_loaded_calibration() {
+        block_on(async {
+            let channel = Cell::new(0);
+            let values = Cell::new([[1625, 0], [0; 2], [0; 2], [0; 2]]);
+            let adc = MockAdc {
+                channel: &channel,
+                values: &values,
+            };
+            let positions = [[Some(KeyPos { row: 0, col: 0 }), None]];
+            let mut calibration = [[None; 1]; 1];
+            calibration[0][0] = Some(AnalogKeyCalibration {
+                rest: 2000,
+                bottom: 1000,
+            });
+            let mut matrix =
+                AnalogMatrix::<_, MockSelectPin, 0, 2, 1, 1, 1>::new(adc, [], positions, AnalogKeyConfig::default())
+                    .with_calibration(calibration);
+
+            // No rest level sampling, the key is pressed in the first scan
+            assert_eq!(key_event(matrix.read_keyboard_event().await), (0, 0, true));
+        });
+    }
+}
@@ -152,6 +152,9 @@ impl<
         }
         Timer::after_micros(1).await;
         info!("Waiting for active level");
+        // The direct pins wake the chip up from deep sleep while waiting
+        #[cfg(feature = "_ble")]
+        crate::power::set_wake_armed(true);
 
         if self.low_active {
             let mut futs: Vec<_, SIZE> = Vec::new();
@@ -170,6 +173,8 @@ impl<
             }
             let _ = select_slice(pin!(futs.as_mut_slice())).await;
         }
+        #[cfg(feature = "_ble")]
+        crate::power::set_wake_armed(false);
         self.scan_start = Some(Instant::now());
     }
 }
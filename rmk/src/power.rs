@@ -0,0 +1,315 @@
+//! Idle power management for wireless keyboards.
+//!
+//! [`PowerManager`] puts the keyboard to sleep after a period of inactivity.
+//! It publishes `SleepStateEvent(true)` so that displays and LEDs turn off, then powers the chip down
+//! through a per-chip deep sleep hook. A key press wakes the chip up and the firmware boots again.
+//!
+//! The idle timeout on USB power and on battery are set separately with `usb_sleep_timeout_seconds`
+//! and `sleep_timeout_seconds` in the `[rmk]` section of `keyboard.toml`.
+//!
+//! Waking up on a key press requires the `async_matrix` feature: when no key is pressed,
+//! the matrix drives all outputs and waits for any input pin to change, which arms the wake-up pins.
+//! The chip is only powered down once the matrix has armed them, if a key is held the keyboard stays in light sleep.
+//! Without a deep sleep hook, or without `async_matrix`, the keyboard only publishes
+//! `SleepStateEvent` and wakes up on the next activity.
+//!
+//! nRF52 has a built-in hook, which enters System OFF. On ESP32, the hook is generated from the direct pins
+//! in `keyboard.toml`, or it can be set with [`PowerManager::with_deep_sleep`] and [`esp_deep_sleep`].
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_futures::select::{Either, select};
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::core_traits::Runnable;
+use crate::event::{SleepStateEvent, publish_event};
+use crate::{RawMutex, SLEEP_TIMEOUT_SECONDS, USB_SLEEP_TIMEOUT_SECONDS};
+
+/// Time given to displays and LEDs to turn off before the chip is powered down
+const DEEP_SLEEP_DELAY: Duration = Duration::from_millis(500);
+
+/// Signaled on user activity
+static ACTIVITY: Signal<RawMutex, ()> = Signal::new();
+
+/// Whether the keyboard is powered by USB
+static USB_POWERED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the matrix is waiting for a key press with its wake-up pins armed
+#[cfg(feature = "async_matrix")]
+static WAKE_ARMED: AtomicBool = AtomicBool::new(false);
+
+/// Maximum time to wait for the matrix to arm its wake-up pins before powering down
+#[cfg(feature = "async_matrix")]
+const WAKE_ARM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Notify the power manager of user activity, which resets the idle timer
+pub(crate) fn notify_activity() {
+    ACTIVITY.signal(());
+}
+
+/// Update whether the keyboard is powered by USB, the idle timer restarts with the matching timeout
+pub(crate) fn set_usb_powered(powered: bool) {
+    USB_POWERED.store(powered, Ordering::Release);
+    ACTIVITY.signal(());
+}
+
+/// Update whether the matrix is waiting for a key press, with all outputs driven and the input pins armed
+#[cfg(feature = "async_matrix")]
+pub(crate) fn set_wake_armed(armed: bool) {
+    WAKE_ARMED.store(armed, Ordering::Release);
+}
+
+/// Wait until the matrix has armed its wake-up pins, returns false on timeout, e.g. when a key is held
+#[cfg(feature = "async_matrix")]
+async fn wait_wake_armed() -> bool {
+    let deadline = Instant::now() + WAKE_ARM_TIMEOUT;
+    while !WAKE_ARMED.load(Ordering::Acquire) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        Timer::after_millis(10).await;
+    }
+    true
+}
+
+/// Action requested by [`IdleStateMachine`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum PowerAction {
+    None,
+    Sleep,
+    Wake,
+}
+
+/// Idle timeout state machine, independent of the chip and the timer driver
+pub(crate) struct IdleStateMachine {
+    battery_timeout: Option<Duration>,
+    usb_timeout: Option<Duration>,
+    last_activity: Instant,
+    sleeping: bool,
+}
+
+impl IdleStateMachine {
+    /// Create the state machine, a timeout of 0 disables sleeping on that power source
+    pub(crate) fn new(battery_timeout_secs: u32, usb_timeout_secs: u32, now: Instant) -> Self {
+        let timeout = |secs: u32| (secs > 0).then(|| Duration::from_secs(secs as u64));
+        Self {
+            battery_timeout: timeout(battery_timeout_secs),
+            usb_timeout: timeout(usb_timeout_secs),
+            last_activity: now,
+            sleeping: false,
+        }
+    }
+
+    /// Time at which the keyboard goes to sleep, `None` if it's sleeping or sleeping is disabled
+    pub(crate) fn deadline(&self, usb_powered: bool) -> Option<Instant> {
+        if self.sleeping {
+            return None;
+        }
+        let timeout = if usb_powered {
+            self.usb_timeout
+        } else {
+            self.battery_timeout
+        };
+        timeout.map(|timeout| self.last_activity + timeout)
+    }
+
+    pub(crate) fn on_activity(&mut self, now: Instant) -> PowerAction {
+        self.last_activity = now;
+        if self.sleeping {
+            self.sleeping = false;
+            PowerAction::Wake
+        } else {
+            PowerAction::None
+        }
+    }
+
+    pub(crate) fn on_tick(&mut self, now: Instant, usb_powered: bool) -> PowerAction {
+        match self.deadline(usb_powered) {
+            Some(deadline) if now >= deadline => {
+                self.sleeping = true;
+                PowerAction::Sleep
+            }
+            _ => PowerAction::None,
+        }
+    }
+}
+
+/// Puts the keyboard to sleep after a period of inactivity, see the [module docs](self).
+pub struct PowerManager {
+    state: IdleStateMachine,
+    deep_sleep: Option<fn() -> !>,
+}
+
+impl Default for PowerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerManager {
+    /// Create the power manager with the timeouts in `[rmk]` and the deep sleep hook of the chip
+    pub fn new() -> Self {
+        Self {
+            state: IdleStateMachine::new(SLEEP_TIMEOUT_SECONDS, USB_SLEEP_TIMEOUT_SECONDS, Instant::now()),
+            deep_sleep: chip_deep_sleep(),
+        }
+    }
+
+    /// Set the idle timeouts in seconds on battery and on USB power, 0 disables sleeping
+    pub fn with_timeouts(mut self, battery_timeout_secs: u32, usb_timeout_secs: u32) -> Self {
+        self.state = IdleStateMachine::new(battery_timeout_secs, usb_timeout_secs, Instant::now());
+        self
+    }
+
+    /// Set the hook which powers the chip down.
+    ///
+    /// The hook configures the wake-up sources which are not kept by the matrix, e.g. the ext1 wake-up of ESP32,
+    /// and enters deep sleep. It never returns, the chip boots again when it wakes up.
+    pub fn with_deep_sleep(mut self, hook: fn() -> !) -> Self {
+        self.deep_sleep = Some(hook);
+        self
+    }
+
+    async fn sleep(&mut self) {
+        info!("Idle timeout, going to sleep");
+        publish_event(SleepStateEvent::new(true));
+
+        #[cfg(feature = "async_matrix")]
+        if let Some(deep_sleep) = self.deep_sleep {
+            Timer::after(DEEP_SLEEP_DELAY).await;
+            if !wait_wake_armed().await {
+                warn!("Wake-up pins of the matrix are not armed, staying in light sleep");
+                return;
+            }
+            info!("Entering deep sleep");
+            deep_sleep();
+        }
+        #[cfg(not(feature = "async_matrix"))]
+        if self.deep_sleep.is_some() {
+            warn!("Deep sleep requires the `async_matrix` feature to wake up on key press");
+        }
+    }
+}
+
+impl Runnable for PowerManager {
+    async fn run(&mut self) -> ! {
+        loop {
+            let usb_powered = USB_POWERED.load(Ordering::Acquire);
+            let deadline = self.state.deadline(usb_powered);
+            let timeout = async {
+                match deadline {
+                    Some(deadline) => Timer::at(deadline).await,
+                    None => core::future::pending().await,
+                }
+            };
+            match select(ACTIVITY.wait(), timeout).await {
+                Either::First(_) => {
+                    if self.state.on_activity(Instant::now()) == PowerAction::Wake {
+                        info!("Activity detected, waking up");
+                        publish_event(SleepStateEvent::new(false));
+                    }
+                }
+                Either::Second(_) => {
+                    if self.state.on_tick(Instant::now(), usb_powered) == PowerAction::Sleep {
+                        self.sleep().await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The built-in deep sleep hook of the chip
+fn chip_deep_sleep() -> Option<fn() -> !> {
+    #[cfg(all(feature = "_nrf_ble", not(any(feature = "nrf54l15_ble", feature = "nrf54lm20_ble"))))]
+    {
+        Some(nrf52_system_off)
+    }
+    #[cfg(not(all(feature = "_nrf_ble", not(any(feature = "nrf54l15_ble", feature = "nrf54lm20_ble")))))]
+    {
+        None
+    }
+}
+
+/// Enter System OFF of nRF52, the GPIO DETECT signal of the matrix pins wakes the chip up with a reset
+#[cfg(all(feature = "_nrf_ble", not(any(feature = "nrf54l15_ble", feature = "nrf54lm20_ble"))))]
+fn nrf52_system_off() -> ! {
+    embassy_nrf::pac::POWER
+        .systemoff()
+        .write_value(embassy_nrf::pac::power::regs::Systemoff(1));
+    // System OFF is emulated when a debugger is attached, don't continue running
+    loop {
+        cortex_m::asm::wfe();
+    }
+}
+
+/// Wake-up pin of ESP32 deep sleep, which must be an RTC GPIO
+#[cfg(feature = "esp32s3_ble")]
+pub type EspWakePin<'a> = &'a mut dyn esp_hal::gpio::RtcPin;
+/// Wake-up pin of ESP32 deep sleep, which must be an RTC GPIO
+#[cfg(any(feature = "esp32c3_ble", feature = "esp32c6_ble"))]
+pub type EspWakePin<'a> = &'a mut dyn esp_hal::gpio::RtcPinWithResistors;
+
+/// Enter deep sleep of ESP32, any of `wake_pins` at its level wakes the chip up with a reset.
+///
+/// The wake-up pins are usually the direct pins of the matrix, the pins are taken over by the RTC while sleeping.
+#[cfg(feature = "_esp_ble")]
+pub fn esp_deep_sleep(wake_pins: &mut [(EspWakePin<'_>, esp_hal::rtc_cntl::sleep::WakeupLevel)]) -> ! {
+    // The RTC is not used by RMK, it's only taken to enter deep sleep
+    let mut rtc = esp_hal::rtc_cntl::Rtc::new(unsafe { esp_hal::peripherals::LPWR::steal() });
+    #[cfg(any(feature = "esp32c3_ble", feature = "esp32s3_ble"))]
+    let source = esp_hal::rtc_cntl::sleep::RtcioWakeupSource::new(wake_pins);
+    #[cfg(feature = "esp32c6_ble")]
+    let source = esp_hal::rtc_cntl::sleep::Ext1WakeupSource::new(wake_pins);
+    rtc.sleep_deep(&[&source])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleeps_after_timeout() {
+        let start = Instant::from_secs(0);
+        let mut state = IdleStateMachine::new(60, 0, start);
+        assert_eq!(state.deadline(false), Some(Instant::from_secs(60)));
+        assert_eq!(state.on_tick(Instant::from_secs(59), false), PowerAction::None);
+
+        // Activity postpones the deadline
+        assert_eq!(state.on_activity(Instant::from_secs(30)), PowerAction::None);
+        assert_eq!(state.on_tick(Instant::from_secs(60), false), PowerAction::None);
+        assert_eq!(state.on_tick(Instant::from_secs(90), false), PowerAction::Sleep);
+        assert_eq!(state.deadline(false), None);
+
+        assert_eq!(state.on_activity(Instant::from_secs(100)), PowerAction::Wake);
+        assert_eq!(state.deadline(false), Some(Instant::from_secs(160)));
+    }
+
+    #[cfg(feature = "async_matrix")]
+    #[test]
+    fn deep_sleep_waits_for_armed_wake_pins() {
+        crate::test_support::test_block_on(async {
+            // A held key keeps the matrix scanning, so the pins are never armed
+            set_wake_armed(false);
+            assert!(!wait_wake_armed().await);
+
+            set_wake_armed(true);
+            assert!(wait_wake_armed().await);
+        });
+    }
+
+    #[test]
+    fn separate_usb_timeout() {
+        let start = Instant::from_secs(0);
+        let mut state = IdleStateMachine::new(60, 0, start);
+        // Disabled on USB power
+        assert_eq!(state.deadline(true), None);
+        assert_eq!(state.on_tick(Instant::from_secs(1000), true), PowerAction::None);
+
+        let mut state = IdleStateMachine::new(60, 600, start);
+        assert_eq!(state.on_tick(Instant::from_secs(60), true), PowerAction::None);
+        assert_eq!(state.on_tick(Instant::from_secs(600), true), PowerAction::Sleep);
+    }
+}
@@ -4,12 +4,14 @@ use rmk_macro::processor;
 use rmk_types::battery::{BatteryStatus, ChargeState};
 
 use crate::driver::gpio::OutputController;
-use crate::event::BatteryStatusEvent;
+use crate::event::{BatteryStatusEvent, LowBatteryEvent, SleepStateEvent};
 
-#[processor(subscribe = [BatteryStatusEvent], poll_interval = 1000)]
+#[processor(subscribe = [BatteryStatusEvent, LowBatteryEvent, SleepStateEvent], poll_interval = 1000)]
 pub struct BatteryLedProcessor<P: StatefulOutputPin> {
     pin: OutputController<P>,
     state: BatteryStatus,
+    low_battery: bool,
+    sleeping: bool,
 }
 
 impl<P: StatefulOutputPin> BatteryLedProcessor<P> {
@@ -17,6 +19,8 @@ impl<P: StatefulOutputPin> BatteryLedProcessor<P> {
         Self {
             pin: OutputController::new(pin, low_active),
             state: BatteryStatus::Unavailable,
+            low_battery: false,
+            sleeping: false,
         }
     }
 
@@ -24,13 +28,28 @@ impl<P: StatefulOutputPin> BatteryLedProcessor<P> {
         self.state = event.into();
     }
 
+    async fn on_low_battery_event(&mut self, event: LowBatteryEvent) {
+        self.low_battery = event.low;
+    }
+
+    async fn on_sleep_state_event(&mut self, event: SleepStateEvent) {
+        self.sleeping = event.0;
+        if self.sleeping {
+            // The pin keeps its level in deep sleep
+            self.pin.deactivate();
+        }
+    }
+
     async fn poll(&mut self) {
+        if self.sleeping {
+            return;
+        }
         match self.state {
             BatteryStatus::Unavailable => self.pin.deactivate(),
-            BatteryStatus::Available { charge_state, level } => {
+            BatteryStatus::Available { charge_state, .. } => {
                 if charge_state == ChargeState::Charging {
                     self.pin.activate();
-                } else if level.unwrap_or(100) < 10 {
+                } else if self.low_battery {
                     // Battery low, blinking the LED
                     self.pin.toggle();
                 } else {
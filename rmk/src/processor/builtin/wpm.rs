@@ -43,6 +43,8 @@ impl WpmProcessor {
 
         if avg_wpm != self.wpm {
             self.wpm = avg_wpm;
+            #[cfg(feature = "split")]
+            crate::split::snapshot::set_wpm(self.wpm);
             publish_event(WpmUpdateEvent::new(self.wpm));
         }
 
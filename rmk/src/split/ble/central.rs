@@ -1,9 +1,11 @@
-use core::cell::RefCell;
-use core::sync::atomic::Ordering;
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use bt_hci::cmd::le::{LeReadLocalSupportedFeatures, LeSetPhy, LeSetScanParams};
+use bt_hci::cmd::status::ReadRssi;
 use bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync};
-use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_futures::select::{Either, Either4, select, select4};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Timer, with_timeout};
@@ -15,10 +17,11 @@ use crate::channel::FLASH_CHANNEL;
 use crate::event::{PeripheralConnectedEvent, SleepStateEvent, publish_event};
 #[cfg(feature = "storage")]
 use crate::split::ble::PeerAddress;
+use crate::split::ble::link_stats;
 use crate::split::driver::{PeripheralManager, SplitDriverError, SplitReader, SplitWriter};
 use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
 use crate::storage::FlashOperationMessage;
-use crate::{CONNECTION_STATE, SPLIT_CENTRAL_SLEEP_TIMEOUT_SECONDS};
+use crate::{CONNECTION_STATE, SPLIT_CENTRAL_SLEEP_TIMEOUT_SECONDS, SPLIT_PERIPHERALS_NUM};
 
 pub(crate) static STACK_STARTED: Signal<crate::RawMutex, bool> = Signal::new();
 pub(crate) static PERIPHERAL_FOUND: Signal<crate::RawMutex, (u8, BdAddr)> = Signal::new();
@@ -28,6 +31,13 @@ static START_SCANNING: Signal<crate::RawMutex, ()> = Signal::new();
 static STOP_SCANNING: Signal<crate::RawMutex, ()> = Signal::new();
 static SCANNING_MUTEX: Mutex<crate::RawMutex, ()> = Mutex::new(());
 
+/// Signal to forget the disconnected peripherals and scan for new ones
+static PAIR_NEW_PERIPHERAL: Signal<crate::RawMutex, ()> = Signal::new();
+
+/// Whether each peripheral is connected, the connected ones are kept when pairing a new peripheral
+static PERIPHERAL_CONNECTED: [AtomicBool; SPLIT_PERIPHERALS_NUM] =
+    [const { AtomicBool::new(false) }; SPLIT_PERIPHERALS_NUM];
+
 /// Sleep management signal for BLE Split Central
 ///
 /// This signal serves dual purposes for sleep management:
@@ -35,6 +45,61 @@ static SCANNING_MUTEX: Mutex<crate::RawMutex, ()> = Mutex::new(());
 /// - `signal(false)`: Indicates activity detected, wake up or reset sleep timer
 pub(crate) static CENTRAL_SLEEP: Signal<crate::RawMutex, bool> = Signal::new();
 
+/// Default connection interval between the central and a peripheral
+pub const DEFAULT_PERIPHERAL_CONN_INTERVAL: Duration = Duration::from_micros(7500);
+/// Default number of connection events which a peripheral can skip, 225ms with the default interval
+pub const DEFAULT_PERIPHERAL_CONN_LATENCY: u16 = 30;
+
+/// Interval between the RSSI reads of a connected peripheral
+const RSSI_READ_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Connection interval and latency of each peripheral
+static PERIPHERAL_CONN_PARAMS: BlockingMutex<crate::RawMutex, Cell<[(Duration, u16); SPLIT_PERIPHERALS_NUM]>> =
+    BlockingMutex::new(Cell::new(
+        [(DEFAULT_PERIPHERAL_CONN_INTERVAL, DEFAULT_PERIPHERAL_CONN_LATENCY); SPLIT_PERIPHERALS_NUM],
+    ));
+
+/// Set the connection interval and latency between the central and the peripheral with the given id.
+///
+/// A shorter interval reduces the latency of key events and costs more power on both halves.
+/// The latency is the number of connection events which the peripheral can skip when it has nothing to send.
+/// It's applied on the next connection, so it should be called before the peripheral managers run.
+pub fn set_peripheral_conn_params(id: usize, interval: Duration, latency: u16) {
+    PERIPHERAL_CONN_PARAMS.lock(|params| {
+        let mut all = params.get();
+        match all.get_mut(id) {
+            Some(p) => *p = (interval, latency),
+            None => warn!("Connection parameters of invalid peripheral {} are ignored", id),
+        }
+        params.set(all);
+    });
+}
+
+/// Forget the saved addresses of the disconnected peripherals and scan for new ones, without rebooting the central.
+///
+/// It's used to pair a new or replaced half, for example with a dongle central.
+pub fn pair_new_peripheral() {
+    PAIR_NEW_PERIPHERAL.signal(());
+}
+
+/// Clear the addresses of the disconnected peripherals, both in memory and in the storage
+async fn forget_disconnected_peripherals(addrs: &RefCell<VecView<Option<[u8; 6]>>>) {
+    for (id, connected) in PERIPHERAL_CONNECTED.iter().enumerate() {
+        if connected.load(Ordering::Acquire) {
+            continue;
+        }
+        let forgotten = addrs.borrow_mut().get_mut(id).and_then(|addr| addr.take()).is_some();
+        if forgotten {
+            info!("Forget peripheral {}", id);
+            FLASH_CHANNEL
+                .send(FlashOperationMessage::PeerAddress(PeerAddress::new(
+                    id as u8, false, [0; 6],
+                )))
+                .await;
+        }
+    }
+}
+
 /// Gatt service used in split central to send split message to peripheral
 #[gatt_service(uuid = "4dd5fbaa-18e5-4b07-bf0a-353698659946")]
 struct SplitBleCentralService {
@@ -62,8 +127,10 @@ pub async fn scan_peripherals<
     addrs: &RefCell<VecView<Option<[u8; 6]>>>,
 ) {
     loop {
-        // Wait unitil `START_SCANNING` is signaled
-        START_SCANNING.wait().await;
+        // Wait unitil `START_SCANNING` is signaled, or a new peripheral is going to be paired
+        if let Either::Second(_) = select(START_SCANNING.wait(), PAIR_NEW_PERIPHERAL.wait()).await {
+            forget_disconnected_peripherals(addrs).await;
+        }
         // Check whether the scanning is needed, aka there's empty slot in the addr list.
         let need_scan = !addrs.borrow().iter().all(|a| a.is_some());
         if need_scan {
@@ -163,7 +230,8 @@ pub(crate) async fn run_ble_peripheral_manager<
     C: Controller
         + ControllerCmdSync<LeSetScanParams>
         + ControllerCmdAsync<LeSetPhy>
-        + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
     const ROW: usize,
     const COL: usize,
     const ROW_OFFSET: usize,
@@ -191,7 +259,7 @@ pub(crate) async fn run_ble_peripheral_manager<
 
         let Host { mut central, .. } = stack.build();
         let config = ConnectConfig {
-            connect_params: defaul_central_conn_param(),
+            connect_params: central_conn_param(peri_id),
             scan_config: ScanConfig {
                 filter_accept_list: &[(address.kind, &address.addr)],
                 ..Default::default()
@@ -202,6 +270,7 @@ pub(crate) async fn run_ble_peripheral_manager<
         publish_event(PeripheralConnectedEvent {
             id: peri_id,
             connected: false,
+            build_mismatch: false,
         });
 
         // Connect to peripheral
@@ -222,11 +291,7 @@ pub(crate) async fn run_ble_peripheral_manager<
         {
             Ok(Ok(conn)) => {
                 info!("Connected to peripheral {}", peri_id);
-
-                publish_event(PeripheralConnectedEvent {
-                    id: peri_id,
-                    connected: true,
-                });
+                PERIPHERAL_CONNECTED[peri_id].store(true, Ordering::Release);
 
                 if let Err(e) =
                     run_central_manager_task::<_, _, ROW, COL, ROW_OFFSET, COL_OFFSET>(peri_id, stack, &conn).await
@@ -235,6 +300,10 @@ pub(crate) async fn run_ble_peripheral_manager<
                     let e = defmt::Debug2Format(&e);
                     error!("BLE central error: {:?}", e);
                 }
+                // The peripheral manager is cancelled when the connection is lost, release the keys here
+                crate::split::driver::release_peripheral_keys(peri_id).await;
+                PERIPHERAL_CONNECTED[peri_id].store(false, Ordering::Release);
+                link_stats::reset_link_stats(peri_id);
             }
             Ok(Err(e)) => {
                 #[cfg(feature = "defmt")]
@@ -254,19 +323,33 @@ pub(crate) async fn run_ble_peripheral_manager<
     }
 }
 
-fn defaul_central_conn_param() -> RequestedConnParams {
+/// Connection parameters used for the peripheral when it's not sleeping
+fn central_conn_param(id: usize) -> RequestedConnParams {
+    let (interval, latency) = PERIPHERAL_CONN_PARAMS.lock(|params| {
+        params
+            .get()
+            .get(id)
+            .copied()
+            .unwrap_or((DEFAULT_PERIPHERAL_CONN_INTERVAL, DEFAULT_PERIPHERAL_CONN_LATENCY))
+    });
     RequestedConnParams {
-        min_connection_interval: Duration::from_micros(7500),
-        max_connection_interval: Duration::from_micros(7500),
-        max_latency: 30, // 225ms
-        supervision_timeout: Duration::from_secs(5),
+        min_connection_interval: interval,
+        max_connection_interval: interval,
+        max_latency: latency,
+        // The timeout must cover the events skipped by the peripheral, 32s is the maximum allowed value
+        supervision_timeout: Duration::from_secs(5)
+            .max(interval * (latency as u32 + 1) * 3)
+            .min(Duration::from_secs(32)),
         ..Default::default()
     }
 }
 
 async fn run_central_manager_task<
     'a,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
     P: PacketPool,
     const ROW: usize,
     const COL: usize,
@@ -283,18 +366,39 @@ async fn run_central_manager_task<
     update_ble_phy(stack, conn).await;
 
     info!("Updating connection parameters for peripheral");
-    update_conn_params(stack, conn, &defaul_central_conn_param()).await;
+    update_conn_params(stack, conn, &central_conn_param(id)).await;
 
-    match select3(
+    match select4(
         ble_central_task(&client, conn),
         run_peripheral_manager::<_, _, ROW, COL, ROW_OFFSET, COL_OFFSET>(id, &client),
-        sleep_manager_task(stack, conn),
+        sleep_manager_task(id, stack, conn),
+        read_rssi_task(id, stack, conn),
     )
     .await
     {
-        Either3::First(e) => e,
-        Either3::Second(e) => e,
-        Either3::Third(e) => e,
+        Either4::First(e) => e,
+        Either4::Second(e) => e,
+        Either4::Third(e) => e,
+        Either4::Fourth(e) => e,
+    }
+}
+
+/// Read the RSSI of the peripheral periodically, for the link statistics
+async fn read_rssi_task<'a, C: Controller + ControllerCmdSync<ReadRssi>, P: PacketPool>(
+    id: usize,
+    stack: &'a Stack<'a, C, P>,
+    conn: &Connection<'a, P>,
+) -> Result<(), BleHostError<C::Error>> {
+    loop {
+        match conn.rssi(stack).await {
+            Ok(rssi) => link_stats::set_rssi(id, rssi),
+            Err(e) => {
+                #[cfg(feature = "defmt")]
+                let e = defmt::Debug2Format(&e);
+                warn!("Read RSSI of peripheral {} error: {:?}", id, e);
+            }
+        }
+        Timer::after(RSSI_READ_INTERVAL).await;
     }
 }
 
@@ -409,7 +513,10 @@ impl<'a, 'b, 'c, C: Controller + ControllerCmdAsync<LeSetPhy>, P: PacketPool> Sp
         info!("Received split message: {:?}", message);
 
         // Update last activity time when receiving key events from peripheral
-        if matches!(message, SplitMessage::Key(_) | SplitMessage::Pointing(_)) {
+        if matches!(
+            message,
+            SplitMessage::Key(_) | SplitMessage::Pointing(_) | SplitMessage::PointingButton(_)
+        ) {
             debug!("Activity {:?} detected from peripheral", &message);
             update_activity_time();
         }
@@ -473,6 +580,7 @@ async fn sleep_manager_task<
     C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
     P: PacketPool,
 >(
+    id: usize,
     stack: &'a Stack<'a, C, P>,
     conn: &Connection<'a, P>,
 ) -> Result<(), BleHostError<C::Error>> {
@@ -549,7 +657,7 @@ async fn sleep_manager_task<
                 publish_event(SleepStateEvent::new(false));
 
                 // Restore normal connection parameters
-                update_conn_params(stack, conn, &defaul_central_conn_param()).await;
+                update_conn_params(stack, conn, &central_conn_param(id)).await;
             }
         }
     }
@@ -0,0 +1,203 @@
+//! Link statistics of BLE split peripherals, measured on the central.
+//!
+//! The central sends a [`SplitMessage::LinkProbe`](crate::split::SplitMessage::LinkProbe) to each peripheral periodically,
+//! and the peripheral sends it back.
+//! The round trip time of the probes gives the latency, and the probes which get no answer give the packet loss.
+//! The RSSI is read from the BLE controller.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::event::{PeripheralLinkStatsEvent, publish_event};
+use crate::{RawMutex, SPLIT_PERIPHERALS_NUM};
+
+/// Number of recent probes used to calculate the packet loss
+const PROBE_WINDOW: u32 = 32;
+
+#[derive(Clone, Copy)]
+struct LinkStats {
+    rssi: Option<i8>,
+    latency_ms: Option<u16>,
+    /// Sequence number of the next probe
+    next_seq: u16,
+    /// Sequence number and send time of the probe waiting for its answer
+    pending: Option<(u16, Instant)>,
+    /// Results of the recent probes, a set bit is a probe which got no answer
+    lost: u32,
+    /// Number of recent probes in `lost`
+    probes: u32,
+}
+
+impl LinkStats {
+    const fn new() -> Self {
+        Self {
+            rssi: None,
+            latency_ms: None,
+            next_seq: 0,
+            pending: None,
+            lost: 0,
+            probes: 0,
+        }
+    }
+
+    /// Start a new probe, the pending one is counted as lost
+    fn start_probe(&mut self, now: Instant) -> (u16, bool) {
+        let lost = self.pending.is_some();
+        if lost {
+            self.record(true);
+        }
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.pending = Some((seq, now));
+        (seq, lost)
+    }
+
+    /// Handle the answer of a probe, returns false if it isn't the pending one
+    fn answer_probe(&mut self, seq: u16, now: Instant) -> bool {
+        match self.pending {
+            Some((pending_seq, sent_at)) if pending_seq == seq => {
+                self.pending = None;
+                let round_trip = now.saturating_duration_since(sent_at).as_millis();
+                self.latency_ms = Some((round_trip / 2).min(u16::MAX as u64) as u16);
+                self.record(false);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn record(&mut self, lost: bool) {
+        self.lost = (self.lost << 1) | lost as u32;
+        self.probes = (self.probes + 1).min(PROBE_WINDOW);
+    }
+
+    fn packet_loss(&self) -> u8 {
+        if self.probes == 0 {
+            0
+        } else {
+            (self.lost.count_ones() * 100 / self.probes) as u8
+        }
+    }
+
+    fn event(&self, id: usize) -> PeripheralLinkStatsEvent {
+        PeripheralLinkStatsEvent {
+            id,
+            rssi: self.rssi,
+            latency_ms: self.latency_ms,
+            packet_loss: self.packet_loss(),
+        }
+    }
+}
+
+static LINK_STATS: Mutex<RawMutex, RefCell<[LinkStats; SPLIT_PERIPHERALS_NUM]>> =
+    Mutex::new(RefCell::new([LinkStats::new(); SPLIT_PERIPHERALS_NUM]));
+
+/// Update the stats of the peripheral, returns the event to publish if the stats changed
+fn update<F: FnOnce(&mut LinkStats) -> bool>(id: usize, f: F) -> Option<PeripheralLinkStatsEvent> {
+    LINK_STATS.lock(|stats| {
+        let mut stats = stats.borrow_mut();
+        let stats = stats.get_mut(id)?;
+        f(stats).then(|| stats.event(id))
+    })
+}
+
+/// Start a new probe to the peripheral, returns its sequence number.
+///
+/// If the previous probe got no answer, it's counted as lost and the stats are published.
+pub(crate) fn start_probe(id: usize) -> Option<u16> {
+    let mut seq = None;
+    if let Some(e) = update(id, |stats| {
+        let (probe_seq, lost) = stats.start_probe(Instant::now());
+        seq = Some(probe_seq);
+        lost
+    }) {
+        publish_event(e);
+    }
+    seq
+}
+
+/// Handle the answer of a probe from the peripheral and publish the stats
+pub(crate) fn answer_probe(id: usize, seq: u16) {
+    match update(id, |stats| stats.answer_probe(seq, Instant::now())) {
+        Some(e) => publish_event(e),
+        None => debug!("Stale link probe {} from peripheral {}", seq, id),
+    }
+}
+
+/// Save the RSSI of the peripheral, it's published with the result of the next probe
+pub(crate) fn set_rssi(id: usize, rssi: i8) {
+    update(id, |stats| {
+        stats.rssi = Some(rssi);
+        false
+    });
+}
+
+/// Clear the stats of a disconnected peripheral and publish them
+pub(crate) fn reset_link_stats(id: usize) {
+    if let Some(e) = update(id, |stats| {
+        *stats = LinkStats::new();
+        true
+    }) {
+        publish_event(e);
+    }
+}
+
+/// Get the link statistics of the peripheral with the given id, `None` if the id is invalid.
+pub fn peripheral_link_stats(id: usize) -> Option<PeripheralLinkStatsEvent> {
+    LINK_STATS.lock(|stats| stats.borrow().get(id).map(|s| s.event(id)))
+}
+
+#[cfg(feature = "rmk_protocol")]
+impl From<PeripheralLinkStatsEvent> for rmk_types::protocol::rmk::PeripheralLinkStatus {
+    fn from(e: PeripheralLinkStatsEvent) -> Self {
+        Self {
+            // The RSSI is sent in -dBm, a positive one can't be represented
+            rssi: e.rssi.map(|rssi| rssi.min(0).unsigned_abs()),
+            latency_ms: e.latency_ms,
+            packet_loss: e.packet_loss,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn latency_is_half_of_the_round_trip() {
+        let mut stats = LinkStats::new();
+        let start = Instant::from_millis(1000);
+        let (seq, lost) = stats.start_probe(start);
+        assert!(!lost);
+
+        // A stale answer is ignored
+        assert!(!stats.answer_probe(seq.wrapping_sub(1), start + Duration::from_millis(10)));
+        assert!(stats.answer_probe(seq, start + Duration::from_millis(30)));
+        assert_eq!(stats.latency_ms, Some(15));
+        assert_eq!(stats.packet_loss(), 0);
+    }
+
+    #[test]
+    fn unanswered_probes_are_lost() {
+        let mut stats = LinkStats::new();
+        let now = Instant::from_millis(0);
+        stats.start_probe(now);
+        let (seq, lost) = stats.start_probe(now);
+        assert!(lost);
+        // The answer of the lost probe doesn't count
+        assert!(!stats.answer_probe(seq.wrapping_sub(1), now));
+        assert!(stats.answer_probe(seq, now));
+        assert_eq!(stats.packet_loss(), 50);
+
+        // Old probes leave the window
+        for _ in 0..PROBE_WINDOW {
+            let (seq, _) = stats.start_probe(now);
+            stats.answer_probe(seq, now);
+        }
+        assert_eq!(stats.packet_loss(), 0);
+    }
+}
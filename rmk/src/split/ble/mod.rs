@@ -1,4 +1,5 @@
 pub mod central;
+pub mod link_stats;
 pub mod peripheral;
 
 use postcard::experimental::max_size::MaxSize;
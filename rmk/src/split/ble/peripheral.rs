@@ -1,6 +1,7 @@
 use bt_hci::cmd::le::LeSetPhy;
 use bt_hci::controller::ControllerCmdAsync;
 use embassy_futures::join::join;
+use embassy_futures::select::{Either, select};
 use embassy_time::{Duration, Timer, with_timeout};
 use trouble_host::prelude::*;
 
@@ -9,7 +10,7 @@ use super::PeerAddress;
 use crate::CONNECTION_STATE;
 use crate::event::{CentralConnectedEvent, KeyboardEvent, SubscribableEvent, publish_event};
 use crate::split::driver::{SplitDriverError, SplitReader, SplitWriter};
-use crate::split::peripheral::SplitPeripheral;
+use crate::split::peripheral::{SplitPeripheral, buffer_event, buffer_events_while_disconnected};
 use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
 
 /// Gatt service used in split peripheral to send split message to central
@@ -126,7 +127,12 @@ impl<'stack, 'server, 'c, P: PacketPool> SplitWriter for BleSplitPeripheralDrive
 /// * `id` - The id of the peripheral
 /// * `central_addr` - The address of the central
 /// * `stack` - The stack to use
-pub async fn initialize_nrf_ble_split_peripheral_and_run<'stack, C: Controller + ControllerCmdAsync<LeSetPhy>>(
+pub async fn initialize_nrf_ble_split_peripheral_and_run<
+    'stack,
+    const ROW: usize,
+    const COL: usize,
+    C: Controller + ControllerCmdAsync<LeSetPhy>,
+>(
     id: usize,
     stack: &'stack Stack<'stack, C, DefaultPacketPool>,
 ) {
@@ -147,11 +153,20 @@ pub async fn initialize_nrf_ble_split_peripheral_and_run<'stack, C: Controller +
         loop {
             CONNECTION_STATE.store(false, core::sync::atomic::Ordering::Release);
             publish_event(CentralConnectedEvent { connected: false });
-            match split_peripheral_advertise(id, central_addr, &mut peripheral, &server).await {
+            // Keep the key events while reconnecting, they are replayed once connected
+            let advertise = select(
+                split_peripheral_advertise(id, central_addr, &mut peripheral, &server),
+                buffer_events_while_disconnected(),
+            );
+            let result = match advertise.await {
+                Either::First(result) => result,
+                Either::Second(never) => never,
+            };
+            match result {
                 Ok(conn) => {
                     info!("Connected to the central");
                     publish_event(CentralConnectedEvent { connected: true });
-                    let mut peripheral = SplitPeripheral::new(BleSplitPeripheralDriver::new(&server, &conn));
+                    let mut peripheral = SplitPeripheral::new(BleSplitPeripheralDriver::new(&server, &conn), ROW, COL);
                     let new_addr = conn.raw().peer_address().into_inner();
                     if central_addr != Some(new_addr) {
                         info!("Saving central address to storage");
@@ -173,7 +188,8 @@ pub async fn initialize_nrf_ble_split_peripheral_and_run<'stack, C: Controller +
                     error!("Connect to central timeout");
                     let mut sub = KeyboardEvent::subscriber();
                     sub.clear();
-                    let _ = sub.next_message_pure().await;
+                    let e = sub.next_message_pure().await;
+                    buffer_event(SplitMessage::Key(e));
                     continue;
                 }
                 Err(e) => {
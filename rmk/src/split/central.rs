@@ -1,16 +1,33 @@
 #[cfg(feature = "_ble")]
 use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 #[cfg(not(feature = "_ble"))]
 use embedded_io_async::{Read, Write};
 #[cfg(feature = "_ble")]
 use {
     bt_hci::cmd::le::{LeReadLocalSupportedFeatures, LeSetPhy, LeSetScanParams},
+    bt_hci::cmd::status::ReadRssi,
     bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync},
     heapless::VecView,
     trouble_host::prelude::*,
 };
 
+/// Whether a peripheral which runs a different firmware build must be confirmed before its keys are used
+static CONFIRM_MISMATCHED_BUILD: AtomicBool = AtomicBool::new(false);
+
+/// Require a confirmation before using a peripheral which runs a different firmware build than the central.
+///
+/// When it's enabled, the keys of such a peripheral are ignored until a key on it is pressed and released,
+/// so that a half flashed with an outdated firmware doesn't type with a wrong keymap unnoticed.
+pub fn set_confirm_mismatched_build(confirm: bool) {
+    CONFIRM_MISMATCHED_BUILD.store(confirm, Ordering::Release);
+}
+
+pub(crate) fn confirm_mismatched_build() -> bool {
+    CONFIRM_MISMATCHED_BUILD.load(Ordering::Acquire)
+}
+
 /// Run central's peripheral manager task.
 ///
 /// # Arguments
@@ -29,7 +46,8 @@ pub async fn run_peripheral_manager<
     #[cfg(feature = "_ble")] C: Controller
         + ControllerCmdSync<LeSetScanParams>
         + ControllerCmdAsync<LeSetPhy>
-        + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>,
     #[cfg(not(feature = "_ble"))] S: Read + Write,
 >(
     id: usize,
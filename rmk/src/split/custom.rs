@@ -0,0 +1,77 @@
+//! Custom messages between split halves.
+//!
+//! Firmware can send its own data, e.g. RGB state or haptic triggers, to the other half with [`send_custom_message`].
+//! The other half publishes it as [`SplitCustomEvent`](crate::event::SplitCustomEvent).
+
+use embassy_sync::channel::Channel;
+
+use crate::{RawMutex, SPLIT_PERIPHERALS_NUM};
+
+/// Size of the payload of a custom split message
+pub const SPLIT_CUSTOM_MESSAGE_SIZE: usize = 16;
+
+/// Number of custom messages which can be queued for a split half
+const CUSTOM_MESSAGE_QUEUE_SIZE: usize = 4;
+
+/// Outgoing custom messages, one queue for each peripheral on the central, only the first one is used on a peripheral
+static CUSTOM_MESSAGES: [Channel<RawMutex, [u8; SPLIT_CUSTOM_MESSAGE_SIZE], CUSTOM_MESSAGE_QUEUE_SIZE>;
+    SPLIT_PERIPHERALS_NUM] = [const { Channel::new() }; SPLIT_PERIPHERALS_NUM];
+
+/// Send a custom message to the other half.
+///
+/// `id` is the id of the target peripheral on the central, and is always 0 on a peripheral.
+/// It waits if the queue of the target is full, messages to an invalid peripheral id are dropped.
+/// Messages are only exchanged when the link is up, queued messages are sent after the (re)connection.
+pub async fn send_custom_message(id: usize, data: [u8; SPLIT_CUSTOM_MESSAGE_SIZE]) {
+    match CUSTOM_MESSAGES.get(id) {
+        Some(channel) => channel.send(data).await,
+        None => warn!("Custom split message to invalid peripheral {} is dropped", id),
+    }
+}
+
+/// Wait for the next custom message to the split half with the given id.
+///
+/// The message is kept in the queue until it's removed by [`pop_custom_message`],
+/// so that a message is not lost when writing it to the split link fails.
+pub(crate) async fn next_custom_message(id: usize) -> [u8; SPLIT_CUSTOM_MESSAGE_SIZE] {
+    match CUSTOM_MESSAGES.get(id) {
+        Some(channel) => loop {
+            channel.ready_to_receive().await;
+            // The queue of each split half has only one receiver
+            if let Ok(data) = channel.try_peek() {
+                return data;
+            }
+        },
+        None => core::future::pending().await,
+    }
+}
+
+/// Remove the message returned by [`next_custom_message`] from the queue, after it's written or dropped
+pub(crate) fn pop_custom_message(id: usize) {
+    if let Some(channel) = CUSTOM_MESSAGES.get(id) {
+        channel.try_receive().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_block_on;
+
+    #[test]
+    fn custom_messages_are_queued_per_peripheral() {
+        test_block_on(async {
+            send_custom_message(0, [1; SPLIT_CUSTOM_MESSAGE_SIZE]).await;
+            send_custom_message(0, [2; SPLIT_CUSTOM_MESSAGE_SIZE]).await;
+            // Dropped instead of blocking forever
+            send_custom_message(SPLIT_PERIPHERALS_NUM, [3; SPLIT_CUSTOM_MESSAGE_SIZE]).await;
+
+            // A message which isn't popped, e.g. because of a failed write, is returned again
+            assert_eq!(next_custom_message(0).await, [1; SPLIT_CUSTOM_MESSAGE_SIZE]);
+            assert_eq!(next_custom_message(0).await, [1; SPLIT_CUSTOM_MESSAGE_SIZE]);
+            pop_custom_message(0);
+            assert_eq!(next_custom_message(0).await, [2; SPLIT_CUSTOM_MESSAGE_SIZE]);
+            pop_custom_message(0);
+        });
+    }
+}
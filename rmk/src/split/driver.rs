@@ -1,14 +1,66 @@
 //! The abstracted driver layer of the split keyboard.
 //!
+use core::cell::RefCell;
 use core::sync::atomic::Ordering;
 
 use embassy_futures::select::{Either3, select3};
-use embassy_time::Instant;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, with_timeout};
 use futures::FutureExt;
 
-use super::SplitMessage;
-use crate::CONNECTION_STATE;
-use crate::event::{KeyboardEvent, KeyboardEventPos, SubscribableEvent, publish_event, publish_event_async};
+use super::{SplitBuildInfo, SplitHandshake, SplitMessage, SplitMismatch, custom, snapshot};
+use crate::event::{
+    KeyboardEvent, KeyboardEventPos, PeripheralConnectedEvent, SplitCustomEvent, SplitMismatchEvent, SubscribableEvent,
+    publish_event, publish_event_async,
+};
+
+use crate::{CONNECTION_STATE, RawMutex};
+
+/// Time to wait for the handshake reply before sending the handshake again
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum number of keys held on all peripherals
+const HELD_KEYS_MAX: usize = 32;
+
+/// Keys which the central believes are held on peripherals, as (peripheral id, row, col) in the keyboard's matrix
+static HELD_KEYS: Mutex<RawMutex, RefCell<heapless::Vec<(usize, u8, u8), HELD_KEYS_MAX>>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// Track the key state of a peripheral
+fn track_held_key(id: usize, row: u8, col: u8, pressed: bool) {
+    HELD_KEYS.lock(|keys| {
+        let mut keys = keys.borrow_mut();
+        let key = (id, row, col);
+        if !pressed {
+            keys.retain(|k| *k != key);
+        } else if !keys.contains(&key) && keys.push(key).is_err() {
+            warn!(
+                "Too many keys held on peripherals, key ({}, {}) isn't tracked",
+                row, col
+            );
+        }
+    });
+}
+
+/// Take the keys held on the peripheral
+fn take_held_keys(id: usize) -> heapless::Vec<(u8, u8), HELD_KEYS_MAX> {
+    HELD_KEYS.lock(|keys| {
+        let mut keys = keys.borrow_mut();
+        let held = keys.iter().filter(|k| k.0 == id).map(|k| (k.1, k.2)).collect();
+        keys.retain(|k| k.0 != id);
+        held
+    })
+}
+
+/// Release every key which the central believes is held on the peripheral.
+///
+/// It's called when the peripheral is disconnected, so that no key stays stuck.
+pub(crate) async fn release_peripheral_keys(id: usize) {
+    for (row, col) in take_held_keys(id) {
+        info!("Releasing key ({}, {}) of disconnected peripheral {}", row, col, id);
+        publish_event_async(KeyboardEvent::key(row, col, false)).await;
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -19,6 +71,12 @@ pub(crate) enum SplitDriverError {
     SerializeError,
     BleError(u8),
     Disconnected,
+    /// The frame is broken, e.g. its CRC doesn't match
+    #[cfg(feature = "split_reliable")]
+    CorruptedFrame,
+    /// No ACK is received from the other half after all retransmits
+    #[cfg(feature = "split_reliable")]
+    Unacknowledged,
 }
 
 /// Split message reader from other split devices
@@ -49,13 +107,23 @@ pub(crate) struct PeripheralManager<
     transceiver: T,
     /// Peripheral id
     id: usize,
+    /// Whether the peripheral has the same features, false means feature-dependent messages are not exchanged
+    compatible_features: bool,
+    /// The peripheral runs a different build and its keys are ignored until a key on it is released,
+    /// see [`set_confirm_mismatched_build`](crate::split::central::set_confirm_mismatched_build)
+    awaiting_confirmation: bool,
 }
 
 impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFSET: usize, T: SplitReader + SplitWriter>
     PeripheralManager<ROW, COL, ROW_OFFSET, COL_OFFSET, T>
 {
     pub(crate) fn new(transceiver: T, id: usize) -> Self {
-        Self { transceiver, id }
+        Self {
+            transceiver,
+            id,
+            compatible_features: true,
+            awaiting_confirmation: false,
+        }
     }
 
     /// Send a message to the peripheral, returning Err on disconnect.
@@ -71,17 +139,174 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
         }
     }
 
+    /// Exchange the handshake with the peripheral, returns Err on disconnect.
+    ///
+    /// The handshake is sent again until the peripheral answers, messages received before the answer are dropped.
+    /// A peripheral with a different protocol version is refused: everything from it is dropped until it disconnects.
+    /// A peripheral with different features is accepted, but feature-dependent messages are not exchanged with it.
+    async fn handshake(&mut self) -> Result<(), ()> {
+        let local = SplitHandshake::new(ROW as u8, COL as u8);
+        let remote = loop {
+            self.send(&SplitMessage::Handshake(local)).await?;
+            let reply = self.read_reply(|message| match message {
+                SplitMessage::Handshake(remote) => Some(remote),
+                _ => None,
+            });
+            match with_timeout(HANDSHAKE_TIMEOUT, reply).await {
+                Ok(remote) => break remote?,
+                Err(_) => warn!(
+                    "No handshake from peripheral {}, is it running an older firmware? Retrying",
+                    self.id
+                ),
+            }
+        };
+
+        let Err(mismatch) = local.check(&remote) else {
+            info!("Handshake with peripheral {} done", self.id);
+            return Ok(());
+        };
+        error!("Peripheral {} mismatch: {:?}", self.id, mismatch);
+        publish_event(SplitMismatchEvent { id: self.id, mismatch });
+        match mismatch {
+            SplitMismatch::Version { .. } => {
+                error!(
+                    "Peripheral {} is refused, please flash both halves with the same RMK version",
+                    self.id
+                );
+                loop {
+                    if let Err(SplitDriverError::Disconnected) = self.transceiver.read().await {
+                        return Err(());
+                    }
+                }
+            }
+            SplitMismatch::Features { .. } => {
+                warn!(
+                    "Feature-dependent messages are not exchanged with peripheral {}",
+                    self.id
+                );
+                self.compatible_features = false;
+            }
+            // Keys outside of the matrix are dropped when they are received
+            SplitMismatch::MatrixSize { .. } => (),
+            // Builds are compared after the handshake
+            SplitMismatch::Build(_) => (),
+        }
+        Ok(())
+    }
+
+    /// Exchange the firmware build with the peripheral, returns whether the builds match, or Err on disconnect.
+    ///
+    /// If they don't match and the confirmation is required, the keys of the peripheral are ignored until confirmed.
+    async fn exchange_build_info(&mut self) -> Result<bool, ()> {
+        let local = SplitBuildInfo::local();
+        let remote = loop {
+            self.send(&SplitMessage::BuildInfo(local)).await?;
+            let reply = self.read_reply(|message| match message {
+                SplitMessage::BuildInfo(remote) => Some(remote),
+                _ => None,
+            });
+            match with_timeout(HANDSHAKE_TIMEOUT, reply).await {
+                Ok(remote) => break remote?,
+                Err(_) => warn!("No build info from peripheral {}, retrying", self.id),
+            }
+        };
+
+        if remote == local {
+            info!("Peripheral {} runs the same build {}", self.id, local);
+            return Ok(true);
+        }
+        warn!(
+            "Peripheral {} runs build {}, but the central runs {}, please flash both halves with the same firmware",
+            self.id, remote, local
+        );
+        publish_event(SplitMismatchEvent {
+            id: self.id,
+            mismatch: SplitMismatch::Build(remote),
+        });
+        if crate::split::central::confirm_mismatched_build() {
+            warn!(
+                "Keys of peripheral {} are ignored until a key on it is pressed",
+                self.id
+            );
+            self.awaiting_confirmation = true;
+        }
+        Ok(false)
+    }
+
+    /// Read until `reply` picks a message from the peripheral, returns Err on disconnect.
+    ///
+    /// Other messages received before the reply are dropped.
+    async fn read_reply<R>(&mut self, reply: impl Fn(SplitMessage) -> Option<R>) -> Result<R, ()> {
+        loop {
+            match self.transceiver.read().await {
+                Ok(message) => {
+                    if let Some(r) = reply(message) {
+                        return Ok(r);
+                    }
+                    debug!("{:?} from peripheral {} is dropped before its reply", message, self.id);
+                }
+                Err(SplitDriverError::Disconnected) => return Err(()),
+                Err(e) => error!("Peripheral message read error: {:?}", e),
+            }
+        }
+    }
+
+    /// Send the snapshot of the central's state, so that the peripheral doesn't keep the stale state.
+    ///
+    /// Returns Err on disconnect.
+    async fn sync_state(&mut self) -> Result<(), ()> {
+        self.send(&SplitMessage::StateSnapshot(snapshot::snapshot())).await?;
+
+        #[cfg(feature = "_ble")]
+        if self.compatible_features {
+            let (battery, peripheral_batteries) = snapshot::batteries();
+            self.send(&SplitMessage::CentralBattery(battery)).await?;
+            for (id, state) in peripheral_batteries.into_iter().enumerate() {
+                if id != self.id {
+                    self.send(&SplitMessage::PeripheralBattery(id as u8, state)).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Run the manager.
     ///
-    /// The manager receives from the peripheral and publishes input events.
-    /// It also syncs the `ConnectionState` to the peripheral periodically.
+    /// The manager exchanges the handshake with the peripheral first and sends the snapshot of the central's state,
+    /// then it receives from the peripheral and publishes input events.
+    /// It also syncs the `ConnectionState` to the peripheral periodically, together with a link probe for BLE.
+    ///
+    /// When the peripheral is disconnected, the keys held on it are released.
     pub(crate) async fn run(mut self) {
+        self.run_until_disconnected().await;
+        release_peripheral_keys(self.id).await;
+        publish_event(PeripheralConnectedEvent {
+            id: self.id,
+            connected: false,
+            build_mismatch: false,
+        });
+    }
+
+    /// Process the messages until the peripheral is disconnected
+    async fn run_until_disconnected(&mut self) {
         use embassy_time::Timer;
 
         use crate::event::EventSubscriber;
 
+        if self.handshake().await.is_err() {
+            return;
+        }
+        let Ok(build_matched) = self.exchange_build_info().await else {
+            return;
+        };
+        publish_event(PeripheralConnectedEvent {
+            id: self.id,
+            connected: true,
+            build_mismatch: !build_matched,
+        });
+
         let mut conn_state = CONNECTION_STATE.load(Ordering::Acquire);
-        if self.send(&SplitMessage::ConnectionState(conn_state)).await.is_err() {
+        if self.send(&SplitMessage::ConnectionState(conn_state)).await.is_err() || self.sync_state().await.is_err() {
             return;
         }
 
@@ -96,6 +321,18 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
         let mut modifier_sub = crate::event::ModifierEvent::subscriber();
         #[cfg(feature = "display")]
         let mut sleep_sub = crate::event::SleepStateEvent::subscriber();
+        #[cfg(feature = "pointing_cpi")]
+        let mut cpi_sub = crate::event::PointingSetCpiEvent::subscriber();
+
+        // Pointing devices on the peripheral use the CPI set before the connection
+        #[cfg(feature = "pointing_cpi")]
+        if self.compatible_features {
+            for e in crate::input_device::pointing::saved_pointing_cpi() {
+                if self.send(&SplitMessage::PointingSetCpi(e)).await.is_err() {
+                    return;
+                }
+            }
+        }
 
         loop {
             let elapsed = last_sync_time.elapsed().as_millis();
@@ -106,6 +343,7 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
                 crate::select_biased_with_feature! {
                     e = indicator_sub.next_event().fuse() => SplitMessage::KeyboardIndicator(e.0.into_bits()),
                     e = layer_sub.next_event().fuse() => SplitMessage::Layer(e.0),
+                    data = custom::next_custom_message(self.id).fuse() => SplitMessage::Custom(data),
                     with_feature("_ble"): _ = clear_peer_sub.next_event().fuse() => {
                         #[cfg(feature = "storage")]
                         {
@@ -123,6 +361,8 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
                     with_feature("display"): e = wpm_sub.next_event().fuse() => SplitMessage::Wpm(e.0),
                     with_feature("display"): e = modifier_sub.next_event().fuse() => SplitMessage::Modifier(e.modifier.into_bits()),
                     with_feature("display"): e = sleep_sub.next_event().fuse() => SplitMessage::SleepState(e.0),
+                    with_feature("pointing_cpi"): e = cpi_sub.next_event().fuse() => SplitMessage::PointingSetCpi(e),
+                    with_feature("dfu"): request = crate::dfu::relay::next_request(self.id).fuse() => SplitMessage::DfuRequest(request),
                 }
             };
 
@@ -142,9 +382,21 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
                     }
                 },
                 Either3::Second(message_to_peri) => {
+                    if !self.compatible_features && message_to_peri.requires_features() {
+                        // Answer at once, instead of letting the firmware update wait for the timeout
+                        #[cfg(feature = "dfu")]
+                        if let SplitMessage::DfuRequest(_) = message_to_peri {
+                            crate::dfu::relay::respond(self.id, Err(crate::dfu::DfuError::Unreachable));
+                        }
+                        continue;
+                    }
                     if self.send(&message_to_peri).await.is_err() {
+                        // An unsent custom message stays queued for the next connection
                         return;
                     }
+                    if let SplitMessage::Custom(_) = message_to_peri {
+                        custom::pop_custom_message(self.id);
+                    }
                 }
                 Either3::Third(_) => {
                     conn_state = CONNECTION_STATE.load(Ordering::Acquire);
@@ -152,6 +404,13 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
                     if self.send(&SplitMessage::ConnectionState(conn_state)).await.is_err() {
                         return;
                     }
+                    #[cfg(feature = "_ble")]
+                    if self.compatible_features
+                        && let Some(seq) = crate::split::ble::link_stats::start_probe(self.id)
+                        && self.send(&SplitMessage::LinkProbe(seq)).await.is_err()
+                    {
+                        return;
+                    }
                     last_sync_time = Instant::now();
                 }
             }
@@ -159,9 +418,32 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
     }
 
     /// Process a single message from the peripheral.
-    async fn process_peripheral_message(&self, split_message: SplitMessage) {
+    async fn process_peripheral_message(&mut self, split_message: SplitMessage) {
         trace!("Got message from peripheral: {:?}", split_message);
+        if !self.compatible_features && split_message.requires_features() {
+            debug!(
+                "{:?} is dropped because peripheral {} has different features",
+                split_message, self.id
+            );
+            return;
+        }
         match split_message {
+            // A late reply of a handshake or build info which has been sent again
+            SplitMessage::Handshake(_) | SplitMessage::BuildInfo(_) => (),
+            // The release of a key confirms the peripheral with a different build
+            SplitMessage::Key(e) if self.awaiting_confirmation => {
+                if !e.pressed {
+                    info!("Peripheral {} with a different build is confirmed", self.id);
+                    self.awaiting_confirmation = false;
+                }
+            }
+            // Custom messages don't need the connection to host
+            SplitMessage::Custom(data) => publish_event(SplitCustomEvent { id: self.id, data }),
+            #[cfg(feature = "_ble")]
+            SplitMessage::LinkProbe(seq) => crate::split::ble::link_stats::answer_probe(self.id, seq),
+            // Firmware update doesn't need the connection to host
+            #[cfg(feature = "dfu")]
+            SplitMessage::DfuResponse(result) => crate::dfu::relay::respond(self.id, result),
             SplitMessage::Key(e) => match e.pos {
                 KeyboardEventPos::Key(key_pos) => {
                     // Verify the row/col
@@ -172,12 +454,10 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
 
                     if CONNECTION_STATE.load(core::sync::atomic::Ordering::Acquire) {
                         // Only when the connection is established, send the key event.
-                        let adjusted_key_event = KeyboardEvent::key(
-                            key_pos.row + ROW_OFFSET as u8,
-                            key_pos.col + COL_OFFSET as u8,
-                            e.pressed,
-                        );
-                        publish_event_async(adjusted_key_event).await;
+                        let row = key_pos.row + ROW_OFFSET as u8;
+                        let col = key_pos.col + COL_OFFSET as u8;
+                        publish_event_async(KeyboardEvent::key(row, col, e.pressed)).await;
+                        track_held_key(self.id, row, col, e.pressed);
                     } else {
                         warn!("Key event from peripheral is ignored because the connection is not established.");
                     }
@@ -194,10 +474,12 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
             _ if CONNECTION_STATE.load(core::sync::atomic::Ordering::Acquire) => match split_message {
                 // Non-key events are drop-on-full to keep the split read loop responsive.
                 SplitMessage::Pointing(e) => publish_event(e),
+                SplitMessage::PointingButton(e) => publish_event(e),
                 #[cfg(feature = "_ble")]
                 SplitMessage::BatteryStatus(state) => {
                     // Publish as PeripheralBatteryEvent with the full state
                     use crate::event::PeripheralBatteryEvent;
+                    snapshot::set_peripheral_battery(self.id, state);
                     publish_event(PeripheralBatteryEvent { id: self.id, state })
                 }
                 _ => warn!("{:?} should not come from peripheral", split_message),
@@ -209,3 +491,21 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_keys_are_tracked_per_peripheral() {
+        track_held_key(0, 0, 1, true);
+        track_held_key(0, 0, 1, true);
+        track_held_key(0, 2, 3, true);
+        track_held_key(1, 0, 7, true);
+        track_held_key(0, 2, 3, false);
+
+        assert_eq!(take_held_keys(0).as_slice(), &[(0, 1)]);
+        assert!(take_held_keys(0).is_empty());
+        assert_eq!(take_held_keys(1).as_slice(), &[(0, 7)]);
+    }
+}
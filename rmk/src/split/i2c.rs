@@ -0,0 +1,363 @@
+//! I2C split driver.
+//!
+//! The central is the I2C controller and polls the peripherals, each peripheral is an I2C target with its own address.
+//! A split message to a peripheral is sent as a single write transaction.
+//! A peripheral answers each read with a fixed-size frame, whose first byte is the length of the message.
+//! An empty frame means that the peripheral has nothing to send.
+
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+use heapless::Deque;
+
+use super::driver::{PeripheralManager, SplitDriverError, SplitReader, SplitWriter};
+use super::peripheral::SplitPeripheral;
+use super::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
+use crate::RawMutex;
+
+/// Size of the frame read from a peripheral, the first byte is the length of the message
+pub const I2C_FRAME_SIZE: usize = SPLIT_MESSAGE_MAX_SIZE + 1;
+
+/// Interval between two polls of a peripheral
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Number of failed transactions in a row, after which the peripheral is considered disconnected
+const MAX_FAILURES: u8 = 10;
+/// Time to wait before trying to reach a disconnected peripheral again
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+/// Number of frames which can wait for the central's poll on a peripheral
+const OUTBOX_SIZE: usize = 8;
+
+/// I2C bus shared by the peripheral managers on the central
+pub type SplitI2cBus<I> = Mutex<RawMutex, I>;
+
+/// Transaction started by the I2C controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2cTargetCommand {
+    /// The controller has written the given number of bytes
+    Write(usize),
+    /// The controller reads from the target
+    Read,
+}
+
+/// I2C target mode of the peripheral, implemented for the I2C target drivers of the HALs.
+pub trait I2cTarget {
+    type Error;
+
+    /// Wait for the next transaction addressed to the target.
+    ///
+    /// Bytes written by the controller are put in `buffer`.
+    async fn listen(&mut self, buffer: &mut [u8]) -> Result<I2cTargetCommand, Self::Error>;
+
+    /// Answer a read of the controller with `data`, the bytes read beyond `data` must be filled with 0.
+    async fn respond_to_read(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Encode the message into a frame read by the central
+fn encode_frame(message: &SplitMessage, frame: &mut [u8; I2C_FRAME_SIZE]) -> Result<(), SplitDriverError> {
+    let len = postcard::to_slice(message, &mut frame[1..])
+        .map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?
+        .len();
+    frame[0] = len as u8;
+    Ok(())
+}
+
+/// Decode a frame read from a peripheral, returns `None` if the frame is empty
+fn decode_frame(frame: &[u8; I2C_FRAME_SIZE]) -> Option<Result<SplitMessage, SplitDriverError>> {
+    let len = frame[0] as usize;
+    if len == 0 {
+        return None;
+    }
+    let Some(bytes) = frame.get(1..=len) else {
+        return Some(Err(SplitDriverError::DeserializeError));
+    };
+    Some(postcard::from_bytes(bytes).map_err(|e| {
+        error!("Postcard deserialize split message error: {}", e);
+        SplitDriverError::DeserializeError
+    }))
+}
+
+/// Poll the peripheral on the I2C bus and process its split messages
+///
+/// Generic parameters:
+/// - `const ROW`: row number of the peripheral's matrix
+/// - `const COL`: column number of the peripheral's matrix
+/// - `const ROW_OFFSET`: row offset of the peripheral's matrix in the whole matrix
+/// - `const COL_OFFSET`: column offset of the peripheral's matrix in the whole matrix
+/// - `I`: an I2C bus that implements `I2c` trait in embedded-hal-async
+///
+/// The bus can be shared by all peripherals, each of them is reached by its `address`.
+/// When the peripheral stops answering, the keys held on it are released and the central tries to reach it again.
+pub async fn run_i2c_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    I: I2c,
+>(
+    id: usize,
+    bus: &SplitI2cBus<I>,
+    address: u8,
+) {
+    loop {
+        debug!("Running peripheral manager {} at I2C address {:#x}", id, address);
+        let driver = I2cCentralDriver::new(bus, address);
+        PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(driver, id)
+            .run()
+            .await;
+        Timer::after(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// Run the split peripheral service over I2C, the peripheral is an I2C target polled by the central.
+///
+/// `ROW` and `COL` are the matrix size of the peripheral, checked by the central in the handshake.
+pub async fn run_rmk_i2c_split_peripheral<const ROW: usize, const COL: usize, T: I2cTarget>(target: T) {
+    let mut peripheral = SplitPeripheral::new(I2cPeripheralDriver::new(target), ROW, COL);
+    loop {
+        peripheral.run().await;
+    }
+}
+
+/// I2C driver of the central, which is the I2C controller
+pub(crate) struct I2cCentralDriver<'a, I> {
+    bus: &'a SplitI2cBus<I>,
+    address: u8,
+    /// Whether the peripheral has answered since the driver is created
+    connected: bool,
+    /// Number of failed transactions in a row
+    failures: u8,
+}
+
+impl<'a, I: I2c> I2cCentralDriver<'a, I> {
+    pub(crate) fn new(bus: &'a SplitI2cBus<I>, address: u8) -> Self {
+        Self {
+            bus,
+            address,
+            connected: false,
+            failures: 0,
+        }
+    }
+
+    /// Check the result of a transaction.
+    ///
+    /// The peripheral is disconnected if it has never answered, or if too many transactions failed in a row.
+    fn check<E>(&mut self, result: Result<(), E>) -> Result<(), SplitDriverError> {
+        match result {
+            Ok(()) => {
+                self.connected = true;
+                self.failures = 0;
+                Ok(())
+            }
+            Err(_) => {
+                self.failures = self.failures.saturating_add(1);
+                if !self.connected || self.failures >= MAX_FAILURES {
+                    self.connected = false;
+                    Err(SplitDriverError::Disconnected)
+                } else {
+                    Err(SplitDriverError::SerialError)
+                }
+            }
+        }
+    }
+}
+
+impl<I: I2c> SplitReader for I2cCentralDriver<'_, I> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        loop {
+            let mut frame = [0_u8; I2C_FRAME_SIZE];
+            let result = self.bus.lock().await.read(self.address, &mut frame).await;
+            match self.check(result) {
+                Ok(()) => {
+                    if let Some(message) = decode_frame(&frame) {
+                        return message;
+                    }
+                }
+                Err(SplitDriverError::Disconnected) => return Err(SplitDriverError::Disconnected),
+                // A single failed poll is retried silently
+                Err(_) => (),
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl<I: I2c> SplitWriter for I2cCentralDriver<'_, I> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let bytes = postcard::to_slice(message, &mut buf).map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?;
+        let result = self.bus.lock().await.write(self.address, bytes).await;
+        self.check(result).map(|_| bytes.len())
+    }
+}
+
+/// I2C driver of the peripheral, which is an I2C target.
+///
+/// Messages to the central are kept until the central reads them.
+pub(crate) struct I2cPeripheralDriver<T> {
+    target: T,
+    outbox: Deque<[u8; I2C_FRAME_SIZE], OUTBOX_SIZE>,
+    buffer: [u8; SPLIT_MESSAGE_MAX_SIZE],
+}
+
+impl<T: I2cTarget> I2cPeripheralDriver<T> {
+    pub(crate) fn new(target: T) -> Self {
+        Self {
+            target,
+            outbox: Deque::new(),
+            buffer: [0_u8; SPLIT_MESSAGE_MAX_SIZE],
+        }
+    }
+}
+
+impl<T: I2cTarget> SplitReader for I2cPeripheralDriver<T> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        loop {
+            match self.target.listen(&mut self.buffer).await {
+                Ok(I2cTargetCommand::Write(n)) => {
+                    let bytes = self.buffer.get(..n).ok_or(SplitDriverError::DeserializeError)?;
+                    return postcard::from_bytes(bytes).map_err(|e| {
+                        error!("Postcard deserialize split message error: {}", e);
+                        SplitDriverError::DeserializeError
+                    });
+                }
+                Ok(I2cTargetCommand::Read) => {
+                    let frame = self.outbox.front().copied().unwrap_or([0_u8; I2C_FRAME_SIZE]);
+                    let len = frame[0] as usize + 1;
+                    if self.target.respond_to_read(&frame[..len]).await.is_err() {
+                        return Err(SplitDriverError::SerialError);
+                    }
+                    // The frame is removed only after the central has read it
+                    self.outbox.pop_front();
+                }
+                Err(_) => return Err(SplitDriverError::SerialError),
+            }
+        }
+    }
+}
+
+impl<T: I2cTarget> SplitWriter for I2cPeripheralDriver<T> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        encode_frame(message, &mut frame)?;
+        if self.outbox.is_full() {
+            warn!("I2C split outbox is full, dropping the oldest message");
+            self.outbox.pop_front();
+        }
+        self.outbox.push_back(frame).ok();
+        Ok(frame[0] as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+
+    use super::*;
+    use crate::test_support::test_block_on;
+
+    const ADDRESS: u8 = 0x42;
+
+    fn frame(message: &SplitMessage) -> Vec<u8> {
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        encode_frame(message, &mut frame).unwrap();
+        frame.to_vec()
+    }
+
+    #[test]
+    fn central_polls_until_a_message_is_read() {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let layer = postcard::to_slice(&SplitMessage::Layer(1), &mut buf).unwrap().to_vec();
+        let bus = SplitI2cBus::new(I2cMock::new(&[
+            I2cTrans::read(ADDRESS, vec![0; I2C_FRAME_SIZE]),
+            I2cTrans::read(ADDRESS, frame(&SplitMessage::ConnectionState(true))),
+            I2cTrans::write(ADDRESS, layer),
+        ]));
+
+        let mut driver = I2cCentralDriver::new(&bus, ADDRESS);
+        let message = test_block_on(driver.read()).unwrap();
+        assert!(matches!(message, SplitMessage::ConnectionState(true)));
+        test_block_on(driver.write(&SplitMessage::Layer(1))).unwrap();
+
+        bus.into_inner().done();
+    }
+
+    #[test]
+    fn central_detects_disconnected_peripheral() {
+        // Never answered
+        let bus = SplitI2cBus::new(I2cMock::new(&[
+            I2cTrans::read(ADDRESS, vec![0; I2C_FRAME_SIZE]).with_error(ErrorKind::Other)
+        ]));
+        let mut driver = I2cCentralDriver::new(&bus, ADDRESS);
+        assert!(matches!(
+            test_block_on(driver.read()),
+            Err(SplitDriverError::Disconnected)
+        ));
+        bus.into_inner().done();
+
+        // Stops answering after the connection, single failures are retried
+        let mut transactions = vec![I2cTrans::read(ADDRESS, vec![0; I2C_FRAME_SIZE])];
+        for _ in 0..MAX_FAILURES {
+            transactions.push(I2cTrans::read(ADDRESS, vec![0; I2C_FRAME_SIZE]).with_error(ErrorKind::Other));
+        }
+        let bus = SplitI2cBus::new(I2cMock::new(&transactions));
+        let mut driver = I2cCentralDriver::new(&bus, ADDRESS);
+        assert!(matches!(
+            test_block_on(driver.read()),
+            Err(SplitDriverError::Disconnected)
+        ));
+        bus.into_inner().done();
+    }
+
+    /// Fake I2C target, which replays the scripted transactions and records the responses
+    struct FakeTarget {
+        commands: VecDeque<(I2cTargetCommand, Vec<u8>)>,
+        responses: Vec<Vec<u8>>,
+    }
+
+    impl I2cTarget for FakeTarget {
+        type Error = ();
+
+        async fn listen(&mut self, buffer: &mut [u8]) -> Result<I2cTargetCommand, ()> {
+            let (command, bytes) = self.commands.pop_front().expect("unexpected listen");
+            buffer[..bytes.len()].copy_from_slice(&bytes);
+            Ok(command)
+        }
+
+        async fn respond_to_read(&mut self, data: &[u8]) -> Result<(), ()> {
+            self.responses.push(data.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn peripheral_answers_polls_with_queued_messages() {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let layer = postcard::to_slice(&SplitMessage::Layer(3), &mut buf).unwrap().to_vec();
+        let mut driver = I2cPeripheralDriver::new(FakeTarget {
+            commands: VecDeque::from([
+                (I2cTargetCommand::Read, vec![]),
+                (I2cTargetCommand::Read, vec![]),
+                (I2cTargetCommand::Write(layer.len()), layer),
+            ]),
+            responses: Vec::new(),
+        });
+
+        test_block_on(driver.write(&SplitMessage::ConnectionState(true))).unwrap();
+        let message = test_block_on(driver.read()).unwrap();
+        assert!(matches!(message, SplitMessage::Layer(3)));
+
+        let queued = frame(&SplitMessage::ConnectionState(true));
+        let len = queued[0] as usize + 1;
+        assert_eq!(driver.target.responses, vec![queued[..len].to_vec(), vec![0]]);
+    }
+}
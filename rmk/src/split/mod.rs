@@ -1,29 +1,207 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 
+use crate::RawMutex;
+#[cfg(feature = "dfu")]
+use crate::dfu::{DfuRequest, DfuResult};
 #[cfg(feature = "_ble")]
 use crate::event::BatteryStatusEvent;
-use crate::event::{KeyboardEvent, PointingEvent};
+#[cfg(feature = "pointing_cpi")]
+use crate::event::PointingSetCpiEvent;
+use crate::event::{KeyboardEvent, PointingButtonEvent, PointingEvent};
+use crate::split::custom::SPLIT_CUSTOM_MESSAGE_SIZE;
+use crate::split::snapshot::SplitStateSnapshot;
 
 #[cfg(feature = "_ble")]
 pub mod ble;
 pub mod central;
+pub mod custom;
 /// Common abstraction layer of split driver
 pub(crate) mod driver;
+#[cfg(not(feature = "_ble"))]
+pub mod i2c;
+#[cfg(feature = "nrf")]
+pub mod nrf;
 pub mod peripheral;
 #[cfg(feature = "rp2040")]
 pub mod rp;
 #[cfg(not(feature = "_ble"))]
 pub mod serial;
+#[cfg(not(feature = "_ble"))]
+pub mod single_wire;
+pub(crate) mod snapshot;
+#[cfg(feature = "stm32")]
+pub mod stm32;
 
 /// Maximum size of a split message
 pub const SPLIT_MESSAGE_MAX_SIZE: usize = SplitMessage::POSTCARD_MAX_SIZE + 4;
 
+/// Version of the split protocol.
+///
+/// Bump it whenever the wire format of the unconditional part of `SplitMessage` changes.
+pub const SPLIT_PROTOCOL_VERSION: u16 = 4;
+
+/// Handshake exchanged between central and peripheral on connect
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitHandshake {
+    /// Split protocol version, see [`SPLIT_PROTOCOL_VERSION`]
+    pub version: u16,
+    /// Bitmap of the enabled features which change the layout of split messages
+    pub features: u32,
+    /// Row number of the peripheral's matrix
+    pub rows: u8,
+    /// Column number of the peripheral's matrix
+    pub cols: u8,
+}
+
+impl SplitHandshake {
+    /// `display` feature is enabled
+    pub const FEATURE_DISPLAY: u32 = 1 << 0;
+    /// `_ble` feature is enabled
+    pub const FEATURE_BLE: u32 = 1 << 1;
+    /// `pointing_cpi` feature is enabled
+    pub const FEATURE_POINTING_CPI: u32 = 1 << 2;
+    /// `dfu` feature is enabled
+    pub const FEATURE_DFU: u32 = 1 << 3;
+
+    /// Create the handshake of this firmware
+    pub(crate) fn new(rows: u8, cols: u8) -> Self {
+        let mut features = 0;
+        if cfg!(feature = "display") {
+            features |= Self::FEATURE_DISPLAY;
+        }
+        if cfg!(feature = "_ble") {
+            features |= Self::FEATURE_BLE;
+        }
+        if cfg!(feature = "pointing_cpi") {
+            features |= Self::FEATURE_POINTING_CPI;
+        }
+        if cfg!(feature = "dfu") {
+            features |= Self::FEATURE_DFU;
+        }
+        Self {
+            version: SPLIT_PROTOCOL_VERSION,
+            features,
+            rows,
+            cols,
+        }
+    }
+
+    /// Compare the handshake received from the other half with the local one.
+    ///
+    /// The version is checked first, because nothing else can be trusted if it differs.
+    pub(crate) fn check(&self, remote: &SplitHandshake) -> Result<(), SplitMismatch> {
+        if self.version != remote.version {
+            Err(SplitMismatch::Version {
+                local: self.version,
+                remote: remote.version,
+            })
+        } else if self.rows != remote.rows || self.cols != remote.cols {
+            Err(SplitMismatch::MatrixSize {
+                rows: remote.rows,
+                cols: remote.cols,
+            })
+        } else if self.features != remote.features {
+            Err(SplitMismatch::Features {
+                local: self.features,
+                remote: remote.features,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Version and configuration hash of the keyboard firmware, see [`set_firmware_build`]
+static FIRMWARE_BUILD: Mutex<RawMutex, Cell<([u8; 3], u32)>> = Mutex::new(Cell::new(([0; 3], 0)));
+
+/// Set the build of the keyboard firmware, which is compared with the other halves in [`SplitBuildInfo`].
+///
+/// `firmware_version` is the version of the keyboard firmware, as major, minor and patch. `config_hash` is a
+/// deterministic hash of the keyboard's configuration and keymap. The code generated from `keyboard.toml` sets them
+/// to the version of the keyboard crate and the hash of `keyboard.toml`.
+pub fn set_firmware_build(firmware_version: [u8; 3], config_hash: u32) {
+    FIRMWARE_BUILD.lock(|b| b.set((firmware_version, config_hash)));
+}
+
+/// Firmware build of a split half, exchanged right after the handshake.
+///
+/// Two builds match if they have the same RMK version and git commit, and the same keyboard firmware version and
+/// configuration, so both halves built from the same sources match even if they are built separately.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitBuildInfo {
+    /// Version of RMK, as major, minor and patch
+    pub version: [u8; 3],
+    /// First 7 hex digits of the git commit which RMK is built from, `None` if it's unknown
+    pub commit: Option<u32>,
+    /// Version of the keyboard firmware, as major, minor and patch
+    pub firmware_version: [u8; 3],
+    /// Hash of the keyboard's configuration and keymap, 0 if it's not set
+    pub config_hash: u32,
+}
+
+impl SplitBuildInfo {
+    /// Build info of this firmware
+    pub(crate) fn local() -> Self {
+        let (firmware_version, config_hash) = FIRMWARE_BUILD.lock(|b| b.get());
+        Self {
+            version: crate::BUILD_VERSION,
+            commit: crate::BUILD_COMMIT,
+            firmware_version,
+            config_hash,
+        }
+    }
+}
+
+impl core::fmt::Display for SplitBuildInfo {
+    /// Format the build as `major.minor.patch-commit`, followed by the keyboard firmware if it's set
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let [major, minor, patch] = self.version;
+        write!(f, "{}.{}.{}", major, minor, patch)?;
+        if let Some(commit) = self.commit {
+            write!(f, "-{:07x}", commit)?;
+        }
+        if self.firmware_version != [0; 3] || self.config_hash != 0 {
+            let [major, minor, patch] = self.firmware_version;
+            write!(
+                f,
+                " (firmware {}.{}.{}, config {:08x})",
+                major, minor, patch, self.config_hash
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Incompatibility found between the central and a peripheral
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SplitMismatch {
+    /// The split protocol versions differ, the link is refused
+    Version { local: u16, remote: u16 },
+    /// The enabled features differ, feature-dependent messages are not exchanged
+    Features { local: u32, remote: u32 },
+    /// The matrix size reported by the other half differs, or a key outside of it is received
+    MatrixSize { rows: u8, cols: u8 },
+    /// The other half runs a different firmware build, which is attached
+    Build(SplitBuildInfo),
+}
+
 /// Message used from central & peripheral communication
+///
+/// `Handshake` must stay the first variant and the feature-gated variants must stay at the end,
+/// so that halves built with different features can still exchange the handshake and the common messages.
 #[repr(u8)]
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) enum SplitMessage {
+    /// Handshake, sent by the central on connect and answered by the peripheral
+    Handshake(SplitHandshake),
     /// Keyboard event, from peripheral to central
     Key(KeyboardEvent),
     /// Pointing device event, from peripheral to central
@@ -41,6 +219,14 @@ pub(crate) enum SplitMessage {
     KeyboardIndicator(u8),
     /// Layer number from central to peripheral
     Layer(u8),
+    /// Buttons of a pointing device, e.g. trackpad taps, from peripheral to central
+    PointingButton(PointingButtonEvent),
+    /// Snapshot of the central's state, sent from central to peripheral right after the connection
+    StateSnapshot(SplitStateSnapshot),
+    /// Custom message from the firmware, in both directions
+    Custom([u8; SPLIT_CUSTOM_MESSAGE_SIZE]),
+    /// Firmware build, sent by the central after the handshake and answered by the peripheral
+    BuildInfo(SplitBuildInfo),
     /// WPM from central to peripheral
     #[cfg(feature = "display")]
     Wpm(u16),
@@ -53,4 +239,152 @@ pub(crate) enum SplitMessage {
     /// Battery status, from peripheral to central
     #[cfg(feature = "_ble")]
     BatteryStatus(BatteryStatusEvent),
+    /// Battery status of the central, from central to peripheral
+    #[cfg(feature = "_ble")]
+    CentralBattery(BatteryStatusEvent),
+    /// Battery status of another peripheral with the given id, from central to peripheral
+    #[cfg(feature = "_ble")]
+    PeripheralBattery(u8, BatteryStatusEvent),
+    /// CPI of pointing devices, from central to peripheral
+    #[cfg(feature = "pointing_cpi")]
+    PointingSetCpi(PointingSetCpiEvent),
+    /// Link probe with a sequence number, sent from central to peripheral and sent back by the peripheral
+    #[cfg(feature = "_ble")]
+    LinkProbe(u16),
+    /// Firmware update request relayed by the central to the peripheral
+    #[cfg(feature = "dfu")]
+    DfuRequest(DfuRequest),
+    /// Result of a firmware update request, from peripheral to central
+    #[cfg(feature = "dfu")]
+    DfuResponse(DfuResult),
+}
+
+impl SplitMessage {
+    /// Whether the message only exists when some features are enabled.
+    ///
+    /// Such messages are not exchanged when the features of the central and the peripheral differ.
+    pub(crate) fn requires_features(&self) -> bool {
+        match self {
+            #[cfg(feature = "display")]
+            SplitMessage::Wpm(_) | SplitMessage::Modifier(_) | SplitMessage::SleepState(_) => true,
+            #[cfg(feature = "_ble")]
+            SplitMessage::BatteryStatus(_)
+            | SplitMessage::CentralBattery(_)
+            | SplitMessage::PeripheralBattery(..)
+            | SplitMessage::LinkProbe(_) => true,
+            #[cfg(feature = "pointing_cpi")]
+            SplitMessage::PointingSetCpi(_) => true,
+            #[cfg(feature = "dfu")]
+            SplitMessage::DfuRequest(_) | SplitMessage::DfuResponse(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_check() {
+        let local = SplitHandshake::new(4, 6);
+        assert_eq!(local.check(&SplitHandshake::new(4, 6)), Ok(()));
+
+        let remote = SplitHandshake {
+            version: SPLIT_PROTOCOL_VERSION + 1,
+            features: 0xFF,
+            rows: 5,
+            cols: 6,
+        };
+        assert_eq!(
+            local.check(&remote),
+            Err(SplitMismatch::Version {
+                local: SPLIT_PROTOCOL_VERSION,
+                remote: SPLIT_PROTOCOL_VERSION + 1
+            })
+        );
+
+        let remote = SplitHandshake {
+            features: local.features ^ SplitHandshake::FEATURE_DISPLAY,
+            ..local
+        };
+        assert_eq!(
+            local.check(&remote),
+            Err(SplitMismatch::Features {
+                local: local.features,
+                remote: remote.features
+            })
+        );
+
+        let remote = SplitHandshake { rows: 5, ..local };
+        assert_eq!(
+            local.check(&remote),
+            Err(SplitMismatch::MatrixSize { rows: 5, cols: 6 })
+        );
+    }
+
+    #[test]
+    fn build_info_display() {
+        let build = SplitBuildInfo {
+            version: [0, 8, 2],
+            commit: Some(0x0abc123),
+            firmware_version: [0; 3],
+            config_hash: 0,
+        };
+        assert_eq!(format!("{}", build), "0.8.2-0abc123");
+        assert_eq!(format!("{}", SplitBuildInfo { commit: None, ..build }), "0.8.2");
+        assert_eq!(
+            format!(
+                "{}",
+                SplitBuildInfo {
+                    firmware_version: [1, 2, 0],
+                    config_hash: 0x1a2b3c,
+                    ..build
+                }
+            ),
+            "0.8.2-0abc123 (firmware 1.2.0, config 001a2b3c)"
+        );
+    }
+
+    #[test]
+    fn builds_of_the_same_commit_match() {
+        // The build info doesn't depend on the time of the build
+        assert_eq!(SplitBuildInfo::local(), SplitBuildInfo::local());
+        let central = SplitBuildInfo {
+            version: [0, 8, 2],
+            commit: Some(0x0abc123),
+            firmware_version: [1, 0, 0],
+            config_hash: 0x1234_5678,
+        };
+        let peripheral = central;
+        assert_eq!(central, peripheral);
+        assert_ne!(
+            central,
+            SplitBuildInfo {
+                commit: Some(0x0abc124),
+                ..peripheral
+            }
+        );
+        assert_ne!(
+            central,
+            SplitBuildInfo {
+                version: [0, 8, 3],
+                ..peripheral
+            }
+        );
+        assert_ne!(
+            central,
+            SplitBuildInfo {
+                firmware_version: [1, 0, 1],
+                ..peripheral
+            }
+        );
+    }
+
+    #[test]
+    fn handshake_is_the_first_variant() {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let bytes = postcard::to_slice(&SplitMessage::Handshake(SplitHandshake::new(4, 6)), &mut buf).unwrap();
+        assert_eq!(bytes[0], 0);
+    }
 }
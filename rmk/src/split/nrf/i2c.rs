@@ -0,0 +1,28 @@
+//! I2C target of nRF, used by the I2C split peripheral
+
+use embassy_nrf::twis::{Command, Error, Twis};
+
+use crate::split::i2c::{I2C_FRAME_SIZE, I2cTarget, I2cTargetCommand};
+
+impl<'d> I2cTarget for Twis<'d> {
+    type Error = Error;
+
+    async fn listen(&mut self, buffer: &mut [u8]) -> Result<I2cTargetCommand, Error> {
+        match Twis::listen(self, buffer).await? {
+            Command::Write(n) => Ok(I2cTargetCommand::Write(n)),
+            Command::Read => Ok(I2cTargetCommand::Read),
+            // The central doesn't use write-read, the read part is answered with an empty frame
+            Command::WriteRead(n) => {
+                Twis::respond_to_read(self, &[0; I2C_FRAME_SIZE]).await?;
+                Ok(I2cTargetCommand::Write(n))
+            }
+        }
+    }
+
+    async fn respond_to_read(&mut self, data: &[u8]) -> Result<(), Error> {
+        // TWIS fails with `OverRead` when the central reads beyond the buffer, so the whole frame is sent
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        frame[..data.len()].copy_from_slice(data);
+        Twis::respond_to_read(self, &frame).await.map(|_| ())
+    }
+}
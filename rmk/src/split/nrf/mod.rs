@@ -0,0 +1,2 @@
+#[cfg(not(feature = "_ble"))]
+pub mod i2c;
@@ -1,34 +1,104 @@
+use core::cell::RefCell;
+
 #[cfg(feature = "_ble")]
 use bt_hci::{cmd::le::LeSetPhy, controller::ControllerCmdAsync};
 use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::InputPin;
 #[cfg(not(feature = "_ble"))]
 use embedded_io_async::{Read, Write};
 use futures::FutureExt;
+use heapless::Deque;
 #[cfg(all(feature = "_ble", feature = "storage"))]
 use {super::ble::PeerAddress, crate::channel::FLASH_CHANNEL};
 #[cfg(feature = "_ble")]
 use {
-    crate::event::{BatteryStatusEvent, ChargingStateEvent, EventSubscriber},
+    crate::event::{
+        BatteryStatusEvent, CentralBatteryEvent, ChargingStateEvent, EventSubscriber, PeripheralBatteryEvent,
+    },
     rmk_types::battery::BatteryStatus,
+    rmk_types::ble::BleStatus,
     trouble_host::prelude::*,
 };
 
-use super::SplitMessage;
+use super::custom::{next_custom_message, pop_custom_message};
 use super::driver::{SplitReader, SplitWriter};
-use crate::CONNECTION_STATE;
+use super::snapshot::SplitStateSnapshot;
+use super::{SplitBuildInfo, SplitHandshake, SplitMessage, SplitMismatch};
 use crate::event::{
-    KeyboardEvent, LayerChangeEvent, LedIndicatorEvent, PointingEvent, SubscribableEvent, publish_event,
+    ConnectionChangeEvent, DefaultLayerChangeEvent, KeyboardEvent, KeyboardEventPos, LayerChangeEvent,
+    LedIndicatorEvent, PointingButtonEvent, PointingEvent, SplitCustomEvent, SplitMismatchEvent, SubscribableEvent,
+    publish_event,
 };
 #[cfg(feature = "display")]
 use crate::event::{ModifierEvent, SleepStateEvent, WpmUpdateEvent};
 #[cfg(not(feature = "_ble"))]
 use crate::split::serial::SerialSplitDriver;
 use crate::state::ConnectionState;
+use crate::{CONNECTION_STATE, RawMutex};
+
+/// Number of events kept while the link to the central is down
+const EVENT_BUFFER_SIZE: usize = 16;
+/// Buffered events older than this are dropped instead of being replayed
+const EVENT_BUFFER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Events waiting for the link to the central, in their original order
+static EVENT_BUFFER: Mutex<RawMutex, RefCell<Deque<(Instant, SplitMessage), EVENT_BUFFER_SIZE>>> =
+    Mutex::new(RefCell::new(Deque::new()));
+
+/// Whether the message is kept while the link is down, only events which change the key state are kept.
+fn is_buffered(message: &SplitMessage) -> bool {
+    matches!(message, SplitMessage::Key(_) | SplitMessage::PointingButton(_))
+}
+
+/// Keep the message until the link to the central is up, the oldest one is dropped when the buffer is full.
+pub(crate) fn buffer_event(message: SplitMessage) {
+    EVENT_BUFFER.lock(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        if buffer.is_full() {
+            warn!("Split event buffer is full, dropping the oldest event");
+            buffer.pop_front();
+        }
+        buffer.push_back((Instant::now(), message)).ok();
+    });
+}
+
+/// Take the oldest buffered message, expired ones are dropped
+fn take_buffered_event() -> Option<SplitMessage> {
+    EVENT_BUFFER.lock(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        while let Some((time, message)) = buffer.pop_front() {
+            if time.elapsed() <= EVENT_BUFFER_TIMEOUT {
+                return Some(message);
+            }
+            debug!("Buffered split message {:?} is expired", message);
+        }
+        None
+    })
+}
+
+/// Keep the key events while the peripheral is not connected to the central.
+///
+/// It runs until it's cancelled, the events are replayed when the central is connected.
+#[cfg(feature = "_ble")]
+pub(crate) async fn buffer_events_while_disconnected() -> ! {
+    let mut key_sub = KeyboardEvent::subscriber();
+    let mut pointing_button_sub = PointingButtonEvent::subscriber();
+    loop {
+        let message = match select(key_sub.next_message_pure(), pointing_button_sub.next_message_pure()).await {
+            Either::First(e) => SplitMessage::Key(e),
+            Either::Second(e) => SplitMessage::PointingButton(e),
+        };
+        buffer_event(message);
+    }
+}
 
 /// Run the split peripheral service.
 ///
 /// # Arguments
 ///
+/// * `ROW`, `COL` - The matrix size of the peripheral, checked by the central in the handshake
 /// * `id` - (optional) The id of the peripheral
 /// * `stack` - (optional) The TrouBLE stack
 /// * `serial` - (optional) serial port used to send peripheral split message. This argument is enabled only for serial split now
@@ -38,6 +108,8 @@ use crate::state::ConnectionState;
 #[allow(clippy::extra_unused_lifetimes)]
 pub async fn run_rmk_split_peripheral<
     'a,
+    const ROW: usize,
+    const COL: usize,
     #[cfg(feature = "_ble")] C: Controller + ControllerCmdAsync<LeSetPhy>,
     #[cfg(not(feature = "_ble"))] S: Write + Read,
 >(
@@ -47,41 +119,200 @@ pub async fn run_rmk_split_peripheral<
 ) {
     #[cfg(not(feature = "_ble"))]
     {
-        let mut peripheral = SplitPeripheral::new(SerialSplitDriver::new(serial));
+        let mut peripheral = SplitPeripheral::new(SerialSplitDriver::new(serial), ROW, COL);
         loop {
             peripheral.run().await;
         }
     }
 
     #[cfg(feature = "_ble")]
-    crate::split::ble::peripheral::initialize_nrf_ble_split_peripheral_and_run(id, stack).await;
+    crate::split::ble::peripheral::initialize_nrf_ble_split_peripheral_and_run::<ROW, COL, _>(id, stack).await;
+}
+
+/// Role of a split half, selected at boot by [`detect_split_role`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SplitRole {
+    /// The half is connected to the host over its own USB, it works as a standalone keyboard with its local keymap
+    Master,
+    /// The half works as a split peripheral of the central
+    Slave,
+}
+
+/// Number of times the USB detect pin is sampled at boot
+const USB_DETECT_SAMPLES: usize = 5;
+/// Interval between two samples of the USB detect pin
+const USB_DETECT_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Select the role of the split half by the USB presence at boot, like QMK does.
+///
+/// `usb_detect` reads high when the USB is powered, e.g. a VBUS sense pin.
+/// The half becomes master only if the USB is present in every sample, so the bounce at power-on doesn't make it master.
+pub async fn detect_split_role<P: InputPin>(usb_detect: &mut P) -> SplitRole {
+    for _ in 0..USB_DETECT_SAMPLES {
+        if !usb_detect.is_high().unwrap_or(false) {
+            info!("No USB at boot, running as split peripheral");
+            return SplitRole::Slave;
+        }
+        Timer::after(USB_DETECT_INTERVAL).await;
+    }
+    info!("USB is present at boot, running as master with the local keymap");
+    SplitRole::Master
 }
 
 /// The split peripheral instance.
 pub(crate) struct SplitPeripheral<S: SplitWriter + SplitReader> {
     split_driver: S,
+    /// Matrix size of this peripheral, sent in the handshake
+    rows: u8,
+    cols: u8,
+    /// Handshake state of the current connection
+    handshake: HandshakeState,
+}
+
+/// Result of the handshake with the central
+#[derive(Clone, Copy, Default)]
+struct HandshakeState {
+    /// Matrix size expected by the central, `None` before the handshake
+    matrix: Option<(u8, u8)>,
+    /// The central runs a different protocol version, nothing is exchanged with it
+    refused: bool,
+    /// The central has different features, feature-dependent messages are not exchanged
+    incompatible_features: bool,
+    /// Whether a key outside of the central's matrix has been reported
+    matrix_mismatch_reported: bool,
 }
 
 impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
-    pub(crate) fn new(split_driver: S) -> Self {
-        Self { split_driver }
+    pub(crate) fn new(split_driver: S, rows: usize, cols: usize) -> Self {
+        Self {
+            split_driver,
+            rows: rows as u8,
+            cols: cols as u8,
+            handshake: HandshakeState::default(),
+        }
+    }
+
+    /// Answer the handshake from the central with the matrix size of this peripheral and check it.
+    ///
+    /// Keys outside of the central's matrix are dropped when the sizes differ.
+    async fn on_handshake(&mut self, remote: SplitHandshake) {
+        let local = SplitHandshake::new(self.rows, self.cols);
+        self.split_driver.write(&SplitMessage::Handshake(local)).await.ok();
+
+        self.handshake = HandshakeState {
+            matrix: Some((remote.rows, remote.cols)),
+            ..Default::default()
+        };
+        let Err(mismatch) = local.check(&remote) else {
+            info!("Handshake with central done");
+            return;
+        };
+        error!("Central mismatch: {:?}", mismatch);
+        publish_event(SplitMismatchEvent { id: 0, mismatch });
+        match mismatch {
+            SplitMismatch::Version { .. } => self.handshake.refused = true,
+            SplitMismatch::Features { .. } => self.handshake.incompatible_features = true,
+            SplitMismatch::MatrixSize { .. } | SplitMismatch::Build(_) => (),
+        }
+    }
+
+    /// Answer the firmware build from the central, a different build is only reported
+    async fn on_build_info(&mut self, remote: SplitBuildInfo) {
+        let local = SplitBuildInfo::local();
+        self.split_driver.write(&SplitMessage::BuildInfo(local)).await.ok();
+        if remote != local {
+            warn!(
+                "Central runs build {}, but this peripheral runs {}, please flash both halves with the same firmware",
+                remote, local
+            );
+            publish_event(SplitMismatchEvent {
+                id: 0,
+                mismatch: SplitMismatch::Build(remote),
+            });
+        }
+    }
+
+    /// Whether the message can be exchanged with the central
+    fn is_allowed(&self, message: &SplitMessage) -> bool {
+        !self.handshake.refused && !(self.handshake.incompatible_features && message.requires_features())
+    }
+
+    /// Whether the message should be sent to the central, according to the handshake
+    fn should_send(&mut self, message: &SplitMessage) -> bool {
+        if !self.is_allowed(message) {
+            debug!("{:?} is not sent because of the handshake mismatch", message);
+            return false;
+        }
+        match message {
+            SplitMessage::Key(key_event) => self.check_key(key_event),
+            _ => true,
+        }
+    }
+
+    /// Send the buffered events to the central
+    async fn replay_buffered_events(&mut self) {
+        while let Some(message) = take_buffered_event() {
+            if self.should_send(&message) {
+                debug!("Replaying buffered split message {:?}", message);
+                self.split_driver.write(&message).await.ok();
+            }
+        }
+    }
+
+    /// Check that the key is inside of the central's matrix, the first key outside of it is reported.
+    fn check_key(&mut self, event: &KeyboardEvent) -> bool {
+        let (KeyboardEventPos::Key(pos), Some((rows, cols))) = (event.pos, self.handshake.matrix) else {
+            return true;
+        };
+        if pos.row < rows && pos.col < cols {
+            return true;
+        }
+        error!(
+            "Key ({}, {}) is outside of the central's {}x{} matrix",
+            pos.row, pos.col, rows, cols
+        );
+        if !self.handshake.matrix_mismatch_reported {
+            self.handshake.matrix_mismatch_reported = true;
+            publish_event(SplitMismatchEvent {
+                id: 0,
+                mismatch: SplitMismatch::MatrixSize { rows, cols },
+            });
+        }
+        false
     }
 
     /// Run the peripheral keyboard service.
     ///
     /// The peripheral uses the general matrix, does scanning and send the key events through `SplitWriter`.
     /// If also receives split messages from the central through `SplitReader`.
+    ///
+    /// Key events are buffered until the central reports its connection state,
+    /// and while the central is not connected, then they are replayed in their original order.
     pub(crate) async fn run(&mut self) {
+        self.handshake = HandshakeState::default();
         CONNECTION_STATE.store(ConnectionState::Connected.into(), core::sync::atomic::Ordering::Release);
+        // Whether the connection state has been received from the central in this connection
+        let mut synced = false;
 
         let mut key_sub = KeyboardEvent::subscriber();
         #[cfg(feature = "_ble")]
         let mut charging_state_sub = ChargingStateEvent::subscriber();
         let mut pointing_sub = PointingEvent::subscriber();
+        let mut pointing_button_sub = PointingButtonEvent::subscriber();
         #[cfg(feature = "_ble")]
         let mut battery_sub = BatteryStatusEvent::subscriber();
 
         loop {
+            // Custom messages are kept in their queue until the link is up
+            let link_up = synced;
+            let next_custom = async {
+                if link_up {
+                    next_custom_message(0).await
+                } else {
+                    core::future::pending().await
+                }
+            };
             let read_message_to_send = async {
                 crate::select_biased_with_feature! {
                     e = key_sub.next_message_pure().fuse() => SplitMessage::Key(e),
@@ -92,6 +323,8 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                         }.into())
                     },
                     e = pointing_sub.next_message_pure().fuse() => SplitMessage::Pointing(e),
+                    e = pointing_button_sub.next_message_pure().fuse() => SplitMessage::PointingButton(e),
+                    data = next_custom.fuse() => SplitMessage::Custom(data),
                     with_feature("_ble"): e = battery_sub.next_event().fuse() => SplitMessage::BatteryStatus(e),
                 }
             };
@@ -100,10 +333,21 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                 Either::First(m) => match m {
                     // Process split messages from the central
                     // Currently only handle the central state message
+                    Ok(SplitMessage::Handshake(remote)) => self.on_handshake(remote).await,
+                    Ok(split_message) if !self.is_allowed(&split_message) => {
+                        debug!(
+                            "{:?} from central is dropped because of the handshake mismatch",
+                            split_message
+                        );
+                    }
                     Ok(split_message) => match split_message {
                         SplitMessage::ConnectionState(state) => {
                             trace!("Received connection state update: {}", state);
                             CONNECTION_STATE.store(state, core::sync::atomic::Ordering::Release);
+                            synced = true;
+                            if state {
+                                self.replay_buffered_events().await;
+                            }
                         }
                         #[cfg(all(feature = "_ble", feature = "storage"))]
                         SplitMessage::ClearPeer => {
@@ -124,6 +368,15 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                             // Publish Layer event
                             publish_event(LayerChangeEvent::new(layer));
                         }
+                        SplitMessage::BuildInfo(remote) => self.on_build_info(remote).await,
+                        SplitMessage::StateSnapshot(snapshot) => apply_snapshot(snapshot),
+                        SplitMessage::Custom(data) => publish_event(SplitCustomEvent { id: 0, data }),
+                        #[cfg(feature = "_ble")]
+                        SplitMessage::CentralBattery(state) => publish_event(CentralBatteryEvent { state }),
+                        #[cfg(feature = "_ble")]
+                        SplitMessage::PeripheralBattery(id, state) => {
+                            publish_event(PeripheralBatteryEvent { id: id as usize, state })
+                        }
                         #[cfg(feature = "display")]
                         SplitMessage::Wpm(wpm) => {
                             publish_event(WpmUpdateEvent::new(wpm));
@@ -138,6 +391,21 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                         SplitMessage::SleepState(sleeping) => {
                             publish_event(SleepStateEvent::new(sleeping));
                         }
+                        #[cfg(feature = "pointing_cpi")]
+                        SplitMessage::PointingSetCpi(e) => {
+                            crate::input_device::pointing::set_pointing_cpi(e);
+                        }
+                        // Send the probe back at once, so that the central can measure the round trip time
+                        #[cfg(feature = "_ble")]
+                        SplitMessage::LinkProbe(seq) => {
+                            self.split_driver.write(&SplitMessage::LinkProbe(seq)).await.ok();
+                        }
+                        // The split link waits while the local DFU updater processes the request
+                        #[cfg(feature = "dfu")]
+                        SplitMessage::DfuRequest(request) => {
+                            let result = crate::dfu::request(crate::dfu::DfuTarget::Local, request).await;
+                            self.split_driver.write(&SplitMessage::DfuResponse(result)).await.ok();
+                        }
                         _ => (),
                     },
                     Err(e) => {
@@ -148,15 +416,226 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                     }
                 },
                 Either::Second(e) => {
+                    if !self.should_send(&e) {
+                        if let SplitMessage::Custom(_) = e {
+                            pop_custom_message(0);
+                        }
+                        continue;
+                    }
                     // Only send the key event if the connection is established
-                    if CONNECTION_STATE.load(core::sync::atomic::Ordering::Acquire) {
+                    if let SplitMessage::Custom(_) = e {
+                        // Custom messages don't need the connection to host, they are only taken when the link is up
+                        debug!("Writing custom split message to central");
+                        match self.split_driver.write(&e).await {
+                            Ok(_) => pop_custom_message(0),
+                            // Kept in the queue and sent again after a while
+                            Err(err) => {
+                                error!("Failed to write custom split message: {:?}", err);
+                                Timer::after_millis(100).await;
+                            }
+                        }
+                    } else if synced && CONNECTION_STATE.load(core::sync::atomic::Ordering::Acquire) {
+                        // Buffered events go first, to keep the original order
+                        self.replay_buffered_events().await;
                         debug!("Writing split message {:?} to central", e);
                         self.split_driver.write(&e).await.ok();
+                    } else if is_buffered(&e) {
+                        debug!("Connection not established, buffering {:?}", e);
+                        buffer_event(e);
                     } else {
-                        debug!("Connection not established, skipping key event");
+                        debug!("Connection not established, skipping {:?}", e);
                     }
                 }
             }
         }
     }
 }
+
+/// Publish the state in the snapshot from the central, as if each part of it has just changed
+fn apply_snapshot(snapshot: SplitStateSnapshot) {
+    debug!("Received state snapshot from central: {:?}", snapshot);
+    publish_event(DefaultLayerChangeEvent::new(snapshot.default_layer));
+    publish_event(LayerChangeEvent::new(snapshot.layer));
+    publish_event(LedIndicatorEvent::new(
+        rmk_types::led_indicator::LedIndicator::from_bits(snapshot.led_indicator),
+    ));
+    publish_event(ConnectionChangeEvent::new(snapshot.connection_type));
+    #[cfg(feature = "display")]
+    {
+        publish_event(ModifierEvent {
+            modifier: rmk_types::modifier::ModifierCombination::from_bits(snapshot.modifiers),
+        });
+        publish_event(WpmUpdateEvent::new(snapshot.wpm));
+        publish_event(SleepStateEvent::new(snapshot.sleeping));
+    }
+    #[cfg(feature = "_ble")]
+    {
+        let state = crate::ble::BLE_STATUS.lock(|c| c.get()).state;
+        crate::ble::set_ble_status(BleStatus {
+            profile: snapshot.ble_profile,
+            state,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::MockDriver;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State as PinState, Transaction as PinTrans};
+
+    use super::*;
+    use crate::split::driver::SplitDriverError;
+    use crate::test_support::test_block_on;
+
+    fn key(col: u8, pressed: bool) -> SplitMessage {
+        SplitMessage::Key(KeyboardEvent::key(0, col, pressed))
+    }
+
+    fn take_col() -> Option<(u8, bool)> {
+        take_buffered_event().map(|m| match m {
+            SplitMessage::Key(KeyboardEvent {
+                pos: crate::event::KeyboardEventPos::Key(pos),
+                pressed,
+            }) => (pos.col, pressed),
+            m => panic!("Unexpected buffered message {:?}", m),
+        })
+    }
+
+    /// Split driver which keeps the written messages
+    #[derive(Default)]
+    struct RecordingDriver {
+        written: Vec<SplitMessage>,
+    }
+
+    impl SplitWriter for RecordingDriver {
+        async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+            self.written.push(*message);
+            Ok(0)
+        }
+    }
+
+    impl SplitReader for RecordingDriver {
+        async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+            core::future::pending().await
+        }
+    }
+
+    fn role(levels: &[bool]) -> SplitRole {
+        let transactions: Vec<PinTrans> = levels
+            .iter()
+            .map(|&high| PinTrans::get(if high { PinState::High } else { PinState::Low }))
+            .collect();
+        let mut pin = PinMock::new(&transactions);
+        let role = test_block_on(detect_split_role(&mut pin));
+        pin.done();
+        role
+    }
+
+    #[test]
+    fn split_role_is_selected_by_usb_presence() {
+        MockDriver::get().reset();
+
+        assert_eq!(role(&[true; USB_DETECT_SAMPLES]), SplitRole::Master);
+        assert_eq!(role(&[false]), SplitRole::Slave);
+        // Bounce at power-on
+        assert_eq!(role(&[true, true, false]), SplitRole::Slave);
+    }
+
+    #[test]
+    fn handshake_answers_the_peripheral_matrix_size() {
+        let mut peripheral = SplitPeripheral::new(RecordingDriver::default(), 4, 5);
+
+        // The central expects a larger matrix
+        let central = SplitHandshake::new(5, 6);
+        test_block_on(peripheral.on_handshake(central));
+        let Some(SplitMessage::Handshake(answer)) = peripheral.split_driver.written.last().copied() else {
+            panic!("The handshake isn't answered");
+        };
+        assert_eq!((answer.rows, answer.cols), (4, 5));
+        // The central detects the mismatch from the answer
+        assert_eq!(
+            central.check(&answer),
+            Err(SplitMismatch::MatrixSize { rows: 4, cols: 5 })
+        );
+        assert!(!peripheral.handshake.refused);
+
+        // Same sizes pass
+        let central = SplitHandshake::new(4, 5);
+        test_block_on(peripheral.on_handshake(central));
+        let Some(SplitMessage::Handshake(answer)) = peripheral.split_driver.written.last().copied() else {
+            panic!("The handshake isn't answered");
+        };
+        assert_eq!(central.check(&answer), Ok(()));
+    }
+
+    #[test]
+    fn different_config_hash_is_a_build_mismatch() {
+        let mut mismatch_sub = SplitMismatchEvent::subscriber();
+        let mut peripheral = SplitPeripheral::new(RecordingDriver::default(), 4, 5);
+        crate::split::set_firmware_build([1, 0, 0], 0x1234_5678);
+
+        // The same build is only answered
+        test_block_on(peripheral.on_build_info(SplitBuildInfo::local()));
+        let Some(SplitMessage::BuildInfo(answer)) = peripheral.split_driver.written.last().copied() else {
+            panic!("The build info isn't answered");
+        };
+        assert_eq!(answer, SplitBuildInfo::local());
+        assert_eq!(mismatch_sub.try_next_message_pure(), None);
+
+        // The central is built from another keyboard.toml
+        let central = SplitBuildInfo {
+            config_hash: 0x1234_5679,
+            ..SplitBuildInfo::local()
+        };
+        test_block_on(peripheral.on_build_info(central));
+        assert_eq!(
+            mismatch_sub.try_next_message_pure(),
+            Some(SplitMismatchEvent {
+                id: 0,
+                mismatch: SplitMismatch::Build(central),
+            })
+        );
+    }
+
+    #[test]
+    fn buffered_events_are_replayed_in_order() {
+        MockDriver::get().reset();
+
+        // Expired events are not replayed
+        buffer_event(key(0, true));
+        MockDriver::get().advance(EVENT_BUFFER_TIMEOUT + Duration::from_millis(1));
+        buffer_event(key(1, true));
+        assert_eq!(take_col(), Some((1, true)));
+        assert_eq!(take_col(), None);
+
+        // The oldest event is dropped when the buffer is full
+        for col in 1..=EVENT_BUFFER_SIZE as u8 + 1 {
+            buffer_event(key(col, col % 2 == 0));
+        }
+        for col in 2..=EVENT_BUFFER_SIZE as u8 + 1 {
+            assert_eq!(take_col(), Some((col, col % 2 == 0)));
+        }
+        assert_eq!(take_col(), None);
+    }
+
+    #[test]
+    fn snapshot_publishes_the_layers() {
+        let mut default_layer_sub = DefaultLayerChangeEvent::subscriber();
+        let mut layer_sub = LayerChangeEvent::subscriber();
+        apply_snapshot(SplitStateSnapshot {
+            layer: 3,
+            default_layer: 1,
+            led_indicator: 0,
+            modifiers: 0,
+            wpm: 0,
+            sleeping: false,
+            connection_type: rmk_types::connection::ConnectionType::Usb,
+            ble_profile: 0,
+        });
+        assert_eq!(
+            default_layer_sub.try_next_message_pure(),
+            Some(DefaultLayerChangeEvent::new(1))
+        );
+        assert_eq!(layer_sub.try_next_message_pure(), Some(LayerChangeEvent::new(3)));
+    }
+}
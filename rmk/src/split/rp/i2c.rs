@@ -0,0 +1,29 @@
+//! I2C target of the RP2040, used by the I2C split peripheral
+
+use embassy_rp::i2c::Instance;
+use embassy_rp::i2c_slave::{Command, Error, I2cSlave};
+
+use crate::split::i2c::{I2cTarget, I2cTargetCommand};
+
+impl<'d, T: Instance> I2cTarget for I2cSlave<'d, T> {
+    type Error = Error;
+
+    async fn listen(&mut self, buffer: &mut [u8]) -> Result<I2cTargetCommand, Error> {
+        loop {
+            match I2cSlave::listen(self, buffer).await? {
+                Command::Write(n) => return Ok(I2cTargetCommand::Write(n)),
+                Command::Read => return Ok(I2cTargetCommand::Read),
+                // The central doesn't use write-read, the read part is answered with an empty frame
+                Command::WriteRead(n) => {
+                    self.respond_and_fill(&[], 0).await?;
+                    return Ok(I2cTargetCommand::Write(n));
+                }
+                Command::GeneralCall(_) => (),
+            }
+        }
+    }
+
+    async fn respond_to_read(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.respond_and_fill(data, 0).await.map(|_| ())
+    }
+}
@@ -1 +1,3 @@
+#[cfg(not(feature = "_ble"))]
+pub mod i2c;
 pub mod uart;
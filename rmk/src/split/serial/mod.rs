@@ -1,8 +1,25 @@
 use embedded_io_async::{Read, Write};
 
 use super::driver::SplitDriverError;
-use crate::split::driver::{PeripheralManager, SplitReader, SplitWriter};
-use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
+use crate::split::SPLIT_MESSAGE_MAX_SIZE;
+use crate::split::driver::PeripheralManager;
+#[cfg(not(feature = "split_reliable"))]
+use crate::split::{
+    SplitMessage,
+    driver::{SplitReader, SplitWriter},
+};
+
+#[cfg(feature = "split_reliable")]
+mod reliable;
+
+/// COBS frame delimiter
+const SENTINEL: u8 = 0x00;
+
+/// Maximum size of a frame on the serial line
+#[cfg(not(feature = "split_reliable"))]
+const FRAME_MAX_SIZE: usize = SPLIT_MESSAGE_MAX_SIZE;
+#[cfg(feature = "split_reliable")]
+const FRAME_MAX_SIZE: usize = SPLIT_MESSAGE_MAX_SIZE + reliable::FRAME_OVERHEAD;
 
 /// Receive split message from peripheral via serial and process it
 ///
@@ -23,6 +40,8 @@ pub(crate) async fn run_serial_peripheral_manager<
     receiver: S,
 ) {
     let split_serial_driver: SerialSplitDriver<S> = SerialSplitDriver::new(receiver);
+    #[cfg(feature = "split_reliable")]
+    let split_serial_driver = split_serial_driver.with_id(id);
     let peripheral_manager = PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(split_serial_driver, id);
     info!("Running peripheral manager {}", id);
 
@@ -30,25 +49,31 @@ pub(crate) async fn run_serial_peripheral_manager<
 }
 
 /// Serial driver for BOTH split central and peripheral
+///
+/// With the `split_reliable` feature, the frames are protected by the reliability layer in [`reliable`].
 pub(crate) struct SerialSplitDriver<S> {
     serial: S,
-    buffer: [u8; SPLIT_MESSAGE_MAX_SIZE],
+    buffer: [u8; FRAME_MAX_SIZE],
     n_bytes_part: usize,
+    #[cfg(feature = "split_reliable")]
+    link: reliable::Link,
 }
 
 impl<S> SerialSplitDriver<S> {
     pub(crate) fn new(serial: S) -> Self {
         Self {
             serial,
-            buffer: [0_u8; SPLIT_MESSAGE_MAX_SIZE],
+            buffer: [0_u8; FRAME_MAX_SIZE],
             n_bytes_part: 0,
+            #[cfg(feature = "split_reliable")]
+            link: reliable::Link::new(),
         }
     }
 }
 
-impl<S: Read> SplitReader for SerialSplitDriver<S> {
-    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
-        const SENTINEL: u8 = 0x00;
+impl<S: Read> SerialSplitDriver<S> {
+    /// Read from the serial until a complete frame is in the buffer, or the buffer is full.
+    async fn fill_buffer(&mut self) -> Result<(), SplitDriverError> {
         // Check the buffer *before* reading: a prior read() call may have
         // pulled in more than one complete message, and the next one is
         // already waiting in `self.buffer[..self.n_bytes_part]`.
@@ -67,6 +92,14 @@ impl<S: Read> SplitReader for SerialSplitDriver<S> {
             debug_assert!(self.n_bytes_part + n_bytes <= self.buffer.len());
             self.n_bytes_part += n_bytes;
         }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "split_reliable"))]
+impl<S: Read> SplitReader for SerialSplitDriver<S> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        self.fill_buffer().await?;
 
         let (result, n_bytes_unused) =
             match postcard::take_from_bytes_cobs::<SplitMessage>(&mut self.buffer[..self.n_bytes_part]) {
@@ -89,13 +122,9 @@ impl<S: Read> SplitReader for SerialSplitDriver<S> {
     }
 }
 
-impl<S: Write> SplitWriter for SerialSplitDriver<S> {
-    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
-        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
-        let bytes = postcard::to_slice_cobs(message, &mut buf).map_err(|e| {
-            error!("Postcard serialize split message error: {}", e);
-            SplitDriverError::SerializeError
-        })?;
+impl<S: Write> SerialSplitDriver<S> {
+    /// Write a complete frame to the serial
+    async fn write_frame(&mut self, bytes: &[u8]) -> Result<usize, SplitDriverError> {
         let mut remaining_bytes = bytes.len();
         while remaining_bytes > 0 {
             let sent_bytes = self
@@ -109,6 +138,18 @@ impl<S: Write> SplitWriter for SerialSplitDriver<S> {
     }
 }
 
+#[cfg(not(feature = "split_reliable"))]
+impl<S: Write> SplitWriter for SerialSplitDriver<S> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let bytes = postcard::to_slice_cobs(message, &mut buf).map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?;
+        self.write_frame(bytes).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::VecDeque;
@@ -118,6 +159,8 @@ mod tests {
     use embedded_io_async::ErrorType;
 
     use super::*;
+    use crate::split::SplitMessage;
+    use crate::split::driver::{SplitReader, SplitWriter};
 
     /// Fake `embedded_io_async::Read`: each `serial.read()` call returns the
     /// next scripted chunk. Panics if the driver calls `read()` more times
@@ -126,6 +169,8 @@ mod tests {
     struct FakeSerial {
         chunks: VecDeque<Vec<u8>>,
         read_calls: usize,
+        /// Bytes written by the driver
+        written: Vec<u8>,
     }
 
     impl FakeSerial {
@@ -133,6 +178,7 @@ mod tests {
             Self {
                 chunks: chunks.into_iter().collect(),
                 read_calls: 0,
+                written: Vec::new(),
             }
         }
     }
@@ -157,25 +203,43 @@ mod tests {
         }
     }
 
-    fn encode(msg: &SplitMessage) -> Vec<u8> {
-        let mut buf = [0u8; SPLIT_MESSAGE_MAX_SIZE];
-        let encoded = postcard::to_slice_cobs(msg, &mut buf).unwrap();
-        encoded.to_vec()
+    impl Write for FakeSerial {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// Encode the messages into the frames which the other half sends, with the framing of the enabled features
+    fn encode_all<const N: usize>(messages: [SplitMessage; N]) -> [Vec<u8>; N] {
+        let mut drv = SerialSplitDriver::new(FakeSerial::new([]));
+        messages.map(|msg| {
+            let start = drv.serial.written.len();
+            block_on(drv.write(&msg)).expect("write should succeed");
+            drv.serial.written[start..].to_vec()
+        })
+    }
+
+    fn encode(msg: SplitMessage) -> Vec<u8> {
+        let [frame] = encode_all([msg]);
+        frame
     }
 
     #[test]
     fn read_single_message_in_one_chunk() {
-        let fake = FakeSerial::new([encode(&SplitMessage::LedState(true))]);
+        let fake = FakeSerial::new([encode(SplitMessage::LedState(true))]);
         let mut drv = SerialSplitDriver::new(fake);
 
         let msg = block_on(drv.read()).expect("read should succeed");
         assert!(matches!(msg, SplitMessage::LedState(true)));
         assert_eq!(drv.serial.read_calls, 1);
+        // Only key events are acknowledged by the reliable framing
+        assert!(drv.serial.written.is_empty());
     }
 
     #[test]
     fn read_message_split_across_chunks() {
-        let bytes = encode(&SplitMessage::LedState(true));
+        let bytes = encode(SplitMessage::LedState(true));
         let (a, b) = bytes.split_at(bytes.len() / 2);
         let fake = FakeSerial::new([a.to_vec(), b.to_vec()]);
         let mut drv = SerialSplitDriver::new(fake);
@@ -190,8 +254,8 @@ mod tests {
     /// must deliver both without issuing a second underlying read.
     #[test]
     fn two_bundled_messages_do_not_trigger_extra_read() {
-        let mut bundled = encode(&SplitMessage::LedState(true));
-        bundled.extend_from_slice(&encode(&SplitMessage::ConnectionState(false)));
+        let [mut bundled, second] = encode_all([SplitMessage::LedState(true), SplitMessage::ConnectionState(false)]);
+        bundled.extend_from_slice(&second);
 
         let fake = FakeSerial::new([bundled]);
         let mut drv = SerialSplitDriver::new(fake);
@@ -211,8 +275,7 @@ mod tests {
     /// prefix plus the next chunk.
     #[test]
     fn trailing_partial_message_is_carried_over() {
-        let full1 = encode(&SplitMessage::LedState(true));
-        let full2 = encode(&SplitMessage::ConnectionState(true));
+        let [full1, full2] = encode_all([SplitMessage::LedState(true), SplitMessage::ConnectionState(true)]);
         let (prefix, suffix) = full2.split_at(full2.len() / 2);
 
         let mut first_chunk = full1;
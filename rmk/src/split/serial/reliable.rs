@@ -0,0 +1,563 @@
+//! Reliability layer of the serial split driver, enabled by the `split_reliable` feature.
+//!
+//! Every frame is `[flags, seq, message.., crc]`, COBS encoded and terminated by `0x00`:
+//! - `flags`: whether the frame is an ACK, or whether the sender waits for an ACK of it
+//! - `seq`: sequence number of the data frame, 0 for the first frame after boot, then 1..=255
+//! - `message`: postcard serialized `SplitMessage`, empty in ACK frames
+//! - `crc`: CRC-16/CCITT-FALSE of everything before it, little endian
+//!
+//! Key and pointing button events wait for the ACK from the other half and are sent again if it doesn't come,
+//! so that a lost release doesn't leave a key stuck.
+//! Messages received while waiting for an ACK are buffered and returned by the following reads.
+
+use embassy_time::{Duration, with_timeout};
+use embedded_io_async::{Read, Write};
+use heapless::Deque;
+
+use super::{FRAME_MAX_SIZE, SENTINEL, SerialSplitDriver};
+use crate::event::{SplitLinkQualityEvent, publish_event};
+use crate::split::SplitMessage;
+use crate::split::driver::{SplitDriverError, SplitReader, SplitWriter};
+
+/// Bytes added to each frame: flags, sequence number and CRC
+pub(super) const FRAME_OVERHEAD: usize = 4;
+
+/// The frame is an ACK of the data frame with the same sequence number
+const FLAG_ACK: u8 = 1 << 0;
+/// The sender waits for the ACK of the frame
+const FLAG_ACK_REQUEST: u8 = 1 << 1;
+
+/// Time to wait for the ACK before sending the frame again
+const ACK_TIMEOUT: Duration = Duration::from_millis(20);
+/// Number of retransmits before giving up a frame
+const MAX_RETRANSMITS: u32 = 5;
+/// Number of messages which can be buffered while waiting for an ACK
+const INBOX_SIZE: usize = 4;
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// COBS encode `data` into `out`, including the trailing sentinel.
+///
+/// Returns the size of the encoded frame, or `None` if `out` is too small.
+fn cobs_encode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut code_index = 0;
+    let mut code: u8 = 1;
+    let mut i = 1;
+    for &byte in data {
+        if byte != SENTINEL {
+            *out.get_mut(i)? = byte;
+            i += 1;
+            code += 1;
+        }
+        if byte == SENTINEL || code == 0xFF {
+            *out.get_mut(code_index)? = code;
+            code_index = i;
+            i += 1;
+            code = 1;
+        }
+    }
+    *out.get_mut(code_index)? = code;
+    *out.get_mut(i)? = SENTINEL;
+    Some(i + 1)
+}
+
+/// COBS decode `frame` in place, `frame` doesn't include the sentinel.
+///
+/// Returns the decoded size, or `None` if the encoding is broken.
+fn cobs_decode(frame: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    let mut write = 0;
+    while read < frame.len() {
+        let code = frame[read] as usize;
+        if code == 0 || read + code > frame.len() {
+            return None;
+        }
+        read += 1;
+        for _ in 1..code {
+            frame[write] = frame[read];
+            write += 1;
+            read += 1;
+        }
+        if code != 0xFF && read < frame.len() {
+            frame[write] = 0;
+            write += 1;
+        }
+    }
+    Some(write)
+}
+
+/// Decoded frame
+#[derive(Debug)]
+enum Frame {
+    Ack(u8),
+    Data {
+        seq: u8,
+        ack_request: bool,
+        message: SplitMessage,
+    },
+}
+
+/// Encode a frame into `buf`, returns the encoded bytes
+fn encode_frame<'a>(
+    flags: u8,
+    seq: u8,
+    message: Option<&SplitMessage>,
+    buf: &'a mut [u8; FRAME_MAX_SIZE],
+) -> Result<&'a [u8], SplitDriverError> {
+    let mut raw = [0_u8; FRAME_MAX_SIZE];
+    raw[0] = flags;
+    raw[1] = seq;
+    let mut len = 2;
+    if let Some(message) = message {
+        len += postcard::to_slice(message, &mut raw[2..FRAME_MAX_SIZE - 2])
+            .map_err(|e| {
+                error!("Postcard serialize split message error: {}", e);
+                SplitDriverError::SerializeError
+            })?
+            .len();
+    }
+    let crc = crc16(&raw[..len]);
+    raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+    len += 2;
+
+    let n = cobs_encode(&raw[..len], buf).ok_or(SplitDriverError::SerializeError)?;
+    Ok(&buf[..n])
+}
+
+/// Decode a frame in place, `frame` doesn't include the sentinel
+fn decode_frame(frame: &mut [u8]) -> Result<Frame, SplitDriverError> {
+    let len = cobs_decode(frame).ok_or(SplitDriverError::CorruptedFrame)?;
+    if len < FRAME_OVERHEAD {
+        return Err(SplitDriverError::CorruptedFrame);
+    }
+    let (raw, crc) = frame[..len].split_at(len - 2);
+    if crc16(raw).to_le_bytes() != crc {
+        return Err(SplitDriverError::CorruptedFrame);
+    }
+
+    let (flags, seq) = (raw[0], raw[1]);
+    if flags & FLAG_ACK != 0 {
+        return Ok(Frame::Ack(seq));
+    }
+    let message = postcard::from_bytes(&raw[2..]).map_err(|e| {
+        error!("Postcard deserialize split message error: {}", e);
+        SplitDriverError::DeserializeError
+    })?;
+    Ok(Frame::Data {
+        seq,
+        ack_request: flags & FLAG_ACK_REQUEST != 0,
+        message,
+    })
+}
+
+/// Sequence number after `seq`, 0 is skipped because it's only used by the first frame
+fn next_seq(seq: u8) -> u8 {
+    if seq == u8::MAX { 1 } else { seq + 1 }
+}
+
+/// State of the reliability layer
+pub(super) struct Link {
+    /// Sequence number of the last sent data frame
+    tx_seq: Option<u8>,
+    /// Sequence number of the last received data frame
+    rx_seq: Option<u8>,
+    /// Messages received while waiting for an ACK
+    inbox: Deque<SplitMessage, INBOX_SIZE>,
+    /// Link quality counters
+    quality: SplitLinkQualityEvent,
+}
+
+impl Link {
+    pub(super) fn new() -> Self {
+        Self {
+            tx_seq: None,
+            rx_seq: None,
+            inbox: Deque::new(),
+            quality: SplitLinkQualityEvent::default(),
+        }
+    }
+
+    fn next_tx_seq(&mut self) -> u8 {
+        let seq = self.tx_seq.map_or(0, next_seq);
+        self.tx_seq = Some(seq);
+        seq
+    }
+
+    /// Check the sequence number of a received data frame, returns false if it's a duplicate.
+    fn check_rx_seq(&mut self, seq: u8, ack_request: bool) -> bool {
+        match self.rx_seq {
+            // The ACK of the frame was lost, and the other half sent it again
+            Some(last) if seq == last => {
+                if ack_request {
+                    return false;
+                }
+            }
+            // 0 means that the other half has restarted, so there's no gap to count
+            Some(last) if seq != 0 => {
+                let gap = (seq as u16 + 255 - next_seq(last) as u16) % 255;
+                if gap > 0 {
+                    self.update(|q| q.lost += gap as u32);
+                }
+            }
+            _ => (),
+        }
+        self.rx_seq = Some(seq);
+        true
+    }
+
+    /// Update the link quality counters and publish them
+    fn update(&mut self, f: impl FnOnce(&mut SplitLinkQualityEvent)) {
+        f(&mut self.quality);
+        publish_event(self.quality);
+    }
+}
+
+impl<S> SerialSplitDriver<S> {
+    /// Set the peripheral id reported in `SplitLinkQualityEvent`
+    pub(crate) fn with_id(mut self, id: usize) -> Self {
+        self.link.quality.id = id;
+        self
+    }
+}
+
+impl<S: Read + Write> SerialSplitDriver<S> {
+    /// Read the next frame from the serial
+    async fn read_frame(&mut self) -> Result<Frame, SplitDriverError> {
+        self.fill_buffer().await?;
+
+        let Some(end) = self.buffer[..self.n_bytes_part].iter().position(|&x| x == SENTINEL) else {
+            // The buffer is full but there's no complete frame in it
+            self.n_bytes_part = 0;
+            self.link.update(|q| q.corrupted += 1);
+            return Err(SplitDriverError::CorruptedFrame);
+        };
+        let result = decode_frame(&mut self.buffer[..end]);
+        self.buffer.copy_within(end + 1..self.n_bytes_part, 0);
+        self.n_bytes_part -= end + 1;
+
+        if let Err(SplitDriverError::CorruptedFrame) = result {
+            self.link.update(|q| q.corrupted += 1);
+        }
+        result
+    }
+
+    /// Acknowledge a received data frame if needed, returns the message if it's not a duplicate
+    async fn receive_data(
+        &mut self,
+        seq: u8,
+        ack_request: bool,
+        message: SplitMessage,
+    ) -> Result<Option<SplitMessage>, SplitDriverError> {
+        if ack_request {
+            let mut buf = [0_u8; FRAME_MAX_SIZE];
+            let frame = encode_frame(FLAG_ACK, seq, None, &mut buf)?;
+            self.write_frame(frame).await?;
+        }
+        Ok(self.link.check_rx_seq(seq, ack_request).then_some(message))
+    }
+
+    /// Wait for the ACK of the data frame `seq`, messages received meanwhile are kept in the inbox
+    async fn wait_ack(&mut self, seq: u8) -> Result<(), SplitDriverError> {
+        loop {
+            match self.read_frame().await {
+                Ok(Frame::Ack(ack)) if ack == seq => return Ok(()),
+                // A late ACK of a frame which has been sent again
+                Ok(Frame::Ack(_)) => (),
+                // Don't acknowledge it, the other half sends it again later
+                Ok(Frame::Data { message, .. }) if self.link.inbox.is_full() => {
+                    warn!("Split inbox is full, {:?} is dropped", message)
+                }
+                Ok(Frame::Data {
+                    seq,
+                    ack_request,
+                    message,
+                }) => {
+                    if let Some(message) = self.receive_data(seq, ack_request, message).await? {
+                        self.link.inbox.push_back(message).ok();
+                    }
+                }
+                Err(e @ (SplitDriverError::SerialError | SplitDriverError::EmptyMessage)) => return Err(e),
+                // Broken frames are counted already
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> SplitReader for SerialSplitDriver<S> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        if let Some(message) = self.link.inbox.pop_front() {
+            return Ok(message);
+        }
+        loop {
+            if let Frame::Data {
+                seq,
+                ack_request,
+                message,
+            } = self.read_frame().await?
+                && let Some(message) = self.receive_data(seq, ack_request, message).await?
+            {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> SplitWriter for SerialSplitDriver<S> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let ack_request = matches!(message, SplitMessage::Key(_) | SplitMessage::PointingButton(_));
+        let flags = if ack_request { FLAG_ACK_REQUEST } else { 0 };
+        let seq = self.link.next_tx_seq();
+        let mut buf = [0_u8; FRAME_MAX_SIZE];
+        let frame = encode_frame(flags, seq, Some(message), &mut buf)?;
+        let n_bytes = self.write_frame(frame).await?;
+        if !ack_request {
+            return Ok(n_bytes);
+        }
+
+        for retransmit in 0..=MAX_RETRANSMITS {
+            if retransmit > 0 {
+                self.link.update(|q| q.retransmitted += 1);
+                self.write_frame(frame).await?;
+            }
+            if let Ok(result) = with_timeout(ACK_TIMEOUT, self.wait_ack(seq)).await {
+                return result.map(|_| n_bytes);
+            }
+        }
+        error!("Split message {:?} is not acknowledged", message);
+        self.link.update(|q| q.failed += 1);
+        Err(SplitDriverError::Unacknowledged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::rc::Rc;
+    use std::task::Poll;
+
+    use embassy_futures::join::join;
+    use embedded_io_async::ErrorType;
+
+    use super::*;
+    use crate::event::{KeyboardEvent, KeyboardEventPos};
+    use crate::test_support::test_block_on as block_on;
+
+    /// One end of an in-memory serial line
+    struct Wire {
+        rx: Rc<RefCell<VecDeque<u8>>>,
+        tx: Rc<RefCell<VecDeque<u8>>>,
+        /// Indices of the writes which get a byte corrupted
+        corrupt_writes: Vec<usize>,
+        writes: usize,
+    }
+
+    /// Create both ends of a serial line
+    fn wires() -> (Wire, Wire) {
+        let a = Rc::new(RefCell::new(VecDeque::new()));
+        let b = Rc::new(RefCell::new(VecDeque::new()));
+        (
+            Wire {
+                rx: a.clone(),
+                tx: b.clone(),
+                corrupt_writes: Vec::new(),
+                writes: 0,
+            },
+            Wire {
+                rx: b,
+                tx: a,
+                corrupt_writes: Vec::new(),
+                writes: 0,
+            },
+        )
+    }
+
+    impl ErrorType for Wire {
+        type Error = Infallible;
+    }
+
+    impl Read for Wire {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            core::future::poll_fn(|_| {
+                let mut rx = self.rx.borrow_mut();
+                if rx.is_empty() {
+                    return Poll::Pending;
+                }
+                let n = buf.len().min(rx.len());
+                for (dst, src) in buf.iter_mut().zip(rx.drain(..n)) {
+                    *dst = src;
+                }
+                Poll::Ready(Ok(n))
+            })
+            .await
+        }
+    }
+
+    impl Write for Wire {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let mut bytes = buf.to_vec();
+            if self.corrupt_writes.contains(&self.writes) {
+                // Flip bits in the middle of the frame, without producing a frame delimiter
+                let byte = &mut bytes[buf.len() / 2];
+                *byte ^= if *byte == 0x55 { 0xAA } else { 0x55 };
+            }
+            self.writes += 1;
+            self.tx.borrow_mut().extend(bytes);
+            Ok(buf.len())
+        }
+    }
+
+    fn key(row: u8, col: u8, pressed: bool) -> SplitMessage {
+        SplitMessage::Key(KeyboardEvent::key(row, col, pressed))
+    }
+
+    fn assert_key(message: SplitMessage, row: u8, col: u8, pressed: bool) {
+        let SplitMessage::Key(e) = message else {
+            panic!("Expected a key event, got {:?}", message);
+        };
+        let KeyboardEventPos::Key(pos) = e.pos else {
+            panic!("Expected a key position, got {:?}", e.pos);
+        };
+        assert_eq!((pos.row, pos.col, e.pressed), (row, col, pressed));
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        let cases: [&[u8]; 5] = [&[], &[0], &[0, 0, 1], &[1, 2, 0, 3, 0], &[0x11; 300]];
+        for data in cases {
+            let mut encoded = [0_u8; 310];
+            let n = cobs_encode(data, &mut encoded).unwrap();
+            assert_eq!(encoded[n - 1], SENTINEL);
+            assert!(!encoded[..n - 1].contains(&SENTINEL));
+            let len = cobs_decode(&mut encoded[..n - 1]).unwrap();
+            assert_eq!(&encoded[..len], data);
+        }
+    }
+
+    #[test]
+    fn corrupted_frame_is_rejected_by_crc() {
+        let mut buf = [0_u8; FRAME_MAX_SIZE];
+        let n = encode_frame(FLAG_ACK_REQUEST, 1, Some(&key(1, 2, true)), &mut buf)
+            .unwrap()
+            .len();
+        // Change the key position, the frame is still valid COBS
+        buf[n / 2] ^= if buf[n / 2] == 0x55 { 0xAA } else { 0x55 };
+        assert!(matches!(
+            decode_frame(&mut buf[..n - 1]),
+            Err(SplitDriverError::CorruptedFrame)
+        ));
+    }
+
+    #[test]
+    fn corrupted_key_event_is_sent_again() {
+        let (mut a, b) = wires();
+        a.corrupt_writes.push(0);
+        let mut peripheral = SerialSplitDriver::new(a);
+        let mut central = SerialSplitDriver::new(b);
+
+        let (sent, received) = block_on(join(peripheral.write(&key(1, 2, false)), async {
+            loop {
+                if let Ok(message) = central.read().await {
+                    break message;
+                }
+            }
+        }));
+        assert!(sent.is_ok());
+        assert_key(received, 1, 2, false);
+        assert_eq!(central.link.quality.corrupted, 1);
+        assert_eq!(peripheral.link.quality.retransmitted, 1);
+        assert_eq!(peripheral.link.quality.failed, 0);
+    }
+
+    #[test]
+    fn lost_ack_does_not_duplicate_key_event() {
+        let (a, mut b) = wires();
+        b.corrupt_writes.push(0);
+        let mut peripheral = SerialSplitDriver::new(a);
+        let mut central = SerialSplitDriver::new(b);
+
+        let (sent, (first, second)) = block_on(join(peripheral.write(&key(0, 0, true)), async {
+            let first = central.read().await;
+            let second = with_timeout(Duration::from_millis(200), central.read()).await;
+            (first, second)
+        }));
+        assert!(sent.is_ok());
+        assert_key(first.unwrap(), 0, 0, true);
+        assert!(
+            second.is_err(),
+            "the retransmitted key event must not be delivered twice"
+        );
+        assert_eq!(peripheral.link.quality.corrupted, 1);
+        assert_eq!(peripheral.link.quality.retransmitted, 1);
+    }
+
+    #[test]
+    fn unacknowledged_key_event_fails() {
+        let (a, _b) = wires();
+        let mut peripheral = SerialSplitDriver::new(a).with_id(1);
+
+        let result = block_on(peripheral.write(&key(0, 0, true)));
+        assert!(matches!(result, Err(SplitDriverError::Unacknowledged)));
+        assert_eq!(peripheral.link.quality.id, 1);
+        assert_eq!(peripheral.link.quality.retransmitted, MAX_RETRANSMITS);
+        assert_eq!(peripheral.link.quality.failed, 1);
+    }
+
+    #[test]
+    fn messages_received_while_waiting_for_ack_are_kept() {
+        let (a, b) = wires();
+        let mut peripheral = SerialSplitDriver::new(a);
+        let mut central = SerialSplitDriver::new(b);
+
+        let (p, c) = block_on(join(
+            peripheral.write(&key(1, 1, true)),
+            central.write(&key(2, 2, true)),
+        ));
+        assert!(p.is_ok() && c.is_ok());
+        assert_key(block_on(central.read()).unwrap(), 1, 1, true);
+        assert_key(block_on(peripheral.read()).unwrap(), 2, 2, true);
+    }
+
+    #[test]
+    fn lost_frames_are_counted() {
+        let (mut a, b) = wires();
+        a.corrupt_writes.push(1);
+        let mut peripheral = SerialSplitDriver::new(a);
+        let mut central = SerialSplitDriver::new(b);
+
+        for state in [true, false, true] {
+            block_on(peripheral.write(&SplitMessage::LedState(state))).unwrap();
+        }
+        assert!(matches!(block_on(central.read()), Ok(SplitMessage::LedState(true))));
+        assert!(matches!(
+            block_on(central.read()),
+            Err(SplitDriverError::CorruptedFrame)
+        ));
+        assert!(matches!(block_on(central.read()), Ok(SplitMessage::LedState(true))));
+        assert_eq!(central.link.quality.corrupted, 1);
+        assert_eq!(central.link.quality.lost, 1);
+        // Messages without ACK request are not acknowledged
+        assert!(peripheral.serial.rx.borrow().is_empty());
+    }
+}
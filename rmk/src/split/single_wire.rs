@@ -0,0 +1,297 @@
+//! Half-duplex UART over a single wire, for wired split keyboards which connect the halves with one data line.
+//!
+//! The UART is bit-banged on a [`FlexPin`], which is an input while the line is idle and an output while sending.
+//! `FlexPin` pulls the line down when it's an input, so the signal is inverted compared to a normal UART:
+//! the line is low when idle, the start bit is high and the stop bit is low.
+//! Both halves must use this driver.
+//!
+//! Both halves can start sending at the same time, so it's recommended to enable the `split_reliable` feature,
+//! which sends the lost key events again.
+
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_hal_async::digital::Wait;
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+
+use crate::driver::flex_pin::FlexPin;
+
+/// Default baud rate of the single-wire UART
+pub const DEFAULT_BAUD_RATE: u32 = 9600;
+
+/// Half-duplex UART on a single pin
+pub struct SingleWireUart<P: FlexPin + Wait> {
+    pin: P,
+    /// Duration of a bit
+    bit_time: Duration,
+}
+
+impl<P: FlexPin + Wait> SingleWireUart<P> {
+    /// Create a single-wire UART with the default baud rate
+    pub fn new(pin: P) -> Self {
+        Self::with_baud_rate(pin, DEFAULT_BAUD_RATE)
+    }
+
+    /// Create a single-wire UART with the given baud rate.
+    ///
+    /// The bits are timed with `embassy-time` timers, so the tick rate and the latency of the executor
+    /// limit the usable baud rate.
+    pub fn with_baud_rate(mut pin: P, baud_rate: u32) -> Self {
+        pin.set_as_input();
+        Self {
+            pin,
+            bit_time: Duration::from_hz(baud_rate as u64),
+        }
+    }
+
+    fn is_high(&mut self) -> bool {
+        self.pin.is_high().unwrap_or(false)
+    }
+
+    /// Wait until the line has been idle for a byte, so that the frame from the other half is not corrupted
+    async fn wait_idle(&mut self) {
+        loop {
+            if self.is_high() {
+                self.pin.wait_for_low().await.ok();
+            }
+            if with_timeout(self.bit_time * 10, self.pin.wait_for_high())
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Send a byte, each bit is set at its time from the start bit
+    async fn write_byte(&mut self, byte: u8) {
+        let start = Instant::now();
+        for (i, high) in frame_levels(byte).into_iter().enumerate() {
+            Timer::at(start + self.bit_time * i as u32).await;
+            if high {
+                self.pin.set_high().ok();
+            } else {
+                self.pin.set_low().ok();
+            }
+        }
+        Timer::at(start + self.bit_time * 10).await;
+    }
+
+    /// Receive a byte whose start bit began at `start`, the bits are sampled in the middle.
+    ///
+    /// Returns `None` if the stop bit is missing.
+    async fn read_byte(&mut self, start: Instant) -> Option<u8> {
+        let mut byte = 0;
+        for i in 0..8 {
+            Timer::at(start + self.bit_time * (i * 2 + 3) / 2).await;
+            if !self.is_high() {
+                byte |= 1 << i;
+            }
+        }
+        Timer::at(start + self.bit_time * 19 / 2).await;
+        if self.is_high() {
+            return None;
+        }
+        Some(byte)
+    }
+
+    /// Wait for the start bit of the next byte of the frame, returns its start or `None` if the frame is over
+    async fn next_start_bit(&mut self) -> Option<Instant> {
+        match with_timeout(self.bit_time * 10, self.pin.wait_for_high()).await {
+            Ok(Ok(())) => Some(Instant::now()),
+            _ => None,
+        }
+    }
+}
+
+/// Levels of the line for the start bit, the data bits from the LSB and the stop bit of a byte
+fn frame_levels(byte: u8) -> [bool; 10] {
+    let mut levels = [false; 10];
+    levels[0] = true;
+    for i in 0..8 {
+        levels[i + 1] = byte & (1 << i) == 0;
+    }
+    levels
+}
+
+impl<P: FlexPin + Wait> ErrorType for SingleWireUart<P> {
+    type Error = ErrorKind;
+}
+
+impl<P: FlexPin + Wait> Read for SingleWireUart<P> {
+    /// Read the bytes of a frame, a byte without its stop bit fails the whole frame
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.pin.wait_for_high().await.map_err(|_| ErrorKind::Other)?;
+        let mut start = Instant::now();
+        let mut n = 0;
+        loop {
+            buf[n] = self.read_byte(start).await.ok_or(ErrorKind::InvalidData)?;
+            n += 1;
+            if n == buf.len() {
+                break;
+            }
+            match self.next_start_bit().await {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<P: FlexPin + Wait> Write for SingleWireUart<P> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.wait_idle().await;
+        self.pin.set_as_output();
+        for &byte in buf {
+            self.write_byte(byte).await;
+        }
+        self.pin.set_as_input();
+        Ok(buf.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use embassy_futures::join::join;
+    use embedded_hal::digital::{InputPin, OutputPin};
+
+    use super::*;
+    use crate::test_support::test_block_on;
+
+    const BAUD_RATE: u32 = 1000;
+
+    /// Level changes of the wire with their time, shared by the pins of both halves
+    type Line = Rc<RefCell<Vec<(Instant, bool)>>>;
+
+    struct FakePin {
+        line: Line,
+    }
+
+    impl FakePin {
+        fn level(&self) -> bool {
+            let now = Instant::now();
+            self.line
+                .borrow()
+                .iter()
+                .rev()
+                .find(|(time, _)| *time <= now)
+                .is_some_and(|(_, high)| *high)
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for FakePin {
+        type Error = core::convert::Infallible;
+    }
+
+    impl InputPin for FakePin {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.level())
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.level())
+        }
+    }
+
+    impl OutputPin for FakePin {
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.line.borrow_mut().push((Instant::now(), true));
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.line.borrow_mut().push((Instant::now(), false));
+            Ok(())
+        }
+    }
+
+    impl FlexPin for FakePin {
+        fn set_as_input(&mut self) {}
+
+        fn set_as_output(&mut self) {}
+    }
+
+    impl Wait for FakePin {
+        async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+            while !self.level() {
+                Timer::after_micros(100).await;
+            }
+            Ok(())
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+            while self.level() {
+                Timer::after_micros(100).await;
+            }
+            Ok(())
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for_low().await?;
+            self.wait_for_high().await
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for_high().await?;
+            self.wait_for_low().await
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+            if self.level() {
+                self.wait_for_low().await
+            } else {
+                self.wait_for_high().await
+            }
+        }
+    }
+
+    fn uart(line: &Line) -> SingleWireUart<FakePin> {
+        SingleWireUart::with_baud_rate(FakePin { line: line.clone() }, BAUD_RATE)
+    }
+
+    #[test]
+    fn frame_is_inverted_with_start_and_stop_bit() {
+        // 0xA1 is sent from the LSB, a 1 bit is low on the wire
+        assert_eq!(
+            frame_levels(0xA1),
+            [true, false, true, true, true, true, false, true, false, false]
+        );
+        assert_eq!(
+            frame_levels(0xFF),
+            [true, false, false, false, false, false, false, false, false, false]
+        );
+        assert_eq!(
+            frame_levels(0x00),
+            [true, true, true, true, true, true, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn written_frame_is_read_by_the_other_half() {
+        let line: Line = Rc::new(RefCell::new(Vec::new()));
+        let mut sender = uart(&line);
+        let mut receiver = uart(&line);
+        let data = [0x00, 0xFF, 0x5A, 0xA1];
+        let mut buf = [0_u8; 8];
+
+        let (written, read) = test_block_on(join(sender.write(&data), receiver.read(&mut buf)));
+        assert_eq!(written, Ok(data.len()));
+        assert_eq!(read, Ok(data.len()));
+        assert_eq!(buf[..data.len()], data);
+    }
+
+    #[test]
+    fn missing_stop_bit_is_a_framing_error() {
+        // The line stays high after the start bit, so the stop bit is never seen
+        let line: Line = Rc::new(RefCell::new(vec![(Instant::now() + Duration::from_millis(1), true)]));
+        let mut receiver = uart(&line);
+        let mut buf = [0_u8; 4];
+
+        assert_eq!(test_block_on(receiver.read(&mut buf)), Err(ErrorKind::InvalidData));
+    }
+}
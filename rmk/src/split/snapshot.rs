@@ -0,0 +1,173 @@
+//! Snapshot of the central's state, sent to a peripheral right after it's connected.
+//!
+//! Most of the state is synced to peripherals only when it changes,
+//! so the central keeps the latest values here for the peripherals connected later.
+
+use core::cell::RefCell;
+use core::sync::atomic::Ordering;
+
+use embassy_sync::blocking_mutex::Mutex;
+use postcard::experimental::max_size::MaxSize;
+#[cfg(feature = "_ble")]
+use rmk_types::battery::BatteryStatus;
+use rmk_types::connection::ConnectionType;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "_ble")]
+use crate::event::BatteryStatusEvent;
+use crate::keyboard::LOCK_LED_STATES;
+use crate::state::CONNECTION_TYPE;
+use crate::{RawMutex, SPLIT_PERIPHERALS_NUM};
+
+/// State of the central, sent to a peripheral on connect
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct SplitStateSnapshot {
+    /// Activated layer
+    pub(crate) layer: u8,
+    /// Default layer
+    pub(crate) default_layer: u8,
+    /// Bits of the lock state led indicator
+    pub(crate) led_indicator: u8,
+    /// Bits of the held modifiers
+    pub(crate) modifiers: u8,
+    pub(crate) wpm: u16,
+    /// Whether the central is sleeping
+    pub(crate) sleeping: bool,
+    pub(crate) connection_type: ConnectionType,
+    /// Active BLE profile, always 0 when BLE isn't enabled
+    pub(crate) ble_profile: u8,
+}
+
+/// State of the central which can't be read from elsewhere
+struct CentralState {
+    layer: u8,
+    default_layer: u8,
+    modifiers: u8,
+    wpm: u16,
+    #[cfg(feature = "_ble")]
+    battery: BatteryStatusEvent,
+    #[cfg(feature = "_ble")]
+    peripheral_batteries: [BatteryStatusEvent; SPLIT_PERIPHERALS_NUM],
+}
+
+static CENTRAL_STATE: Mutex<RawMutex, RefCell<CentralState>> = Mutex::new(RefCell::new(CentralState {
+    layer: 0,
+    default_layer: 0,
+    modifiers: 0,
+    wpm: 0,
+    #[cfg(feature = "_ble")]
+    battery: BatteryStatusEvent(BatteryStatus::Unavailable),
+    #[cfg(feature = "_ble")]
+    peripheral_batteries: [BatteryStatusEvent(BatteryStatus::Unavailable); SPLIT_PERIPHERALS_NUM],
+}));
+
+fn update(f: impl FnOnce(&mut CentralState)) {
+    CENTRAL_STATE.lock(|state| f(&mut state.borrow_mut()));
+}
+
+pub(crate) fn set_layer(layer: u8) {
+    update(|s| s.layer = layer);
+}
+
+pub(crate) fn set_default_layer(layer: u8) {
+    update(|s| s.default_layer = layer);
+}
+
+pub(crate) fn set_modifiers(modifiers: u8) {
+    update(|s| s.modifiers = modifiers);
+}
+
+pub(crate) fn set_wpm(wpm: u16) {
+    update(|s| s.wpm = wpm);
+}
+
+/// Record the battery status of the central
+#[cfg(feature = "_ble")]
+pub(crate) fn set_battery(state: BatteryStatusEvent) {
+    update(|s| s.battery = state);
+}
+
+/// Record the battery status of a peripheral
+#[cfg(feature = "_ble")]
+pub(crate) fn set_peripheral_battery(id: usize, state: BatteryStatusEvent) {
+    update(|s| {
+        if let Some(slot) = s.peripheral_batteries.get_mut(id) {
+            *slot = state;
+        }
+    });
+}
+
+/// Battery status of the central and all peripherals
+#[cfg(feature = "_ble")]
+pub(crate) fn batteries() -> (BatteryStatusEvent, [BatteryStatusEvent; SPLIT_PERIPHERALS_NUM]) {
+    CENTRAL_STATE.lock(|state| {
+        let state = state.borrow();
+        (state.battery, state.peripheral_batteries)
+    })
+}
+
+/// Take a snapshot of the current state of the central
+pub(crate) fn snapshot() -> SplitStateSnapshot {
+    #[cfg(feature = "_ble")]
+    let (sleeping, ble_profile) = (
+        crate::ble::SLEEPING_STATE.load(Ordering::Acquire),
+        crate::ble::get_current_profile(),
+    );
+    #[cfg(not(feature = "_ble"))]
+    let (sleeping, ble_profile) = (false, 0);
+
+    CENTRAL_STATE.lock(|state| {
+        let state = state.borrow();
+        SplitStateSnapshot {
+            layer: state.layer,
+            default_layer: state.default_layer,
+            led_indicator: LOCK_LED_STATES.load(Ordering::Relaxed),
+            modifiers: state.modifiers,
+            wpm: state.wpm,
+            sleeping,
+            connection_type: CONNECTION_TYPE.load(Ordering::Acquire).into(),
+            ble_profile,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_has_the_latest_state() {
+        set_layer(2);
+        set_layer(3);
+        set_default_layer(1);
+        set_modifiers(0b0000_0010);
+        set_wpm(42);
+
+        let snapshot = snapshot();
+        assert_eq!(snapshot.layer, 3);
+        assert_eq!(snapshot.default_layer, 1);
+        assert_eq!(snapshot.modifiers, 0b0000_0010);
+        assert_eq!(snapshot.wpm, 42);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = SplitStateSnapshot {
+            layer: 3,
+            default_layer: 2,
+            led_indicator: 0b0000_0011,
+            modifiers: 0b0100_0001,
+            wpm: 1234,
+            sleeping: true,
+            connection_type: ConnectionType::Ble,
+            ble_profile: 1,
+        };
+        let mut buf = [0u8; crate::split::SplitMessage::POSTCARD_MAX_SIZE];
+        let bytes = postcard::to_slice(&crate::split::SplitMessage::StateSnapshot(snapshot), &mut buf).unwrap();
+        match postcard::from_bytes(bytes).unwrap() {
+            crate::split::SplitMessage::StateSnapshot(decoded) => assert_eq!(decoded, snapshot),
+            m => panic!("Unexpected message {:?}", m),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! I2C target of STM32, used by the I2C split peripheral
+
+use embassy_stm32::i2c::{Error, I2c, MultiMaster, SlaveCommandKind};
+use embassy_stm32::mode::Async;
+
+use crate::split::i2c::{I2C_FRAME_SIZE, I2cTarget, I2cTargetCommand};
+
+impl<'d> I2cTarget for I2c<'d, Async, MultiMaster> {
+    type Error = Error;
+
+    async fn listen(&mut self, buffer: &mut [u8]) -> Result<I2cTargetCommand, Error> {
+        let command = I2c::listen(self).await?;
+        match command.kind {
+            SlaveCommandKind::Write => Ok(I2cTargetCommand::Write(self.respond_to_write(buffer).await?)),
+            SlaveCommandKind::Read => Ok(I2cTargetCommand::Read),
+        }
+    }
+
+    async fn respond_to_read(&mut self, data: &[u8]) -> Result<(), Error> {
+        // The central reads the whole frame, the bytes after the message are 0
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        frame[..data.len()].copy_from_slice(data);
+        I2c::respond_to_read(self, &frame).await.map(|_| ())
+    }
+}
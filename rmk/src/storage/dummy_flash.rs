@@ -75,3 +75,104 @@ impl embedded_storage_async::nor_flash::ReadNorFlash for DummyFlash {
         0
     }
 }
+
+#[cfg(test)]
+pub(crate) use test_flash::TestFlash;
+
+#[cfg(test)]
+mod test_flash {
+    /// Error of [`TestFlash`]
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct TestFlashError;
+
+    impl embedded_storage_async::nor_flash::NorFlashError for TestFlashError {
+        fn kind(&self) -> embedded_storage_async::nor_flash::NorFlashErrorKind {
+            embedded_storage_async::nor_flash::NorFlashErrorKind::Other
+        }
+    }
+
+    /// A RAM-backed `NorFlash` for tests, which behaves like a real NOR flash: erased bytes are `0xFF`
+    /// and writing can only clear bits
+    pub(crate) struct TestFlash<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> {
+        bytes: [u8; SIZE],
+    }
+
+    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE> {
+        pub(crate) fn new() -> Self {
+            Self { bytes: [0xFF; SIZE] }
+        }
+    }
+
+    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> embedded_storage::nor_flash::ErrorType
+        for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+    {
+        type Error = TestFlashError;
+    }
+
+    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> embedded_storage::nor_flash::ReadNorFlash
+        for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+    {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            let end = start + bytes.len();
+            bytes.copy_from_slice(&self.bytes[start..end]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            SIZE
+        }
+    }
+
+    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> embedded_storage::nor_flash::NorFlash
+        for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+    {
+        const WRITE_SIZE: usize = WRITE_SIZE;
+        const ERASE_SIZE: usize = ERASE_SIZE;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.bytes[from as usize..to as usize].fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            let end = start + bytes.len();
+            for (dst, src) in self.bytes[start..end].iter_mut().zip(bytes.iter()) {
+                *dst &= *src;
+            }
+            Ok(())
+        }
+    }
+
+    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>
+        embedded_storage_async::nor_flash::ReadNorFlash for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+    {
+        const READ_SIZE: usize = 1;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::ReadNorFlash::read(self, offset, bytes)
+        }
+
+        fn capacity(&self) -> usize {
+            SIZE
+        }
+    }
+
+    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>
+        embedded_storage_async::nor_flash::NorFlash for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+    {
+        const WRITE_SIZE: usize = WRITE_SIZE;
+        const ERASE_SIZE: usize = ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::NorFlash::erase(self, from, to)
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::NorFlash::write(self, offset, bytes)
+        }
+    }
+}
@@ -12,7 +12,9 @@ use sequential_storage::cache::NoCache;
 use sequential_storage::map::{Key, MapConfig, MapStorage, PostcardValue, SerializationError};
 #[cfg(feature = "_ble")]
 use {
+    crate::NUM_BLE_PROFILE,
     crate::ble::{ble_server::CCCD_TABLE_SIZE, profile::ProfileInfo},
+    rmk_types::ble::{OutputProfile, ProfileLabel, ProfileSettings},
     trouble_host::prelude::CccdTable,
 };
 #[cfg(feature = "host")]
@@ -24,10 +26,15 @@ use {
 };
 
 use crate::channel::FLASH_CHANNEL;
+#[cfg(feature = "pointing_cpi")]
+use crate::config::PointingCpiConfig;
 use crate::config::StorageConfig;
-#[cfg(all(feature = "_ble", feature = "split"))]
-use crate::split::ble::PeerAddress;
+use crate::matrix::analog_matrix::AnalogKeyCalibration;
 use crate::{BUILD_HASH, config};
+#[cfg(all(feature = "_ble", feature = "split"))]
+use crate::{SPLIT_PERIPHERALS_NUM, split::ble::PeerAddress};
+
+pub mod dummy_flash;
 
 /// Signal to synchronize the flash operation status, usually used outside of the flash task.
 /// True if the flash operation is finished correctly, false if the flash operation is finished with error.
@@ -49,6 +56,12 @@ pub(crate) static PEER_ADDRESS_RESPONSE: Signal<crate::RawMutex, Option<PeerAddr
 #[cfg(feature = "_ble")]
 pub(crate) static SETTING_RESPONSE: Signal<crate::RawMutex, Option<u8>> = Signal::new();
 
+#[cfg(feature = "_ble")]
+pub(crate) static PROFILE_LABEL_RESPONSE: Signal<crate::RawMutex, Option<ProfileLabel>> = Signal::new();
+
+#[cfg(feature = "_ble")]
+pub(crate) static PROFILE_SETTINGS_RESPONSE: Signal<crate::RawMutex, Option<ProfileSettings>> = Signal::new();
+
 #[cfg(feature = "_ble")]
 async fn request_read<T: Send>(msg: FlashOperationMessage, response: &Signal<crate::RawMutex, T>) -> T {
     response.reset();
@@ -71,6 +84,24 @@ pub(crate) async fn read_setting(key: StorageKey) -> Option<u8> {
     request_read(FlashOperationMessage::ReadBleSetting(key), &SETTING_RESPONSE).await
 }
 
+#[cfg(feature = "_ble")]
+pub(crate) async fn read_profile_label(slot_num: u8) -> Option<ProfileLabel> {
+    request_read(
+        FlashOperationMessage::ReadProfileLabel(slot_num),
+        &PROFILE_LABEL_RESPONSE,
+    )
+    .await
+}
+
+#[cfg(feature = "_ble")]
+pub(crate) async fn read_profile_settings(output: OutputProfile) -> Option<ProfileSettings> {
+    request_read(
+        FlashOperationMessage::ReadProfileSettings(output),
+        &PROFILE_SETTINGS_RESPONSE,
+    )
+    .await
+}
+
 /// Send a peer address to be persisted; wait for the storage task to finish.
 /// Returns `true` if the write completed successfully.
 #[cfg(all(feature = "_ble", feature = "split"))]
@@ -101,6 +132,9 @@ pub(crate) enum FlashOperationMessage {
     #[cfg(feature = "_ble")]
     // Clear info of given slot number
     ClearSlot(u8),
+    #[cfg(feature = "_ble")]
+    // User label of the given slot number
+    ProfileLabel(u8, ProfileLabel),
     // Layout option
     LayoutOptions(u32),
     // Default layer number
@@ -149,6 +183,18 @@ pub(crate) enum FlashOperationMessage {
     PriorIdleTime(u16),
     // Default morse profile containing all morse/tap-hold settings (mode, timeouts, unilateral_tap)
     MorseDefaultProfile(MorseProfile),
+    // Calibration of an analog key
+    AnalogCalibration {
+        row: u8,
+        col: u8,
+        calibration: AnalogKeyCalibration,
+    },
+    #[cfg(feature = "pointing_cpi")]
+    // Current CPI preset of a pointing device
+    PointingCpi {
+        device_id: u8,
+        preset: u8,
+    },
     #[cfg(feature = "_ble")]
     // Read bond info for the given slot; storage task replies via `BOND_INFO_RESPONSE`.
     ReadBleBondInfo(u8),
@@ -158,6 +204,15 @@ pub(crate) enum FlashOperationMessage {
     #[cfg(feature = "_ble")]
     // Read a byte-valued setting: ConnectionType or ActiveBleProfile
     ReadBleSetting(StorageKey),
+    #[cfg(feature = "_ble")]
+    // Read the label of the given slot; storage task replies via `PROFILE_LABEL_RESPONSE`.
+    ReadProfileLabel(u8),
+    #[cfg(feature = "_ble")]
+    // Settings of the given output
+    ProfileSettings(OutputProfile, ProfileSettings),
+    #[cfg(feature = "_ble")]
+    // Read the settings of the given output; storage task replies via `PROFILE_SETTINGS_RESPONSE`.
+    ReadProfileSettings(OutputProfile),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -192,6 +247,16 @@ pub(crate) enum StorageKey {
     ActiveBleProfile,
     #[cfg(feature = "_ble")]
     BondInfo(u8),
+    AnalogCalibration {
+        row: u8,
+        col: u8,
+    },
+    #[cfg(feature = "pointing_cpi")]
+    PointingCpi(u8),
+    #[cfg(feature = "_ble")]
+    ProfileLabel(u8),
+    #[cfg(feature = "_ble")]
+    ProfileSettings(OutputProfile),
 }
 
 impl StorageKey {
@@ -229,6 +294,25 @@ impl StorageKey {
     pub(crate) const fn morse(idx: u8) -> Self {
         Self::Morse(idx)
     }
+
+    pub(crate) const fn analog_calibration(row: u8, col: u8) -> Self {
+        Self::AnalogCalibration { row, col }
+    }
+
+    #[cfg(feature = "pointing_cpi")]
+    pub(crate) const fn pointing_cpi(device_id: u8) -> Self {
+        Self::PointingCpi(device_id)
+    }
+
+    #[cfg(feature = "_ble")]
+    pub(crate) const fn profile_label(slot_num: u8) -> Self {
+        Self::ProfileLabel(slot_num)
+    }
+
+    #[cfg(feature = "_ble")]
+    pub(crate) const fn profile_settings(output: OutputProfile) -> Self {
+        Self::ProfileSettings(output)
+    }
 }
 
 impl Key for StorageKey {
@@ -273,6 +357,13 @@ pub(crate) enum StorageData {
     BondInfo(ProfileInfo),
     #[cfg(feature = "_ble")]
     ActiveBleProfile(u8),
+    AnalogCalibration(AnalogKeyCalibration),
+    #[cfg(feature = "pointing_cpi")]
+    PointingCpi(u8),
+    #[cfg(feature = "_ble")]
+    ProfileLabel(ProfileLabel),
+    #[cfg(feature = "_ble")]
+    ProfileSettings(ProfileSettings),
 }
 
 impl<'a> PostcardValue<'a> for StorageData {}
@@ -335,6 +426,17 @@ impl From<&config::BehaviorConfig> for StorageData {
     }
 }
 
+/// BLE bonds carried over when the storage is reinitialized after a build change
+#[cfg(feature = "_ble")]
+#[derive(Default)]
+struct SavedBonds {
+    profiles: heapless::Vec<ProfileInfo, NUM_BLE_PROFILE>,
+    labels: heapless::Vec<(u8, ProfileLabel), NUM_BLE_PROFILE>,
+    active_profile: Option<u8>,
+    #[cfg(feature = "split")]
+    peer_addresses: heapless::Vec<PeerAddress, SPLIT_PERIPHERALS_NUM>,
+}
+
 pub fn async_flash_wrapper<F: NorFlash>(flash: F) -> BlockingAsync<F> {
     embassy_embedded_hal::adapter::BlockingAsync::new(flash)
 }
@@ -451,6 +553,14 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
 
         // Check whether keymap and configs have been storaged in flash
         if !storage.check_enable().await || storage_config.clear_storage {
+            // BLE bonds don't depend on the keymap, keep them unless the storage is cleared on purpose
+            #[cfg(feature = "_ble")]
+            let bonds = if storage_config.clear_storage {
+                SavedBonds::default()
+            } else {
+                storage.read_saved_bonds().await
+            };
+
             // Clear storage first
             debug!("Clearing storage!");
             let _ = storage.flash.erase_all().await;
@@ -479,6 +589,9 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
                     .await
                     .ok();
             }
+
+            #[cfg(feature = "_ble")]
+            storage.restore_bonds(bonds).await;
         } else if storage_config.clear_layout {
             #[cfg(feature = "host")]
             {
@@ -626,6 +739,62 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         Ok(())
     }
 
+    /// Read the BLE bonds, profile labels and peer addresses, which are kept when the storage is reinitialized
+    #[cfg(feature = "_ble")]
+    async fn read_saved_bonds(&mut self) -> SavedBonds {
+        let mut bonds = SavedBonds::default();
+        for slot_num in 0..NUM_BLE_PROFILE as u8 {
+            if let Some(StorageData::BondInfo(info)) = self.fetch_data(StorageKey::bond_info(slot_num)).await
+                && !info.removed
+            {
+                bonds.profiles.push(info).ok();
+            }
+            if let Some(StorageData::ProfileLabel(label)) = self.fetch_data(StorageKey::profile_label(slot_num)).await {
+                bonds.labels.push((slot_num, label)).ok();
+            }
+        }
+        if let Some(StorageData::ActiveBleProfile(profile)) = self.fetch_data(StorageKey::ActiveBleProfile).await {
+            bonds.active_profile = Some(profile);
+        }
+        #[cfg(feature = "split")]
+        for peer_id in 0..SPLIT_PERIPHERALS_NUM as u8 {
+            if let Some(StorageData::PeerAddress(addr)) = self.fetch_data(StorageKey::peer_address(peer_id)).await
+                && addr.is_valid
+            {
+                bonds.peer_addresses.push(addr).ok();
+            }
+        }
+        bonds
+    }
+
+    /// Write back the BLE bonds read by [`Self::read_saved_bonds`]
+    #[cfg(feature = "_ble")]
+    async fn restore_bonds(&mut self, bonds: SavedBonds) {
+        if !bonds.profiles.is_empty() {
+            info!(
+                "Keeping {} BLE bonds after reinitializing the storage",
+                bonds.profiles.len()
+            );
+        }
+        for info in bonds.profiles {
+            let key = StorageKey::bond_info(info.slot_num);
+            self.store_data(key, &StorageData::BondInfo(info)).await.ok();
+        }
+        for (slot_num, label) in bonds.labels {
+            let key = StorageKey::profile_label(slot_num);
+            self.store_data(key, &StorageData::ProfileLabel(label)).await.ok();
+        }
+        if let Some(profile) = bonds.active_profile {
+            let data = StorageData::ActiveBleProfile(profile);
+            self.store_data(StorageKey::ActiveBleProfile, &data).await.ok();
+        }
+        #[cfg(feature = "split")]
+        for addr in bonds.peer_addresses {
+            let key = StorageKey::peer_address(addr.peer_id);
+            self.store_data(key, &StorageData::PeerAddress(addr)).await.ok();
+        }
+    }
+
     async fn check_enable(&mut self) -> bool {
         if let Some(StorageData::StorageConfig(config)) = self.fetch_data(StorageKey::StorageConfig).await
             && config.enable
@@ -655,6 +824,41 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         }
         core::cell::RefCell::new(peripheral_addresses)
     }
+
+    /// Read the saved calibration of analog keys, for `AnalogMatrix::with_calibration`.
+    ///
+    /// Must be called before the storage task starts.
+    pub async fn read_analog_calibration<const R: usize, const C: usize>(
+        &mut self,
+    ) -> [[Option<AnalogKeyCalibration>; C]; R] {
+        let mut calibration = [[None; C]; R];
+        for (row, cals) in calibration.iter_mut().enumerate() {
+            for (col, cal) in cals.iter_mut().enumerate() {
+                if let Some(StorageData::AnalogCalibration(c)) = self
+                    .fetch_data(StorageKey::analog_calibration(row as u8, col as u8))
+                    .await
+                {
+                    *cal = Some(c);
+                }
+            }
+        }
+        calibration
+    }
+
+    /// Restore the saved CPI presets of pointing devices.
+    ///
+    /// Must be called before the storage task starts.
+    #[cfg(feature = "pointing_cpi")]
+    pub(crate) async fn read_pointing_cpi(&mut self, config: &mut PointingCpiConfig) {
+        for device in config.devices.iter_mut() {
+            if let Some(StorageData::PointingCpi(preset)) =
+                self.fetch_data(StorageKey::pointing_cpi(device.device_id)).await
+                && (preset as usize) < device.presets.len()
+            {
+                device.current = preset;
+            }
+        }
+    }
 }
 
 impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usize, const NUM_ENCODER: usize>
@@ -693,6 +897,24 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
                     SETTING_RESPONSE.signal(resp);
                     continue;
                 }
+                #[cfg(feature = "_ble")]
+                FlashOperationMessage::ReadProfileLabel(slot_num) => {
+                    let resp = match self.fetch_data(StorageKey::profile_label(slot_num)).await {
+                        Some(StorageData::ProfileLabel(label)) => Some(label),
+                        _ => None,
+                    };
+                    PROFILE_LABEL_RESPONSE.signal(resp);
+                    continue;
+                }
+                #[cfg(feature = "_ble")]
+                FlashOperationMessage::ReadProfileSettings(output) => {
+                    let resp = match self.fetch_data(StorageKey::profile_settings(output)).await {
+                        Some(StorageData::ProfileSettings(settings)) => Some(settings),
+                        _ => None,
+                    };
+                    PROFILE_SETTINGS_RESPONSE.signal(resp);
+                    continue;
+                }
 
                 FlashOperationMessage::LayoutOptions(layout_option) => {
                     update_storage_field!(&mut self.flash, &mut self.buffer, LayoutConfig, layout_option)
@@ -755,7 +977,7 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
                 }
                 #[cfg(feature = "_ble")]
                 FlashOperationMessage::ClearSlot(slot_num) => {
-                    use bt_hci::param::BdAddr;
+                    use bt_hci::param::{AddrKind, BdAddr};
                     use trouble_host::prelude::{CCCD, SecurityLevel};
                     use trouble_host::{BondInformation, Identity, LongTermKey};
 
@@ -773,12 +995,26 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
                             SecurityLevel::NoEncryption,
                             false,
                         ),
+                        addr_kind: AddrKind::PUBLIC,
                         cccd_table: CccdTable::new([(0u16, CCCD::default()); CCCD_TABLE_SIZE]),
                     };
                     self.store_data(StorageKey::bond_info(slot_num), &StorageData::BondInfo(empty))
                         .await
                 }
                 #[cfg(feature = "_ble")]
+                FlashOperationMessage::ProfileLabel(slot_num, label) => {
+                    self.store_data(StorageKey::profile_label(slot_num), &StorageData::ProfileLabel(label))
+                        .await
+                }
+                #[cfg(feature = "_ble")]
+                FlashOperationMessage::ProfileSettings(output, settings) => {
+                    self.store_data(
+                        StorageKey::profile_settings(output),
+                        &StorageData::ProfileSettings(settings),
+                    )
+                    .await
+                }
+                #[cfg(feature = "_ble")]
                 FlashOperationMessage::ProfileInfo(b) => {
                     debug!("Saving profile info: {:?}", b);
                     self.store_data(StorageKey::bond_info(b.slot_num), &StorageData::BondInfo(b))
@@ -802,6 +1038,18 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
                 FlashOperationMessage::MorseDefaultProfile(morse_default_profile) => {
                     update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, morse_default_profile)
                 }
+                FlashOperationMessage::AnalogCalibration { row, col, calibration } => {
+                    self.store_data(
+                        StorageKey::analog_calibration(row, col),
+                        &StorageData::AnalogCalibration(calibration),
+                    )
+                    .await
+                }
+                #[cfg(feature = "pointing_cpi")]
+                FlashOperationMessage::PointingCpi { device_id, preset } => {
+                    self.store_data(StorageKey::pointing_cpi(device_id), &StorageData::PointingCpi(preset))
+                        .await
+                }
             };
 
             match write_result {
@@ -858,100 +1106,9 @@ mod tests {
 
     use super::*;
     use crate::config::{BehaviorConfig as RuntimeBehaviorConfig, StorageConfig as RuntimeStorageConfig};
+    use crate::storage::dummy_flash::TestFlash;
     use crate::test_support::test_block_on as block_on;
 
-    #[derive(Debug, Clone, Copy)]
-    struct TestFlashError;
-
-    impl embedded_storage_async::nor_flash::NorFlashError for TestFlashError {
-        fn kind(&self) -> embedded_storage_async::nor_flash::NorFlashErrorKind {
-            embedded_storage_async::nor_flash::NorFlashErrorKind::Other
-        }
-    }
-
-    struct TestFlash<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> {
-        bytes: [u8; SIZE],
-    }
-
-    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE> {
-        fn new() -> Self {
-            Self { bytes: [0xFF; SIZE] }
-        }
-    }
-
-    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> embedded_storage::nor_flash::ErrorType
-        for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
-    {
-        type Error = TestFlashError;
-    }
-
-    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> embedded_storage::nor_flash::ReadNorFlash
-        for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
-    {
-        const READ_SIZE: usize = 1;
-
-        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-            let start = offset as usize;
-            let end = start + bytes.len();
-            bytes.copy_from_slice(&self.bytes[start..end]);
-            Ok(())
-        }
-
-        fn capacity(&self) -> usize {
-            SIZE
-        }
-    }
-
-    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> embedded_storage::nor_flash::NorFlash
-        for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
-    {
-        const WRITE_SIZE: usize = WRITE_SIZE;
-        const ERASE_SIZE: usize = ERASE_SIZE;
-
-        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-            self.bytes[from as usize..to as usize].fill(0xFF);
-            Ok(())
-        }
-
-        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-            let start = offset as usize;
-            let end = start + bytes.len();
-            for (dst, src) in self.bytes[start..end].iter_mut().zip(bytes.iter()) {
-                *dst &= *src;
-            }
-            Ok(())
-        }
-    }
-
-    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>
-        embedded_storage_async::nor_flash::ReadNorFlash for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
-    {
-        const READ_SIZE: usize = 1;
-
-        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-            embedded_storage::nor_flash::ReadNorFlash::read(self, offset, bytes)
-        }
-
-        fn capacity(&self) -> usize {
-            SIZE
-        }
-    }
-
-    impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>
-        embedded_storage_async::nor_flash::NorFlash for TestFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
-    {
-        const WRITE_SIZE: usize = WRITE_SIZE;
-        const ERASE_SIZE: usize = ERASE_SIZE;
-
-        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-            embedded_storage::nor_flash::NorFlash::erase(self, from, to)
-        }
-
-        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-            embedded_storage::nor_flash::NorFlash::write(self, offset, bytes)
-        }
-    }
-
     #[cfg(feature = "host")]
     #[test]
     fn storage_key_round_trip() {
@@ -981,6 +1138,153 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "_ble")]
+    #[test]
+    fn profile_settings_fit_in_buffer() {
+        let mut buffer = [0u8; 64];
+        let key = StorageKey::profile_settings(OutputProfile::Ble(u8::MAX));
+        let key_size = <StorageKey as Key>::serialize_into(&key, &mut buffer).unwrap();
+        // Variant tag of `StorageData`
+        let settings = ProfileSettings::default();
+        let value_size = postcard::to_slice(&StorageData::ProfileSettings(settings.clone()), &mut buffer)
+            .unwrap()
+            .len();
+        let tag_size = value_size - postcard::to_slice(&settings, &mut buffer).unwrap().len();
+        assert!(key_size + tag_size + ProfileSettings::POSTCARD_MAX_SIZE <= get_buffer_size());
+    }
+
+    #[cfg(feature = "_ble")]
+    #[test]
+    fn profile_settings_round_trip() {
+        use rmk_types::action::{Action, KeyAction};
+        use rmk_types::ble::{KeyOverride, MAX_KEY_OVERRIDES, OsMode};
+        use rmk_types::keycode::{HidKeyCode, KeyCode};
+
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            let storage_range = (16_384 - 2 * 4_096) as u32..16_384u32;
+            let mut storage = Storage::<Flash, 2, 2, 1, 0> {
+                flash: MapStorage::new(Flash::new(), MapConfig::new(storage_range), NoCache::new()),
+                buffer: [0; get_buffer_size()],
+            };
+            // The largest settings
+            let mut settings = ProfileSettings {
+                default_layer: Some(u8::MAX),
+                os_mode: OsMode::MacOs,
+                key_overrides: heapless::Vec::new(),
+            };
+            for i in 0..MAX_KEY_OVERRIDES as u8 {
+                let action = KeyAction::Single(Action::Key(KeyCode::Hid(HidKeyCode::A)));
+                settings
+                    .key_overrides
+                    .push(KeyOverride {
+                        layer: u8::MAX,
+                        row: i,
+                        col: u8::MAX,
+                        action,
+                    })
+                    .unwrap();
+            }
+            let usb_settings = ProfileSettings {
+                os_mode: OsMode::Windows,
+                ..Default::default()
+            };
+            let ble = StorageKey::profile_settings(OutputProfile::Ble(u8::MAX));
+            let usb = StorageKey::profile_settings(OutputProfile::Usb);
+            storage
+                .store_data(ble, &StorageData::ProfileSettings(settings.clone()))
+                .await
+                .unwrap();
+            storage
+                .store_data(usb, &StorageData::ProfileSettings(usb_settings.clone()))
+                .await
+                .unwrap();
+
+            match storage.fetch_data(ble).await {
+                Some(StorageData::ProfileSettings(read)) => assert_eq!(read, settings),
+                _ => panic!("profile settings of the BLE profile are not stored"),
+            }
+            match storage.fetch_data(usb).await {
+                Some(StorageData::ProfileSettings(read)) => assert_eq!(read, usb_settings),
+                _ => panic!("profile settings of USB are not stored"),
+            }
+            assert!(
+                storage
+                    .fetch_data(StorageKey::profile_settings(OutputProfile::Ble(0)))
+                    .await
+                    .is_none()
+            );
+        });
+    }
+
+    #[test]
+    fn analog_calibration_round_trip() {
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            let storage_range = (16_384 - 2 * 4_096) as u32..16_384u32;
+            let mut storage = Storage::<Flash, 2, 2, 1, 0> {
+                flash: MapStorage::new(Flash::new(), MapConfig::new(storage_range), NoCache::new()),
+                buffer: [0; get_buffer_size()],
+            };
+            let calibration = AnalogKeyCalibration {
+                rest: 2048,
+                bottom: 900,
+            };
+            storage
+                .store_data(
+                    StorageKey::analog_calibration(1, 0),
+                    &StorageData::AnalogCalibration(calibration),
+                )
+                .await
+                .unwrap();
+
+            let read = storage.read_analog_calibration::<2, 2>().await;
+            assert_eq!(read, [[None, None], [Some(calibration), None]]);
+        });
+    }
+
+    #[cfg(feature = "pointing_cpi")]
+    #[test]
+    fn pointing_cpi_round_trip() {
+        use crate::config::{PointingCpiConfig, PointingCpiPresets};
+
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            let storage_range = (16_384 - 2 * 4_096) as u32..16_384u32;
+            let mut storage = Storage::<Flash, 2, 2, 1, 0> {
+                flash: MapStorage::new(Flash::new(), MapConfig::new(storage_range), NoCache::new()),
+                buffer: [0; get_buffer_size()],
+            };
+            storage
+                .store_data(StorageKey::pointing_cpi(0), &StorageData::PointingCpi(2))
+                .await
+                .unwrap();
+            // Out of range presets are ignored
+            storage
+                .store_data(StorageKey::pointing_cpi(1), &StorageData::PointingCpi(5))
+                .await
+                .unwrap();
+
+            let mut config = PointingCpiConfig::default();
+            for device_id in 0..2 {
+                config
+                    .devices
+                    .push(PointingCpiPresets {
+                        device_id,
+                        presets: &[400, 800, 1600],
+                        current: 1,
+                    })
+                    .unwrap();
+            }
+            storage.read_pointing_cpi(&mut config).await;
+            assert_eq!(config.devices[0].cpi(), Some(1600));
+            assert_eq!(config.devices[1].cpi(), Some(800));
+        });
+    }
+
     #[cfg(feature = "host")]
     #[test]
     fn build_hash_mismatch_reinitializes_storage() {
@@ -1012,6 +1316,26 @@ mod tests {
             )
             .await
             .unwrap();
+            // BLE bonds are kept when the storage is reinitialized
+            #[cfg(feature = "_ble")]
+            let bond = rmk_types::ble::BleBond {
+                slot: 1,
+                address: [1, 2, 3, 4, 5, 6],
+                address_kind: rmk_types::ble::BondAddressKind::Public,
+                irk: Some([7; 16]),
+                ltk: [8; 16],
+                security_level: rmk_types::ble::BondSecurityLevel::Encrypted,
+                is_bonded: true,
+                cccd_table: heapless::Vec::new(),
+            };
+            #[cfg(feature = "_ble")]
+            map.store_item(
+                &mut buffer,
+                &StorageKey::bond_info(1),
+                &StorageData::BondInfo(ProfileInfo::from_bond(&bond)),
+            )
+            .await
+            .unwrap();
 
             let (flash, _) = map.destroy();
             let keymap = [[[KeyAction::No; 1]; 1]; 1];
@@ -1043,6 +1367,12 @@ mod tests {
                     build_hash: BUILD_HASH,
                 })
             ));
+
+            #[cfg(feature = "_ble")]
+            match storage.fetch_data(StorageKey::bond_info(1)).await {
+                Some(StorageData::BondInfo(info)) => assert_eq!(info.to_bond(), bond),
+                _ => panic!("BLE bond is lost after reinitializing the storage"),
+            }
         });
     }
 }
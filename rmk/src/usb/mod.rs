@@ -46,18 +46,23 @@ pub(crate) struct UsbKeyboardWriter<'a, 'd, D: Driver<'d>> {
     pub(crate) other_writer: &'a mut HidWriter<'d, D, 9>,
     #[cfg(feature = "steno")]
     pub(crate) steno_writer: &'a mut HidWriter<'d, D, 9>,
+    #[cfg(feature = "gamepad")]
+    pub(crate) gamepad_writer: &'a mut HidWriter<'d, D, 11>,
 }
 impl<'a, 'd, D: Driver<'d>> UsbKeyboardWriter<'a, 'd, D> {
     pub(crate) fn new(
         keyboard_writer: &'a mut HidWriter<'d, D, 8>,
         other_writer: &'a mut HidWriter<'d, D, 9>,
         #[cfg(feature = "steno")] steno_writer: &'a mut HidWriter<'d, D, 9>,
+        #[cfg(feature = "gamepad")] gamepad_writer: &'a mut HidWriter<'d, D, 11>,
     ) -> Self {
         Self {
             keyboard_writer,
             other_writer,
             #[cfg(feature = "steno")]
             steno_writer,
+            #[cfg(feature = "gamepad")]
+            gamepad_writer,
         }
     }
 }
@@ -143,6 +148,18 @@ impl<'d, D: Driver<'d>> HidWriterTrait for UsbKeyboardWriter<'_, 'd, D> {
                 }
                 Ok(n)
             }
+            #[cfg(feature = "gamepad")]
+            Report::GamepadReport(gamepad_report) => {
+                let mut buf: [u8; 11] = [0; 11];
+                let n = gamepad_report
+                    .serialize(&mut buf)
+                    .map_err(|_| HidError::ReportSerializeError)?;
+                self.gamepad_writer
+                    .write(&buf[0..n])
+                    .await
+                    .map_err(HidError::UsbEndpointError)?;
+                Ok(n)
+            }
         }
     }
 }
@@ -163,10 +180,10 @@ pub(crate) fn new_usb_builder<'d, D: Driver<'d>>(driver: D, keyboard_config: Dev
     usb_config.device_protocol = 0x01;
     usb_config.composite_with_iads = true;
 
-    // Extra HID interfaces (usb_log, steno) overflow the 128-byte config descriptor buffer.
-    #[cfg(any(feature = "usb_log", feature = "steno"))]
+    // Extra HID interfaces (usb_log, steno, gamepad) overflow the 128-byte config descriptor buffer.
+    #[cfg(any(feature = "usb_log", feature = "steno", feature = "gamepad"))]
     const USB_BUF_SIZE: usize = 256;
-    #[cfg(not(any(feature = "usb_log", feature = "steno")))]
+    #[cfg(not(any(feature = "usb_log", feature = "steno", feature = "gamepad")))]
     const USB_BUF_SIZE: usize = 128;
 
     // Create embassy-usb DeviceBuilder using the driver and config.
@@ -298,6 +315,9 @@ static USB_DISABLED: Watch<crate::RawMutex, (), 1> = Watch::new();
 
 impl Handler for UsbDeviceHandler {
     fn enabled(&mut self, enabled: bool) {
+        // The USB peripheral is enabled when VBUS is present
+        #[cfg(feature = "_ble")]
+        crate::power::set_usb_powered(enabled);
         if enabled {
             info!("Device enabled");
         } else {
@@ -71,6 +71,10 @@ pub async fn run_key_sequence_test<'a>(
     static REPORTS_DONE: Mutex<CriticalSectionRawMutex, bool> = Mutex::new(false);
     static SEQ_SEND_DONE: Mutex<CriticalSectionRawMutex, bool> = Mutex::new(false);
 
+    // The same keyboard can run several sequences
+    *REPORTS_DONE.lock().await = false;
+    *SEQ_SEND_DONE.lock().await = false;
+
     let sender = KeyboardEvent::publisher_async();
     sender.clear();
     KEYBOARD_REPORT_CHANNEL.clear();
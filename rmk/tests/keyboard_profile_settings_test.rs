@@ -0,0 +1,176 @@
+#![cfg(feature = "_ble")]
+
+pub mod common;
+
+use rmk::ble::profile_settings::{KeyOverride, OsMode, OutputProfile, ProfileSettings, set_profile_settings};
+use rmk::config::{BehaviorConfig, PositionalConfig};
+use rmk::hid::KeyboardReport;
+use rmk::keyboard::Keyboard;
+use rmk::types::action::{Action, KeyAction};
+use rmk::types::keycode::{HidKeyCode, KeyCode};
+
+use crate::common::test_block_on::test_block_on;
+use crate::common::{KC_LCTRL, KC_LGUI, TestKeyPress, run_key_sequence_test, wrap_keymap};
+
+fn create_simple_keyboard() -> Keyboard<'static> {
+    let keymap = [
+        [[
+            KeyAction::Single(Action::Key(KeyCode::Hid(HidKeyCode::A))),
+            KeyAction::Single(Action::Key(KeyCode::Hid(HidKeyCode::LCtrl))),
+            KeyAction::Single(Action::LayerOn(1)),
+        ]],
+        [[
+            KeyAction::Single(Action::Key(KeyCode::Hid(HidKeyCode::B))),
+            KeyAction::Transparent,
+            KeyAction::Transparent,
+        ]],
+    ];
+    let behavior_config: &'static mut BehaviorConfig = Box::leak(Box::new(BehaviorConfig::default()));
+    let per_key_config: &'static PositionalConfig<1, 3> = Box::leak(Box::new(PositionalConfig::default()));
+    Keyboard::new(wrap_keymap(keymap, per_key_config, behavior_config))
+}
+
+fn key_override(layer: u8, col: u8, key: HidKeyCode) -> KeyOverride {
+    KeyOverride {
+        layer,
+        row: 0,
+        col,
+        action: KeyAction::Single(Action::Key(KeyCode::Hid(key))),
+    }
+}
+
+/// Set the settings of USB, the active output of the tests
+fn set_usb_settings(settings: ProfileSettings) {
+    assert!(test_block_on(set_profile_settings(OutputProfile::Usb, settings)));
+}
+
+#[test]
+fn test_key_override_replaces_keymap_action() {
+    let mut settings = ProfileSettings::default();
+    settings.key_overrides.push(key_override(0, 0, HidKeyCode::C)).unwrap();
+    set_usb_settings(settings);
+
+    key_sequence_test!(
+        keyboard: create_simple_keyboard(),
+        sequence: [
+            [0, 0, true, 10],
+            [0, 0, false, 10],
+        ],
+        expected_reports: [
+            [0, [kc_to_u8!(C), 0, 0, 0, 0, 0]],
+            [0, [0, 0, 0, 0, 0, 0]],
+        ]
+    );
+}
+
+#[test]
+fn test_key_override_only_applies_to_its_layer() {
+    let mut settings = ProfileSettings::default();
+    settings.key_overrides.push(key_override(1, 0, HidKeyCode::D)).unwrap();
+    set_usb_settings(settings);
+
+    key_sequence_test!(
+        keyboard: create_simple_keyboard(),
+        sequence: [
+            [0, 0, true, 10],  // Layer 0 is not overridden
+            [0, 0, false, 10],
+            [0, 2, true, 10],  // Layer 1 on
+            [0, 0, true, 10],  // Overridden on layer 1
+            [0, 0, false, 10],
+            [0, 2, false, 10],
+        ],
+        expected_reports: [
+            [0, [kc_to_u8!(A), 0, 0, 0, 0, 0]],
+            [0, [0, 0, 0, 0, 0, 0]],
+            [0, [kc_to_u8!(D), 0, 0, 0, 0, 0]],
+            [0, [0, 0, 0, 0, 0, 0]],
+        ]
+    );
+}
+
+#[test]
+fn test_macos_swaps_ctrl_and_gui() {
+    set_usb_settings(ProfileSettings {
+        os_mode: OsMode::MacOs,
+        ..Default::default()
+    });
+
+    key_sequence_test!(
+        keyboard: create_simple_keyboard(),
+        sequence: [
+            [0, 1, true, 10],
+            [0, 0, true, 10],
+            [0, 0, false, 10],
+            [0, 1, false, 10],
+        ],
+        expected_reports: [
+            [KC_LGUI, [0, 0, 0, 0, 0, 0]],
+            [KC_LGUI, [kc_to_u8!(A), 0, 0, 0, 0, 0]],
+            [KC_LGUI, [0, 0, 0, 0, 0, 0]],
+            [0, [0, 0, 0, 0, 0, 0]],
+        ]
+    );
+}
+
+#[test]
+fn test_windows_keeps_ctrl() {
+    set_usb_settings(ProfileSettings {
+        os_mode: OsMode::Windows,
+        ..Default::default()
+    });
+
+    key_sequence_test!(
+        keyboard: create_simple_keyboard(),
+        sequence: [
+            [0, 1, true, 10],
+            [0, 1, false, 10],
+        ],
+        expected_reports: [
+            [KC_LCTRL, [0, 0, 0, 0, 0, 0]],
+            [0, [0, 0, 0, 0, 0, 0]],
+        ]
+    );
+}
+
+#[test]
+fn test_default_layer_is_restored_without_output_default_layer() {
+    let tap_a = [
+        TestKeyPress {
+            row: 0,
+            col: 0,
+            pressed: true,
+            delay: 10,
+        },
+        TestKeyPress {
+            row: 0,
+            col: 0,
+            pressed: false,
+            delay: 10,
+        },
+    ];
+    let report = |key: u8| KeyboardReport {
+        modifier: 0,
+        keycodes: [key, 0, 0, 0, 0, 0],
+        leds: 0,
+        reserved: 0,
+    };
+
+    test_block_on(async {
+        let mut keyboard = create_simple_keyboard();
+
+        // The output switches the default layer to layer 1
+        set_profile_settings(
+            OutputProfile::Usb,
+            ProfileSettings {
+                default_layer: Some(1),
+                ..Default::default()
+            },
+        )
+        .await;
+        run_key_sequence_test(&mut keyboard, &tap_a, &[report(kc_to_u8!(B)), report(0)]).await;
+
+        // Without default layer in the settings, the default layer of the keyboard is back
+        set_profile_settings(OutputProfile::Usb, ProfileSettings::default()).await;
+        run_key_sequence_test(&mut keyboard, &tap_a, &[report(kc_to_u8!(A)), report(0)]).await;
+    });
+}
@@ -20,10 +20,12 @@ nx=(nextest run --config-file "$repo_root/.config/nextest.toml")
 cargo "${nx[@]}" --manifest-path rmk-types/Cargo.toml
 cargo "${nx[@]}" --manifest-path rmk-types/Cargo.toml --features host
 cargo "${nx[@]}" --manifest-path rmk-types/Cargo.toml --features steno
+cargo "${nx[@]}" --manifest-path rmk-types/Cargo.toml --features gamepad
 
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,vial,async_matrix,_ble"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,vial,async_matrix"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,async_matrix"
+cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,async_matrix,split_reliable"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,async_matrix,_ble"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "async_matrix,storage"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "vial,storage"
@@ -34,6 +36,9 @@ cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features
 # the _ble combo verifies the BLE silent-drop arm compiles.
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "vial,storage,steno"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,vial,storage,async_matrix,_ble,steno"
+# Gamepad: USB-only path and the BLE gamepad report
+cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "vial,storage,gamepad"
+cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features --features "split,vial,storage,async_matrix,_ble,gamepad"
 cargo "${nx[@]}" --manifest-path rmk/Cargo.toml --no-default-features
 
 # Doctests: nextest doesn't run them. rmk/ has `doctest = false` so only